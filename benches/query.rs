@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use std::process::Command;
 
 fn criterion_benchmark(c: &mut Criterion) {
@@ -29,11 +29,76 @@ fn criterion_benchmark(c: &mut Criterion) {
     curl_insert(ent_str);
     curl_insert(ent_str);
     c.bench_function("select_all_20_entity", |b| b.iter(|| curl_select(ent_str)));
+
+    curl_insert_a(ent_str, 1);
+    c.bench_function("select_where_1_of_21_entity", |b| {
+        b.iter(|| curl_select_where(ent_str))
+    });
+    print_peak_memory("select_all_21_entity", || curl_select(ent_str));
+    print_peak_memory("select_where_1_of_21_entity", || curl_select_where(ent_str));
+    c.bench_function("count_where_1_of_21_entity", |b| {
+        b.iter(|| curl_count_where(ent_str))
+    });
+    print_peak_memory("count_where_1_of_21_entity", || curl_count_where(ent_str));
+
+    // Every insert of the throughput bench matches the WHERE clause, so the count is big.
+    let big_str = "bench_big_entity_name";
+    curl_create(big_str);
+    let mut group = c.benchmark_group("insert");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("insert_a_1", |b| b.iter(|| curl_insert_a(big_str, 1)));
+    group.finish();
+    print_peak_memory("select_where_all_of_big_entity", || {
+        curl_select_where(big_str)
+    });
+    print_peak_memory("count_where_all_of_big_entity", || {
+        curl_count_where(big_str)
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
 criterion_main!(benches);
 
+/// Prints how much the resident memory of the local `wooridb` process grows while `query`
+/// runs. The peak is reset through `/proc/<pid>/clear_refs` first, so this needs Linux and
+/// a server owned by the same user.
+fn print_peak_memory<F: Fn()>(name: &str, query: F) {
+    let pid = Command::new("pgrep")
+        .args(["-x", "wooridb"])
+        .output()
+        .ok()
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .and_then(|pids| pids.lines().next().map(str::to_owned));
+    let pid = match pid {
+        Some(pid) => pid,
+        None => return println!("{}: peak memory unavailable, wooridb is not running", name),
+    };
+    let clear_refs = format!("/proc/{}/clear_refs", pid);
+    if std::fs::write(&clear_refs, "5").is_err() {
+        return println!(
+            "{}: peak memory unavailable, can't reset {}",
+            name, clear_refs
+        );
+    }
+    let rss = status_kb(&pid, "VmRSS:");
+    query();
+    let hwm = status_kb(&pid, "VmHWM:");
+    println!("{}: peak memory +{} kB", name, hwm.saturating_sub(rss));
+}
+
+fn status_kb(pid: &str, field: &str) -> u64 {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find(|line| line.starts_with(field))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .and_then(|kb| kb.parse().ok())
+        })
+        .unwrap_or_default()
+}
+
 fn curl_create(entity: &str) {
     let action = format!("CREATE ENTITY {}", entity);
     let val = Command::new("curl")
@@ -66,6 +131,60 @@ fn curl_insert(entity: &str) {
     };
 }
 
+fn curl_insert_a(entity: &str, a: i64) {
+    let action = format!("INSERT {{a: {},}} INTO {}", a, entity);
+    let val = Command::new("curl")
+        .args(["-X", "POST"])
+        .args(["-H", "Content-Type: application/wql"])
+        .arg("localhost:1438/wql/tx")
+        .args(["-d", &action])
+        .output()
+        .expect("failed to execute process")
+        .stdout;
+    match String::from_utf8(val) {
+        Ok(_) => (),
+        Err(e) => panic!("{:?}", e),
+    };
+}
+
+fn curl_select_where(entity: &str) {
+    let action = format!(
+        "SELECT * FROM {} WHERE {{ ?* {}:a ?a, (== ?a 1), }}",
+        entity, entity
+    );
+    let val = Command::new("curl")
+        .args(["-X", "POST"])
+        .args(["-H", "Content-Type: application/wql"])
+        .arg("localhost:1438/wql/query")
+        .args(["-d", &action])
+        .output()
+        .expect("failed to execute process")
+        .stdout;
+    match String::from_utf8(val) {
+        Ok(_) => print!("OK,"),
+        Err(e) => panic!("{:?}", e),
+    };
+}
+
+fn curl_count_where(entity: &str) {
+    let action = format!(
+        "SELECT COUNT(*) FROM {} WHERE {{ ?* {}:a ?a, (== ?a 1), }}",
+        entity, entity
+    );
+    let val = Command::new("curl")
+        .args(["-X", "POST"])
+        .args(["-H", "Content-Type: application/wql"])
+        .arg("localhost:1438/wql/query")
+        .args(["-d", &action])
+        .output()
+        .expect("failed to execute process")
+        .stdout;
+    match String::from_utf8(val) {
+        Ok(_) => print!("OK,"),
+        Err(e) => panic!("{:?}", e),
+    };
+}
+
 fn curl_select(entity: &str) {
     let action = format!("SELECT * FROM {}", entity);
     let val = Command::new("curl")
@@ -0,0 +1,44 @@
+use actix::prelude::*;
+use chrono::{DateTime, Utc};
+use ron::ser::to_string_pretty;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::core::pretty_config_inner;
+
+/// Appends records of executed transactions and queries to `audit.log` in the data directory. It runs
+/// on its own thread, so requests send records without waiting for them to be written.
+pub struct Auditor;
+
+impl Actor for Auditor {
+    type Context = SyncContext<Self>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub user_id: Option<Uuid>,
+    pub endpoint: String,
+    pub wql: String,
+    pub status: u16,
+    pub error: Option<String>,
+}
+
+impl Message for AuditRecord {
+    type Result = ();
+}
+
+impl Handler<AuditRecord> for Auditor {
+    type Result = ();
+
+    fn handle(&mut self, msg: AuditRecord, _: &mut Self::Context) -> Self::Result {
+        use crate::io::write::write_to_audit;
+        let written = to_string_pretty(&msg, pretty_config_inner())
+            .map_err(|e| e.to_string())
+            .and_then(|record| write_to_audit(&record).map_err(|e| e.to_string()));
+
+        if let Err(e) = written {
+            log::error!("Audit record could not be written: {}", e);
+        }
+    }
+}
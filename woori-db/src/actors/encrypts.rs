@@ -8,11 +8,11 @@ use chrono::{DateTime, Utc};
 use rayon::prelude::*;
 use ron::ser::{to_string_pretty, PrettyConfig};
 use serde::{Deserialize, Serialize};
-use wql::Types;
+use wql::{verify_hash, Types};
 
 use crate::{
-    actors::wql::Executor, model::error::Error, repository::local::EncryptContext,
-    schemas::query::Response as QueryResponse,
+    actors::wql::Executor, core::hash_algorithm, model::error::Error,
+    repository::local::EncryptContext, schemas::query::Response as QueryResponse,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,13 +108,15 @@ impl Handler<EncryptContent> for Executor {
             encrypts_data.get_mut(&msg.entity).map_or(
                 Ok(msg.content.clone()),
                 |encrypts_for_entity| {
+                    let algorithm = hash_algorithm();
                     let mut new_content = HashMap::new();
                     msg.content.iter().for_each(|(k, v)| {
                         if encrypts_for_entity.contains(k) {
                             #[cfg(test)]
-                            let hashed_v = v.to_hash(Some(4)).unwrap();
+                            let hashed_v = v.to_hash_with(algorithm, Some(4)).unwrap();
                             #[cfg(not(test))]
-                            let hashed_v = v.to_hash(Some(msg.hashing_cost)).unwrap();
+                            let hashed_v =
+                                v.to_hash_with(algorithm, Some(msg.hashing_cost)).unwrap();
                             new_content.insert(k.to_owned(), hashed_v);
                         } else {
                             new_content.insert(k.to_owned(), v.to_owned());
@@ -137,6 +139,23 @@ impl VerifyEncryption {
     pub fn new(filtered: HashMap<String, Types>, content: HashMap<String, String>) -> Self {
         Self { filtered, content }
     }
+
+    /// If each value of `content` matches the hash of its key, keys without a hash are `false`.
+    pub fn verify(&self) -> HashMap<String, bool> {
+        let type_nil = Types::Nil;
+        self.content
+            .par_iter()
+            .map(|(k, v)| {
+                let original_hash = self.filtered.get(k).unwrap_or(&type_nil);
+                let result = if let Types::Hash(hash) = original_hash {
+                    verify_hash(v, hash)
+                } else {
+                    false
+                };
+                (k.to_owned(), result)
+            })
+            .collect::<HashMap<String, bool>>()
+    }
 }
 
 impl Message for VerifyEncryption {
@@ -147,23 +166,7 @@ impl Handler<VerifyEncryption> for Executor {
     type Result = Result<QueryResponse, Error>;
 
     fn handle(&mut self, msg: VerifyEncryption, _: &mut Self::Context) -> Self::Result {
-        let type_nil = Types::Nil;
-        let results = msg
-            .content
-            .clone()
-            .into_par_iter()
-            .map(|(k, v)| {
-                let original = msg.filtered.clone();
-                let original_hash = original.get(&k).unwrap_or(&type_nil);
-                let result = if let Types::Hash(hash) = original_hash {
-                    bcrypt::verify(v, hash).unwrap()
-                } else {
-                    false
-                };
-                (k, result)
-            })
-            .collect::<HashMap<String, bool>>();
-        Ok(results.into())
+        Ok(msg.verify().into())
     }
 }
 
@@ -25,6 +25,7 @@ impl Handler<History> for Executor {
             Ok((content?, date?, previous_registry))
         } else if fractions[0].eq("UPDATE_SET")
             || fractions[0].eq("UPDATE_CONTENT")
+            || fractions[0].eq("REPLACE")
             || fractions[0].eq("DELETE")
         {
             let date = get_date(&fractions);
@@ -37,6 +38,16 @@ impl Handler<History> for Executor {
     }
 }
 
+/// Registry that the registry `log` points to, `None` for inserts, which start the chain.
+pub(crate) fn previous_registry(log: &str) -> Result<Option<DataRegister>, Error> {
+    let fractions = log.split('|').collect::<Vec<&str>>();
+    if fractions[0].eq("INSERT") {
+        Ok(None)
+    } else {
+        get_previous_registry(&fractions)
+    }
+}
+
 fn get_insert_content(fractions: &[&str]) -> Result<HashMap<String, Types>, Error> {
     let state = fractions
         .last()
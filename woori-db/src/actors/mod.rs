@@ -1,8 +1,12 @@
+pub mod audit;
 pub mod encrypts;
 pub mod history;
 pub mod recovery;
 pub mod scheduler;
+pub mod schemas;
+pub mod sequences;
 pub mod state;
+pub mod subscriptions;
 pub mod uniques;
 pub mod when;
 pub mod wql;
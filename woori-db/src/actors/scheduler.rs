@@ -1,3 +1,4 @@
+use crate::io::paths::data_dir;
 use actix::prelude::*;
 use chrono::{Local, TimeZone, Utc};
 use cron::Schedule;
@@ -33,14 +34,15 @@ impl Scheduler {
         use glob::glob;
         log::debug!("schedule_task event - {:?}", Local::now());
         let date_to_clear = Utc::now() - chrono::Duration::days(10);
-        let files: Vec<PathBuf> = glob("*data/*.log")
+        let files: Vec<PathBuf> = glob(&format!("{}/**/*.log", data_dir()))
             .unwrap()
             .map(std::result::Result::unwrap)
             .collect();
 
         files.iter().for_each(|f| {
-            if let Some(file_name) = f.to_str() {
-                let date = file_name.replace(".log", "").replace("data/", "");
+            if let (Some(file_name), Some(date)) =
+                (f.to_str(), f.file_stem().and_then(|stem| stem.to_str()))
+            {
                 let file_date =
                     Utc.datetime_from_str(&format!("{} 00:00:00", date), "%Y_%m_%d %H:%M:%S");
 
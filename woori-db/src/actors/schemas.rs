@@ -0,0 +1,525 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use actix::prelude::*;
+use ron::ser::to_string_pretty;
+use serde::{Deserialize, Serialize};
+use wql::{EntitySchema, Types};
+
+use crate::{
+    actors::wql::Executor,
+    core::{
+        pretty_config_inner,
+        query::{VALID_FROM_KEY, VALID_TO_KEY},
+    },
+    model::error::Error,
+    repository::local::SchemaContext,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WriteWithSchema {
+    pub entity: String,
+    pub schema: EntitySchema,
+}
+
+impl Message for WriteWithSchema {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<WriteWithSchema> for Executor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: WriteWithSchema, _: &mut Self::Context) -> Self::Result {
+        use crate::io::write::write_to_schemas;
+        let schema_log =
+            to_string_pretty(&msg, pretty_config_inner()).map_err(Error::Serialization)?;
+        Ok(write_to_schemas(&schema_log)?)
+    }
+}
+
+pub struct CreateWithSchema {
+    pub entity: String,
+    pub schema: EntitySchema,
+    pub data: Arc<Mutex<SchemaContext>>,
+}
+
+impl Message for CreateWithSchema {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<CreateWithSchema> for Executor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: CreateWithSchema, _: &mut Self::Context) -> Self::Result {
+        let mut schema_data = if let Ok(guard) = msg.data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+
+        schema_data.insert(msg.entity, msg.schema);
+        Ok(())
+    }
+}
+
+/// How values of keys declared as `Boolean` are read, set with `STRICT_BOOLEANS`.
+/// `Lenient` also accepts `1/0`, `yes/no` and any casing of `true/false`, written as
+/// integers or strings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BooleanParsing {
+    Strict,
+    Lenient,
+}
+
+impl BooleanParsing {
+    /// Reads `STRICT_BOOLEANS`, lenient unless it is `true`.
+    pub fn from_env() -> Self {
+        match std::env::var("STRICT_BOOLEANS") {
+            Ok(s) if s.eq_ignore_ascii_case("true") => BooleanParsing::Strict,
+            _ => BooleanParsing::Lenient,
+        }
+    }
+}
+
+fn lenient_boolean(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Validates `content` and returns it with the loose booleans of `Boolean` keys
+/// converted when `booleans` is `Lenient`. When `fill_defaults` is set, keys with a
+/// schema default that are missing from `content` are added first, a `Nil` is kept.
+pub struct ValidateSchema {
+    pub entity: String,
+    pub content: HashMap<String, Types>,
+    pub schemas: Arc<Mutex<SchemaContext>>,
+    pub booleans: BooleanParsing,
+    pub fill_defaults: bool,
+}
+
+impl Message for ValidateSchema {
+    type Result = Result<HashMap<String, Types>, Error>;
+}
+
+impl Handler<ValidateSchema> for Executor {
+    type Result = Result<HashMap<String, Types>, Error>;
+
+    fn handle(&mut self, msg: ValidateSchema, _: &mut Self::Context) -> Self::Result {
+        let schema_data = if let Ok(guard) = msg.schemas.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+        let schema = if let Some(schema) = schema_data.get(&msg.entity) {
+            schema
+        } else {
+            return Ok(msg.content);
+        };
+
+        let mut content = msg.content;
+        if msg.fill_defaults {
+            for (key, default) in &schema.defaults {
+                if !content.contains_key(key) {
+                    let value = default.clone().or_else(|| {
+                        schema
+                            .types
+                            .get(key)
+                            .and_then(|type_name| Types::default_of(type_name))
+                    });
+                    content.insert(key.to_owned(), value.unwrap_or(Types::Nil));
+                }
+            }
+        }
+        for (key, value) in &mut content {
+            let is_boolean = schema.types.get(key).map(String::as_str) == Some("Boolean");
+            if is_boolean && msg.booleans == BooleanParsing::Lenient {
+                let loose = match value {
+                    Types::Integer(i) => Some(i.to_string()),
+                    Types::String(s) => Some(s.to_owned()),
+                    _ => None,
+                };
+                if let Some(loose) = loose {
+                    *value = match lenient_boolean(&loose) {
+                        Some(boolean) => Types::Boolean(boolean),
+                        None => {
+                            return Err(Error::InvalidBoolean(msg.entity, key.to_owned(), loose))
+                        }
+                    };
+                }
+            }
+
+            let value = &*value;
+            match schema.types.get(key) {
+                Some(_) if value == &Types::Nil => (),
+                Some(expected) if expected != value.type_name() => {
+                    return Err(Error::SchemaTypeMismatch(
+                        msg.entity,
+                        key.to_owned(),
+                        expected.to_owned(),
+                        value.type_name().to_owned(),
+                    ))
+                }
+                None if schema.exhaustive => {
+                    return Err(Error::KeyNotInSchema(msg.entity, key.to_owned()))
+                }
+                _ => (),
+            }
+            if let (Some(allowed), Types::String(variant)) = (schema.enums.get(key), value) {
+                if !allowed.contains(variant) {
+                    return Err(Error::EnumVariantNotAllowed(
+                        msg.entity,
+                        key.to_owned(),
+                        variant.to_owned(),
+                        allowed.to_owned(),
+                    ));
+                }
+            }
+            if let (Some(expected), Types::Vector(elements)) = (schema.vectors.get(key), value) {
+                if let Some(element) = elements.iter().find(|e| e.type_name() != expected) {
+                    return Err(Error::VectorElementTypeMismatch(
+                        msg.entity,
+                        key.to_owned(),
+                        expected.to_owned(),
+                        element.type_name().to_owned(),
+                    ));
+                }
+            }
+        }
+        if let (Some(Types::DateTime(from)), Some(Types::DateTime(to))) =
+            (content.get(VALID_FROM_KEY), content.get(VALID_TO_KEY))
+        {
+            if to <= from {
+                return Err(Error::InvalidValidInterval(
+                    msg.entity,
+                    from.to_rfc3339(),
+                    to.to_rfc3339(),
+                ));
+            }
+        }
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::actors::wql::Executor;
+
+    fn schema_context(exhaustive: bool) -> Arc<Mutex<SchemaContext>> {
+        let mut types = BTreeMap::new();
+        types.insert("age".to_string(), "Integer".to_string());
+        let mut data = SchemaContext::new();
+        types.insert("status".to_string(), "String".to_string());
+        types.insert("tags".to_string(), "Vector".to_string());
+        types.insert("active".to_string(), "Boolean".to_string());
+        types.insert("valid_from".to_string(), "DateTime".to_string());
+        types.insert("valid_to".to_string(), "DateTime".to_string());
+        let mut vectors = BTreeMap::new();
+        vectors.insert("tags".to_string(), "String".to_string());
+        let mut enums = BTreeMap::new();
+        enums.insert(
+            "status".to_string(),
+            vec!["Open".to_string(), "Closed".to_string()],
+        );
+        let mut defaults = BTreeMap::new();
+        defaults.insert(
+            "status".to_string(),
+            Some(Types::String("Open".to_string())),
+        );
+        defaults.insert("tags".to_string(), None);
+        data.insert(
+            "my-entity".to_string(),
+            EntitySchema {
+                types,
+                exhaustive,
+                enums,
+                vectors,
+                defaults,
+                append_only: false,
+            },
+        );
+        Arc::new(Mutex::new(data))
+    }
+
+    #[actix_rt::test]
+    async fn validate_schema_types() {
+        let actor = Executor::new().start();
+        let mut content = HashMap::new();
+        content.insert("age".to_string(), Types::Integer(34));
+        content.insert("name".to_string(), Types::String("julia".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert!(resp.is_ok());
+
+        content.insert("age".to_string(), Types::String("34".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "(\n error_type: \"SchemaTypeMismatch\",\n error_message: \"Key `age` of entity `my-entity` must be of type Integer, found String\",\n)"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn validate_enum_schema() {
+        let actor = Executor::new().start();
+        let mut content = HashMap::new();
+        content.insert("status".to_string(), Types::String("Open".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert!(resp.is_ok());
+
+        content.insert("status".to_string(), Types::String("Pending".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "(\n error_type: \"EnumVariantNotAllowed\",\n error_message: \"Value `Pending` of key `status` in entity `my-entity` is not one of the variants [\\\"Open\\\", \\\"Closed\\\"]\",\n)"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn validate_vector_schema() {
+        let actor = Executor::new().start();
+        let mut content = HashMap::new();
+        content.insert("tags".to_string(), Types::Vector(Vec::new()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert!(resp.is_ok());
+
+        content.insert(
+            "tags".to_string(),
+            Types::Vector(vec![Types::String("db".to_string()), Types::Integer(3)]),
+        );
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "(\n error_type: \"VectorElementTypeMismatch\",\n error_message: \"Elements of key `tags` in entity `my-entity` must be of type String, found Integer\",\n)"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn validate_exhaustive_schema() {
+        let actor = Executor::new().start();
+        let mut content = HashMap::new();
+        content.insert("age".to_string(), Types::Nil);
+        content.insert("name".to_string(), Types::String("julia".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(true),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "(\n error_type: \"KeyNotInSchema\",\n error_message: \"Key `name` is not declared in the EXHAUSTIVE schema of entity `my-entity`\",\n)"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn validate_lenient_boolean_schema() {
+        let actor = Executor::new().start();
+        let mut content = HashMap::new();
+        content.insert("active".to_string(), Types::String("YES".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Lenient,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.unwrap()["active"], Types::Boolean(true));
+
+        content.insert("active".to_string(), Types::Integer(0));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Lenient,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(resp.unwrap()["active"], Types::Boolean(false));
+
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert!(resp
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("SchemaTypeMismatch"));
+
+        content.insert("active".to_string(), Types::String("maybe".to_string()));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Lenient,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "(\n error_type: \"InvalidBoolean\",\n error_message: \"Value `maybe` of key `active` in entity `my-entity` is not a boolean, expected true/false, 1/0 or yes/no\",\n)"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn validate_valid_interval() {
+        let actor = Executor::new().start();
+        let date = |d: &str| Types::DateTime(d.parse().unwrap());
+        let mut content = HashMap::new();
+        content.insert("valid_from".to_string(), date("2023-01-01T00:00:00Z"));
+        content.insert("valid_to".to_string(), date("2024-01-01T00:00:00Z"));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(true),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert!(resp.is_ok());
+
+        content.insert("valid_to".to_string(), date("2022-01-01T00:00:00Z"));
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(true),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.err().unwrap().to_string(),
+            "(\n error_type: \"InvalidValidInterval\",\n error_message: \"Key `valid_to` 2022-01-01T00:00:00+00:00 in entity `my-entity` must be after `valid_from` 2023-01-01T00:00:00+00:00\",\n)"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn validate_fills_defaults() {
+        let actor = Executor::new().start();
+        let mut content = HashMap::new();
+        content.insert("age".to_string(), Types::Integer(34));
+        content.insert("tags".to_string(), Types::Nil);
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: true,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resp["status"], Types::String("Open".to_string()));
+        assert_eq!(resp["tags"], Types::Nil);
+
+        content.remove("tags");
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content: content.clone(),
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: true,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resp["tags"], Types::Vector(Vec::new()));
+
+        let resp = actor
+            .send(ValidateSchema {
+                entity: "my-entity".to_string(),
+                content,
+                schemas: schema_context(false),
+                booleans: BooleanParsing::Strict,
+                fill_defaults: false,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!resp.contains_key("status"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+};
+
+use actix::prelude::*;
+use uuid::Uuid;
+
+use crate::{
+    actors::wql::Executor,
+    core::pretty_config_inner,
+    io::write::write_sequences,
+    model::error::Error,
+    repository::local::{LocalContext, SequenceContext},
+};
+
+/// Id of the `number` of an entity sequence, `00000000-0000-0000-0000-00000000002a` for 42.
+pub fn sequence_id(number: u64) -> Uuid {
+    Uuid::from_u128(u128::from(number))
+}
+
+/// Number of an id assigned by `INSERT ... WITH NEXT`, `None` for random ids.
+pub fn sequence_number(id: &Uuid) -> Option<u64> {
+    u64::try_from(id.as_u128()).ok()
+}
+
+/// Raises the persisted sequences to the largest numbered id of each entity, in case
+/// `sequences.log` is older than `local_data.log`.
+pub fn with_local_sequences(
+    mut sequences: SequenceContext,
+    local_data: &LocalContext,
+) -> SequenceContext {
+    for (entity, ids) in local_data {
+        if let Some(last) = ids.keys().filter_map(sequence_number).max() {
+            let sequence = sequences.entry(entity.to_owned()).or_insert(0);
+            *sequence = (*sequence).max(last);
+        }
+    }
+    sequences
+}
+
+/// Next number of the sequence of `entity`. With `reserve` the number is persisted before it
+/// is returned, so concurrent or later inserts never get it again, otherwise it is only read,
+/// like for `DRY RUN`.
+pub struct NextSequence {
+    pub entity: String,
+    pub reserve: bool,
+    pub data: Arc<Mutex<SequenceContext>>,
+}
+
+impl Message for NextSequence {
+    type Result = Result<u64, Error>;
+}
+
+impl Handler<NextSequence> for Executor {
+    type Result = Result<u64, Error>;
+
+    fn handle(&mut self, msg: NextSequence, _: &mut Self::Context) -> Self::Result {
+        let mut sequences = if let Ok(guard) = msg.data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+
+        let next = sequences
+            .get(&msg.entity)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(1)
+            .ok_or_else(|| {
+                Error::QueryFormat(format!("Sequence of entity `{}` is exhausted", msg.entity))
+            })?;
+        if msg.reserve {
+            sequences.insert(msg.entity, next);
+            let sequences_ron =
+                ron::ser::to_string_pretty(&sequences.clone(), pretty_config_inner())?;
+            write_sequences(&sequences_ron)?;
+        }
+
+        Ok(next)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::DataRegister;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn sequence_ids_round_trip() {
+        assert_eq!(
+            sequence_id(42).to_string(),
+            "00000000-0000-0000-0000-00000000002a"
+        );
+        assert_eq!(sequence_number(&sequence_id(u64::MAX)), Some(u64::MAX));
+        assert_eq!(sequence_number(&Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn local_ids_raise_sequences() {
+        let mut local_data = LocalContext::new();
+        let mut ids = BTreeMap::new();
+        for id in [sequence_id(3), sequence_id(7), Uuid::new_v4()] {
+            let register = DataRegister {
+                file_name: String::new(),
+                offset: 0,
+                bytes_length: 0,
+                checksum: None,
+            };
+            ids.insert(id, (register, Vec::new()));
+        }
+        local_data.insert("numbered".to_string(), ids);
+        local_data.insert("random".to_string(), BTreeMap::new());
+        let mut sequences = SequenceContext::new();
+        sequences.insert("numbered".to_string(), 5);
+        sequences.insert("random".to_string(), 9);
+
+        let sequences = with_local_sequences(sequences, &local_data);
+
+        assert_eq!(sequences.get("numbered"), Some(&7));
+        assert_eq!(sequences.get("random"), Some(&9));
+    }
+}
@@ -1,6 +1,7 @@
 use actix::prelude::*;
+use chrono::Utc;
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 use wql::{MatchCondition, Types};
 
 use crate::actors::wql::Executor;
@@ -33,6 +34,7 @@ impl Handler<State> for Executor {
             resp
         } else if fractions[0].eq("UPDATE_SET")
             || fractions[0].eq("UPDATE_CONTENT")
+            || fractions[0].eq("REPLACE")
             || fractions[0].eq("DELETE")
         {
             let state = fractions
@@ -68,6 +70,7 @@ impl Handler<PreviousRegistry> for Executor {
             Ok(None)
         } else if fractions[0].eq("UPDATE_SET")
             || fractions[0].eq("UPDATE_CONTENT")
+            || fractions[0].eq("REPLACE")
             || fractions[0].eq("DELETE")
         {
             let state = fractions
@@ -100,14 +103,23 @@ impl Handler<MatchUpdate> for Executor {
     type Result = Result<(), Error>;
 
     fn handle(&mut self, msg: MatchUpdate, _: &mut Self::Context) -> Self::Result {
-        match msg.conditions.clone() {
-            MatchCondition::All(all) => match_all(all.as_slice(), &msg.previous_state),
-            MatchCondition::Any(any) => match_any(any.as_slice(), &msg.previous_state),
-            _ => Err(Error::UnknownCondition),
-        }?;
-        Ok(())
+        match_conditions(&msg.conditions, &msg.previous_state)
+    }
+}
+
+/// Checks the `MATCH` conditions against `previous_state`, without a round trip to the
+/// actor, so it can run while the registries are locked.
+pub fn match_conditions(
+    conditions: &MatchCondition,
+    previous_state: &HashMap<String, Types>,
+) -> Result<(), Error> {
+    match conditions {
+        MatchCondition::All(all) => match_all(all.as_slice(), previous_state),
+        MatchCondition::Any(any) => match_any(any.as_slice(), previous_state),
+        _ => Err(Error::UnknownCondition),
     }
 }
+
 fn match_all(all: &[MatchCondition], previous_state: &HashMap<String, Types>) -> Result<(), Error> {
     let conds = all
         .par_iter()
@@ -154,6 +166,7 @@ fn match_all(all: &[MatchCondition], previous_state: &HashMap<String, Types>) ->
                     false
                 }
             }
+            MatchCondition::OlderThan(key, age) => older_than(previous_state.get(&key), age),
             _ => false,
         })
         .all(|c| c);
@@ -210,6 +223,7 @@ fn match_any(any: &[MatchCondition], previous_state: &HashMap<String, Types>) ->
                     false
                 }
             }
+            MatchCondition::OlderThan(key, age) => older_than(previous_state.get(&key), age),
             _ => false,
         })
         .any(|c| c);
@@ -220,10 +234,22 @@ fn match_any(any: &[MatchCondition], previous_state: &HashMap<String, Types>) ->
     }
 }
 
+fn older_than(state: Option<&Types>, age: Duration) -> bool {
+    let limit = chrono::Duration::from_std(age)
+        .ok()
+        .and_then(|age| Utc::now().checked_sub_signed(age));
+    match (state, limit) {
+        (Some(Types::DateTime(date)), Some(limit)) => *date < limit,
+        _ => false,
+    }
+}
+
 fn ge_match_types(cond: Types, state: Types) -> bool {
     match (cond, state) {
         (Types::Integer(c), Types::Integer(s)) => s >= c,
         (Types::Float(c), Types::Float(s)) => s >= c,
+        (Types::Percentage(c), Types::Percentage(s)) => s >= c,
+        (c @ Types::Ratio { .. }, s @ Types::Ratio { .. }) => s >= c,
         _ => false,
     }
 }
@@ -232,6 +258,8 @@ fn g_match_types(cond: Types, state: Types) -> bool {
     match (cond, state) {
         (Types::Integer(c), Types::Integer(s)) => s > c,
         (Types::Float(c), Types::Float(s)) => s > c,
+        (Types::Percentage(c), Types::Percentage(s)) => s > c,
+        (c @ Types::Ratio { .. }, s @ Types::Ratio { .. }) => s > c,
         _ => false,
     }
 }
@@ -240,6 +268,8 @@ fn le_match_types(cond: Types, state: Types) -> bool {
     match (cond, state) {
         (Types::Integer(c), Types::Integer(s)) => s <= c,
         (Types::Float(c), Types::Float(s)) => s <= c,
+        (Types::Percentage(c), Types::Percentage(s)) => s <= c,
+        (c @ Types::Ratio { .. }, s @ Types::Ratio { .. }) => s <= c,
         _ => false,
     }
 }
@@ -248,6 +278,8 @@ fn l_match_types(cond: Types, state: Types) -> bool {
     match (cond, state) {
         (Types::Integer(c), Types::Integer(s)) => s < c,
         (Types::Float(c), Types::Float(s)) => s < c,
+        (Types::Percentage(c), Types::Percentage(s)) => s < c,
+        (c @ Types::Ratio { .. }, s @ Types::Ratio { .. }) => s < c,
         _ => false,
     }
 }
@@ -349,6 +381,39 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[actix_rt::test]
+    async fn test_older_than() {
+        let actor = Executor::new().start();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let mut state = previous_state();
+        state.insert(
+            "last_seen".to_string(),
+            Types::DateTime(Utc::now() - chrono::Duration::days(31)),
+        );
+        let older_than =
+            |key: &str, days: u32| MatchCondition::OlderThan(key.to_string(), day * days);
+
+        for (condition, matches) in [
+            (older_than("last_seen", 30), true),
+            (older_than("last_seen", 32), false),
+            (older_than("a", 30), false),
+            (older_than("missing", 30), false),
+            (
+                MatchCondition::OlderThan("last_seen".to_string(), Duration::MAX),
+                false,
+            ),
+        ] {
+            let result = actor
+                .send(MatchUpdate {
+                    conditions: MatchCondition::All(vec![condition.clone()]),
+                    previous_state: state.clone(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(result.is_ok(), matches, "{:?}", condition);
+        }
+    }
+
     fn previous_state() -> HashMap<String, Types> {
         let mut hm = HashMap::new();
         hm.insert("a".to_string(), Types::Float(4.5f64));
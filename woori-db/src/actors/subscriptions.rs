@@ -0,0 +1,153 @@
+use actix::prelude::*;
+use actix_web_actors::ws;
+use serde::Serialize;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::broadcast::{self, RecvError};
+use uuid::Uuid;
+use wql::Types;
+
+#[cfg(not(feature = "json"))]
+use crate::core::pretty_config_output;
+use crate::schemas::tx::TxType;
+
+const DEFAULT_BUFFER_SIZE: usize = 256;
+
+/// State of an entity id after a committed insert or update, pushed to the subscribers of `entity`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntityChange {
+    pub tx_type: TxType,
+    pub entity: String,
+    pub id: Uuid,
+    pub state: HashMap<String, Types>,
+}
+
+impl EntityChange {
+    pub fn write(&self) -> String {
+        #[cfg(feature = "json")]
+        return serde_json::to_string(self).unwrap_or_else(|_| "SERVER ERROR".to_string());
+        #[cfg(not(feature = "json"))]
+        ron::ser::to_string_pretty(self, pretty_config_output())
+            .unwrap_or_else(|_| "SERVER ERROR".to_string())
+    }
+}
+
+/// Broadcasts entity changes to every open subscription. Writers never wait for subscribers,
+/// each subscriber buffers up to `buffer_size` changes and a slow one loses its oldest changes.
+#[derive(Debug, Clone)]
+pub struct Subscriptions {
+    sender: broadcast::Sender<Arc<EntityChange>>,
+}
+
+impl Subscriptions {
+    pub fn new(buffer_size: usize) -> Self {
+        let (sender, _) = broadcast::channel(buffer_size.max(1));
+        Self { sender }
+    }
+
+    /// Reads `SUBSCRIPTION_BUFFER_SIZE`, defaults to 256 changes per subscriber.
+    pub fn from_env() -> Self {
+        let buffer_size = std::env::var("SUBSCRIPTION_BUFFER_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_BUFFER_SIZE);
+
+        Self::new(buffer_size)
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn publish(&self, change: EntityChange) {
+        // Sending only fails when there are no subscribers.
+        let _ = self.sender.send(Arc::new(change));
+    }
+
+    pub fn subscribe(&self, entity: String) -> Subscriber {
+        Subscriber {
+            entity,
+            receiver: Some(self.sender.subscribe()),
+        }
+    }
+}
+
+/// WebSocket connection of `GET /subscribe/{entity}`, it sends each change of `entity` as a text message.
+pub struct Subscriber {
+    entity: String,
+    receiver: Option<broadcast::Receiver<Arc<EntityChange>>>,
+}
+
+impl Actor for Subscriber {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(receiver) = self.receiver.take() {
+            ctx.add_stream(receiver);
+        }
+    }
+}
+
+impl StreamHandler<Result<Arc<EntityChange>, RecvError>> for Subscriber {
+    fn handle(&mut self, msg: Result<Arc<EntityChange>, RecvError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(change) if change.entity == self.entity => ctx.text(change.write()),
+            Ok(_) => (),
+            Err(RecvError::Lagged(skipped)) => log::warn!(
+                "Subscriber of entity {} is lagging, {} changes were dropped",
+                self.entity,
+                skipped
+            ),
+            Err(RecvError::Closed) => ctx.stop(),
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Subscriber {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => (),
+            Err(_) => ctx.stop(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn change(entity: &str) -> EntityChange {
+        EntityChange {
+            tx_type: TxType::Insert,
+            entity: entity.to_string(),
+            id: Uuid::new_v4(),
+            state: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn slow_subscribers_lose_oldest_changes() {
+        let subscriptions = Subscriptions::new(2);
+        assert!(!subscriptions.has_subscribers());
+        let mut receiver = subscriptions
+            .subscribe("my_entity".to_string())
+            .receiver
+            .unwrap();
+        assert!(subscriptions.has_subscribers());
+
+        for entity in &["first", "second", "third"] {
+            subscriptions.publish(change(entity));
+        }
+
+        assert_eq!(
+            receiver.try_recv().unwrap_err(),
+            broadcast::TryRecvError::Lagged(1)
+        );
+        assert_eq!(receiver.try_recv().unwrap().entity, "second");
+        assert_eq!(receiver.try_recv().unwrap().entity, "third");
+    }
+}
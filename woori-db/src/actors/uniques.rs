@@ -140,6 +140,155 @@ impl Handler<CheckForUniqueKeys> for Executor {
     }
 }
 
+/// Removes every registered unique value of `entity`, keeping its unique keys.
+pub struct ClearUniqueValues {
+    pub entity: String,
+    pub data: Arc<Arc<Mutex<UniquenessContext>>>,
+}
+
+impl Message for ClearUniqueValues {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<ClearUniqueValues> for Executor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: ClearUniqueValues, _: &mut Self::Context) -> Self::Result {
+        let mut uniqueness_data = if let Ok(guard) = msg.data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+
+        if let Some(uniques_for_entity) = uniqueness_data.get_mut(&msg.entity) {
+            uniques_for_entity
+                .values_mut()
+                .for_each(|values| values.clear());
+            let unique_ron =
+                ron::ser::to_string_pretty(&uniqueness_data.clone(), pretty_config_inner())?;
+            unique_data(&unique_ron)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the unique values of `content` from `entity`, the ones a `BEGIN` block
+/// registered before failing to commit.
+pub struct RemoveUniqueValues {
+    pub entity: String,
+    pub content: HashMap<String, Types>,
+    pub data: Arc<Arc<Mutex<UniquenessContext>>>,
+}
+
+impl Message for RemoveUniqueValues {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<RemoveUniqueValues> for Executor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: RemoveUniqueValues, _: &mut Self::Context) -> Self::Result {
+        let mut uniqueness_data = if let Ok(guard) = msg.data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+
+        if let Some(uniques_for_entity) = uniqueness_data.get_mut(&msg.entity) {
+            msg.content.iter().for_each(|(k, v)| {
+                if let Some(values) = uniques_for_entity.get_mut(k) {
+                    values.remove(&format!("{:?}", v));
+                }
+            });
+            let unique_ron =
+                ron::ser::to_string_pretty(&uniqueness_data.clone(), pretty_config_inner())?;
+            unique_data(&unique_ron)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Swaps the unique values of `entity` by the ones rebuilt from its registries. `stale` are
+/// the values registered when the rebuild started that no registry holds anymore, values
+/// registered during the rebuild are kept.
+pub struct SwapUniqueValues {
+    pub entity: String,
+    pub values: HashMap<String, HashSet<String>>,
+    pub stale: HashMap<String, HashSet<String>>,
+    pub data: Arc<Mutex<UniquenessContext>>,
+}
+
+impl Message for SwapUniqueValues {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<SwapUniqueValues> for Executor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: SwapUniqueValues, _: &mut Self::Context) -> Self::Result {
+        let mut uniqueness_data = if let Ok(guard) = msg.data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+
+        if let Some(uniques_for_entity) = uniqueness_data.get_mut(&msg.entity) {
+            for (key, values) in msg.values {
+                let current = uniques_for_entity.entry(key.clone()).or_default();
+                if let Some(stale) = msg.stale.get(&key) {
+                    current.retain(|value| !stale.contains(value));
+                }
+                current.extend(values);
+            }
+            let unique_ron =
+                ron::ser::to_string_pretty(&uniqueness_data.clone(), pretty_config_inner())?;
+            unique_data(&unique_ron)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Same verification as `CheckForUniqueKeys` without registering the new values.
+pub struct VerifyUniqueKeys {
+    pub entity: String,
+    pub content: HashMap<String, Types>,
+    pub uniqueness: Arc<Arc<Mutex<UniquenessContext>>>,
+}
+
+impl Message for VerifyUniqueKeys {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<VerifyUniqueKeys> for Executor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: VerifyUniqueKeys, _: &mut Self::Context) -> Self::Result {
+        let uniqueness_data = if let Ok(guard) = msg.uniqueness.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+
+        if let Some(uniques_for_entity) = uniqueness_data.get(&msg.entity) {
+            msg.content
+                .iter()
+                .try_for_each(|(k, v)| match uniques_for_entity.get(k) {
+                    Some(val) if val.contains(&format!("{:?}", v)) => Err(Error::DuplicatedUnique(
+                        msg.entity.to_owned(),
+                        k.to_owned(),
+                        v.to_owned(),
+                    )),
+                    _ => Ok(()),
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -4,9 +4,21 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use uuid::Uuid;
 use wql::Types;
 
-use crate::{core::query::filter_keys_and_hash, io::read::read_date_log, model::error::Error};
+use crate::{
+    core::query::filter_keys_and_hash,
+    io::{paths::date_logs_until, read::read_date_log},
+    model::error::Error,
+};
 
 use super::wql::Executor;
+
+/// States of an entity id in a day log by the date they were logged.
+pub type DayStates = BTreeMap<DateTime<Utc>, HashMap<String, Types>>;
+
+/// Reads the states of an entity id inside a time range from a single day log.
+/// Long ranges are read one day log at a time, so each day can be sent to the
+/// client as soon as it is read. States logged before a `CLEAR ENTITY` of the entity
+/// in the range are dropped and the `bool` of the result is `true`.
 pub struct ReadEntityRange {
     entity_name: String,
     uuid: Uuid,
@@ -34,82 +46,299 @@ impl ReadEntityRange {
 }
 
 impl Message for ReadEntityRange {
-    type Result = Result<BTreeMap<DateTime<Utc>, HashMap<String, Types>>, Error>;
+    type Result = Result<(DayStates, bool), Error>;
 }
 
 impl Handler<ReadEntityRange> for Executor {
-    type Result = Result<BTreeMap<DateTime<Utc>, HashMap<String, Types>>, Error>;
+    type Result = Result<(DayStates, bool), Error>;
 
     fn handle(&mut self, msg: ReadEntityRange, _: &mut Self::Context) -> Self::Result {
-        use ron::de::from_str;
-        let date_log = msg.date_log.clone();
-        let date_log = read_date_log(date_log)?;
-        let mut hm = BTreeMap::new();
-        date_log.split(';').try_for_each(|line| {
-            let fractions = line.split('|').collect::<Vec<&str>>();
+        read_entity_range(&msg)
+    }
+}
 
-            if fractions[0].eq("INSERT")
-                && fractions[3].eq(&msg.entity_name)
-                && fractions[2].eq(&msg.uuid.to_string())
-            {
-                let state = fractions
-                    .last()
-                    .ok_or(Error::FailedToParseState)?
-                    .to_owned();
-                let date: Result<DateTime<Utc>, Error> = match from_str(fractions[1]) {
+/// Date of the `CLEAR_ENTITY|date|entity;` line of a day log, if it clears `entity`.
+fn cleared_at(fractions: &[&str], entity: &str) -> Result<Option<DateTime<Utc>>, Error> {
+    if !fractions[0].eq("CLEAR_ENTITY") || fractions.get(2) != Some(&entity) {
+        return Ok(None);
+    }
+    ron::de::from_str(fractions[1])
+        .map(Some)
+        .map_err(|_| Error::FailedToParseDate)
+}
+
+fn read_entity_range(msg: &ReadEntityRange) -> Result<(DayStates, bool), Error> {
+    use ron::de::from_str;
+    let mut hm = BTreeMap::new();
+    let mut cleared = false;
+    let date_log = match read_date_log(msg.date_log.clone()) {
+        Ok(date_log) => date_log,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((hm, cleared)),
+        Err(e) => return Err(Error::Io(e)),
+    };
+    if !date_log.contains(&msg.uuid.to_string()) && !date_log.contains("CLEAR_ENTITY|") {
+        return Ok((hm, cleared));
+    }
+    date_log.split(';').try_for_each(|line| {
+        let fractions = line.split('|').collect::<Vec<&str>>();
+
+        if let Some(date) = cleared_at(&fractions, &msg.entity_name)? {
+            if date > msg.start_date && date < msg.end_date {
+                hm.clear();
+                cleared = true;
+            }
+        } else if fractions[0].eq("INSERT")
+            && fractions[3].eq(&msg.entity_name)
+            && fractions[2].eq(&msg.uuid.to_string())
+        {
+            let state = fractions
+                .last()
+                .ok_or(Error::FailedToParseState)?
+                .to_owned();
+            let date: Result<DateTime<Utc>, Error> = match from_str(fractions[1]) {
+                Ok(x) => Ok(x),
+                Err(_) => Err(Error::FailedToParseDate),
+            };
+            let date = date?;
+
+            if date > msg.start_date && date < msg.end_date {
+                let resp: Result<HashMap<String, Types>, Error> = match from_str(state) {
                     Ok(x) => Ok(x),
-                    Err(_) => Err(Error::FailedToParseDate),
+                    Err(_) => Err(Error::FailedToParseState),
                 };
-                let date = date?;
-
-                if date > msg.start_date && date < msg.end_date {
-                    let resp: Result<HashMap<String, Types>, Error> = match from_str(state) {
-                        Ok(x) => Ok(x),
-                        Err(_) => Err(Error::FailedToParseState),
-                    };
-                    match resp {
-                        Ok(map) => {
-                            let map = filter_keys_and_hash(map, None);
-                            hm.insert(date, map);
-                        }
-                        Err(e) => return Err(e),
-                    };
-                }
-            } else if (fractions[0].eq("UPDATE_SET") || fractions[0].eq("UPDATE_CONTENT"))
-                && fractions[3].eq(&msg.entity_name)
-                && fractions[2].eq(&msg.uuid.to_string())
-            {
-                let state = fractions
-                    .get(fractions.len() - 2)
-                    .ok_or(Error::FailedToParseState)?
-                    .to_owned();
-                let date: Result<DateTime<Utc>, Error> = match from_str(fractions[1]) {
+                match resp {
+                    Ok(map) => {
+                        let map = filter_keys_and_hash(map, None, false);
+                        hm.insert(date, map);
+                    }
+                    Err(e) => return Err(e),
+                };
+            }
+        } else if (fractions[0].eq("UPDATE_SET")
+            || fractions[0].eq("UPDATE_CONTENT")
+            || fractions[0].eq("REPLACE"))
+            && fractions[3].eq(&msg.entity_name)
+            && fractions[2].eq(&msg.uuid.to_string())
+        {
+            let state = fractions
+                .get(fractions.len() - 2)
+                .ok_or(Error::FailedToParseState)?
+                .to_owned();
+            let date: Result<DateTime<Utc>, Error> = match from_str(fractions[1]) {
+                Ok(x) => Ok(x),
+                Err(_) => Err(Error::FailedToParseDate),
+            };
+            let date = date?;
+
+            if date > msg.start_date && date < msg.end_date {
+                let resp: Result<HashMap<String, Types>, Error> = match from_str(state) {
                     Ok(x) => Ok(x),
-                    Err(_) => Err(Error::FailedToParseDate),
+                    Err(_) => Err(Error::FailedToParseState),
+                };
+                match resp {
+                    Ok(map) => {
+                        let map = filter_keys_and_hash(map, None, false);
+                        hm.insert(date, map);
+                    }
+                    Err(e) => return Err(e),
                 };
-                let date = date?;
-
-                if date > msg.start_date && date < msg.end_date {
-                    let resp: Result<HashMap<String, Types>, Error> = match from_str(state) {
-                        Ok(x) => Ok(x),
-                        Err(_) => Err(Error::FailedToParseState),
-                    };
-                    match resp {
-                        Ok(map) => {
-                            let map = filter_keys_and_hash(map, None);
-                            hm.insert(date, map);
-                        }
-                        Err(e) => return Err(e),
-                    };
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok((hm, cleared))
+}
+
+/// Reads the last `n` states of an entity id, newest first, scanning the day logs
+/// backward from today until `n` states are found or the entity was cleared.
+pub struct ReadEntityLastN {
+    entity_name: String,
+    uuid: Uuid,
+    n: usize,
+}
+
+impl ReadEntityLastN {
+    pub fn new(entity_name: &str, uuid: Uuid, n: usize) -> Self {
+        Self {
+            entity_name: entity_name.to_owned(),
+            uuid,
+            n,
+        }
+    }
+}
+
+impl Message for ReadEntityLastN {
+    type Result = Result<Vec<(DateTime<Utc>, HashMap<String, Types>)>, Error>;
+}
+
+impl Handler<ReadEntityLastN> for Executor {
+    type Result = Result<Vec<(DateTime<Utc>, HashMap<String, Types>)>, Error>;
+
+    fn handle(&mut self, msg: ReadEntityLastN, _: &mut Self::Context) -> Self::Result {
+        let mut states = Vec::new();
+        for date_log in date_logs_until(&msg.entity_name, Utc::now()) {
+            if states.len() >= msg.n {
+                break;
+            }
+            let (day_states, cleared) = read_entity_range(&ReadEntityRange::new(
+                &msg.entity_name,
+                msg.uuid,
+                chrono::MIN_DATETIME,
+                chrono::MAX_DATETIME,
+                date_log,
+            ))?;
+            let missing = msg.n - states.len();
+            states.extend(day_states.into_iter().rev().take(missing));
+            if cleared {
+                break;
+            }
+        }
+
+        Ok(states)
+    }
+}
+
+/// Last state of an entity id logged at or before `date`, scanning the day logs backward
+/// from that day until one of them has a state of the id or clears the entity.
+pub struct ReadEntityIdUntil {
+    entity_name: String,
+    uuid: Uuid,
+    date: DateTime<Utc>,
+}
+
+impl ReadEntityIdUntil {
+    pub fn new(entity_name: &str, uuid: Uuid, date: DateTime<Utc>) -> Self {
+        Self {
+            entity_name: entity_name.to_owned(),
+            uuid,
+            date,
+        }
+    }
+}
+
+impl Message for ReadEntityIdUntil {
+    type Result = Result<Option<HashMap<String, Types>>, Error>;
+}
+
+impl Handler<ReadEntityIdUntil> for Executor {
+    type Result = Result<Option<HashMap<String, Types>>, Error>;
+
+    fn handle(&mut self, msg: ReadEntityIdUntil, _: &mut Self::Context) -> Self::Result {
+        let end_date = msg.date + chrono::Duration::nanoseconds(1);
+        for date_log in date_logs_until(&msg.entity_name, msg.date) {
+            let (states, cleared) = read_entity_range(&ReadEntityRange::new(
+                &msg.entity_name,
+                msg.uuid,
+                chrono::MIN_DATETIME,
+                end_date,
+                date_log,
+            ))?;
+            if let Some((_, state)) = states.into_iter().next_back() {
+                return Ok(Some(state));
+            }
+            if cleared {
+                break;
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Date of the last `CLEAR ENTITY` of an entity before `end_date`, reading `date_logs`
+/// from the newest until one of them has it.
+pub struct ReadLastClear {
+    entity_name: String,
+    end_date: DateTime<Utc>,
+    date_logs: Vec<String>,
+}
+
+impl ReadLastClear {
+    pub fn new(entity_name: &str, end_date: DateTime<Utc>, date_logs: Vec<String>) -> Self {
+        Self {
+            entity_name: entity_name.to_owned(),
+            end_date,
+            date_logs,
+        }
+    }
+}
+
+impl Message for ReadLastClear {
+    type Result = Result<Option<DateTime<Utc>>, Error>;
+}
+
+impl Handler<ReadLastClear> for Executor {
+    type Result = Result<Option<DateTime<Utc>>, Error>;
+
+    fn handle(&mut self, msg: ReadLastClear, _: &mut Self::Context) -> Self::Result {
+        for date_log in msg.date_logs.iter().rev() {
+            let date_log = match read_date_log(date_log.to_owned()) {
+                Ok(date_log) => date_log,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::Io(e)),
+            };
+            if !date_log.contains("CLEAR_ENTITY|") {
+                continue;
+            }
+            let mut last = None;
+            for line in date_log.split(';') {
+                let fractions = line.split('|').collect::<Vec<&str>>();
+                match cleared_at(&fractions, &msg.entity_name)? {
+                    Some(date) if date < msg.end_date => last = Some(date),
+                    _ => (),
                 }
             }
-            Ok(())
-        })?;
+            if last.is_some() {
+                return Ok(last);
+            }
+        }
+        Ok(None)
+    }
+}
 
-        Ok(hm)
+/// Date of the last entry of each id of an entity in a day log.
+pub struct ReadEntityWrites {
+    entity_name: String,
+    date_log: String,
+}
+
+impl ReadEntityWrites {
+    pub fn new(entity_name: &str, date_log: String) -> Self {
+        Self {
+            entity_name: entity_name.to_owned(),
+            date_log,
+        }
     }
 }
 
+impl Message for ReadEntityWrites {
+    type Result = Result<HashMap<Uuid, DateTime<Utc>>, Error>;
+}
+
+impl Handler<ReadEntityWrites> for Executor {
+    type Result = Result<HashMap<Uuid, DateTime<Utc>>, Error>;
+
+    fn handle(&mut self, msg: ReadEntityWrites, _: &mut Self::Context) -> Self::Result {
+        let date_log = read_date_log(msg.date_log.clone())?;
+        let mut writes = HashMap::new();
+        for line in date_log.split(';') {
+            let fractions = line.split('|').collect::<Vec<&str>>();
+            if fractions.len() < 4 || !fractions[3].eq(&msg.entity_name) {
+                continue;
+            }
+            if let Ok(uuid) = Uuid::parse_str(fractions[2]) {
+                let date = ron::de::from_str(fractions[1]).map_err(|_| Error::FailedToParseDate)?;
+                writes.insert(uuid, date);
+            }
+        }
+
+        Ok(writes)
+    }
+}
+
+/// States of the entity ids in a day log, ids logged before a `CLEAR ENTITY` of the
+/// entity are dropped.
 pub struct ReadEntitiesAt {
     entity_name: String,
     date_log: String,
@@ -140,7 +369,9 @@ impl Handler<ReadEntitiesAt> for Executor {
         let mut hm = HashMap::new();
         date_log.split(';').try_for_each(|line| {
             let fractions = line.split('|').collect::<Vec<&str>>();
-            if fractions[0].eq("INSERT") && fractions[3].eq(&msg.entity_name) {
+            if cleared_at(&fractions, &msg.entity_name)?.is_some() {
+                hm.clear();
+            } else if fractions[0].eq("INSERT") && fractions[3].eq(&msg.entity_name) {
                 let state = fractions
                     .last()
                     .ok_or(Error::FailedToParseState)?
@@ -152,12 +383,14 @@ impl Handler<ReadEntitiesAt> for Executor {
                 };
                 match resp {
                     Ok(map) => {
-                        let map = filter_keys_and_hash(map, msg.keys.clone());
+                        let map = filter_keys_and_hash(map, msg.keys.clone(), false);
                         hm.insert(fractions[2].to_owned(), map);
                     }
                     Err(e) => return Err(e),
                 };
-            } else if (fractions[0].eq("UPDATE_SET") || fractions[0].eq("UPDATE_CONTENT"))
+            } else if (fractions[0].eq("UPDATE_SET")
+                || fractions[0].eq("UPDATE_CONTENT")
+                || fractions[0].eq("REPLACE"))
                 && fractions[3].eq(&msg.entity_name)
             {
                 let state = fractions
@@ -171,7 +404,7 @@ impl Handler<ReadEntitiesAt> for Executor {
                 };
                 match resp {
                     Ok(map) => {
-                        let map = filter_keys_and_hash(map, msg.keys.clone());
+                        let map = filter_keys_and_hash(map, msg.keys.clone(), false);
                         hm.insert(fractions[2].to_owned(), map);
                     }
                     Err(e) => return Err(e),
@@ -184,6 +417,7 @@ impl Handler<ReadEntitiesAt> for Executor {
     }
 }
 
+/// Last state of an entity id in a day log, empty if the entity was cleared after it.
 pub struct ReadEntityIdAt {
     entity_name: String,
     uuid: Uuid,
@@ -215,7 +449,9 @@ impl Handler<ReadEntityIdAt> for Executor {
         date_log.split(';').try_for_each(|line| {
             let fractions = line.split('|').collect::<Vec<&str>>();
 
-            if fractions[0].eq("INSERT")
+            if cleared_at(&fractions, &msg.entity_name)?.is_some() {
+                hm.clear();
+            } else if fractions[0].eq("INSERT")
                 && fractions[3].eq(&msg.entity_name)
                 && fractions[2].eq(&msg.uuid.to_string())
             {
@@ -234,7 +470,9 @@ impl Handler<ReadEntityIdAt> for Executor {
                     }
                     Err(e) => return Err(e),
                 };
-            } else if (fractions[0].eq("UPDATE_SET") || fractions[0].eq("UPDATE_CONTENT"))
+            } else if (fractions[0].eq("UPDATE_SET")
+                || fractions[0].eq("UPDATE_CONTENT")
+                || fractions[0].eq("REPLACE"))
                 && fractions[3].eq(&msg.entity_name)
                 && fractions[2].eq(&msg.uuid.to_string())
             {
@@ -260,3 +498,126 @@ impl Handler<ReadEntityIdAt> for Executor {
         Ok(hm)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+    use std::str::FromStr;
+
+    #[actix_rt::test]
+    async fn read_last_n_newest_first() {
+        let actor = Executor::new().start();
+        let uuid = Uuid::from_str("fb1ccddb-2465-4504-a4a4-e28ee75c7981").unwrap();
+
+        let states = actor
+            .send(ReadEntityLastN::new("test_update", uuid, 3))
+            .await
+            .unwrap()
+            .unwrap();
+        let dates = states.iter().map(|(date, _)| *date).collect::<Vec<_>>();
+
+        assert_eq!(
+            dates,
+            vec![
+                Utc.ymd(2021, 2, 9).and_hms_micro(19, 57, 6, 237_774),
+                Utc.ymd(2021, 2, 9).and_hms_micro(16, 57, 6, 237_774),
+                Utc.ymd(2021, 2, 9).and_hms_micro(16, 54, 6, 237_774),
+            ]
+        );
+
+        let states = actor
+            .send(ReadEntityLastN::new("test_update", uuid, 10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(states.len(), 4);
+    }
+
+    #[actix_rt::test]
+    async fn read_id_until_date() {
+        let actor = Executor::new().start();
+        let uuid = Uuid::from_str("fb1ccddb-2465-4504-a4a4-e28ee75c7981").unwrap();
+        let name = |date| {
+            let actor = actor.clone();
+            async move {
+                actor
+                    .send(ReadEntityIdUntil::new("test_update", uuid, date))
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .map(|state| state["f"].clone())
+            }
+        };
+
+        assert_eq!(
+            name(Utc.ymd(2021, 2, 9).and_hms_micro(16, 57, 6, 237_774)).await,
+            Some(Types::String("JULIA".to_string()))
+        );
+        assert_eq!(
+            name(Utc.ymd(2021, 2, 10).and_hms(0, 0, 0)).await,
+            Some(Types::String("NAOMI".to_string()))
+        );
+        assert_eq!(name(Utc.ymd(2021, 2, 1).and_hms(0, 0, 0)).await, None);
+    }
+
+    #[actix_rt::test]
+    async fn readers_drop_states_before_clear() {
+        let date_log = "data/when_clear_test.txt";
+        let uuid = "fb1ccddb-2465-4504-a4a4-e28ee75c7981";
+        std::fs::write(
+            date_log,
+            format!(
+                "INSERT|\"2021-02-10T10:00:00Z\"|{uuid}|test_clear|{{\"a\": Integer(1),}};\
+                 CLEAR_ENTITY|\"2021-02-10T11:00:00Z\"|test_clear;\
+                 INSERT|\"2021-02-10T12:00:00Z\"|{other}|test_clear|{{\"a\": Integer(2),}};",
+                uuid = uuid,
+                other = "0aa8930c-6dc8-410c-b544-fec099b48efd"
+            ),
+        )
+        .unwrap();
+        let actor = Executor::new().start();
+        let uuid = Uuid::from_str(uuid).unwrap();
+
+        let entities = actor
+            .send(ReadEntitiesAt::new("test_clear", date_log.to_owned(), None))
+            .await
+            .unwrap()
+            .unwrap();
+        let state = actor
+            .send(ReadEntityIdAt::new("test_clear", uuid, date_log.to_owned()))
+            .await
+            .unwrap()
+            .unwrap();
+        let (states, cleared) = actor
+            .send(ReadEntityRange::new(
+                "test_clear",
+                uuid,
+                chrono::MIN_DATETIME,
+                chrono::MAX_DATETIME,
+                date_log.to_owned(),
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        let last_clear = actor
+            .send(ReadLastClear::new(
+                "test_clear",
+                chrono::MAX_DATETIME,
+                vec![date_log.to_owned()],
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(date_log).unwrap();
+
+        assert_eq!(
+            entities.keys().collect::<Vec<_>>(),
+            vec!["0aa8930c-6dc8-410c-b544-fec099b48efd"]
+        );
+        assert!(state.is_empty());
+        assert!(states.is_empty());
+        assert!(cleared);
+        assert_eq!(last_clear, Some(Utc.ymd(2021, 2, 10).and_hms(11, 0, 0)));
+    }
+}
@@ -4,8 +4,13 @@ use std::io::Error;
 use uuid::Uuid;
 
 use crate::core::wql::{
-    create_entity, delete_entity_content, evict_entity_content, evict_entity_id_content,
-    insert_entity_content, update_content_entity_content, update_set_entity_content,
+    clear_entity_content, create_entity, delete_entity_content, evict_entity_content,
+    evict_entity_id_content, insert_entity_content, replace_entity_content,
+    update_content_entity_content, update_set_entity_content,
+};
+use crate::io::{
+    paths::date_log,
+    write::{checksum, write_block, write_to_log},
 };
 
 pub struct Executor;
@@ -40,9 +45,10 @@ impl Handler<CreateEntity> for Executor {
     type Result = Result<(usize, bool), Error>;
 
     fn handle(&mut self, msg: CreateEntity, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let entity = create_entity(&msg.name);
-        write_to_log(&entity)
+        let (_, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &Utc::now()), &entity)?;
+        Ok((bytes_written, is_empty))
     }
 }
 
@@ -65,17 +71,24 @@ impl InsertEntityContent {
 }
 
 impl Message for InsertEntityContent {
-    type Result = Result<(DateTime<Utc>, Uuid, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, Uuid, usize, bool, usize, u32), Error>;
 }
 
 impl Handler<InsertEntityContent> for Executor {
-    type Result = Result<(DateTime<Utc>, Uuid, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, Uuid, usize, bool, usize, u32), Error>;
 
     fn handle(&mut self, msg: InsertEntityContent, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let (date, uuid, content) = insert_entity_content(&msg);
-        let (bytes_written, is_empty) = write_to_log(&content)?;
-        Ok((date, uuid, bytes_written, is_empty))
+        let (offset, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &date), &content)?;
+        Ok((
+            date,
+            uuid,
+            bytes_written,
+            is_empty,
+            offset,
+            checksum(content.as_bytes()),
+        ))
     }
 }
 
@@ -109,17 +122,63 @@ impl UpdateSetEntityContent {
 }
 
 impl Message for UpdateSetEntityContent {
-    type Result = Result<(DateTime<Utc>, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
 }
 
 impl Handler<UpdateSetEntityContent> for Executor {
-    type Result = Result<(DateTime<Utc>, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
 
     fn handle(&mut self, msg: UpdateSetEntityContent, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let (date, content) = update_set_entity_content(&msg);
-        let (bytes_written, is_empty) = write_to_log(&content)?;
-        Ok((date, bytes_written, is_empty))
+        let (offset, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &date), &content)?;
+        Ok((
+            date,
+            bytes_written,
+            is_empty,
+            offset,
+            checksum(content.as_bytes()),
+        ))
+    }
+}
+
+/// Logs the content of a `REPLACE` as the whole new state of the entity id.
+pub struct ReplaceEntityContent(pub UpdateSetEntityContent);
+
+impl Message for ReplaceEntityContent {
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
+}
+
+impl Handler<ReplaceEntityContent> for Executor {
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
+
+    fn handle(&mut self, msg: ReplaceEntityContent, _: &mut Self::Context) -> Self::Result {
+        let (date, content) = replace_entity_content(&msg.0);
+        let (offset, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.0.name, &date), &content)?;
+        Ok((
+            date,
+            bytes_written,
+            is_empty,
+            offset,
+            checksum(content.as_bytes()),
+        ))
+    }
+}
+
+/// Appends the day log entries of a `BEGIN` block, `(date_log, offset, entries)` for each
+/// day log it writes. Either all of them are written or none.
+pub struct WriteBlock(pub Vec<(String, usize, String)>);
+
+impl Message for WriteBlock {
+    type Result = Result<Vec<bool>, Error>;
+}
+
+impl Handler<WriteBlock> for Executor {
+    type Result = Result<Vec<bool>, Error>;
+
+    fn handle(&mut self, msg: WriteBlock, _: &mut Self::Context) -> Self::Result {
+        write_block(&msg.0)
     }
 }
 
@@ -151,17 +210,23 @@ impl UpdateContentEntityContent {
 }
 
 impl Message for UpdateContentEntityContent {
-    type Result = Result<(DateTime<Utc>, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
 }
 
 impl Handler<UpdateContentEntityContent> for Executor {
-    type Result = Result<(DateTime<Utc>, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
 
     fn handle(&mut self, msg: UpdateContentEntityContent, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let (date, content) = update_content_entity_content(&msg);
-        let (bytes_written, is_empty) = write_to_log(&content)?;
-        Ok((date, bytes_written, is_empty))
+        let (offset, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &date), &content)?;
+        Ok((
+            date,
+            bytes_written,
+            is_empty,
+            offset,
+            checksum(content.as_bytes()),
+        ))
     }
 }
 
@@ -184,17 +249,23 @@ impl DeleteId {
 }
 
 impl Message for DeleteId {
-    type Result = Result<(DateTime<Utc>, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
 }
 
 impl Handler<DeleteId> for Executor {
-    type Result = Result<(DateTime<Utc>, usize, bool), Error>;
+    type Result = Result<(DateTime<Utc>, usize, bool, usize, u32), Error>;
 
     fn handle(&mut self, msg: DeleteId, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let (date, content) = delete_entity_content(&msg);
-        let (bytes_written, is_empty) = write_to_log(&content)?;
-        Ok((date, bytes_written, is_empty))
+        let (offset, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &date), &content)?;
+        Ok((
+            date,
+            bytes_written,
+            is_empty,
+            offset,
+            checksum(content.as_bytes()),
+        ))
     }
 }
 
@@ -218,9 +289,37 @@ impl Handler<EvictEntity> for Executor {
     type Result = Result<(usize, bool), Error>;
 
     fn handle(&mut self, msg: EvictEntity, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let content = evict_entity_content(&msg.name);
-        Ok(write_to_log(&content)?)
+        let (_, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &Utc::now()), &content)?;
+        Ok((bytes_written, is_empty))
+    }
+}
+
+pub struct ClearEntity {
+    pub name: String,
+}
+
+impl ClearEntity {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+        }
+    }
+}
+
+impl Message for ClearEntity {
+    type Result = Result<(usize, bool), Error>;
+}
+
+impl Handler<ClearEntity> for Executor {
+    type Result = Result<(usize, bool), Error>;
+
+    fn handle(&mut self, msg: ClearEntity, _: &mut Self::Context) -> Self::Result {
+        let content = clear_entity_content(&msg.name);
+        let (_, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &Utc::now()), &content)?;
+        Ok((bytes_written, is_empty))
     }
 }
 
@@ -246,9 +345,10 @@ impl Handler<EvictEntityId> for Executor {
     type Result = Result<(usize, bool), Error>;
 
     fn handle(&mut self, msg: EvictEntityId, _: &mut Self::Context) -> Self::Result {
-        use crate::io::write::write_to_log;
         let content = evict_entity_id_content(&msg);
-        Ok(write_to_log(&content)?)
+        let (_, bytes_written, is_empty) =
+            write_to_log(&date_log(&msg.name, &Utc::now()), &content)?;
+        Ok((bytes_written, is_empty))
     }
 }
 
@@ -260,8 +360,8 @@ mod test {
     use crate::io::read;
 
     use super::{
-        CreateEntity, DeleteId, EvictEntity, EvictEntityId, Executor, InsertEntityContent,
-        UpdateSetEntityContent,
+        ClearEntity, CreateEntity, DeleteId, EvictEntity, EvictEntityId, Executor,
+        InsertEntityContent, UpdateSetEntityContent,
     };
 
     #[actix_rt::test]
@@ -365,6 +465,17 @@ mod test {
         read::assert_content("evict-my-entity");
     }
 
+    #[actix_rt::test]
+    async fn clear_test() {
+        let clear = ClearEntity::new("clear-my-entity");
+        let actor = Executor::new().start();
+
+        let resp = actor.send(clear).await.unwrap();
+        assert!(resp.is_ok());
+        read::assert_content("CLEAR_ENTITY|");
+        read::assert_content("clear-my-entity");
+    }
+
     #[actix_rt::test]
     async fn evict_id_test() {
         let uuid = uuid::Uuid::new_v4();
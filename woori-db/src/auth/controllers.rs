@@ -1,45 +1,47 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
 
-use actix_web::{web, HttpResponse, Responder};
-use bcrypt::hash;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use chrono::Utc;
 use ron::de::from_str;
 use uuid::Uuid;
+use wql::Types;
 
 use crate::{
-    core::pretty_config_output,
+    core::{hash_algorithm, read_lock, MediaType, OutputFormat},
     model::{
         error::{error_to_http, Error},
-        DataI64,
+        DataLocalContext,
     },
-    repository::local::{SessionContext, SessionInfo},
+    repository::local::{SessionContext, SessionInfo, StateInfo},
 };
 
 use super::{
     io,
-    models::{AdminInfo, User},
-    schemas::{CreateUserWithAdmin, DeleteUsersWithAdmin, UserId},
+    models::{AdminInfo, SessionExpiration, User},
+    schemas::{
+        ActiveSession, AdminCredentials, CreateUserWithAdmin, DeleteUsersWithAdmin, EntityMemory,
+        RevokeSessionWithAdmin, UserId,
+    },
 };
 
-pub async fn create_user(body: String, admin: web::Data<AdminInfo>) -> impl Responder {
+pub async fn create_user(
+    req: HttpRequest,
+    body: String,
+    admin: web::Data<AdminInfo>,
+) -> impl Responder {
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req);
     match create_user_controller(body, admin).await {
         Err(e) => error_to_http(&e),
-        Ok(body) => {
-            #[cfg(feature = "json")]
-            match serde_json::to_string(&body) {
-                Ok(ron) => HttpResponse::Created().body(ron),
-                Err(_) => {
-                    HttpResponse::ServiceUnavailable().body(Error::FailedToCreateUser.to_string())
-                }
+        Ok(body) => match format.write(media, &body) {
+            Ok(body) => HttpResponse::Created().body(body),
+            Err(_) => {
+                HttpResponse::ServiceUnavailable().body(Error::FailedToCreateUser.to_string())
             }
-            #[cfg(not(feature = "json"))]
-            match ron::ser::to_string_pretty(&body, pretty_config_output()) {
-                Ok(ron) => HttpResponse::Created().body(ron),
-                Err(_) => {
-                    HttpResponse::ServiceUnavailable().body(Error::FailedToCreateUser.to_string())
-                }
-            }
-        }
+        },
     }
 }
 
@@ -61,7 +63,9 @@ pub async fn create_user_controller(
     if let Ok(cred) = credentials {
         if admin.is_valid_hash(&cred.admin_password, &cred.admin_id) {
             let new_user_id = Uuid::new_v4();
-            if let Ok(new_user_hash) = hash(&cred.user_info.user_password, admin.cost()) {
+            if let Ok(new_user_hash) =
+                hash_algorithm().hash(&cred.user_info.user_password, Some(admin.cost()))
+            {
                 let user = User::new(new_user_id, new_user_hash, cred.user_info.role);
                 if io::to_users_log(&user).is_ok() {
                     Ok(UserId {
@@ -83,25 +87,21 @@ pub async fn create_user_controller(
     }
 }
 
-pub async fn delete_users(body: String, admin: web::Data<AdminInfo>) -> impl Responder {
+pub async fn delete_users(
+    req: HttpRequest,
+    body: String,
+    admin: web::Data<AdminInfo>,
+) -> impl Responder {
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req);
     match delete_users_controller(body, admin).await {
         Err(e) => error_to_http(&e),
-        Ok(body) => {
-            #[cfg(feature = "json")]
-            match serde_json::to_string(&body) {
-                Ok(ron) => HttpResponse::Created().body(ron),
-                Err(_) => {
-                    HttpResponse::ServiceUnavailable().body(Error::FailedToDeleteUsers.to_string())
-                }
-            }
-            #[cfg(not(feature = "json"))]
-            match ron::ser::to_string_pretty(&body, pretty_config_output()) {
-                Ok(ron) => HttpResponse::Created().body(ron),
-                Err(_) => {
-                    HttpResponse::ServiceUnavailable().body(Error::FailedToDeleteUsers.to_string())
-                }
+        Ok(body) => match format.write(media, &body) {
+            Ok(body) => HttpResponse::Created().body(body),
+            Err(_) => {
+                HttpResponse::ServiceUnavailable().body(Error::FailedToDeleteUsers.to_string())
             }
-        }
+        },
     }
 }
 
@@ -139,9 +139,9 @@ pub async fn delete_users_controller(
 pub async fn put_user_session(
     body: String,
     session_context: web::Data<Arc<Mutex<SessionContext>>>,
-    expiration_time: DataI64,
+    session_expiration: web::Data<SessionExpiration>,
 ) -> impl Responder {
-    match put_user_session_controller(body, session_context, expiration_time).await {
+    match put_user_session_controller(body, session_context, session_expiration).await {
         Err(e) => error_to_http(&e),
         Ok(token) => HttpResponse::Created().body(token),
     }
@@ -150,9 +150,8 @@ pub async fn put_user_session(
 pub async fn put_user_session_controller(
     body: String,
     session_context: web::Data<Arc<Mutex<SessionContext>>>,
-    expiration_time: DataI64,
+    session_expiration: web::Data<SessionExpiration>,
 ) -> Result<String, Error> {
-    let exp_time: i64 = *expiration_time.into_inner();
     #[cfg(feature = "json")]
     let ok_user: Result<super::schemas::User, Error> = match serde_json::from_str(&body) {
         Ok(x) => Ok(x),
@@ -165,23 +164,21 @@ pub async fn put_user_session_controller(
     };
 
     if let Ok(user) = ok_user {
+        let exp_time = session_expiration.expiration_time(user.expiration_time)?;
         let user_registry = io::find_user(user.clone()).await;
         if let Ok(reg) = user_registry {
             let (hash, roles) = reg.context();
-            match bcrypt::verify(&(user.user_password), &hash) {
-                Err(_) | Ok(false) => (),
-                Ok(true) => {
-                    if let Ok(mut session) = session_context.lock() {
-                        let token = bcrypt::hash(&Uuid::new_v4().to_string(), 4)
-                            .unwrap_or_else(|_| Uuid::new_v4().to_string());
-                        let expiration = Utc::now() + chrono::Duration::seconds(exp_time);
-
-                        session.insert(token.clone(), SessionInfo::new(expiration, roles));
-
-                        return Ok(token);
-                    }
+            if wql::verify_hash(&user.user_password, &hash) {
+                if let Ok(mut session) = session_context.lock() {
+                    let token = bcrypt::hash(Uuid::new_v4().to_string(), 4)
+                        .unwrap_or_else(|_| Uuid::new_v4().to_string());
+                    let expiration = Utc::now() + chrono::Duration::seconds(exp_time);
+
+                    session.insert(token.clone(), SessionInfo::new(user.id, expiration, roles));
+
+                    return Ok(token);
                 }
-            };
+            }
         }
 
         Err(Error::Unknown)
@@ -192,6 +189,179 @@ pub async fn put_user_session_controller(
     }
 }
 
+pub async fn list_sessions(
+    req: HttpRequest,
+    body: String,
+    admin: web::Data<AdminInfo>,
+    session_context: web::Data<Arc<Mutex<SessionContext>>>,
+) -> impl Responder {
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req);
+    match list_sessions_controller(body, admin, session_context).await {
+        Err(e) => error_to_http(&e),
+        Ok(body) => match format.write(media, &body) {
+            Ok(body) => HttpResponse::Ok().body(body),
+            Err(e) => error_to_http(&e),
+        },
+    }
+}
+
+pub async fn list_sessions_controller(
+    body: String,
+    admin: web::Data<AdminInfo>,
+    session_context: web::Data<Arc<Mutex<SessionContext>>>,
+) -> Result<Vec<ActiveSession>, Error> {
+    #[cfg(feature = "json")]
+    let credentials: AdminCredentials = serde_json::from_str(&body)
+        .map_err(|e| Error::AuthenticationBadRequestBody(e.to_string()))?;
+    #[cfg(not(feature = "json"))]
+    let credentials: AdminCredentials =
+        from_str(&body).map_err(|e| Error::AuthenticationBadRequestBody(e.to_string()))?;
+
+    if !admin.is_valid_hash(&credentials.admin_password, &credentials.admin_id) {
+        return Err(Error::AuthenticationBadRequest);
+    }
+
+    let session = if let Ok(guard) = session_context.lock() {
+        guard
+    } else {
+        return Err(Error::LockData);
+    };
+    let now = Utc::now();
+
+    Ok(session
+        .iter()
+        .filter(|(_, info)| info.expiration() > now)
+        .map(|(token, info)| ActiveSession {
+            token: mask_token(token),
+            user_id: info.user_id(),
+            expiration: info.expiration(),
+            roles: info.roles(),
+        })
+        .collect())
+}
+
+pub async fn revoke_session(
+    body: String,
+    admin: web::Data<AdminInfo>,
+    session_context: web::Data<Arc<Mutex<SessionContext>>>,
+) -> impl Responder {
+    match revoke_session_controller(body, admin, session_context).await {
+        Err(e) => error_to_http(&e),
+        Ok(token) => HttpResponse::Ok().body(token),
+    }
+}
+
+pub async fn revoke_session_controller(
+    body: String,
+    admin: web::Data<AdminInfo>,
+    session_context: web::Data<Arc<Mutex<SessionContext>>>,
+) -> Result<String, Error> {
+    #[cfg(feature = "json")]
+    let credentials: RevokeSessionWithAdmin = serde_json::from_str(&body)
+        .map_err(|e| Error::AuthenticationBadRequestBody(e.to_string()))?;
+    #[cfg(not(feature = "json"))]
+    let credentials: RevokeSessionWithAdmin =
+        from_str(&body).map_err(|e| Error::AuthenticationBadRequestBody(e.to_string()))?;
+
+    if !admin.is_valid_hash(&credentials.admin_password, &credentials.admin_id) {
+        return Err(Error::AuthenticationBadRequest);
+    }
+
+    let mut session = if let Ok(guard) = session_context.lock() {
+        guard
+    } else {
+        return Err(Error::LockData);
+    };
+
+    let revoked = session.remove(&credentials.token).is_some() || {
+        let sessions = session.len();
+        session.retain(|token, _| mask_token(token) != credentials.token);
+        session.len() != sessions
+    };
+    if revoked {
+        Ok(mask_token(&credentials.token))
+    } else {
+        Err(Error::SessionNotFound)
+    }
+}
+
+pub async fn memory_usage(
+    req: HttpRequest,
+    body: String,
+    admin: web::Data<AdminInfo>,
+    local_data: DataLocalContext,
+) -> impl Responder {
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req);
+    match memory_usage_controller(body, admin, local_data).await {
+        Err(e) => error_to_http(&e),
+        Ok(body) => match format.write(media, &body) {
+            Ok(body) => HttpResponse::Ok().body(body),
+            Err(e) => error_to_http(&e),
+        },
+    }
+}
+
+pub async fn memory_usage_controller(
+    body: String,
+    admin: web::Data<AdminInfo>,
+    local_data: DataLocalContext,
+) -> Result<BTreeMap<String, EntityMemory>, Error> {
+    #[cfg(feature = "json")]
+    let credentials: AdminCredentials = serde_json::from_str(&body)
+        .map_err(|e| Error::AuthenticationBadRequestBody(e.to_string()))?;
+    #[cfg(not(feature = "json"))]
+    let credentials: AdminCredentials =
+        from_str(&body).map_err(|e| Error::AuthenticationBadRequestBody(e.to_string()))?;
+
+    if !admin.is_valid_hash(&credentials.admin_password, &credentials.admin_id) {
+        return Err(Error::AuthenticationBadRequest);
+    }
+
+    let local_data = read_lock(&local_data)?;
+
+    Ok(local_data
+        .iter()
+        .map(|(entity, states)| (entity.to_owned(), entity_memory(states.values())))
+        .collect())
+}
+
+fn entity_memory<'a>(states: impl Iterator<Item = &'a StateInfo>) -> EntityMemory {
+    states.fold(
+        EntityMemory {
+            ids: 0,
+            stored_bytes: 0,
+            state_bytes: 0,
+        },
+        |mut memory, (registry, state)| {
+            memory.ids += 1;
+            memory.stored_bytes +=
+                std::mem::size_of::<(Uuid, StateInfo)>() + registry.file_name.len() + state.len();
+            memory.state_bytes += bincode::deserialize::<HashMap<String, Types>>(state)
+                .map(|state| {
+                    state
+                        .iter()
+                        .map(|(k, v)| std::mem::size_of::<String>() + k.len() + v.approx_size())
+                        .sum::<usize>()
+                })
+                .unwrap_or_default();
+            memory
+        },
+    )
+}
+
+/// Only the last 8 characters of a session token are exposed by the admin endpoints, the
+/// masked token listed by `/auth/sessions` also revokes its session.
+fn mask_token(token: &str) -> String {
+    let visible = token
+        .char_indices()
+        .rev()
+        .nth(7)
+        .map_or(token, |(i, _)| &token[i..]);
+    format!("...{}", visible)
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -357,10 +527,15 @@ mod test {
 
 #[cfg(test)]
 mod routes_test_with_auth {
-    use crate::{auth::schemas::UserId, http::routes};
+    use crate::{
+        auth::schemas::{ActiveSession, EntityMemory, UserId},
+        http::routes,
+    };
     use actix_http::body::ResponseBody;
-    use actix_web::{body::Body, test, App};
+    use actix_web::{body::Body, dev::Service, http::StatusCode, test, App};
+    use std::collections::BTreeMap;
     use uuid::Uuid;
+    use wql::Types;
 
     #[ignore]
     #[actix_rt::test]
@@ -416,6 +591,132 @@ mod routes_test_with_auth {
         assert!(body.contains("\"a\": Integer(123)"))
     }
 
+    #[ignore]
+    #[actix_rt::test]
+    async fn list_and_revoke_sessions() {
+        let mut app = test::init_service(App::new().configure(routes)).await;
+        let req = test::TestRequest::post()
+            .set_payload("(admin_id: \"your_admin\",admin_password: \"your_password\",user_info: (user_password: \"my_password\",role: [User,],),)")
+            .uri("/auth/createUser")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let uuid: UserId = ron::de::from_str(&body).unwrap();
+
+        let payload = format!(
+            "(id: \"{}\", user_password: \"my_password\",)",
+            uuid.user_id
+        );
+        let req = test::TestRequest::put()
+            .set_payload(payload)
+            .uri("/auth/putUserSession")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        let raw_token = resp.take_body().as_str().to_string();
+        let token = format!("Bearer {}", raw_token);
+
+        let req = test::TestRequest::get()
+            .set_payload("(admin_id: \"your_admin\",admin_password: \"your_password\",)")
+            .uri("/auth/sessions")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+
+        assert!(resp.status().is_success());
+        assert!(!body.contains(&raw_token));
+        let sessions: Vec<ActiveSession> = ron::de::from_str(&body).unwrap();
+        let listed = sessions
+            .iter()
+            .find(|session| session.user_id == uuid.user_id)
+            .unwrap();
+
+        let payload = format!(
+            "(admin_id: \"your_admin\",admin_password: \"your_password\", token: \"{}\",)",
+            listed.token
+        );
+        let req = test::TestRequest::delete()
+            .set_payload(payload)
+            .uri("/auth/sessions")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .header("Authorization", token)
+            .set_payload("CREATE ENTITY token_revoked")
+            .uri("/wql/tx")
+            .to_request();
+        let resp = app.call(req).await;
+        assert_eq!(
+            resp.err()
+                .unwrap()
+                .as_response_error()
+                .error_response()
+                .status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[ignore]
+    #[actix_rt::test]
+    async fn memory_usage_per_entity() {
+        let mut app = test::init_service(App::new().configure(routes)).await;
+        let req = test::TestRequest::post()
+            .set_payload("(admin_id: \"your_admin\",admin_password: \"your_password\",user_info: (user_password: \"my_password\",role: [User,],),)")
+            .uri("/auth/createUser")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let uuid: UserId = ron::de::from_str(&body).unwrap();
+
+        let payload = format!(
+            "(id: \"{}\", user_password: \"my_password\",)",
+            uuid.user_id
+        );
+        let req = test::TestRequest::put()
+            .set_payload(payload)
+            .uri("/auth/putUserSession")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        let token = format!("Bearer {}", resp.take_body().as_str());
+
+        for payload in &[
+            "CREATE ENTITY token_memory",
+            "INSERT {a: 123, b: \"hello\",} INTO token_memory",
+            "INSERT {a: 456, b: \"world\",} INTO token_memory",
+        ] {
+            let req = test::TestRequest::post()
+                .header("Content-Type", "application/wql")
+                .header("Authorization", token.clone())
+                .set_payload(*payload)
+                .uri("/wql/tx")
+                .to_request();
+            let _ = test::call_service(&mut app, req).await;
+        }
+
+        let req = test::TestRequest::get()
+            .set_payload("(admin_id: \"your_admin\",admin_password: \"your_password\",)")
+            .uri("/auth/memory")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        let memory: BTreeMap<String, EntityMemory> = ron::de::from_str(&body).unwrap();
+
+        let entity = &memory["token_memory"];
+        assert_eq!(entity.ids, 2);
+        assert!(entity.stored_bytes > 0);
+        assert!(entity.state_bytes >= 2 * (2 * std::mem::size_of::<Types>() + 5));
+
+        let req = test::TestRequest::get()
+            .set_payload("(admin_id: \"your_admin\",admin_password: \"wrong_password\",)")
+            .uri("/auth/memory")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_client_error());
+    }
+
     #[ignore]
     #[actix_rt::test]
     async fn history_with_token() {
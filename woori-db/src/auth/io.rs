@@ -3,8 +3,8 @@ use std::{
     io::{BufRead, BufReader, Seek, SeekFrom, Write},
 };
 
-use crate::model::error::Error;
-use bcrypt::{hash, DEFAULT_COST};
+use crate::{core::hash_algorithm, io::paths::data_file, model::error::Error};
+use bcrypt::DEFAULT_COST;
 use chrono::{DateTime, Utc};
 use ron::from_str;
 use uuid::Uuid;
@@ -28,7 +28,7 @@ pub fn read_admin_info() -> Result<AdminInfo, Error> {
         std::env::var("AUTH_HASHING_COST").map_err(|_| Error::AdminNotConfigured)?;
     let cost = auth_hashing_cost.parse::<u32>().unwrap_or(DEFAULT_COST);
 
-    let pswd_hash = match hash(&admin_password, cost) {
+    let pswd_hash = match hash_algorithm().hash(&admin_password, Some(cost)) {
         Ok(hash) => hash,
         Err(_) => return Err(Error::AdminNotConfigured),
     };
@@ -38,12 +38,12 @@ pub fn read_admin_info() -> Result<AdminInfo, Error> {
 
 pub fn to_users_log(user: &User) -> Result<(), Error> {
     let utc: DateTime<Utc> = Utc::now();
-    let users_info_log = "data/users_info.log";
+    let users_info_log = data_file("users_info.log");
 
     let mut file = OpenOptions::new()
         .append(true)
         .create(true)
-        .open(users_info_log)?;
+        .open(&users_info_log)?;
 
     let log = user.format_user_log(utc)?;
     file.write_all(log.as_bytes())?;
@@ -52,9 +52,9 @@ pub fn to_users_log(user: &User) -> Result<(), Error> {
 }
 
 pub fn remove_users_from_log(users: &[Uuid]) -> Result<(), Error> {
-    let users_info_log = "data/users_info.log";
+    let users_info_log = data_file("users_info.log");
 
-    let file = OpenOptions::new().read(true).open(users_info_log)?;
+    let file = OpenOptions::new().read(true).open(&users_info_log)?;
 
     let lines = BufReader::new(file)
         .lines()
@@ -72,11 +72,11 @@ pub fn remove_users_from_log(users: &[Uuid]) -> Result<(), Error> {
         .join("\r\n");
 
     // Improve this, OpenOptions is not overwriting this file
-    std::fs::remove_file(users_info_log)?;
+    std::fs::remove_file(&users_info_log)?;
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
-        .open(users_info_log)?;
+        .open(&users_info_log)?;
 
     file.seek(SeekFrom::Start(0))?;
     file.write_all(lines.as_bytes())?;
@@ -85,9 +85,9 @@ pub fn remove_users_from_log(users: &[Uuid]) -> Result<(), Error> {
 }
 
 pub async fn find_user(user: schemas::User) -> Result<UserRegistry, Error> {
-    let users_info_log = "data/users_info.log";
+    let users_info_log = data_file("users_info.log");
 
-    let file = OpenOptions::new().read(true).open(users_info_log)?;
+    let file = OpenOptions::new().read(true).open(&users_info_log)?;
     let buffer = BufReader::new(file);
     let uuid = user.id;
 
@@ -19,7 +19,7 @@ pub async fn wql_validator(
                 validate_token(
                     &db,
                     Some(credentials.token()),
-                    vec![Role::Write, Role::User],
+                    vec![Role::Write, Role::User, Role::Admin],
                 )
             });
 
@@ -28,7 +28,10 @@ pub async fn wql_validator(
         } else {
             Err(crate::model::error::Error::AuthorizationBadRequest.into())
         }
-    } else if req.path().starts_with("/wql/query") {
+    } else if req.path().starts_with("/wql/query")
+        || req.path().starts_with("/wql/batch")
+        || req.path().starts_with("/wql/check")
+    {
         let allow = req
             .app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
             .and_then(|db| {
@@ -49,7 +52,7 @@ pub async fn history_validator(
     req: ServiceRequest,
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, Error> {
-    if req.path().starts_with("/entity-history") {
+    if req.path().starts_with("/entity-history") || req.path().starts_with("/entity-by-hash") {
         let allow = req
             .app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
             .and_then(|db| {
@@ -70,6 +73,67 @@ pub async fn history_validator(
     }
 }
 
+pub async fn subscribe_validator(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+) -> Result<ServiceRequest, Error> {
+    if req.path().starts_with("/subscribe") {
+        let allow = req
+            .app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
+            .and_then(|db| {
+                validate_token(&db, Some(credentials.token()), vec![Role::Read, Role::User])
+            });
+
+        if let Some(true) = allow {
+            Ok(req)
+        } else {
+            Err(crate::model::error::Error::AuthorizationBadRequest.into())
+        }
+    } else {
+        Ok(req)
+    }
+}
+
+pub async fn entities_validator(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+) -> Result<ServiceRequest, Error> {
+    if req.path().starts_with("/entities") {
+        let allow = req
+            .app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
+            .and_then(|db| {
+                validate_token(&db, Some(credentials.token()), vec![Role::Read, Role::User])
+            });
+
+        if let Some(true) = allow {
+            Ok(req)
+        } else {
+            Err(crate::model::error::Error::AuthorizationBadRequest.into())
+        }
+    } else {
+        Ok(req)
+    }
+}
+
+pub async fn raw_log_validator(
+    req: ServiceRequest,
+    credentials: BearerAuth,
+) -> Result<ServiceRequest, Error> {
+    if req.path().starts_with("/raw-log") {
+        let allow = req
+            .app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
+            .and_then(|db| validate_token(&db, Some(credentials.token()), vec![Role::Admin]));
+
+        if let Some(true) = allow {
+            Ok(req)
+        } else {
+            Err(crate::model::error::Error::AuthorizationBadRequest.into())
+        }
+    } else {
+        Ok(req)
+    }
+}
+
 fn validate_token(
     db: &Arc<Mutex<BTreeMap<String, SessionInfo>>>,
     token: Option<&str>,
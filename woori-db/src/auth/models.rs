@@ -1,4 +1,3 @@
-use bcrypt::verify;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -24,10 +23,7 @@ impl AdminInfo {
     }
 
     pub fn is_valid_hash(&self, pswd: &str, id: &str) -> bool {
-        match verify(pswd, &self.admin_hash) {
-            Ok(b) => b && id == self.admin_id,
-            Err(_) => false,
-        }
+        wql::verify_hash(pswd, &self.admin_hash) && id == self.admin_id
     }
 
     pub fn cost(&self) -> u32 {
@@ -35,6 +31,40 @@ impl AdminInfo {
     }
 }
 
+/// Session expiration in seconds, a session can request its own expiration up to `max`.
+#[derive(Debug, Clone)]
+pub struct SessionExpiration {
+    default: i64,
+    max: i64,
+    reject_exceeding: bool,
+}
+
+impl SessionExpiration {
+    pub fn new(default: i64, max: i64, reject_exceeding: bool) -> Self {
+        Self {
+            default: default.min(max),
+            max,
+            reject_exceeding,
+        }
+    }
+
+    /// Expiration for the `requested` time, or the default one. Times longer than `max`
+    /// are clamped to `max`, or rejected when `reject_exceeding` is set.
+    pub fn expiration_time(&self, requested: Option<i64>) -> Result<i64, Error> {
+        match requested {
+            None => Ok(self.default),
+            Some(time) if time <= 0 => Err(Error::AuthenticationBadRequestBody(format!(
+                "expiration_time must be positive, found {}",
+                time
+            ))),
+            Some(time) if time > self.max && self.reject_exceeding => {
+                Err(Error::SessionExpirationTooLong(time, self.max))
+            }
+            Some(time) => Ok(time.min(self.max)),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct User {
     id: Uuid,
@@ -78,3 +108,53 @@ impl User {
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_expiration_defaults_and_clamps() {
+        let expiration = SessionExpiration::new(3600, 7200, false);
+
+        assert_eq!(expiration.expiration_time(None).unwrap(), 3600);
+        assert_eq!(expiration.expiration_time(Some(60)).unwrap(), 60);
+        assert_eq!(expiration.expiration_time(Some(86400)).unwrap(), 7200);
+        assert!(expiration.expiration_time(Some(0)).is_err());
+        assert_eq!(
+            SessionExpiration::new(86400, 7200, false)
+                .expiration_time(None)
+                .unwrap(),
+            7200
+        );
+    }
+
+    #[test]
+    fn admin_hash_of_both_algorithms() {
+        use wql::HashAlgorithm;
+
+        for algorithm in &[HashAlgorithm::Bcrypt, HashAlgorithm::Argon2] {
+            let hash = algorithm.hash("your_password", Some(4)).unwrap();
+            let admin = AdminInfo::new("your_admin".to_string(), hash, 4);
+
+            assert!(admin.is_valid_hash("your_password", "your_admin"));
+            assert!(!admin.is_valid_hash("other_password", "your_admin"));
+            assert!(!admin.is_valid_hash("your_password", "other_admin"));
+        }
+    }
+
+    #[test]
+    fn session_expiration_rejects_exceeding() {
+        let expiration = SessionExpiration::new(3600, 7200, true);
+
+        assert_eq!(expiration.expiration_time(Some(7200)).unwrap(), 7200);
+        assert_eq!(
+            expiration
+                .expiration_time(Some(7201))
+                .err()
+                .unwrap()
+                .to_string(),
+            Error::SessionExpirationTooLong(7201, 7200).to_string()
+        );
+    }
+}
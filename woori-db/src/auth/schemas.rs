@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -15,6 +16,19 @@ pub struct DeleteUsersWithAdmin {
     pub users_ids: Vec<Uuid>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct AdminCredentials {
+    pub admin_id: String,
+    pub admin_password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RevokeSessionWithAdmin {
+    pub admin_id: String,
+    pub admin_password: String,
+    pub token: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct UserInfo {
     pub user_password: String,
@@ -23,7 +37,7 @@ pub struct UserInfo {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum Role {
-    // Admin,
+    Admin,
     User,
     Read,
     Write,
@@ -39,4 +53,24 @@ pub struct UserId {
 pub struct User {
     pub id: Uuid,
     pub user_password: String,
+    #[serde(default)]
+    pub expiration_time: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActiveSession {
+    pub token: String,
+    pub user_id: Uuid,
+    pub expiration: DateTime<Utc>,
+    pub roles: Vec<Role>,
+}
+
+/// Approximate memory of an entity in the local context, reported by `/auth/memory`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EntityMemory {
+    pub ids: usize,
+    /// Bytes of the ids, registries and encoded states kept in memory.
+    pub stored_bytes: usize,
+    /// Bytes of the states once decoded by queries, estimated with `Types::approx_size`.
+    pub state_bytes: usize,
 }
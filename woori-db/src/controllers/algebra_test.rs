@@ -369,6 +369,495 @@ async fn test_select_all_order_ok() {
     };
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_select_all_order_by_created_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for req in inserts("order_by_created") {
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (payload, first, last) in &[
+        ("Select * FROM order_by_created ORDER BY _created :asc", 123, 295),
+        ("Select * FROM order_by_created ORDER BY _created :desc", 295, 123),
+        (
+            "Select * FROM order_by_created WHERE { ?* order_by_created:b 12.3, } ORDER BY _created :asc",
+            123,
+            295,
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        let states: Vec<(Uuid, HashMap<String, Types>)> = ron::de::from_str(&body).unwrap();
+        assert_eq!(states.len(), 6);
+        assert_eq!(states.first().unwrap().1.get("a"), Some(&Types::Integer(*first)));
+        assert_eq!(states.last().unwrap().1.get("a"), Some(&Types::Integer(*last)));
+    }
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_coalesce_nullif_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for req in inserts("scalar_functions") {
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload =
+        "Select #{a, COALESCE(c, missing, a) AS first, NULLIF(a, 235) AS a,} FROM scalar_functions";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let a_values = states
+        .values()
+        .map(|state| state.get("a").unwrap().to_owned())
+        .collect::<Vec<Types>>();
+    assert!(a_values.contains(&Types::Nil));
+    assert!(!a_values.contains(&Types::Integer(235)));
+    assert!(states.values().all(|state| state.len() == 2));
+    assert!(states
+        .values()
+        .any(|state| state.get("first") == Some(&Types::Char('d'))));
+    assert!(states
+        .values()
+        .any(|state| state.get("first") == Some(&Types::Integer(123))));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_literal_projection_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for req in inserts("literal_projection") {
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "Select *, \"ordersv2\" AS source, 2 AS version FROM literal_projection";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert!(!states.is_empty());
+    assert!(states.values().all(|state| state.get("source")
+        == Some(&Types::String("ordersv2".to_string()))
+        && state.get("version") == Some(&Types::Integer(2))
+        && state.contains_key("a")));
+
+    let payload = "Select #{a, \"ordersv2\" AS source,} FROM literal_projection";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert!(states.values().all(|state| state.len() == 2
+        && state.get("source") == Some(&Types::String("ordersv2".to_string()))));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_all_omit_nulls_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY omit_nulls",
+        "INSERT {a: 123, c: Nil,} INTO omit_nulls",
+        "INSERT {a: 235, c: 'c',} INTO omit_nulls",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for payload in &[
+        "Select * FROM omit_nulls OMIT NULLS",
+        "Select #{a, c,} FROM omit_nulls OMIT NULLS",
+        "Select * FROM omit_nulls WHERE { ?* omit_nulls:a ?a, (> ?a 100), } OMIT NULLS",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+        assert_eq!(states.len(), 2);
+        assert!(states
+            .values()
+            .all(|state| !state.values().any(|v| v == &Types::Nil)));
+        assert!(states.values().any(|state| !state.contains_key("c")));
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("Select * FROM omit_nulls")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let id = states
+        .iter()
+        .find(|(_, state)| state.get("c") == Some(&Types::Nil))
+        .map(|(id, _)| id.to_owned())
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("Select * FROM omit_nulls ID {} OMIT NULLS", id))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let state: HashMap<String, Types> = ron::de::from_str(&body).unwrap();
+    assert_eq!(state.get("a"), Some(&Types::Integer(123)));
+    assert!(!state.contains_key("c"));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_valid_at_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY valid_prices {valid_from: DateTime, valid_to: DateTime,}",
+        "INSERT {price: 10, valid_from: 2023-01-01T00:00:00Z, valid_to: 2023-06-01T00:00:00Z,} INTO valid_prices",
+        "INSERT {price: 12, valid_from: 2023-06-01T00:00:00Z,} INTO valid_prices",
+        "INSERT {price: 8,} INTO valid_prices",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (payload, prices) in &[
+        ("Select * FROM valid_prices VALID AT \"2023-06-01\"", vec![8, 12]),
+        ("Select #{price,} FROM valid_prices VALID AT 2023-05-31T23:59:59Z", vec![8, 10]),
+        (
+            "Select * FROM valid_prices WHERE { ?* valid_prices:price ?p, (> ?p 9), } VALID AT 2022-01-01",
+            vec![],
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+        let mut found = states
+            .values()
+            .filter_map(|state| match state.get("price") {
+                Some(Types::Integer(price)) => Some(*price),
+                _ => None,
+            })
+            .collect::<Vec<isize>>();
+        found.sort_unstable();
+        assert_eq!(&found, prices);
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {price: 9, valid_from: 2023-06-01T00:00:00Z, valid_to: 2023-01-01T00:00:00Z,} INTO valid_prices")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("InvalidValidInterval"));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_index_projection_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY index_projection",
+        "INSERT {name: \"seoul\", coords: [37.56, 126.97,],} INTO index_projection",
+        "INSERT {name: \"nowhere\", coords: 3,} INTO index_projection",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_string())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "Select #{name, coords[0] AS lat, coords[1] AS lng,} FROM index_projection";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert_eq!(states.len(), 2);
+    let seoul = states
+        .values()
+        .find(|state| state.get("name") == Some(&Types::String("seoul".to_string())))
+        .unwrap();
+    assert_eq!(seoul.get("lat"), Some(&Types::Float(37.56)));
+    assert_eq!(seoul.get("lng"), Some(&Types::Float(126.97)));
+    let nowhere = states
+        .values()
+        .find(|state| state.get("name") == Some(&Types::String("nowhere".to_string())))
+        .unwrap();
+    assert_eq!(nowhere.get("lat"), Some(&Types::Nil));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_vector_functions_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY vector_functions",
+        "INSERT {tags: [3, 1, 2, 1,],} INTO vector_functions",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_string())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "Select *, SORT(tags) AS sorted_tags, REVERSE(tags) AS reversed_tags, UNIQUE(tags) AS unique_tags FROM vector_functions";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let state = states.values().next().unwrap();
+    let integers =
+        |values: &[isize]| Types::Vector(values.iter().map(|v| Types::Integer(*v)).collect());
+    assert_eq!(state.get("tags"), Some(&integers(&[3, 1, 2, 1])));
+    assert_eq!(state.get("sorted_tags"), Some(&integers(&[1, 1, 2, 3])));
+    assert_eq!(state.get("reversed_tags"), Some(&integers(&[1, 2, 1, 3])));
+    assert_eq!(state.get("unique_tags"), Some(&integers(&[3, 1, 2])));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_flatten_and_slice_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY flatten_slice",
+        "INSERT {matrix: [[1, 2,], [3,], 4,], tags: [1, 2, 3, 4, 5,],} INTO flatten_slice",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_string())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "Select #{FLATTEN(matrix) AS cells, SLICE(tags, 1, 3) AS middle, SLICE(tags, 3, 100) AS tail, SLICE(matrix, 5, 6) AS none,} FROM flatten_slice";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let state = states.values().next().unwrap();
+    let integers =
+        |values: &[isize]| Types::Vector(values.iter().map(|v| Types::Integer(*v)).collect());
+    assert_eq!(state.get("cells"), Some(&integers(&[1, 2, 3, 4])));
+    assert_eq!(state.get("middle"), Some(&integers(&[2, 3])));
+    assert_eq!(state.get("tail"), Some(&integers(&[4, 5])));
+    assert_eq!(state.get("none"), Some(&integers(&[])));
+    assert_eq!(state.get("tags"), None);
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_color_components_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY colors",
+        "INSERT {name: \"orange\", color: #FF8800,} INTO colors",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_string())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"invalid\", color: #ff88zz,} INTO colors")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let payload = "Select #{color, RED(color) AS r, GREEN(color) AS g, BLUE(color) AS b, RED(name) AS none,} FROM colors";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert_eq!(states.len(), 1);
+    let state = states.values().next().unwrap();
+    assert_eq!(state.get("color"), Some(&Types::Color(255, 136, 0)));
+    assert_eq!(state.get("r"), Some(&Types::Integer(255)));
+    assert_eq!(state.get("g"), Some(&Types::Integer(136)));
+    assert_eq!(state.get("b"), Some(&Types::Integer(0)));
+    assert_eq!(state.get("none"), Some(&Types::Nil));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_cast_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY casts ENCRYPT #{pswd,}",
+        "INSERT {age: 42, price: 12.75P, score: \"7\", pswd: \"secret\",} INTO casts",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_string())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "Select #{age, CAST(age AS String) AS age_str, CAST(price AS Integer) AS price_int, CAST(score AS Float) AS score_float, CAST(pswd AS String) AS pswd_str,} FROM casts";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let state = states.values().next().unwrap();
+    assert_eq!(state.get("age"), Some(&Types::Integer(42)));
+    assert_eq!(state.get("age_str"), Some(&Types::String("42".to_string())));
+    assert_eq!(state.get("price_int"), Some(&Types::Integer(12)));
+    assert_eq!(state.get("score_float"), Some(&Types::Float(7.0)));
+    assert_eq!(state.get("pswd_str"), Some(&Types::Nil));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_array_aggregates_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY array_aggregates",
+        "INSERT {scores: [3, 9.5, \"skip\", -1,], tags: [],} INTO array_aggregates",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_string())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "Select #{SUM_ARRAY(scores) AS total, MAX_ARRAY(scores) AS best, MIN_ARRAY(scores) AS worst, AVG_ARRAY(scores) AS average, SUM_ARRAY(tags) AS tags_total,} FROM array_aggregates";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let state = states.values().next().unwrap();
+    assert_eq!(state.get("total"), Some(&Types::Float(11.5)));
+    assert_eq!(state.get("best"), Some(&Types::Float(9.5)));
+    assert_eq!(state.get("worst"), Some(&Types::Integer(-1)));
+    assert_eq!(state.get("average"), Some(&Types::Float(11.5 / 3.0)));
+    assert_eq!(state.get("tags_total"), Some(&Types::Nil));
+    assert!(state.get("scores").is_none());
+}
+
 fn inserts(entity_name: &str) -> Vec<Request> {
     vec![
         test::TestRequest::post()
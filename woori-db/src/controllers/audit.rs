@@ -0,0 +1,55 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::{
+    actors::audit::AuditRecord,
+    model::{error::Error, DataAuditor},
+    repository::local::SessionContext,
+};
+
+/// Sends the audit record of a request to the `Auditor` without waiting for it.
+pub(crate) fn audit(req: &HttpRequest, wql: &str, response: &HttpResponse, error: Option<&Error>) {
+    if let Some(auditor) = req.app_data::<DataAuditor>() {
+        auditor.do_send(AuditRecord {
+            timestamp: Utc::now(),
+            user_id: session_user_id(req),
+            endpoint: req.path().to_owned(),
+            wql: wql.to_owned(),
+            status: response.status().as_u16(),
+            error: error.map(ToString::to_string),
+        });
+    }
+}
+
+pub(crate) fn bearer_token(req: &HttpRequest) -> String {
+    req.headers()
+        .get("Authorization")
+        .and_then(|auth| auth.to_str().ok())
+        .map(|auth| auth.trim_start_matches("Bearer ").to_owned())
+        .unwrap_or_default()
+}
+
+pub(crate) fn session_roles(req: &HttpRequest) -> Vec<crate::auth::schemas::Role> {
+    let token = bearer_token(req);
+    req.app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
+        .and_then(|sessions| {
+            sessions
+                .lock()
+                .ok()
+                .and_then(|sessions| sessions.get(&token).map(|s| s.roles()))
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn session_user_id(req: &HttpRequest) -> Option<Uuid> {
+    let token = bearer_token(req);
+    req.app_data::<web::Data<Arc<Mutex<SessionContext>>>>()
+        .and_then(|sessions| {
+            sessions
+                .lock()
+                .ok()
+                .and_then(|sessions| sessions.get(&token).map(|s| s.user_id()))
+        })
+}
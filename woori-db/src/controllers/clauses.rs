@@ -1,16 +1,33 @@
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::IpAddr,
+};
 
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
 use rayon::prelude::*;
 use uuid::Uuid;
-use wql::{Algebra, Clause, ToSelect, Types, Value};
+use wql::{Algebra, Clause, Expression, ScalarArg, ToSelect, Types, Value, Wql};
 
 use crate::{
+    actors::when::{ReadEntityIdUntil, ReadEntityWrites},
     core::registry::get_registries,
-    model::{error::Error, DataLocalContext, DataRegister},
+    model::{error::Error, DataExecutor, DataLocalContext, DataRegister},
     schemas::query::Response as QueryResponse,
 };
 
-use crate::core::query::{dedup_states, get_limit_offset_count, get_result_after_manipulation};
+use crate::core::query::{
+    dedup_states, get_limit_offset_count, get_result_after_manipulation, is_valid_at, omit_nulls,
+    project_scalars, registry_times,
+};
+
+/// Filtered states and the registries needed to sort them with `ORDER BY _created`.
+type WhereStates = Result<
+    (
+        BTreeMap<Uuid, HashMap<String, Types>>,
+        BTreeMap<Uuid, DataRegister>,
+    ),
+    Error,
+>;
 
 pub async fn select_where_controller(
     entity: String,
@@ -18,226 +35,894 @@ pub async fn select_where_controller(
     clauses: Vec<Clause>,
     local_data: DataLocalContext,
     functions: HashMap<String, wql::Algebra>,
+    actor: DataExecutor,
 ) -> Result<QueryResponse, Error> {
-    let states = select_where(entity, args_to_select, clauses, local_data, &functions);
+    let count_only = matches!(functions.get("COUNT"), Some(Algebra::CountOnly));
+    if count_only && functions.len() == 1 {
+        return count_where(entity, clauses, local_data, actor)
+            .await
+            .map(QueryResponse::Count);
+    }
+    let states = select_where(
+        entity,
+        args_to_select,
+        clauses,
+        local_data,
+        &functions,
+        actor,
+    );
     let count = matches!(functions.get("COUNT"), Some(Algebra::Count));
+    let (states, times) = states.await?;
+    if count_only {
+        return Ok(QueryResponse::Count(states.len()));
+    }
 
     Ok(get_result_after_manipulation(
-        states.await?,
-        &functions,
-        count,
+        states, &times, &functions, count,
     ))
 }
 
+/// Number of entities matching `clauses`, for `SELECT COUNT(*)` without other functions.
+/// Without `AS OF` clauses the matching registries are only counted, their states are
+/// dropped as soon as the clauses are applied.
+async fn count_where(
+    entity: String,
+    clauses: Vec<Clause>,
+    local_data: DataLocalContext,
+    actor: DataExecutor,
+) -> Result<usize, Error> {
+    let subqueries = subquery_sets(&clauses, &local_data, &actor).await?;
+    let has_as_of = clauses
+        .iter()
+        .any(|clause| matches!(clause, Clause::ValueAttributionAsOf(..)));
+    if has_as_of {
+        let (states, _) = where_states(
+            entity,
+            ToSelect::All,
+            clauses,
+            local_data,
+            &HashMap::new(),
+            actor,
+            &subqueries,
+        )
+        .await?;
+        return Ok(states.len());
+    }
+
+    let args_to_key = args_to_key(&clauses);
+    let registries = get_registries(&entity, &local_data)?;
+    let hash_keys = verify_keys(&clauses, &args_to_key);
+    let changed = changed_ids(&entity, &registries, &clauses, &actor).await?;
+    let filter = WhereFilter::new(&args_to_key, &clauses, &subqueries, &changed);
+    count_matching(&registries, &hash_keys, &filter)
+}
+
 pub async fn select_where(
     entity: String,
     args_to_select: ToSelect,
     clauses: Vec<Clause>,
     local_data: DataLocalContext,
     functions: &HashMap<String, wql::Algebra>,
-) -> Result<BTreeMap<Uuid, HashMap<String, Types>>, Error> {
+    actor: DataExecutor,
+) -> WhereStates {
+    let subqueries = subquery_sets(&clauses, &local_data, &actor).await?;
+    where_states(
+        entity,
+        args_to_select,
+        clauses,
+        local_data,
+        functions,
+        actor,
+        &subqueries,
+    )
+    .await
+}
+
+/// Executes the `IN` subqueries of `clauses`, mapping each bound `?arg` to the set of
+/// values of the selected key. Selecting `id` from an entity without an `id` key
+/// returns the entities' Uuids.
+async fn subquery_sets(
+    clauses: &[Clause],
+    local_data: &DataLocalContext,
+    actor: &DataExecutor,
+) -> Result<HashMap<String, HashSet<Types>>, Error> {
+    let mut sets: HashMap<String, HashSet<Types>> = HashMap::new();
+    for clause in clauses {
+        let (arg, subquery) = match clause {
+            Clause::SubqueryIn(arg, subquery) => (arg, &**subquery),
+            _ => continue,
+        };
+        let (entity, key, inner_clauses, functions) = match subquery {
+            Wql::Select(entity, ToSelect::Keys(keys), None, functions) => {
+                (entity, &keys[0], Vec::new(), functions)
+            }
+            Wql::SelectWhere(entity, ToSelect::Keys(keys), inner_clauses, functions) => {
+                (entity, &keys[0], inner_clauses.to_owned(), functions)
+            }
+            _ => {
+                return Err(Error::QueryFormat(String::from(
+                    "IN subquery must select a single key",
+                )))
+            }
+        };
+        let (states, _) = where_states(
+            entity.to_owned(),
+            ToSelect::All,
+            inner_clauses,
+            local_data.clone(),
+            functions,
+            actor.clone(),
+            &HashMap::new(),
+        )
+        .await?;
+        let values = states
+            .into_iter()
+            .filter_map(|(uuid, mut state)| match state.remove(key) {
+                Some(value) => Some(value),
+                None if key == "id" => Some(Types::Uuid(uuid)),
+                None => None,
+            })
+            .collect::<HashSet<Types>>();
+
+        let values = match sets.remove(arg) {
+            Some(previous) => previous.intersection(&values).cloned().collect(),
+            None => values,
+        };
+        sets.insert(arg.to_owned(), values);
+    }
+    Ok(sets)
+}
+
+async fn where_states(
+    entity: String,
+    args_to_select: ToSelect,
+    clauses: Vec<Clause>,
+    local_data: DataLocalContext,
+    functions: &HashMap<String, wql::Algebra>,
+    actor: DataExecutor,
+    subqueries: &HashMap<String, HashSet<Types>>,
+) -> WhereStates {
     let (limit, offset, _) = get_limit_offset_count(functions);
-    let args_to_key = clauses
-        .clone()
-        .into_par_iter()
+    let args_to_key = args_to_key(&clauses);
+    let as_of_keys = clauses
+        .iter()
         .filter_map(|clause| {
-            if let Clause::ValueAttribution(_, key, Value(arg)) = clause {
-                Some((arg, key))
+            if let Clause::ValueAttributionAsOf(_, key, _, date) = clause {
+                Some((key.to_owned(), date.to_owned()))
             } else {
                 None
             }
         })
-        .collect::<HashMap<String, String>>();
+        .collect::<Vec<(String, DateTime<Utc>)>>();
     let registries = get_registries(&entity, &local_data)?;
-    let states = generate_state(&registries, args_to_select).await?;
-    let states = filter_where_clauses(states, args_to_key, &clauses)
-        .await
+    let times = registry_times(
+        registries.iter().map(|(id, (registry, _))| (id, registry)),
+        functions,
+    );
+    let hash_keys = verify_keys(&clauses, &args_to_key);
+    let changed = changed_ids(&entity, &registries, &clauses, &actor).await?;
+    let filter = WhereFilter::new(&args_to_key, &clauses, subqueries, &changed);
+    // `AS OF` values are added after the states are generated, without them the clauses
+    // are applied to each registry so the ones that don't match are never collected.
+    let pushdown = as_of_keys.is_empty();
+    let states = generate_state(
+        &registries,
+        args_to_select,
+        functions,
+        &hash_keys,
+        Some(&filter).filter(|_| pushdown),
+    )
+    .await?;
+    let states = if pushdown {
+        states
+    } else {
+        let states = historical_states(&entity, states, &as_of_keys, &actor).await?;
+        verify_hash_values(&states, &hash_keys)?;
+        states
+            .into_iter()
+            .filter(|(id, state)| filter.matches(id, state))
+            .collect::<BTreeMap<Uuid, HashMap<String, Types>>>()
+    };
+    let states = states
         .into_iter()
         .skip(offset)
         .take(limit)
+        .map(|(id, mut state)| {
+            if omit_nulls(functions) {
+                state.retain(|_, v| v != &Types::Nil);
+            }
+            (id, remove_hashes(remove_as_of_keys(state, &as_of_keys)))
+        })
         .collect();
 
     let states = dedup_states(states, &functions);
-    Ok(states)
+    Ok((states, times))
 }
 
-async fn filter_where_clauses(
-    states: BTreeMap<Uuid, HashMap<String, Types>>,
-    args_to_key: HashMap<String, String>,
-    clauses: &[Clause],
-) -> BTreeMap<Uuid, HashMap<String, Types>> {
+/// Keys bound to each `?arg` by the attribution clauses, `AS OF` keys are named with their date.
+fn args_to_key(clauses: &[Clause]) -> HashMap<String, String> {
+    clauses
+        .par_iter()
+        .filter_map(|clause| match clause {
+            Clause::ValueAttribution(_, key, Value(arg)) => Some((arg.to_owned(), key.to_owned())),
+            Clause::ValueAttributionAsOf(_, key, Value(arg), date) => {
+                Some((arg.to_owned(), as_of_key(key, date)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Clauses of a `WHERE` with the keys bound to their args and the values of `IN` subqueries.
+struct WhereFilter<'a> {
+    args_to_key: &'a HashMap<String, String>,
+    clauses: &'a [Clause],
+    subqueries: &'a HashMap<String, HashSet<Types>>,
+    changed: &'a ChangedIds,
+    now: DateTime<Utc>,
+}
+
+impl<'a> WhereFilter<'a> {
+    fn new(
+        args_to_key: &'a HashMap<String, String>,
+        clauses: &'a [Clause],
+        subqueries: &'a HashMap<String, HashSet<Types>>,
+        changed: &'a ChangedIds,
+    ) -> Self {
+        Self {
+            args_to_key,
+            clauses,
+            subqueries,
+            changed,
+            now: Utc::now(),
+        }
+    }
+
+    /// If the state of `id` is kept by all the clauses.
+    fn matches(&self, id: &Uuid, state: &HashMap<String, Types>) -> bool {
+        let changed = self
+            .changed
+            .iter()
+            .filter(|(_, ids)| ids.contains(id))
+            .map(|(date, _)| *date)
+            .collect::<BTreeSet<DateTime<Utc>>>();
+        self.clauses.iter().all(|clause| {
+            keeps_state(
+                state,
+                &changed,
+                self.args_to_key,
+                clause,
+                self.subqueries,
+                &self.now,
+            )
+        })
+    }
+}
+
+/// If `state` is kept by a top level clause. Comparisons on keys missing from the state keep it.
+fn keeps_state(
+    state: &HashMap<String, Types>,
+    changed: &BTreeSet<DateTime<Utc>>,
+    args_to_key: &HashMap<String, String>,
+    clause: &Clause,
+    subqueries: &HashMap<String, HashSet<Types>>,
+    now: &DateTime<Utc>,
+) -> bool {
     let default = String::new();
-    let mut states = states.clone();
-    for clause in clauses {
-        match clause {
-            Clause::ValueAttribution(_, _, _) => {}
-            Clause::Or(_, inner_clauses) => {
-                for (id, state) in states.clone() {
-                    if !or_clauses(&state, &args_to_key, &inner_clauses) {
-                        states.remove(&id);
+    match clause {
+        Clause::ValueAttribution(_, _, _) | Clause::ValueAttributionAsOf(_, _, _, _) => true,
+        Clause::Or(wql::Function::Xor, inner_clauses) => {
+            xor_clauses(state, changed, args_to_key, inner_clauses)
+        }
+        Clause::Or(_, inner_clauses) => or_clauses(state, changed, args_to_key, inner_clauses),
+        Clause::Not(inner) => !matches_clause(state, changed, args_to_key, inner),
+        Clause::ContainsKeyValue(_, key, value) => state.get(key).is_some_and(|v| value == v),
+        Clause::SimpleComparisonFunction(f, key, value) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key).is_none_or(|v| match f {
+                wql::Function::Eq => v == value,
+                wql::Function::NotEq => v != value,
+                wql::Function::GEq => v >= value,
+                wql::Function::G => v > value,
+                wql::Function::LEq => v <= value,
+                wql::Function::L => v < value,
+                wql::Function::Like => {
+                    if let (Types::String(content), Types::String(regex)) = (v, value) {
+                        let pattern = regex.replace("%", "");
+
+                        (regex.starts_with('%')
+                            && regex.ends_with('%')
+                            && content.contains(&pattern))
+                            || (regex.starts_with('%') && content.ends_with(&pattern))
+                            || (regex.ends_with('%') && content.starts_with(&pattern))
+                            || content.contains(&pattern)
+                    } else {
+                        false
                     }
                 }
+                _ => true,
+            })
+        }
+        Clause::KeyComparisonFunction(f, key, other_key) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            let other_key = args_to_key.get(other_key).unwrap_or(&default);
+            compare_keys(state, f, key, other_key)
+        }
+        Clause::ComplexComparisonFunctions(wql::Function::In, key, set) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key).is_some_and(|v| set.contains(v))
+        }
+        Clause::SubqueryIn(arg, _) => {
+            let key = args_to_key.get(arg).unwrap_or(&default);
+            let set = subqueries.get(arg);
+            matches!((state.get(key), set), (Some(v), Some(set)) if set.contains(v))
+        }
+        Clause::Predicate(wql::Function::WithinLast(key, seconds)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            is_within_last(state.get(key), *seconds, now)
+        }
+        Clause::Predicate(wql::Function::ComparedToNow(key, f)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            compare_to_now(state.get(key), f, now)
+        }
+        Clause::ComplexComparisonFunctions(wql::Function::Between, _, _)
+        | Clause::Predicate(wql::Function::IsType(..))
+        | Clause::Predicate(wql::Function::Within(..))
+        | Clause::Predicate(wql::Function::InSubnet(..))
+        | Clause::Predicate(wql::Function::HasKey(..))
+        | Clause::Predicate(wql::Function::Domain(..))
+        | Clause::Predicate(wql::Function::Country(..))
+        | Clause::Predicate(wql::Function::IsBool(..))
+        | Clause::Predicate(wql::Function::MapSize(..))
+        | Clause::Predicate(wql::Function::StrLen(..))
+        | Clause::Predicate(wql::Function::Arithmetic(..))
+        | Clause::Predicate(wql::Function::UuidTime(..))
+        | Clause::Predicate(wql::Function::NilUuid(..))
+        | Clause::Predicate(wql::Function::UuidVersion(..))
+        | Clause::Predicate(wql::Function::Verify(..))
+        | Clause::Predicate(wql::Function::VerifyAll(..))
+        | Clause::Predicate(wql::Function::HashEq(..))
+        | Clause::Predicate(wql::Function::ChangedSince(..)) => {
+            matches_clause(state, changed, args_to_key, clause)
+        }
+        _ => true,
+    }
+}
+
+/// Compares the values of two keys of the same state. States missing any of the keys
+/// and values of types that can't be compared, like `Integer` and `String`, are `false`.
+fn compare_keys(
+    state: &HashMap<String, Types>,
+    function: &wql::Function,
+    key: &str,
+    other_key: &str,
+) -> bool {
+    match (state.get(key), state.get(other_key)) {
+        (Some(value), Some(other)) => compare_values(value, function, other),
+        _ => false,
+    }
+}
+
+/// Compares two values with a comparison `function`. Values of types that
+/// can't be compared, like `Integer` and `String`, are `false`.
+pub(crate) fn compare_values(value: &Types, function: &wql::Function, other: &Types) -> bool {
+    if value.partial_cmp(other).is_none() {
+        return false;
+    }
+
+    match function {
+        wql::Function::Eq => value == other,
+        wql::Function::NotEq => value != other,
+        wql::Function::GEq => value >= other,
+        wql::Function::G => value > other,
+        wql::Function::LEq => value <= other,
+        wql::Function::L => value < other,
+        _ => false,
+    }
+}
+
+/// Euclidean distance between two points is at most `distance`. Values that are not
+/// `Point` are never within.
+fn is_within(value: Option<&Types>, distance: f64, center: &Types) -> bool {
+    if let (Some(Types::Point { x, y }), Types::Point { x: cx, y: cy }) = (value, center) {
+        (x - cx).hypot(y - cy) <= distance
+    } else {
+        false
+    }
+}
+
+/// Address is inside the subnet of `network` with `prefix` bits. Values that are not
+/// `IpAddr`, or are from another address family, are never inside.
+fn in_subnet(value: Option<&Types>, network: &IpAddr, prefix: u8) -> bool {
+    match (value, network) {
+        (Some(Types::IpAddr(IpAddr::V4(ip))), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(*ip) & mask == u32::from(*network) & mask
+        }
+        (Some(Types::IpAddr(IpAddr::V6(ip))), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(*ip) & mask == u128::from(*network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Values that are not `Map` never have a key.
+fn has_map_key(value: Option<&Types>, map_key: &str) -> bool {
+    matches!(value, Some(Types::Map(map)) if map.contains_key(map_key))
+}
+
+/// Compares the number of entries of a `Map` with `size`, values that are not `Map` are `false`.
+fn compare_map_size(value: Option<&Types>, function: &wql::Function, size: usize) -> bool {
+    match value {
+        Some(Types::Map(map)) => compare_values(
+            &Types::Integer(map.len() as isize),
+            function,
+            &Types::Integer(size as isize),
+        ),
+        _ => false,
+    }
+}
+
+/// Compares the number of chars of a `String` or `Precise` with `len`, other values are `false`.
+fn compare_str_len(value: Option<&Types>, function: &wql::Function, len: usize) -> bool {
+    match value {
+        Some(Types::String(s)) | Some(Types::Precise(s)) => compare_values(
+            &Types::Integer(s.chars().count() as isize),
+            function,
+            &Types::Integer(len as isize),
+        ),
+        _ => false,
+    }
+}
+
+/// Compares the value of `expression` over the keys of the `?` args with `other`. Missing
+/// keys, division by zero and operands that are not numbers are `false`.
+fn compare_expression(
+    state: &HashMap<String, Types>,
+    args_to_key: &HashMap<String, String>,
+    expression: &Expression,
+    function: &wql::Function,
+    other: &ScalarArg,
+) -> bool {
+    let key_of = |arg: &ScalarArg| match arg {
+        ScalarArg::Key(arg) => ScalarArg::Key(args_to_key.get(arg).cloned().unwrap_or_default()),
+        value => value.to_owned(),
+    };
+    let expression = Expression {
+        left: key_of(&expression.left),
+        operator: expression.operator,
+        right: key_of(&expression.right),
+    };
+    let other = match other {
+        ScalarArg::Key(arg) => args_to_key.get(arg).and_then(|key| state.get(key)),
+        ScalarArg::Value(value) => Some(value),
+    };
+    match (expression.evaluate(state), other) {
+        (Ok(value), Some(other)) => compare_values(&value, function, other),
+        _ => false,
+    }
+}
+
+/// Compares the time embedded in a v1 `Uuid` with `date`, other `Uuid` versions and values
+/// that are not `Uuid` are `false`.
+fn compare_uuid_time(
+    value: Option<&Types>,
+    function: &wql::Function,
+    date: &DateTime<Utc>,
+) -> bool {
+    let time = match value {
+        Some(Types::Uuid(id)) => id.to_timestamp().map(|timestamp| timestamp.to_unix()),
+        _ => None,
+    }
+    .and_then(|(seconds, nanos)| Utc.timestamp_opt(seconds as i64, nanos).single());
+
+    match (time, function) {
+        (Some(time), wql::Function::L) => &time < date,
+        (Some(time), wql::Function::G) => &time > date,
+        _ => false,
+    }
+}
+
+/// Checks if the date is at most `seconds` before `now`, values that are not
+/// `Types::DateTime` and dates after `now` are excluded.
+fn is_within_last(value: Option<&Types>, seconds: i64, now: &DateTime<Utc>) -> bool {
+    matches!(
+        value,
+        Some(Types::DateTime(date))
+            if date <= now && (*now - *date).num_milliseconds() <= seconds.saturating_mul(1000)
+    )
+}
+
+/// Compares the date with `now`, the same time for all states of a query. Values that are
+/// not `Types::DateTime` are excluded.
+fn compare_to_now(value: Option<&Types>, function: &wql::Function, now: &DateTime<Utc>) -> bool {
+    match value {
+        Some(Types::DateTime(date)) => match function {
+            wql::Function::Eq => date == now,
+            wql::Function::NotEq => date != now,
+            wql::Function::GEq => date >= now,
+            wql::Function::G => date > now,
+            wql::Function::LEq => date <= now,
+            wql::Function::L => date < now,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Checks `candidate` against a bcrypt or argon2 hash, values that are not `Types::Hash` never match.
+fn verify_hash(value: Option<&Types>, candidate: &str) -> bool {
+    matches!(value, Some(Types::Hash(hash)) if wql::verify_hash(candidate, hash))
+}
+
+/// Checks `candidate` against the hashes of all `args`, states missing any of them never match.
+fn verify_all_hashes(
+    state: &HashMap<String, Types>,
+    args: &[String],
+    args_to_key: &HashMap<String, String>,
+    candidate: &str,
+) -> bool {
+    args.iter().all(
+        |arg| matches!(args_to_key.get(arg), Some(key) if verify_hash(state.get(key), candidate)),
+    )
+}
+
+/// Compares the hash stored at the key with `hash` without stopping at the first different
+/// byte, so the time taken doesn't tell how much of the hash matched.
+fn hash_eq(value: Option<&Types>, hash: &str) -> bool {
+    match value {
+        Some(Types::Hash(stored)) if stored.len() == hash.len() => {
+            stored
+                .bytes()
+                .zip(hash.bytes())
+                .fold(0_u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+        }
+        _ => false,
+    }
+}
+
+/// Entity keys used by `VERIFY`, `VERIFY_ALL` and `HASH_EQ` clauses, their hashes are kept
+/// until the clauses are applied.
+fn verify_keys(clauses: &[Clause], args_to_key: &HashMap<String, String>) -> HashSet<String> {
+    clauses
+        .iter()
+        .flat_map(|clause| match clause {
+            Clause::Predicate(wql::Function::Verify(arg, _))
+            | Clause::Predicate(wql::Function::HashEq(arg, _)) => {
+                args_to_key.get(arg).into_iter().cloned().collect()
             }
-            Clause::ContainsKeyValue(_, key, value) => {
-                for (id, state) in states.clone() {
-                    if !state.get(key).map_or(false, |v| value == v) {
-                        states.remove(&id);
-                    }
-                }
+            Clause::Predicate(wql::Function::VerifyAll(args, _)) => args
+                .iter()
+                .filter_map(|arg| args_to_key.get(arg).cloned())
+                .collect(),
+            Clause::Or(_, inner_clauses) => verify_keys(inner_clauses, args_to_key),
+            Clause::Not(inner) => verify_keys(std::slice::from_ref(&**inner), args_to_key),
+            _ => HashSet::new(),
+        })
+        .collect()
+}
+
+fn verify_hash_values(
+    states: &BTreeMap<Uuid, HashMap<String, Types>>,
+    hash_keys: &HashSet<String>,
+) -> Result<(), Error> {
+    states
+        .values()
+        .try_for_each(|state| verify_hash_state(state, hash_keys))
+}
+
+fn verify_hash_state(
+    state: &HashMap<String, Types>,
+    hash_keys: &HashSet<String>,
+) -> Result<(), Error> {
+    hash_keys
+        .iter()
+        .filter_map(|k| state.get_key_value(k))
+        .find(|(_, v)| !v.is_hash())
+        .map_or(Ok(()), |(k, _)| Err(Error::VerifyNonHashKey(k.to_owned())))
+}
+
+fn remove_hashes(mut state: HashMap<String, Types>) -> HashMap<String, Types> {
+    state.retain(|_, v| !v.is_hash());
+    state
+}
+
+fn as_of_key(key: &str, date: &DateTime<Utc>) -> String {
+    format!("{} AS OF {}", key, date.to_rfc3339())
+}
+
+/// Adds the values bound by `AS OF` clauses to each state, read from the last state of the
+/// entity id registered at or before the requested date. Entities without a state at that
+/// date are excluded.
+async fn historical_states(
+    entity: &str,
+    states: BTreeMap<Uuid, HashMap<String, Types>>,
+    as_of_keys: &[(String, DateTime<Utc>)],
+    actor: &DataExecutor,
+) -> Result<BTreeMap<Uuid, HashMap<String, Types>>, Error> {
+    if as_of_keys.is_empty() {
+        return Ok(states);
+    }
+
+    let mut historical = BTreeMap::new();
+    'states: for (uuid, mut state) in states {
+        for (key, date) in as_of_keys {
+            let old_state = match actor
+                .send(ReadEntityIdUntil::new(entity, uuid, *date))
+                .await??
+            {
+                Some(old_state) if !old_state.is_empty() => old_state,
+                _ => continue 'states,
+            };
+
+            if let Some(value) = old_state.get(key) {
+                state.insert(as_of_key(key, date), value.to_owned());
             }
-            Clause::SimpleComparisonFunction(f, key, value) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                for (id, state) in states.clone() {
-                    state.get(key).map(|v| match f {
-                        wql::Function::Eq => {
-                            if !(v == value) {
-                                states.remove(&id);
-                            }
-                        }
-                        wql::Function::NotEq => {
-                            if !(v != value) {
-                                states.remove(&id);
-                            }
-                        }
-                        wql::Function::GEq => {
-                            if !(v >= &value) {
-                                states.remove(&id);
-                            }
-                        }
-                        wql::Function::G => {
-                            if !(v > &value) {
-                                states.remove(&id);
-                            }
-                        }
-                        wql::Function::LEq => {
-                            if !(v <= &value) {
-                                states.remove(&id);
-                            }
-                        }
-                        wql::Function::L => {
-                            if !(v < &value) {
-                                states.remove(&id);
-                            }
-                        }
-                        wql::Function::Like => {
-                            if let (Types::String(content), Types::String(regex)) = (v, value) {
-                                let pattern = regex.replace("%", "");
-
-                                if (regex.starts_with('%')
-                                    && regex.ends_with('%')
-                                    && content.contains(&pattern))
-                                    || (regex.starts_with('%') && content.ends_with(&pattern))
-                                    || (regex.ends_with('%') && content.starts_with(&pattern))
-                                    || content.contains(&pattern)
-                                {
-                                    ()
-                                } else {
-                                    states.remove(&id);
-                                }
-                            } else {
-                                states.remove(&id);
-                            }
-                        }
-                        _ => {}
-                    });
-                }
+        }
+        historical.insert(uuid, state);
+    }
+    Ok(historical)
+}
+
+fn remove_as_of_keys(
+    mut state: HashMap<String, Types>,
+    as_of_keys: &[(String, DateTime<Utc>)],
+) -> HashMap<String, Types> {
+    for (key, date) in as_of_keys {
+        state.remove(&as_of_key(key, date));
+    }
+    state
+}
+
+/// Ids of an entity with a write after each `CHANGED SINCE` date.
+type ChangedIds = HashMap<DateTime<Utc>, HashSet<Uuid>>;
+
+/// Dates used by `CHANGED SINCE` clauses, including the ones inside `or`.
+fn changed_since_dates(clauses: &[Clause]) -> BTreeSet<DateTime<Utc>> {
+    clauses
+        .iter()
+        .flat_map(|clause| match clause {
+            Clause::Predicate(wql::Function::ChangedSince(_, date)) => {
+                std::iter::once(date.to_owned()).collect()
             }
-            Clause::ComplexComparisonFunctions(wql::Function::In, key, set) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                for (id, state) in states.clone() {
-                    if !state.get(key).map_or(false, |v| set.contains(v)) {
-                        states.remove(&id);
-                    }
+            Clause::Or(_, inner_clauses) => changed_since_dates(inner_clauses),
+            Clause::Not(inner) => changed_since_dates(std::slice::from_ref(&**inner)),
+            _ => BTreeSet::new(),
+        })
+        .collect()
+}
+
+/// Ids whose latest write, the one their registry points to, is after each `CHANGED SINCE`
+/// date. The registry's day log gives the day of the write, only day logs of the same day
+/// as a date are read, once, to compare the write time.
+async fn changed_ids(
+    entity: &str,
+    registries: &BTreeMap<Uuid, (DataRegister, Vec<u8>)>,
+    clauses: &[Clause],
+    actor: &DataExecutor,
+) -> Result<ChangedIds, Error> {
+    let mut changed = HashMap::new();
+    let mut writes: HashMap<String, HashMap<Uuid, DateTime<Utc>>> = HashMap::new();
+    for date in changed_since_dates(clauses) {
+        let mut ids = HashSet::new();
+        for (uuid, (registry, _)) in registries {
+            match registry_day(registry) {
+                Some(day) if day > date.naive_utc().date() => {
+                    ids.insert(*uuid);
+                    continue;
                 }
+                Some(day) if day < date.naive_utc().date() => continue,
+                _ => (),
             }
-            Clause::ComplexComparisonFunctions(wql::Function::Between, key, start_end) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                for (id, state) in states.clone() {
-                    if !state
-                        .get(key)
-                        .map_or(false, |v| v >= &start_end[0] && v <= &start_end[1])
-                    {
-                        states.remove(&id);
-                    }
-                }
+            if !writes.contains_key(&registry.file_name) {
+                let day_writes = actor
+                    .send(ReadEntityWrites::new(entity, registry.file_name.clone()))
+                    .await??;
+                writes.insert(registry.file_name.clone(), day_writes);
+            }
+            if matches!(writes[&registry.file_name].get(uuid), Some(written) if *written > date) {
+                ids.insert(*uuid);
             }
-            _ => (),
         }
+        changed.insert(date, ids);
     }
+    Ok(changed)
+}
 
-    states
+/// Day of the `%Y_%m_%d` day log a registry was written to.
+fn registry_day(registry: &DataRegister) -> Option<NaiveDate> {
+    let stem = std::path::Path::new(&registry.file_name)
+        .file_stem()?
+        .to_str()?;
+    NaiveDate::parse_from_str(stem, "%Y_%m_%d").ok()
 }
 
 fn or_clauses(
     state: &HashMap<std::string::String, wql::Types>,
+    changed: &BTreeSet<DateTime<Utc>>,
     args_to_key: &HashMap<String, String>,
     inner_clauses: &[Clause],
 ) -> bool {
-    let default = String::new();
     inner_clauses
         .par_iter()
-        .map(|clause| match clause {
-            Clause::ValueAttribution(_, _, _) => true,
-            Clause::Or(_, or_inner_clauses) => or_clauses(state, &args_to_key, or_inner_clauses),
-            Clause::ContainsKeyValue(_, key, value) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                state.get(key).map_or(false, |v| value == v)
-            }
-            Clause::SimpleComparisonFunction(f, key, value) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                state.get(key).map_or(false, |v| match f {
-                    wql::Function::Eq => v == value,
-                    wql::Function::NotEq => v != value,
-                    wql::Function::GEq => v >= value,
-                    wql::Function::G => v > value,
-                    wql::Function::LEq => v <= value,
-                    wql::Function::L => v < value,
-                    wql::Function::Like => {
-                        if let (Types::String(content), Types::String(regex)) = (v, value) {
-                            if regex.starts_with('%') && regex.ends_with('%') {
-                                let regex = regex.replace("%", "");
-                                content.contains(&regex)
-                            } else if regex.starts_with('%') {
-                                let regex = regex.replace("%", "");
-                                content.ends_with(&regex)
-                            } else if regex.ends_with('%') {
-                                let regex = regex.replace("%", "");
-                                content.starts_with(&regex)
-                            } else {
-                                content.contains(&regex[..])
-                            }
+        .map(|clause| matches_clause(state, changed, args_to_key, clause))
+        .any(|f| f)
+}
+
+/// `true` when exactly one of the clauses matches, all of them are evaluated.
+fn xor_clauses(
+    state: &HashMap<std::string::String, wql::Types>,
+    changed: &BTreeSet<DateTime<Utc>>,
+    args_to_key: &HashMap<String, String>,
+    inner_clauses: &[Clause],
+) -> bool {
+    inner_clauses
+        .iter()
+        .filter(|clause| matches_clause(state, changed, args_to_key, clause))
+        .count()
+        == 1
+}
+
+/// Evaluates a single clause against `state`, used by `or`, `xor` and `not`.
+fn matches_clause(
+    state: &HashMap<std::string::String, wql::Types>,
+    changed: &BTreeSet<DateTime<Utc>>,
+    args_to_key: &HashMap<String, String>,
+    clause: &Clause,
+) -> bool {
+    let default = String::new();
+    match clause {
+        Clause::ValueAttribution(_, _, _) | Clause::ValueAttributionAsOf(_, _, _, _) => true,
+        Clause::Or(wql::Function::Xor, xor_inner_clauses) => {
+            xor_clauses(state, changed, args_to_key, xor_inner_clauses)
+        }
+        Clause::Or(_, or_inner_clauses) => {
+            or_clauses(state, changed, args_to_key, or_inner_clauses)
+        }
+        Clause::Not(inner) => !matches_clause(state, changed, args_to_key, inner),
+        Clause::ContainsKeyValue(_, key, value) => state.get(key).is_some_and(|v| value == v),
+        Clause::SimpleComparisonFunction(f, key, value) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key).is_some_and(|v| match f {
+                wql::Function::Eq => v == value,
+                wql::Function::NotEq => v != value,
+                wql::Function::GEq => v >= value,
+                wql::Function::G => v > value,
+                wql::Function::LEq => v <= value,
+                wql::Function::L => v < value,
+                wql::Function::Like => {
+                    if let (Types::String(content), Types::String(regex)) = (v, value) {
+                        if regex.starts_with('%') && regex.ends_with('%') {
+                            let regex = regex.replace("%", "");
+                            content.contains(&regex)
+                        } else if regex.starts_with('%') {
+                            let regex = regex.replace("%", "");
+                            content.ends_with(&regex)
+                        } else if regex.ends_with('%') {
+                            let regex = regex.replace("%", "");
+                            content.starts_with(&regex)
                         } else {
-                            false
+                            content.contains(&regex[..])
                         }
+                    } else {
+                        false
                     }
-                    _ => false,
-                })
-            }
-            Clause::ComplexComparisonFunctions(wql::Function::In, key, set) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                state.get(key).map_or(false, |v| set.contains(v))
-            }
-            Clause::ComplexComparisonFunctions(wql::Function::Between, key, start_end) => {
-                let key = args_to_key.get(key).unwrap_or(&default);
-                state
-                    .get(key)
-                    .map_or(false, |v| v >= &start_end[0] && v <= &start_end[1])
-            }
-            _ => false,
-        })
-        .any(|f| f)
+                }
+                _ => false,
+            })
+        }
+        Clause::KeyComparisonFunction(f, key, other_key) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            let other_key = args_to_key.get(other_key).unwrap_or(&default);
+            compare_keys(state, f, key, other_key)
+        }
+        Clause::ComplexComparisonFunctions(wql::Function::In, key, set) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key).is_some_and(|v| set.contains(v))
+        }
+        Clause::ComplexComparisonFunctions(wql::Function::Between, key, start_end) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state
+                .get(key)
+                .is_some_and(|v| v >= &start_end[0] && v <= &start_end[1])
+        }
+        Clause::Predicate(wql::Function::IsType(key, type_name)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key).map(Types::type_name) == Some(type_name)
+        }
+        Clause::Predicate(wql::Function::Within(key, distance, center)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            is_within(state.get(key), *distance, center)
+        }
+        Clause::Predicate(wql::Function::InSubnet(key, network, prefix)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            in_subnet(state.get(key), network, *prefix)
+        }
+        Clause::Predicate(wql::Function::HasKey(key, map_key)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            has_map_key(state.get(key), map_key)
+        }
+        Clause::Predicate(wql::Function::Domain(key, domain)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key).and_then(Types::email_domain) == Some(domain.as_str())
+        }
+        Clause::Predicate(wql::Function::Country(key, code)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            matches!(state.get(key), Some(Types::Phone(phone)) if phone.starts_with(code.as_str()))
+        }
+        Clause::Predicate(wql::Function::IsBool(key, expected)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.get(key) == Some(&Types::Boolean(*expected))
+        }
+        Clause::Predicate(wql::Function::MapSize(key, f, size)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            compare_map_size(state.get(key), f, *size)
+        }
+        Clause::Predicate(wql::Function::StrLen(key, f, len)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            compare_str_len(state.get(key), f, *len)
+        }
+        Clause::Predicate(wql::Function::Arithmetic(expression, f, other)) => {
+            compare_expression(state, args_to_key, expression, f, other)
+        }
+        Clause::Predicate(wql::Function::UuidTime(key, f, date)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            compare_uuid_time(state.get(key), f, date)
+        }
+        Clause::Predicate(wql::Function::NilUuid(key)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            matches!(state.get(key), Some(Types::Uuid(id)) if id.is_nil())
+        }
+        Clause::Predicate(wql::Function::UuidVersion(key, version)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            matches!(state.get(key), Some(Types::Uuid(id)) if id.get_version_num() == *version)
+        }
+        Clause::Predicate(wql::Function::WithinLast(key, seconds)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            is_within_last(state.get(key), *seconds, &Utc::now())
+        }
+        Clause::Predicate(wql::Function::ComparedToNow(key, f)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            compare_to_now(state.get(key), f, &Utc::now())
+        }
+        Clause::Predicate(wql::Function::Verify(key, candidate)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            verify_hash(state.get(key), candidate)
+        }
+        Clause::Predicate(wql::Function::VerifyAll(keys, candidate)) => {
+            verify_all_hashes(state, keys, args_to_key, candidate)
+        }
+        Clause::Predicate(wql::Function::HashEq(key, hash)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            hash_eq(state.get(key), hash)
+        }
+        Clause::Predicate(wql::Function::ChangedSince(key, date)) => {
+            let key = args_to_key.get(key).unwrap_or(&default);
+            state.contains_key(key) && changed.contains(date)
+        }
+        _ => false,
+    }
 }
 
 async fn generate_state(
     registries: &BTreeMap<Uuid, (DataRegister, Vec<u8>)>,
     args_to_select: ToSelect,
+    functions: &HashMap<String, Algebra>,
+    hash_keys: &HashSet<String>,
+    filter: Option<&WhereFilter<'_>>,
 ) -> Result<BTreeMap<Uuid, HashMap<String, Types>>, Error> {
     let mut states: BTreeMap<Uuid, HashMap<String, Types>> = BTreeMap::new();
     for (uuid, (_, state)) in registries {
         let state: HashMap<String, Types> = bincode::deserialize(&state).unwrap();
+        if !is_valid_at(&state, functions) {
+            continue;
+        }
+        let state = project_scalars(state, functions);
         let state = state
             .into_par_iter()
-            .filter(|(_, v)| !v.is_hash())
+            .filter(|(k, v)| !v.is_hash() || hash_keys.contains(k))
             .map(|(k, v)| (k.to_owned(), v.to_owned()));
         let filtered = if let ToSelect::Keys(ref keys) = args_to_select {
             state
@@ -247,8 +932,33 @@ async fn generate_state(
         } else {
             state.collect::<HashMap<String, Types>>()
         };
+        if let Some(filter) = filter {
+            verify_hash_state(&filtered, hash_keys)?;
+            if !filter.matches(uuid, &filtered) {
+                continue;
+            }
+        }
 
         states.insert(uuid.to_owned(), filtered);
     }
     Ok(states)
 }
+
+/// Counts the registries whose state is kept by `filter`, like `generate_state` for all keys
+/// but without collecting the states.
+fn count_matching(
+    registries: &BTreeMap<Uuid, (DataRegister, Vec<u8>)>,
+    hash_keys: &HashSet<String>,
+    filter: &WhereFilter<'_>,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    for (uuid, (_, state)) in registries {
+        let mut state: HashMap<String, Types> = bincode::deserialize(state).unwrap();
+        state.retain(|k, v| !v.is_hash() || hash_keys.contains(k));
+        verify_hash_state(&state, hash_keys)?;
+        if filter.matches(uuid, &state) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -360,6 +360,144 @@ async fn clause_or() {
     clear();
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn clause_not() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_not")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {a: 3, status: \"open\",} INTO test_not",
+        "INSERT {a: 123, status: \"closed\",} INTO test_not",
+        "INSERT {a: 45, status: \"open\",} INTO test_not",
+        "INSERT {a: 7, status: \"pending\",} INTO test_not",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_not WHERE {
+            ?* test_not:a ?a,
+            ?* test_not:status ?status,
+            (not (== ?status \"open\")),
+            (not (not (< ?a 100))),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["status"],
+        Types::String("pending".to_string())
+    );
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_not WHERE {
+            ?* test_not:a ?a,
+            ?* test_not:status ?status,
+            (or
+                (not (between ?a 0 50))
+                (== ?status \"pending\")
+            ),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|state| state["a"] == Types::Integer(123) || state["a"] == Types::Integer(7)));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_xor() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_xor",
+        "INSERT {name: \"tt\", p: true, q: true,} INTO test_xor",
+        "INSERT {name: \"tf\", p: true, q: false,} INTO test_xor",
+        "INSERT {name: \"ft\", p: false, q: true,} INTO test_xor",
+        "INSERT {name: \"ff\", p: false, q: false,} INTO test_xor",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (clause, expected) in &[
+        ("(xor (== ?p true) (== ?q true))", vec!["ft", "tf"]),
+        ("(not (xor (== ?p true) (== ?q true)))", vec!["ff", "tt"]),
+        (
+            "(or (xor (== ?p true) (== ?q true)) (== ?name \"ff\"))",
+            vec!["ff", "ft", "tf"],
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_xor WHERE {{
+                ?* test_xor:name ?name,
+                ?* test_xor:p ?p,
+                ?* test_xor:q ?q,
+                {},
+            }}",
+                clause
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+        let mut names = result
+            .values()
+            .map(|state| match &state["name"] {
+                Types::String(name) => name.to_owned(),
+                _ => panic!(),
+            })
+            .collect::<Vec<String>>();
+        names.sort();
+
+        assert_eq!(&names, expected);
+    }
+
+    clear();
+}
+
 #[ignore]
 #[actix_rt::test]
 async fn clause_like() {
@@ -429,6 +567,1403 @@ async fn clause_like() {
     clear();
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn clause_is_of_type() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_is_of_type")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {a: 3,} INTO test_is_of_type",
+        "INSERT {a: 4.5,} INTO test_is_of_type",
+        "INSERT {a: \"hello\",} INTO test_is_of_type",
+        "INSERT {a: 43,} INTO test_is_of_type",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_is_of_type WHERE {
+            ?* test_is_of_type:a ?a,
+            (?a IS OF TYPE Integer),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|map| matches!(map["a"], Types::Integer(_))));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_is_of_type WHERE {
+            ?* test_is_of_type:a ?a,
+            (?a IS OF TYPE Number),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_key_comparison() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_key_comparison")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {start: 3, end: 5,} INTO test_key_comparison",
+        "INSERT {start: 7, end: 5,} INTO test_key_comparison",
+        "INSERT {start: 2, end: 2.5,} INTO test_key_comparison",
+        "INSERT {start: 1, end: \"later\",} INTO test_key_comparison",
+        "INSERT {start: 1,} INTO test_key_comparison",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_key_comparison WHERE {
+            ?* test_key_comparison:start ?start,
+            ?* test_key_comparison:end ?end,
+            (< ?start ?end),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|map| map["start"] == Types::Integer(3) || map["start"] == Types::Integer(2)));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_key_comparison WHERE {
+            ?* test_key_comparison:start ?start,
+            ?* test_key_comparison:end ?end,
+            (or
+                (> ?start ?end)
+                (== ?start 1)
+            ),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 3);
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_within_point() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_within")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {name: \"near\", loc: point(13.0, 48.0),} INTO test_within",
+        "INSERT {name: \"far\", loc: point(30.0, 45.0),} INTO test_within",
+        "INSERT {name: \"center\", loc: point(12.0, 45.0),} INTO test_within",
+        "INSERT {name: \"no_point\", loc: 12.0,} INTO test_within",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_within WHERE {
+            ?* test_within:loc ?loc,
+            (?loc WITHIN 5.0 OF point(12.0, 45.0)),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|map| map["name"] == Types::String("near".to_string())
+            || map["name"] == Types::String("center".to_string())));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_in_subnet() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_in_subnet")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {name: \"inside\", ip: 10.1.2.3,} INTO test_in_subnet",
+        "INSERT {name: \"outside\", ip: 11.0.0.1,} INTO test_in_subnet",
+        "INSERT {name: \"v6\", ip: 2001:db8::1,} INTO test_in_subnet",
+        "INSERT {name: \"string\", ip: \"10.0.0.1\",} INTO test_in_subnet",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_in_subnet WHERE {
+            ?* test_in_subnet:ip ?ip,
+            (or
+                (?ip IN_SUBNET \"10.0.0.0/8\")
+                (?ip IN_SUBNET \"2001:db8::/32\")
+            ),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|map| map["name"] == Types::String("inside".to_string())
+            || map["name"] == Types::String("v6".to_string())));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_in_subnet WHERE {
+            ?* test_in_subnet:ip ?ip,
+            (> ?ip 10.255.255.255),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["ip"],
+        Types::IpAddr("11.0.0.1".parse().unwrap())
+    );
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_percentage() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_percentage",
+        "INSERT {name: \"done\", completion: 95.5%,} INTO test_percentage",
+        "INSERT {name: \"half\", completion: 50%,} INTO test_percentage",
+        "INSERT {name: \"float\", completion: 99.0,} INTO test_percentage",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"invalid\", completion: 120%,} INTO test_percentage")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_percentage WHERE {
+            ?* test_percentage:completion ?completion,
+            (> ?completion 80%),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["completion"],
+        Types::Percentage(95.5)
+    );
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_within_last() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let now = chrono::Utc::now();
+    let recent = (now - chrono::Duration::hours(2)).to_rfc3339();
+    let old = (now - chrono::Duration::days(8)).to_rfc3339();
+
+    for payload in &[
+        "CREATE ENTITY test_within_last".to_string(),
+        format!(
+            "INSERT {{name: \"recent\", created_at: {}, status: \"open\",}} INTO test_within_last",
+            recent
+        ),
+        format!(
+            "INSERT {{name: \"closed\", created_at: {}, status: \"closed\",}} INTO test_within_last",
+            recent
+        ),
+        format!(
+            "INSERT {{name: \"old\", created_at: {}, status: \"open\",}} INTO test_within_last",
+            old
+        ),
+        "INSERT {name: \"string\", created_at: \"yesterday\", status: \"open\",} INTO test_within_last".to_string(),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_within_last WHERE {
+            ?* test_within_last:created_at ?created,
+            ?* test_within_last:status \"open\",
+            (?created WITHIN LAST 7d),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["name"],
+        Types::String("recent".to_string())
+    );
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_within_last WHERE {
+            ?* test_within_last:created_at ?created,
+            (?created WITHIN LAST 30m),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "{}");
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_compared_to_now() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let now = chrono::Utc::now();
+    let expired = (now - chrono::Duration::hours(1)).to_rfc3339();
+    let valid = (now + chrono::Duration::days(1)).to_rfc3339();
+
+    for payload in &[
+        "CREATE ENTITY test_compared_to_now".to_string(),
+        format!(
+            "INSERT {{name: \"expired\", expires_at: {}, kind: \"token\",}} INTO test_compared_to_now",
+            expired
+        ),
+        format!(
+            "INSERT {{name: \"valid\", expires_at: {}, kind: \"token\",}} INTO test_compared_to_now",
+            valid
+        ),
+        format!(
+            "INSERT {{name: \"expired_key\", expires_at: {}, kind: \"key\",}} INTO test_compared_to_now",
+            expired
+        ),
+        "INSERT {name: \"string\", expires_at: \"yesterday\", kind: \"token\",} INTO test_compared_to_now".to_string(),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (clause, expected) in &[
+        ("(< ?expires now())", "expired"),
+        ("(>= ?expires NOW())", "valid"),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_compared_to_now WHERE {{
+                ?* test_compared_to_now:expires_at ?expires,
+                ?* test_compared_to_now:kind \"token\",
+                {},
+            }}",
+                clause
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result.values().next().unwrap()["name"],
+            Types::String(expected.to_string())
+        );
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_count_only() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let mut payloads = vec!["CREATE ENTITY test_count_only".to_string()];
+    for a in 1..=5 {
+        payloads.push(format!(
+            "INSERT {{a: {}, b: \"odd\",}} INTO test_count_only",
+            a
+        ));
+    }
+    for payload in payloads {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (query, expected) in &[
+        ("SELECT COUNT(*) FROM test_count_only", "5"),
+        (
+            "SELECT COUNT(*) FROM test_count_only WHERE { ?* test_count_only:a ?a, (> ?a 2), }",
+            "3",
+        ),
+        (
+            "SELECT COUNT(*) FROM test_count_only WHERE { ?* test_count_only:a ?a, (> ?a 2), } LIMIT 2",
+            "2",
+        ),
+        (
+            "SELECT COUNT(*) FROM test_count_only WHERE { ?* test_count_only:b \"even\", }",
+            "0",
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(query.to_string())
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        assert_eq!(&body, expected);
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_ratio() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_ratio",
+        "INSERT {name: \"third\", amount: 2/6,} INTO test_ratio",
+        "INSERT {name: \"half\", amount: 1/2,} INTO test_ratio",
+        "INSERT {name: \"float\", amount: 0.5,} INTO test_ratio",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"invalid\", amount: 1/0,} INTO test_ratio")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_ratio WHERE {
+            ?* test_ratio:amount ?amount,
+            (< ?amount 5/12),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["amount"],
+        Types::Ratio { num: 1, den: 3 }
+    );
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_email_domain() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_email",
+        "INSERT {name: \"ana\", email: Ana@Example.com,} INTO test_email",
+        "INSERT {name: \"bia\", email: bia@mail.example.com,} INTO test_email",
+        "INSERT {name: \"string\", email: \"carla@example.com\",} INTO test_email",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"invalid\", email: ana@example,} INTO test_email")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_email WHERE {
+            ?* test_email:email ?email,
+            (?email DOMAIN \"EXAMPLE.com\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["email"],
+        Types::Email("ana@example.com".to_string())
+    );
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_str_len() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_str_len",
+        "INSERT {name: \"julia naomi\", code: 12.345P,} INTO test_str_len",
+        "INSERT {name: \"ãé\", code: 1P,} INTO test_str_len",
+        "INSERT {name: 12345, code: 1234,} INTO test_str_len",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (clause, expected) in &[
+        ("(?name STR_LEN > 4)", vec!["julia naomi"]),
+        ("(?name STR_LEN == 2)", vec!["ãé"]),
+        ("(?code STR_LEN >= 1)", vec!["julia naomi", "ãé"]),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_str_len WHERE {{
+                ?* test_str_len:name ?name,
+                ?* test_str_len:code ?code,
+                {},
+            }}",
+                clause
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+        let mut names = result
+            .values()
+            .filter_map(|state| match &state["name"] {
+                Types::String(name) => Some(name.to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        names.sort();
+        let mut expected = expected.clone();
+        expected.sort_unstable();
+
+        assert_eq!(result.len(), expected.len());
+        assert_eq!(names, expected);
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_arithmetic() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_arithmetic",
+        "INSERT {name: \"cheap\", price: 50, budget: 100,} INTO test_arithmetic",
+        "INSERT {name: \"close\", price: 90.0, budget: 100,} INTO test_arithmetic",
+        "INSERT {name: \"free\", price: 0, budget: 0,} INTO test_arithmetic",
+        "INSERT {name: \"text\", price: \"50\", budget: 100,} INTO test_arithmetic",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (clause, expected) in &[
+        ("(?price * 1.2 < ?budget)", vec!["cheap"]),
+        ("(?budget - ?price >= 50)", vec!["cheap"]),
+        ("(?budget / ?price > 1)", vec!["cheap", "close"]),
+        ("(not (?price + 10 > 60))", vec!["cheap", "free", "text"]),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_arithmetic WHERE {{
+                ?* test_arithmetic:price ?price,
+                ?* test_arithmetic:budget ?budget,
+                {},
+            }}",
+                clause
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+        let mut names = result
+            .values()
+            .filter_map(|state| match &state["name"] {
+                Types::String(name) => Some(name.to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        names.sort();
+        let mut expected = expected.clone();
+        expected.sort_unstable();
+
+        assert_eq!(names, expected, "{}", clause);
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_phone_country() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_phone",
+        "INSERT {name: \"ana\", phone: phone(+1 555-123-4567),} INTO test_phone",
+        "INSERT {name: \"bia\", phone: phone(0044 20 7946 0958),} INTO test_phone",
+        "INSERT {name: \"string\", phone: \"+1 555 987 6543\",} INTO test_phone",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"invalid\", phone: phone(555 123),} INTO test_phone")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_phone WHERE {
+            ?* test_phone:phone ?phone,
+            (?phone COUNTRY \"+1\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["phone"],
+        Types::Phone("+15551234567".to_string())
+    );
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_boolean_shorthand() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_boolean",
+        "INSERT {name: \"on\", active: true,} INTO test_boolean",
+        "INSERT {name: \"off\", active: false,} INTO test_boolean",
+        "INSERT {name: \"string\", active: \"true\",} INTO test_boolean",
+        "INSERT {name: \"missing\",} INTO test_boolean",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (clause, expected) in &[("(?active)", "on"), ("(not ?active)", "off")] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_boolean WHERE {{
+                ?* test_boolean:active ?active,
+                {},
+            }}",
+                clause
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+        assert_eq!(result.len(), 1, "{}", clause);
+        assert_eq!(
+            result.values().next().unwrap()["name"],
+            Types::String(expected.to_string())
+        );
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_map_structure() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_map_structure")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {name: \"owned\", metadata: {owner: \"julia\", tags: 2,},} INTO test_map_structure",
+        "INSERT {name: \"empty\", metadata: {},} INTO test_map_structure",
+        "INSERT {name: \"string\", metadata: \"owner\",} INTO test_map_structure",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_map_structure WHERE {
+            ?* test_map_structure:metadata ?metadata,
+            (?metadata HAS_KEY \"owner\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["name"],
+        Types::String("owned".to_string())
+    );
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_map_structure WHERE {
+            ?* test_map_structure:metadata ?metadata,
+            (not (?metadata MAP_SIZE > 0)),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|map| map["name"] == Types::String("empty".to_string())
+            || map["name"] == Types::String("string".to_string())));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_subquery_in() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let active_id = "a6f3bb46-d1ec-4eb0-8f3c-b3f0e8a9de29";
+    let inactive_id = "8d3ba9f0-3ab1-4ab0-9b1d-7e27e5c6b1c4";
+
+    for payload in &[
+        String::from("CREATE ENTITY subquery_users"),
+        String::from("CREATE ENTITY subquery_orders"),
+        format!(
+            "INSERT {{name: \"ana\", active: true,}} INTO subquery_users WITH {}",
+            active_id
+        ),
+        format!(
+            "INSERT {{name: \"bo\", active: false,}} INTO subquery_users WITH {}",
+            inactive_id
+        ),
+        format!(
+            "INSERT {{total: 10, user_id: {}, user: \"ana\",}} INTO subquery_orders",
+            active_id
+        ),
+        format!(
+            "INSERT {{total: 20, user_id: {}, user: \"bo\",}} INTO subquery_orders",
+            inactive_id
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for payload in &[
+        "Select * From subquery_orders WHERE {
+            ?* subquery_orders:user_id ?user_id,
+            (in ?user_id (SELECT #{id,} FROM subquery_users WHERE {
+                ?* subquery_users:active true,
+            })),
+        }",
+        "Select * From subquery_orders WHERE {
+            ?* subquery_orders:user ?user,
+            (in ?user (SELECT #{name,} FROM subquery_users WHERE {
+                ?* subquery_users:active ?active,
+                (== ?active true),
+            })),
+        }",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert!(result
+            .values()
+            .all(|map| map["total"] == Types::Integer(10)));
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_verify_hash() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_verify ENCRYPT #{pswd,}",
+        "INSERT {name: \"ana\", pswd: \"my password\",} INTO test_verify",
+        "INSERT {name: \"bo\", pswd: \"other password\",} INTO test_verify",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_verify WHERE {
+            ?* test_verify:pswd ?pswd,
+            (?pswd VERIFY \"my password\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert!(result
+        .values()
+        .all(|map| map["name"] == Types::String("ana".to_string()) && !map.contains_key("pswd")));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_verify WHERE {
+            ?* test_verify:name ?name,
+            (?name VERIFY \"ana\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("VerifyNonHashKey"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_hash_eq() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_hash_eq ENCRYPT #{content,}",
+        "INSERT {name: \"ana\", content: \"event a\",} INTO test_hash_eq",
+        "INSERT {name: \"bo\", content: \"event b\",} INTO test_hash_eq",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let date_log = chrono::Utc::now().format("data/%Y_%m_%d.log").to_string();
+    let log = std::fs::read_to_string(date_log).unwrap();
+    let start = log.find("Hash(\"").unwrap() + 6;
+    let hash = &log[start..start + log[start..].find('"').unwrap()];
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "Select * From test_hash_eq WHERE {{
+            ?* test_hash_eq:content ?content,
+            (?content HASH_EQ \"{}\"),
+        }}",
+            hash
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert!(
+        result
+            .values()
+            .all(|map| map["name"] == Types::String("ana".to_string())
+                && !map.contains_key("content"))
+    );
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "Select * From test_hash_eq WHERE {{
+            ?* test_hash_eq:content ?content,
+            (?content HASH_EQ \"{}x\"),
+        }}",
+            hash
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert!(result.is_empty());
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_verify_all_hashes() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_verify_all ENCRYPT #{pswd, pin,}",
+        "INSERT {name: \"ana\", pswd: \"secret\", pin: \"secret\",} INTO test_verify_all",
+        "INSERT {name: \"bo\", pswd: \"secret\", pin: \"other\",} INTO test_verify_all",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_verify_all WHERE {
+            ?* test_verify_all:pswd ?pswd,
+            ?* test_verify_all:pin ?pin,
+            (\"secret\" VERIFY_ALL {?pswd, ?pin}),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert!(result
+        .values()
+        .all(|map| map["name"] == Types::String("ana".to_string())
+            && !map.contains_key("pswd")
+            && !map.contains_key("pin")));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_changed_since() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_changed",
+        "INSERT {name: \"ana\",} INTO test_changed",
+        "INSERT {name: \"bo\",} INTO test_changed",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_changed WHERE {
+            ?* test_changed:name ?name,
+            (?name CHANGED SINCE \"2999-01-01T00:00:00Z\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert!(result.is_empty());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_changed WHERE {
+            ?* test_changed:name ?name,
+            (or
+                (?name CHANGED SINCE \"2999-01-01T00:00:00Z\")
+                (== ?name \"ana\")
+            ),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert!(result
+        .values()
+        .all(|map| map["name"] == Types::String("ana".to_string())
+            && !map.keys().any(|k| k.starts_with("CHANGED SINCE"))));
+
+    let since = chrono::Utc::now();
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"cy\",} INTO test_changed")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    for (date, names) in &[
+        ("2000-01-01T00:00:00Z".to_string(), vec!["ana", "bo", "cy"]),
+        (since.to_rfc3339(), vec!["cy"]),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_changed WHERE {{
+                ?* test_changed:name ?name,
+                (?name CHANGED SINCE \"{}\"),
+            }}",
+                date
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+        let mut result = result
+            .values()
+            .map(|map| map["name"].clone())
+            .collect::<Vec<Types>>();
+        result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        assert_eq!(
+            result,
+            names
+                .iter()
+                .map(|name| Types::String(name.to_string()))
+                .collect::<Vec<Types>>()
+        );
+    }
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_uuid_time() {
+    use uuid::v1::{Context, Timestamp};
+
+    let context = Context::new(0);
+    let v1 = |seconds: u64| {
+        Uuid::new_v1(
+            Timestamp::from_unix(&context, seconds, 0),
+            &[1, 2, 3, 4, 5, 6],
+        )
+        .unwrap()
+    };
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_uuid_time".to_string(),
+        // 2022-06-01T00:00:00Z
+        format!(
+            "INSERT {{name: \"old\", key: {},}} INTO test_uuid_time",
+            v1(1654041600)
+        ),
+        // 2023-06-01T00:00:00Z
+        format!(
+            "INSERT {{name: \"new\", key: {},}} INTO test_uuid_time",
+            v1(1685577600)
+        ),
+        format!(
+            "INSERT {{name: \"v4\", key: {},}} INTO test_uuid_time",
+            Uuid::new_v4()
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_uuid_time WHERE {
+            ?* test_uuid_time:key ?key,
+            (?key UUID_TIME BEFORE \"2023-01-01\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(
+        result.values().next().unwrap()["name"],
+        Types::String("old".to_string())
+    );
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "Select * From test_uuid_time WHERE {
+            ?* test_uuid_time:key ?key,
+            (?key UUID_TIME AFTER \"2022-01-01T00:00:00Z\"),
+        }",
+        )
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+    assert_eq!(result.len(), 2);
+    assert!(result
+        .values()
+        .all(|map| map["name"] != Types::String("v4".to_string())));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn clause_uuid_nil_and_version() {
+    use uuid::v1::{Context, Timestamp};
+
+    let context = Context::new(0);
+    let v1 = Uuid::new_v1(
+        Timestamp::from_unix(&context, 1654041600, 0),
+        &[1, 2, 3, 4, 5, 6],
+    )
+    .unwrap();
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for payload in &[
+        "CREATE ENTITY test_uuid_version".to_string(),
+        format!(
+            "INSERT {{name: \"nil\", key: {},}} INTO test_uuid_version",
+            Uuid::nil()
+        ),
+        format!(
+            "INSERT {{name: \"v1\", key: {},}} INTO test_uuid_version",
+            v1
+        ),
+        format!(
+            "INSERT {{name: \"v4\", key: {},}} INTO test_uuid_version",
+            Uuid::new_v4()
+        ),
+        "INSERT {name: \"string\", key: \"00000000-0000-0000-0000-000000000000\",} INTO test_uuid_version".to_string(),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    for (predicate, expected) in &[
+        ("(?key IS NIL_UUID)", "nil"),
+        ("(?key UUID_VERSION 1)", "v1"),
+        ("(?key UUID_VERSION 4)", "v4"),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "Select * From test_uuid_version WHERE {{
+                ?* test_uuid_version:key ?key,
+                {},
+            }}",
+                predicate
+            ))
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result.values().next().unwrap()["name"],
+            Types::String(expected.to_string())
+        );
+    }
+
+    clear();
+}
+
 trait BodyTest {
     fn as_str(&self) -> &str;
 }
@@ -0,0 +1,41 @@
+use crate::{
+    core::{read_lock, MediaType, OutputFormat},
+    model::{
+        error::{error_to_http, Error},
+        DataLocalContext,
+    },
+};
+use actix_web::{HttpRequest, HttpResponse};
+use std::collections::BTreeMap;
+
+/// Lists the names of the created entities in order, `?counts=true` maps each name to its
+/// number of ids instead.
+pub async fn entities_handler(req: HttpRequest, local_data: DataLocalContext) -> HttpResponse {
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req);
+    let counts = req
+        .query_string()
+        .split('&')
+        .any(|param| param.eq_ignore_ascii_case("counts=true"));
+
+    let body = entity_counts(&local_data).and_then(|entities| {
+        if counts {
+            format.write(media, &entities)
+        } else {
+            format.write(media, &entities.keys().collect::<Vec<_>>())
+        }
+    });
+    match body {
+        Ok(body) => HttpResponse::Ok().body(body),
+        Err(e) => error_to_http(&e),
+    }
+}
+
+/// Names and number of ids of the entities, the lock is released before serializing them.
+fn entity_counts(local_data: &DataLocalContext) -> Result<BTreeMap<String, usize>, Error> {
+    let local_data = read_lock(local_data)?;
+    Ok(local_data
+        .iter()
+        .map(|(entity, ids)| (entity.to_owned(), ids.len()))
+        .collect())
+}
@@ -0,0 +1,65 @@
+use crate::http::routes;
+use actix_http::body::ResponseBody;
+use actix_web::{body::Body, test, App};
+use std::collections::BTreeMap;
+
+#[ignore]
+#[actix_rt::test]
+async fn test_list_entities_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    for payload in &[
+        "CREATE ENTITY list_entities_b",
+        "CREATE ENTITY list_entities_a",
+        "INSERT {a: 1,} INTO list_entities_b",
+        "INSERT {a: 2,} INTO list_entities_b",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/entities?format=compact")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body();
+    let body = body.as_ref().unwrap();
+    assert_eq!(
+        &Body::from("[\"list_entities_a\",\"list_entities_b\"]"),
+        body
+    );
+
+    let req = test::TestRequest::get()
+        .uri("/entities?counts=true")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let counts: BTreeMap<String, usize> = ron::de::from_str(&body).unwrap();
+    assert_eq!(counts.get("list_entities_a"), Some(&0));
+    assert_eq!(counts.get("list_entities_b"), Some(&2));
+    assert_eq!(counts.len(), 2);
+}
+
+trait BodyTest {
+    fn as_str(&self) -> &str;
+}
+
+impl BodyTest for ResponseBody<Body> {
+    fn as_str(&self) -> &str {
+        match self {
+            ResponseBody::Body(ref b) => match b {
+                Body::Bytes(ref by) => std::str::from_utf8(by).unwrap(),
+                _ => panic!(),
+            },
+            ResponseBody::Other(ref b) => match b {
+                Body::Bytes(ref by) => std::str::from_utf8(by).unwrap(),
+                _ => panic!(),
+            },
+        }
+    }
+}
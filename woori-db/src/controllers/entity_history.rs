@@ -1,15 +1,16 @@
 use crate::model::error::{error_to_http, Error};
 use crate::{
-    actors::history::History,
-    core::pretty_config_output,
-    model::{DataExecutor, DataLocalContext},
-    schemas::history::EntityHistoryInfo,
+    actors::{history::History, when::ReadEntityLastN},
+    core::{pretty_config_output, read_lock},
+    model::{DataExecutor, DataLocalContext, DataRegister},
+    schemas::history::{EntityByHashInfo, EntityHistoryInfo, HashedRegistry},
 };
 
 use actix_web::{HttpResponse, Responder};
 use chrono::{DateTime, Utc};
 use rayon::prelude::*;
 use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
 use wql::Types;
 
 pub async fn history_handler(
@@ -48,11 +49,7 @@ pub async fn history_controller(
     let info: EntityHistoryInfo = ron::de::from_str(&body)?;
 
     let registry = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registry = if let Some(id_to_registry) = local_data.get(&info.entity_key) {
             if let Some(reg) = id_to_registry.get(&info.entity_id) {
                 reg
@@ -68,6 +65,13 @@ pub async fn history_controller(
         .to_owned();
         registry
     };
+    if let Some(n) = info.last_n {
+        let states = actor
+            .send(ReadEntityLastN::new(&info.entity_key, info.entity_id, n))
+            .await??;
+        return Ok(states.into_iter().collect());
+    }
+
     let mut content = actor.send(registry.0).await??;
     let mut btree: BTreeMap<DateTime<Utc>, HashMap<String, Types>> = BTreeMap::new();
 
@@ -114,3 +118,73 @@ pub async fn history_controller(
 
     Ok(filtered_tree)
 }
+
+pub async fn entity_by_hash_handler(
+    body: String,
+    local_data: DataLocalContext,
+    actor: DataExecutor,
+) -> impl Responder {
+    let response = entity_by_hash_controller(body, local_data, actor).await;
+
+    match response {
+        Err(e) => error_to_http(&e),
+        Ok(resp) => {
+            #[cfg(feature = "json")]
+            let response = serde_json::to_string(&resp).map_err(Error::SerdeJson);
+
+            #[cfg(not(feature = "json"))]
+            let response =
+                ron::ser::to_string_pretty(&resp, pretty_config_output()).map_err(Error::Ron);
+
+            match response {
+                Err(e) => error_to_http(&e),
+                Ok(resp) => HttpResponse::Ok().body(resp),
+            }
+        }
+    }
+}
+
+/// Finds the registry of the entity whose `DataRegister::version` is the hash, walking the
+/// previous registries of every id from the logs, and returns its state.
+pub async fn entity_by_hash_controller(
+    body: String,
+    local_data: DataLocalContext,
+    actor: DataExecutor,
+) -> Result<HashedRegistry, Error> {
+    #[cfg(feature = "json")]
+    let info: EntityByHashInfo = serde_json::from_str(&body)?;
+    #[cfg(not(feature = "json"))]
+    let info: EntityByHashInfo = ron::de::from_str(&body)?;
+
+    let registries = {
+        let local_data = read_lock(&local_data)?;
+        local_data
+            .get(&info.entity_key)
+            .ok_or_else(|| Error::EntityNotCreated(info.entity_key.clone()))?
+            .iter()
+            .map(|(id, (registry, _))| (*id, registry.to_owned()))
+            .collect::<Vec<(Uuid, DataRegister)>>()
+    };
+
+    for (entity_id, registry) in registries {
+        let mut registry = Some(registry);
+        while let Some(current) = registry {
+            let content = actor.send(current.clone()).await??;
+            let (state, datetime, previous_registry) = actor.send(History(content)).await??;
+            if current.version() == info.hash {
+                let state = state
+                    .into_iter()
+                    .filter(|(_, v)| !v.is_hash())
+                    .collect::<HashMap<String, Types>>();
+                return Ok(HashedRegistry {
+                    entity_id,
+                    datetime,
+                    state,
+                });
+            }
+            registry = previous_registry;
+        }
+    }
+
+    Err(Error::RegistryHashNotFound(info.entity_key, info.hash))
+}
@@ -1,8 +1,12 @@
-use crate::{http::routes, schemas::tx::TxResponse};
+use crate::{
+    http::routes,
+    schemas::{history::HashedRegistry, tx::TxResponse},
+};
 use actix_http::body::ResponseBody;
-use actix_web::{body::Body, test, App};
+use actix_web::{body::Body, http::StatusCode, test, App};
 use chrono::Utc;
 use std::{thread, time};
+use wql::Types;
 
 #[actix_rt::test]
 async fn test_history_ok() {
@@ -163,6 +167,82 @@ async fn test_start_end_time_ok() {
     clear();
 }
 
+#[actix_rt::test]
+async fn test_entity_by_hash_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_by_hash")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_by_hash")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "SELECT * FROM test_by_hash ID {} WITH VERSION",
+            uuid
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let version = body
+        .split("\"_version\": String(\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_string();
+
+    for a in &[12, 34] {
+        let payload = format!("UPDATE test_by_hash SET {{a: {},}} INTO {}", a, uuid);
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = format!("(entity_key: \"test_by_hash\", hash: \"{}\",)", version);
+    let req = test::TestRequest::post()
+        .set_payload(payload)
+        .uri("/entity-by-hash")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let registry: HashedRegistry = ron::de::from_str(&body).unwrap();
+    assert_eq!(registry.entity_id, uuid);
+    assert_eq!(registry.state["a"], Types::Integer(123));
+    assert_eq!(registry.state["b"], Types::Float(12.3));
+
+    let req = test::TestRequest::post()
+        .set_payload("(entity_key: \"test_by_hash\", hash: \"0000000000000000\",)")
+        .uri("/entity-by-hash")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    clear();
+}
+
 trait BodyTest {
     fn as_str(&self) -> &str;
 }
@@ -1,8 +1,12 @@
 #[cfg(test)]
 pub mod algebra_test;
+pub(crate) mod audit;
 pub mod clauses;
 #[cfg(test)]
 pub mod clauses_test;
+pub(crate) mod entities;
+#[cfg(test)]
+pub mod entities_test;
 pub(crate) mod entity_history;
 #[cfg(all(test, feature = "history"))]
 pub mod entity_history_test;
@@ -11,9 +15,16 @@ pub mod json_history_test;
 pub(crate) mod query;
 #[cfg(test)]
 pub mod query_test;
+pub(crate) mod raw_log;
+#[cfg(test)]
+pub mod raw_log_test;
 pub(crate) mod relation;
 #[cfg(test)]
 pub mod relation_test;
+pub(crate) mod subscribe;
+#[cfg(test)]
+pub mod subscribe_test;
+pub(crate) mod transaction;
 pub(crate) mod tx;
 #[cfg(test)]
 pub mod tx_test;
@@ -1,9 +1,14 @@
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     str::FromStr,
+    time::Instant,
 };
 
-use actix_web::{HttpResponse, Responder};
+use actix_web::{
+    web::{self, Bytes},
+    HttpRequest, HttpResponse, Responder,
+};
+use chrono::{DateTime, Utc};
 use rayon::prelude::*;
 use uuid::Uuid;
 use wql::{ToSelect, Types, Wql};
@@ -12,98 +17,485 @@ use crate::{
     actors::{
         encrypts::VerifyEncryption,
         state::State,
-        when::{ReadEntitiesAt, ReadEntityIdAt, ReadEntityRange},
+        when::{ReadEntitiesAt, ReadEntityIdAt, ReadEntityIdUntil, ReadEntityRange, ReadLastClear},
     },
-    core::query::{
-        dedup_option_states, dedup_states, filter_keys_and_hash, get_limit_offset_count,
-        get_result_after_manipulation, get_result_after_manipulation_for_options,
-        registries_to_states,
+    auth::schemas::Role,
+    core::{
+        cache::QueryCache,
+        envelope_requested,
+        query::{
+            dedup_option_states, dedup_states, describe_schema, describe_states, encrypted_keys,
+            filter_keys_and_hash, get_limit_offset_count, get_result_after_manipulation,
+            get_result_after_manipulation_for_options, is_valid_at, is_valid_registry, mask_keys,
+            omit_nulls, project_scalars, query_pool, registries_to_states, registry_times,
+            with_proof, with_version,
+        },
+        read_lock, MediaType, OutputFormat,
     },
+    io::paths::{query_date_log, range_date_logs},
     model::{
         error::{error_to_http, Error},
-        DataEncryptContext, DataExecutor, DataLocalContext, DataRegister,
+        DataEncryptContext, DataExecutor, DataLocalContext, DataRegister, DataSchemaContext,
+    },
+    schemas::query::{
+        time_range_chunk, time_range_end, CheckEntry, ParameterizedQuery,
+        Response as QueryResponse, VersionInfo,
     },
-    schemas::query::Response as QueryResponse,
 };
 
 use super::{
+    audit::{audit, session_roles},
     clauses::select_where_controller,
     relation::{difference, intersect, join, union},
 };
 
 pub async fn wql_handler(
+    req: HttpRequest,
+    body: String,
+    local_data: DataLocalContext,
+    encryption: DataEncryptContext,
+    schemas: DataSchemaContext,
+    actor: DataExecutor,
+) -> impl Responder {
+    let response = match Wql::from_str(&body) {
+        Ok(query) => respond(&req, query, local_data, encryption, schemas, actor).await,
+        Err(e) => Err(Error::QueryFormat(e)),
+    };
+
+    match response {
+        Err(e) => {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            response
+        }
+        Ok(response) => {
+            audit(&req, &body, &response, None);
+            response
+        }
+    }
+}
+
+/// Executes a query with `$1`, `$2`, ... placeholders, sent with its params like
+/// `(query: "SELECT * FROM my_entity WHERE {?* my_entity:name $1,}", params: [String("julia")])`.
+/// Params are bound to the parsed query, so they are never read as WQL.
+pub async fn params_handler(
+    req: HttpRequest,
+    body: String,
+    local_data: DataLocalContext,
+    encryption: DataEncryptContext,
+    schemas: DataSchemaContext,
+    actor: DataExecutor,
+) -> impl Responder {
+    let response = match parameterized_query(&body) {
+        Ok(query) => respond(&req, query, local_data, encryption, schemas, actor).await,
+        Err(e) => Err(e),
+    };
+
+    match response {
+        Err(e) => {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            response
+        }
+        Ok(response) => {
+            audit(&req, &body, &response, None);
+            response
+        }
+    }
+}
+
+fn parameterized_query(body: &str) -> Result<Wql, Error> {
+    #[cfg(feature = "json")]
+    let query: ParameterizedQuery = serde_json::from_str(body)?;
+    #[cfg(not(feature = "json"))]
+    let query: ParameterizedQuery = ron::de::from_str(body)?;
+
+    let wql = Wql::from_str_with_params(&query.query, &query.params).map_err(Error::QueryFormat)?;
+    if is_query(&wql) {
+        Ok(wql)
+    } else {
+        Err(Error::NonSelectQuery)
+    }
+}
+
+async fn respond(
+    req: &HttpRequest,
+    query: Wql,
+    local_data: DataLocalContext,
+    encryption: DataEncryptContext,
+    schemas: DataSchemaContext,
+    actor: DataExecutor,
+) -> Result<HttpResponse, Error> {
+    let format = OutputFormat::from_request(req);
+    let media = MediaType::from_request(req);
+    let masked = masked_keys(req, &query, &encryption)?;
+    if let Wql::SelectWhenRange(entity_name, uuid, start_date, end_date) = query {
+        return select_all_when_range_controller(
+            entity_name,
+            uuid,
+            start_date,
+            end_date,
+            masked,
+            actor,
+        )
+        .await;
+    }
+
+    let limit = query_limit(&query);
+    let start = Instant::now();
+    let cache = req.app_data::<web::Data<QueryCache>>();
+    cached_query_controller(cache, query, local_data, encryption, schemas, actor)
+        .await
+        .and_then(|mut resp| {
+            resp.mask_keys(&masked);
+            if envelope_requested(req) {
+                let elapsed_ms = start.elapsed().as_millis() as u64;
+                resp.to_envelope(format, media, elapsed_ms, limit)
+            } else {
+                resp.to_string(format, media)
+            }
+        })
+        .map(|resp_body| HttpResponse::Ok().body(resp_body))
+}
+
+/// Executes a list of read-only queries written as an array of strings like
+/// `["SELECT ...", "SELECT ..."]`, so a query can span many lines. The response is an
+/// array with the result, or the error, of each query in order. The whole batch is
+/// rejected if any statement is not a query.
+///
+/// Queries await the worker's `Executor`, so they are joined as futures instead of
+/// running on the rayon query pool, which only serializes their responses.
+pub async fn batch_handler(
+    req: HttpRequest,
+    body: String,
+    local_data: DataLocalContext,
+    encryption: DataEncryptContext,
+    schemas: DataSchemaContext,
+    actor: DataExecutor,
+) -> impl Responder {
+    let statements = match batch_statements(&body) {
+        Ok(statements) => statements,
+        Err(e) => {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            return response;
+        }
+    };
+    let queries = statements
+        .iter()
+        .map(|statement| Wql::from_str(statement))
+        .collect::<Vec<Result<Wql, String>>>();
+    if queries.iter().flatten().any(|query| !is_query(query)) {
+        let e = Error::NonSelectQuery;
+        let response = error_to_http(&e);
+        audit(&req, &body, &response, Some(&e));
+        return response;
+    }
+
+    let cache = req.app_data::<web::Data<QueryCache>>();
+    let responses = futures::future::join_all(queries.into_iter().map(|query| {
+        let local_data = local_data.clone();
+        let encryption = encryption.clone();
+        let schemas = schemas.clone();
+        let actor = actor.clone();
+        let masked = query
+            .as_ref()
+            .map_err(|e| Error::QueryFormat(e.to_owned()))
+            .and_then(|query| masked_keys(&req, query, &encryption));
+        async move {
+            match (query, masked) {
+                (Ok(query), Ok(masked)) => {
+                    cached_query_controller(cache, query, local_data, encryption, schemas, actor)
+                        .await
+                        .map(|mut resp| {
+                            resp.mask_keys(&masked);
+                            resp
+                        })
+                }
+                (_, Err(e)) => Err(e),
+                (Err(e), _) => Err(Error::QueryFormat(e)),
+            }
+        }
+    }))
+    .await;
+
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req).nested();
+    let responses = query_pool().install(|| {
+        responses
+            .into_par_iter()
+            .map(|response| {
+                response
+                    .and_then(|resp| resp.to_string(format, media))
+                    .unwrap_or_else(|e| e.to_string())
+            })
+            .collect::<Vec<String>>()
+    });
+
+    let response = HttpResponse::Ok().body(format!("[\n{}\n]", responses.join(",\n")));
+    audit(&req, &body, &response, None);
+    response
+}
+
+/// Checks the encrypted values of many ids, like `[(entity: "my_entity", id: "<uuid>",
+/// content: {"pswd": "my-password"}),]`. The response maps each id to its `CHECK` result
+/// or to its error, so one invalid entry doesn't fail the others.
+pub async fn check_batch_handler(
+    req: HttpRequest,
     body: String,
     local_data: DataLocalContext,
     encryption: DataEncryptContext,
     actor: DataExecutor,
 ) -> impl Responder {
-    let query = Wql::from_str(&body);
-    let response = match query {
-        Ok(Wql::Select(entity, ToSelect::All, Some(uuid), _)) => {
-            select_all_with_id(entity, uuid, local_data).await
+    #[cfg(feature = "json")]
+    let entries = serde_json::from_str::<Vec<CheckEntry>>(&body).map_err(Error::from);
+    #[cfg(not(feature = "json"))]
+    let entries = ron::de::from_str::<Vec<CheckEntry>>(&body).map_err(Error::from);
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            return response;
         }
-        Ok(Wql::Select(entity, ToSelect::Keys(keys), Some(uuid), _)) => {
-            select_keys_with_id(entity, uuid, keys, local_data).await
+    };
+
+    let states = futures::future::join_all(entries.into_iter().map(|entry| {
+        let local_data = local_data.clone();
+        let encryption = encryption.clone();
+        let actor = actor.clone();
+        async move {
+            let state = checked_state(&entry, &local_data, &encryption, &actor).await;
+            (entry, state)
         }
-        Ok(Wql::Select(entity, ToSelect::All, None, functions)) => {
+    }))
+    .await;
+
+    let format = OutputFormat::from_request(&req);
+    let media = MediaType::from_request(&req).nested();
+    let results = query_pool().install(|| {
+        states
+            .into_par_iter()
+            .map(|(CheckEntry { id, content, .. }, state)| {
+                let result = state
+                    .map(|state| VerifyEncryption::new(state, content).verify())
+                    .and_then(|checked| QueryResponse::from(checked).to_string(format, media))
+                    .unwrap_or_else(|e| e.to_string());
+                (id, result)
+            })
+            .collect::<BTreeMap<Uuid, String>>()
+    });
+
+    let results = results
+        .into_iter()
+        .map(|(id, result)| format!("\"{}\": {}", id, result))
+        .collect::<Vec<String>>();
+    let response = HttpResponse::Ok().body(format!("{{\n{}\n}}", results.join(",\n")));
+    audit(&req, &body, &response, None);
+    response
+}
+
+/// Encrypted keys of `query` to mask in its response, admins receive every value.
+fn masked_keys(
+    req: &HttpRequest,
+    query: &Wql,
+    encryption: &DataEncryptContext,
+) -> Result<HashSet<String>, Error> {
+    if session_roles(req).contains(&Role::Admin) {
+        return Ok(HashSet::new());
+    }
+    let encryption = read_lock(encryption)?;
+    Ok(encrypted_keys(query, &encryption))
+}
+
+fn batch_statements(body: &str) -> Result<Vec<String>, Error> {
+    ron::de::from_str::<Vec<String>>(body.trim()).map_err(|_| {
+        Error::QueryFormat(String::from(
+            "Batch must be an array of queries, like `[\"SELECT ...\", \"SELECT ...\"]`",
+        ))
+    })
+}
+
+/// `LIMIT` of the query, used to tell if an enveloped response was truncated.
+fn query_limit(query: &Wql) -> Option<usize> {
+    let functions = match query {
+        Wql::Select(_, _, _, functions)
+        | Wql::SelectIds(_, _, _, functions)
+        | Wql::SelectWhere(_, _, _, functions) => functions,
+        _ => return None,
+    };
+    match functions.get("LIMIT") {
+        Some(wql::Algebra::Limit(limit)) => Some(*limit),
+        _ => None,
+    }
+}
+
+fn is_query(query: &Wql) -> bool {
+    matches!(
+        query,
+        Wql::Select(..)
+            | Wql::SelectIds(..)
+            | Wql::SelectWhen(..)
+            | Wql::SelectWhenRange(..)
+            | Wql::SelectWhenEffective(..)
+            | Wql::SelectWhere(..)
+            | Wql::CheckValue(..)
+            | Wql::RelationQuery(..)
+            | Wql::Join(..)
+            | Wql::Version
+            | Wql::Describe(..)
+            | Wql::Exists(..)
+    )
+}
+
+/// Serves `query` from the cache when its entity was not written since it was cached,
+/// responses are cached before masking encrypted keys.
+async fn cached_query_controller(
+    cache: Option<&web::Data<QueryCache>>,
+    query: Wql,
+    local_data: DataLocalContext,
+    encryption: DataEncryptContext,
+    schemas: DataSchemaContext,
+    actor: DataExecutor,
+) -> Result<QueryResponse, Error> {
+    let (cache, key) = match cache.and_then(|cache| Some((cache, cache.key(&query)?))) {
+        Some(cached) => cached,
+        None => return query_controller(query, local_data, encryption, schemas, actor).await,
+    };
+    if let Some(response) = cache.get(&key, &query) {
+        return Ok(response);
+    }
+
+    let response = query_controller(query.clone(), local_data, encryption, schemas, actor).await?;
+    cache.insert(key, query, response.clone());
+    Ok(response)
+}
+
+async fn query_controller(
+    query: Wql,
+    local_data: DataLocalContext,
+    encryption: DataEncryptContext,
+    schemas: DataSchemaContext,
+    actor: DataExecutor,
+) -> Result<QueryResponse, Error> {
+    match query {
+        Wql::Select(entity, ToSelect::All, Some(uuid), functions) => {
+            select_all_with_id(entity, uuid, local_data, functions).await
+        }
+        Wql::Select(entity, ToSelect::Keys(keys), Some(uuid), functions) => {
+            select_keys_with_id(entity, uuid, keys, local_data, functions).await
+        }
+        Wql::Select(entity, ToSelect::All, None, functions) => {
             select_all(entity, local_data, functions).await
         }
-        Ok(Wql::Select(entity, ToSelect::Keys(keys), None, functions)) => {
+        Wql::Select(entity, ToSelect::Keys(keys), None, functions) => {
             select_args(entity, keys, local_data, functions).await
         }
-        Ok(Wql::SelectIds(entity, ToSelect::All, uuids, functions)) => {
+        Wql::SelectIds(entity, ToSelect::All, uuids, functions) => {
             select_all_with_ids(entity, uuids, local_data, functions).await
         }
-        Ok(Wql::SelectIds(entity, ToSelect::Keys(keys), uuids, functions)) => {
+        Wql::SelectIds(entity, ToSelect::Keys(keys), uuids, functions) => {
             select_keys_with_ids(entity, keys, uuids, local_data, functions).await
         }
-        Ok(Wql::SelectWhen(entity, ToSelect::All, None, date)) => {
+        Wql::SelectWhen(entity, ToSelect::All, None, date) => {
             select_all_when_controller(entity, date, actor).await
         }
-        Ok(Wql::SelectWhen(entity, ToSelect::Keys(keys), None, date)) => {
+        Wql::SelectWhen(entity, ToSelect::Keys(keys), None, date) => {
             select_keys_when_controller(entity, date, keys, actor).await
         }
-        Ok(Wql::SelectWhen(entity, ToSelect::All, Some(uuid), date)) => {
+        Wql::SelectWhen(entity, ToSelect::All, Some(uuid), date) => {
             select_all_id_when_controller(entity, date, uuid, actor).await
         }
-        Ok(Wql::SelectWhen(entity, ToSelect::Keys(keys), Some(uuid), date)) => {
+        Wql::SelectWhen(entity, ToSelect::Keys(keys), Some(uuid), date) => {
             select_keys_id_when_controller(entity, date, keys, uuid, actor).await
         }
-        Ok(Wql::SelectWhenRange(entity_name, uuid, start_date, end_date)) => {
-            select_all_when_range_controller(entity_name, uuid, start_date, end_date, actor).await
+        Wql::SelectWhenRange(entity_name, uuid, start_date, end_date) => {
+            select_all_when_range(entity_name, uuid, start_date, end_date, actor).await
+        }
+        Wql::SelectWhenEffective(entity, ToSelect::All, uuid, date) => {
+            select_when_effective_controller(entity, None, uuid, date, actor).await
+        }
+        Wql::SelectWhenEffective(entity, ToSelect::Keys(keys), uuid, date) => {
+            let keys = keys.into_par_iter().collect::<HashSet<String>>();
+            select_when_effective_controller(entity, Some(keys), uuid, date, actor).await
         }
-        Ok(Wql::SelectWhere(entity_name, args_to_select, clauses, functions)) => {
-            select_where_controller(entity_name, args_to_select, clauses, local_data, functions)
-                .await
+        Wql::SelectWhere(entity_name, args_to_select, clauses, functions) => {
+            select_where_controller(
+                entity_name,
+                args_to_select,
+                clauses,
+                local_data,
+                functions,
+                actor,
+            )
+            .await
         }
-        Ok(Wql::CheckValue(entity, uuid, content)) => {
+        Wql::CheckValue(entity, uuid, content) => {
             check_value_controller(entity, uuid, content, local_data, encryption, actor).await
         }
-        Ok(Wql::RelationQuery(queries, wql::Relation::Intersect, relation_type)) => {
+        Wql::RelationQuery(queries, wql::Relation::Intersect, relation_type) => {
             intersect(queries, relation_type, local_data, actor).await
         }
-        Ok(Wql::RelationQuery(queries, wql::Relation::Difference, relation_type)) => {
+        Wql::RelationQuery(queries, wql::Relation::Difference, relation_type) => {
             difference(queries, relation_type, local_data, actor).await
         }
-        Ok(Wql::RelationQuery(queries, wql::Relation::Union, relation_type)) => {
+        Wql::RelationQuery(queries, wql::Relation::Union, relation_type) => {
             union(queries, relation_type, local_data, actor).await
         }
-        Ok(Wql::Join(entity_a, entity_b, queries)) => {
-            join(entity_a, entity_b, queries, local_data).await
+        Wql::Join(mode, entity_a, function, entity_b, queries) => {
+            join(
+                mode, entity_a, function, entity_b, queries, local_data, actor,
+            )
+            .await
         }
-        Ok(_) => Err(Error::NonSelectQuery),
-        Err(e) => Err(Error::QueryFormat(e)),
-    };
+        Wql::Version => Ok(VersionInfo::new().into()),
+        Wql::Describe(entity) => describe_controller(entity, local_data, schemas).await,
+        Wql::Exists(entity, uuid) => exists_controller(&entity, &uuid, &local_data),
+        _ => Err(Error::NonSelectQuery),
+    }
+}
 
-    match response {
-        Err(e) => error_to_http(&e),
-        Ok(resp) => match resp.to_string() {
-            Ok(body) => HttpResponse::Ok().body(body),
-            Err(e) => error_to_http(&e),
-        },
+/// If `uuid` is one of the ids of `entity`, checked under the lock without cloning its state.
+fn exists_controller(
+    entity: &str,
+    uuid: &Uuid,
+    local_data: &DataLocalContext,
+) -> Result<QueryResponse, Error> {
+    let local_data = read_lock(local_data)?;
+    if let Some(id_to_registry) = local_data.get(entity) {
+        Ok(id_to_registry.contains_key(uuid).into())
+    } else {
+        Err(Error::EntityNotCreated(entity.to_owned()))
     }
 }
 
+/// Describes the keys of `entity` with its declared schema or, if it has none, with the
+/// types found in its current states.
+async fn describe_controller(
+    entity: String,
+    local_data: DataLocalContext,
+    schemas: DataSchemaContext,
+) -> Result<QueryResponse, Error> {
+    let schema = read_lock(&schemas)?.get(&entity).cloned();
+    if let Some(schema) = schema.filter(|schema| !schema.types.is_empty()) {
+        return Ok(describe_schema(&schema).into());
+    }
+
+    let registries = {
+        let local_data = read_lock(&local_data)?;
+        if let Some(registries) = local_data.get(&entity) {
+            registries.clone()
+        } else {
+            return Err(Error::EntityNotCreated(entity));
+        }
+    };
+
+    let states = registries
+        .into_values()
+        .map(|(_, state)| bincode::deserialize::<HashMap<String, Types>>(&state).unwrap());
+    Ok(describe_states(states).into())
+}
+
 pub async fn check_value_controller(
     entity: String,
     uuid: Uuid,
@@ -112,77 +504,191 @@ pub async fn check_value_controller(
     encryption: DataEncryptContext,
     actor: DataExecutor,
 ) -> Result<QueryResponse, Error> {
-    if let Ok(guard) = encryption.lock() {
-        if guard.contains_key(&entity) {
-            let encrypts = guard.get(&entity).unwrap();
-            let non_encrypt_keys = content
-                .par_iter()
-                .filter(|(k, _)| !encrypts.contains(&(*k).to_string()))
-                .map(|(k, _)| k.to_owned())
-                .collect::<Vec<String>>();
-
-            if !non_encrypt_keys.is_empty() {
-                return Err(Error::CheckNonEncryptedKeys(non_encrypt_keys));
-            }
-        }
+    let entry = CheckEntry {
+        entity,
+        id: uuid,
+        content,
     };
+    let filtered_state = checked_state(&entry, &local_data, &encryption, &actor).await?;
+    let results = actor
+        .send(VerifyEncryption::new(filtered_state, entry.content))
+        .await??;
+    Ok(results)
+}
 
-    let local_data = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
-        if !local_data.contains_key(&entity) {
-            return Err(Error::EntityNotCreated(entity));
+/// State of the entry id with only the keys of its content, which must all be encrypted.
+async fn checked_state(
+    entry: &CheckEntry,
+    local_data: &DataLocalContext,
+    encryption: &DataEncryptContext,
+    actor: &DataExecutor,
+) -> Result<HashMap<String, Types>, Error> {
+    if let Some(encrypts) = read_lock(encryption)?.get(&entry.entity) {
+        let non_encrypt_keys = entry
+            .content
+            .keys()
+            .filter(|k| !encrypts.contains(*k))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<String>>();
+
+        if !non_encrypt_keys.is_empty() {
+            return Err(Error::CheckNonEncryptedKeys(non_encrypt_keys));
         }
-        local_data.clone()
+    }
+
+    let registry = {
+        let local_data = read_lock(local_data)?;
+        let id_to_registry = local_data
+            .get(&entry.entity)
+            .ok_or_else(|| Error::EntityNotCreated(entry.entity.clone()))?;
+        id_to_registry
+            .get(&entry.id)
+            .ok_or_else(|| Error::UuidNotCreatedForEntity(entry.entity.clone(), entry.id))?
+            .0
+            .to_owned()
     };
 
-    let previous_entry = local_data.get(&entity).unwrap().get(&uuid).unwrap();
-    let previous_state_str = actor.send(previous_entry.0.to_owned()).await??;
+    let previous_state_str = actor.send(registry).await??;
     let state = actor.send(State(previous_state_str)).await??;
-    let keys = content
-        .keys()
-        .map(ToOwned::to_owned)
-        .collect::<HashSet<String>>();
-    let filtered_state: HashMap<String, Types> = state
+    Ok(state
         .into_par_iter()
-        .filter(|(k, _)| keys.contains(k))
-        .collect();
-    let results = actor
-        .send(VerifyEncryption::new(filtered_state, content))
-        .await??;
-    Ok(results)
+        .filter(|(k, _)| entry.content.contains_key(k))
+        .collect())
 }
 
+/// Streams the states of `uuid` between `start_date` and `end_date`, reading one
+/// day log at a time and sending its states as soon as they are read.
 async fn select_all_when_range_controller(
+    entity: String,
+    uuid: Uuid,
+    start_date: String,
+    end_date: String,
+    masked: HashSet<String>,
+    actor: DataExecutor,
+) -> Result<HttpResponse, Error> {
+    use futures::stream::{self, StreamExt};
+
+    let start_date: DateTime<Utc> = start_date
+        .parse::<DateTime<Utc>>()
+        .map_err(Error::DateTimeParse)?;
+    let end_date: DateTime<Utc> = end_date
+        .parse::<DateTime<Utc>>()
+        .map_err(Error::DateTimeParse)?;
+    let date_logs = range_date_logs(&entity, start_date, end_date);
+    let (start_date, date_logs) =
+        after_last_clear(&entity, start_date, end_date, date_logs, &actor).await?;
+    let date_logs = date_logs.into_iter();
+
+    let chunks = stream::unfold(
+        (date_logs, false, false),
+        move |(mut date_logs, emitted, finished)| {
+            let entity = entity.clone();
+            let masked = masked.clone();
+            let actor = actor.clone();
+            async move {
+                if finished {
+                    return None;
+                }
+                for date_log in &mut date_logs {
+                    let mut states = match actor
+                        .send(ReadEntityRange::new(
+                            &entity, uuid, start_date, end_date, date_log,
+                        ))
+                        .await
+                    {
+                        Ok(Ok((states, _))) if states.is_empty() => continue,
+                        Ok(Ok((states, _))) => states,
+                        Ok(Err(e)) => return Some((Err(e), (date_logs, emitted, true))),
+                        Err(e) => return Some((Err(e.into()), (date_logs, emitted, true))),
+                    };
+                    states.values_mut().for_each(|s| mask_keys(s, &masked));
+                    let chunk = time_range_chunk(&states, !emitted).map(Bytes::from);
+                    let finished = chunk.is_err();
+                    return Some((chunk, (date_logs, true, finished)));
+                }
+                let end = Bytes::from_static(time_range_end(!emitted).as_bytes());
+                Some((Ok(end), (date_logs, emitted, true)))
+            }
+        },
+    );
+
+    Ok(HttpResponse::Ok().streaming(chunks.boxed_local()))
+}
+
+async fn select_all_when_range(
     entity: String,
     uuid: Uuid,
     start_date: String,
     end_date: String,
     actor: DataExecutor,
 ) -> Result<QueryResponse, Error> {
-    use chrono::{DateTime, Utc};
     let start_date: DateTime<Utc> = start_date
         .parse::<DateTime<Utc>>()
         .map_err(Error::DateTimeParse)?;
     let end_date: DateTime<Utc> = end_date
         .parse::<DateTime<Utc>>()
         .map_err(Error::DateTimeParse)?;
-    #[cfg(test)]
-    let date_log = start_date.format("data/%Y_%m_%d.txt").to_string();
-    #[cfg(not(test))]
-    let date_log = start_date.format("data/%Y_%m_%d.log").to_string();
 
-    let result = actor
-        .send(ReadEntityRange::new(
-            &entity, uuid, start_date, end_date, date_log,
-        ))
+    let date_logs = range_date_logs(&entity, start_date, end_date);
+    let (start_date, date_logs) =
+        after_last_clear(&entity, start_date, end_date, date_logs, &actor).await?;
+
+    let mut states = BTreeMap::new();
+    for date_log in date_logs {
+        let (day_states, _) = actor
+            .send(ReadEntityRange::new(
+                &entity, uuid, start_date, end_date, date_log,
+            ))
+            .await??;
+        states.extend(day_states);
+    }
+    Ok(states.into())
+}
+
+/// Starts a time range at the last `CLEAR ENTITY` inside it, the states logged before
+/// it belong to cleared ids. Day logs before the clear are skipped.
+async fn after_last_clear(
+    entity: &str,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+    date_logs: Vec<String>,
+    actor: &DataExecutor,
+) -> Result<(DateTime<Utc>, Vec<String>), Error> {
+    let cleared = actor
+        .send(ReadLastClear::new(entity, end_date, date_logs.clone()))
         .await??;
+    match cleared {
+        Some(cleared) if cleared > start_date => {
+            let date_logs = date_logs
+                .into_iter()
+                .skip_while(|date_log| *date_log != query_date_log(entity, &cleared))
+                .collect();
+            Ok((cleared, date_logs))
+        }
+        _ => Ok((start_date, date_logs)),
+    }
+}
 
-    Ok(result.into())
+/// Last state of `uuid` registered at or before `date`, reading the day logs backward until
+/// one of them has a state of the id or clears the entity.
+async fn select_when_effective_controller(
+    entity: String,
+    keys: Option<HashSet<String>>,
+    uuid: Uuid,
+    date: String,
+    actor: DataExecutor,
+) -> Result<QueryResponse, Error> {
+    let date: DateTime<Utc> = date
+        .parse::<DateTime<Utc>>()
+        .map_err(Error::DateTimeParse)?;
+
+    let state = actor
+        .send(ReadEntityIdUntil::new(&entity, uuid, date))
+        .await??
+        .unwrap_or_default();
+    Ok(filter_keys_and_hash(state, keys, false).into())
 }
+
 async fn select_all_when_controller(
     entity: String,
     date: String,
@@ -192,10 +698,7 @@ async fn select_all_when_controller(
     let date = date
         .parse::<DateTime<Utc>>()
         .map_err(Error::DateTimeParse)?;
-    #[cfg(test)]
-    let date_log = date.format("data/%Y_%m_%d.txt").to_string();
-    #[cfg(not(test))]
-    let date_log = date.format("data/%Y_%m_%d.log").to_string();
+    let date_log = query_date_log(&entity, &date);
     let result = actor
         .send(ReadEntitiesAt::new(&entity, date_log, None))
         .await??;
@@ -213,14 +716,11 @@ pub async fn select_all_id_when_controller(
     let date = date
         .parse::<DateTime<Utc>>()
         .map_err(Error::DateTimeParse)?;
-    #[cfg(test)]
-    let date_log = date.format("data/%Y_%m_%d.txt").to_string();
-    #[cfg(not(test))]
-    let date_log = date.format("data/%Y_%m_%d.log").to_string();
+    let date_log = query_date_log(&entity, &date);
     let result = actor
         .send(ReadEntityIdAt::new(&entity, uuid, date_log))
         .await??;
-    let result = filter_keys_and_hash(result, None);
+    let result = filter_keys_and_hash(result, None, false);
     Ok(result.into())
 }
 
@@ -236,14 +736,11 @@ pub async fn select_keys_id_when_controller(
     let date = date
         .parse::<DateTime<Utc>>()
         .map_err(Error::DateTimeParse)?;
-    #[cfg(test)]
-    let date_log = date.format("data/%Y_%m_%d.txt").to_string();
-    #[cfg(not(test))]
-    let date_log = date.format("data/%Y_%m_%d.log").to_string();
+    let date_log = query_date_log(&entity, &date);
     let result = actor
         .send(ReadEntityIdAt::new(&entity, uuid, date_log))
         .await??;
-    let result = filter_keys_and_hash(result, Some(keys));
+    let result = filter_keys_and_hash(result, Some(keys), false);
 
     Ok(result.into())
 }
@@ -260,10 +757,7 @@ async fn select_keys_when_controller(
         .parse::<DateTime<Utc>>()
         .map_err(Error::DateTimeParse)?;
 
-    #[cfg(test)]
-    let date_log = date.format("data/%Y_%m_%d.txt").to_string();
-    #[cfg(not(test))]
-    let date_log = date.format("data/%Y_%m_%d.log").to_string();
+    let date_log = query_date_log(&entity, &date);
     let result = actor
         .send(ReadEntitiesAt::new(&entity, date_log, Some(keys)))
         .await??;
@@ -275,13 +769,10 @@ pub async fn select_all_with_id(
     entity: String,
     uuid: Uuid,
     local_data: DataLocalContext,
+    functions: HashMap<String, wql::Algebra>,
 ) -> Result<QueryResponse, Error> {
     let registry = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registry = if let Some(id_to_registry) = local_data.get(&entity) {
             if let Some(reg) = id_to_registry.get(&uuid) {
                 reg
@@ -296,8 +787,13 @@ pub async fn select_all_with_id(
     };
 
     let state: HashMap<String, Types> = bincode::deserialize(&registry.1).unwrap();
-    let filtered_state = filter_keys_and_hash(state, None);
-    Ok(filtered_state.into())
+    if !is_valid_at(&state, &functions) {
+        return Ok(HashMap::<String, Types>::new().into());
+    }
+    let state = project_scalars(state, &functions);
+    let filtered_state = filter_keys_and_hash(state, None, omit_nulls(&functions));
+    let state = with_version(filtered_state, &registry.0, &functions);
+    Ok(with_proof(state, &registry.0, &functions)?.into())
 }
 
 pub async fn select_all_with_ids(
@@ -308,11 +804,7 @@ pub async fn select_all_with_ids(
 ) -> Result<QueryResponse, Error> {
     let (limit, offset, count) = get_limit_offset_count(&functions);
     let registries = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registries = if let Some(id_to_registry) = local_data.get(&entity) {
             uuids
                 .into_par_iter()
@@ -334,12 +826,27 @@ pub async fn select_all_with_ids(
         registries
     };
 
+    let times = registry_times(
+        registries
+            .iter()
+            .filter_map(|(id, registry)| registry.as_ref().map(|(registry, _)| (id, registry))),
+        &functions,
+    );
     let mut states: BTreeMap<Uuid, Option<HashMap<String, Types>>> = BTreeMap::new();
-    for (uuid, registry) in registries.into_iter().skip(offset).take(limit) {
-        if let Some((_, state)) = registry {
+    for (uuid, registry) in registries
+        .into_iter()
+        .filter(|(_, registry)| match registry {
+            Some((_, state)) => is_valid_registry(state, &functions),
+            None => true,
+        })
+        .skip(offset)
+        .take(limit)
+    {
+        if let Some((registry, state)) = registry {
             let state: HashMap<String, Types> = bincode::deserialize(&state).unwrap();
-            let filtered = filter_keys_and_hash(state, None);
-            states.insert(uuid, Some(filtered));
+            let state = project_scalars(state, &functions);
+            let filtered = filter_keys_and_hash(state, None, omit_nulls(&functions));
+            states.insert(uuid, Some(with_proof(filtered, &registry, &functions)?));
         } else {
             states.insert(uuid, None);
         }
@@ -348,7 +855,7 @@ pub async fn select_all_with_ids(
     let states = dedup_option_states(states, &functions);
 
     Ok(get_result_after_manipulation_for_options(
-        states, &functions, count,
+        states, &times, &functions, count,
     ))
 }
 
@@ -357,14 +864,11 @@ pub async fn select_keys_with_id(
     uuid: Uuid,
     keys: Vec<String>,
     local_data: DataLocalContext,
+    functions: HashMap<String, wql::Algebra>,
 ) -> Result<QueryResponse, Error> {
     let keys = keys.into_par_iter().collect::<HashSet<String>>();
     let registry = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registry = if let Some(id_to_registry) = local_data.get(&entity) {
             if let Some(reg) = id_to_registry.get(&uuid) {
                 reg
@@ -379,8 +883,13 @@ pub async fn select_keys_with_id(
     };
 
     let state: HashMap<String, Types> = bincode::deserialize(&registry.1).unwrap();
-    let filtered = filter_keys_and_hash(state, Some(keys));
-    Ok(filtered.into())
+    if !is_valid_at(&state, &functions) {
+        return Ok(HashMap::<String, Types>::new().into());
+    }
+    let state = project_scalars(state, &functions);
+    let filtered = filter_keys_and_hash(state, Some(keys), omit_nulls(&functions));
+    let state = with_version(filtered, &registry.0, &functions);
+    Ok(with_proof(state, &registry.0, &functions)?.into())
 }
 
 pub async fn select_keys_with_ids(
@@ -393,11 +902,7 @@ pub async fn select_keys_with_ids(
     let keys = keys.into_par_iter().collect::<HashSet<String>>();
     let (limit, offset, count) = get_limit_offset_count(&functions);
     let registries = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registries = if let Some(id_to_registry) = local_data.get(&entity) {
             uuids
                 .into_par_iter()
@@ -419,12 +924,27 @@ pub async fn select_keys_with_ids(
         registries
     };
 
+    let times = registry_times(
+        registries
+            .iter()
+            .filter_map(|(id, registry)| registry.as_ref().map(|(registry, _)| (id, registry))),
+        &functions,
+    );
     let mut states: BTreeMap<Uuid, Option<HashMap<String, Types>>> = BTreeMap::new();
-    for (uuid, registry) in registries.into_iter().skip(offset).take(limit) {
-        if let Some((_, state)) = registry {
+    for (uuid, registry) in registries
+        .into_iter()
+        .filter(|(_, registry)| match registry {
+            Some((_, state)) => is_valid_registry(state, &functions),
+            None => true,
+        })
+        .skip(offset)
+        .take(limit)
+    {
+        if let Some((registry, state)) = registry {
             let state: HashMap<String, Types> = bincode::deserialize(&state).unwrap();
-            let filtered = filter_keys_and_hash(state, Some(keys.clone()));
-            states.insert(uuid, Some(filtered));
+            let state = project_scalars(state, &functions);
+            let filtered = filter_keys_and_hash(state, Some(keys.clone()), omit_nulls(&functions));
+            states.insert(uuid, Some(with_proof(filtered, &registry, &functions)?));
         } else {
             states.insert(uuid, None);
         }
@@ -433,7 +953,7 @@ pub async fn select_keys_with_ids(
     let states = dedup_option_states(states, &functions);
 
     Ok(get_result_after_manipulation_for_options(
-        states, &functions, count,
+        states, &times, &functions, count,
     ))
 }
 
@@ -445,11 +965,7 @@ pub async fn select_all(
     let (limit, offset, count) = get_limit_offset_count(&functions);
 
     let registries = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registries = if let Some(id_to_registries) = local_data.get(&entity) {
             id_to_registries
         } else {
@@ -459,10 +975,16 @@ pub async fn select_all(
         registries
     };
 
-    let states = registries_to_states(registries, None, offset, limit);
+    let times = registry_times(
+        registries.iter().map(|(id, (registry, _))| (id, registry)),
+        &functions,
+    );
+    let states = registries_to_states(registries, None, &functions, offset, limit)?;
     let states = dedup_states(states, &functions);
 
-    Ok(get_result_after_manipulation(states, &functions, count))
+    Ok(get_result_after_manipulation(
+        states, &times, &functions, count,
+    ))
 }
 
 pub async fn select_args(
@@ -474,11 +996,7 @@ pub async fn select_args(
     let (limit, offset, count) = get_limit_offset_count(&functions);
     let keys = keys.into_par_iter().collect::<HashSet<String>>();
     let registries = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
+        let local_data = read_lock(&local_data)?;
         let registries = if let Some(id_to_registries) = local_data.get(&entity) {
             id_to_registries
         } else {
@@ -488,7 +1006,13 @@ pub async fn select_args(
         registries
     };
 
-    let states = registries_to_states(registries, Some(keys), offset, limit);
+    let times = registry_times(
+        registries.iter().map(|(id, (registry, _))| (id, registry)),
+        &functions,
+    );
+    let states = registries_to_states(registries, Some(keys), &functions, offset, limit)?;
     let states = dedup_states(states, &functions);
-    Ok(get_result_after_manipulation(states, &functions, count))
+    Ok(get_result_after_manipulation(
+        states, &times, &functions, count,
+    ))
 }
@@ -1,7 +1,21 @@
-use crate::{http::routes, schemas::tx::TxResponse};
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use wql::Types;
+
+use crate::{
+    http::routes,
+    schemas::{
+        query::{KeyDescription, VersionInfo},
+        tx::TxResponse,
+    },
+};
 use actix_http::body::ResponseBody;
 use actix_web::{body::Body, test, App};
 
+use super::tx_test::clear;
+
 #[ignore]
 #[actix_rt::test]
 async fn test_select_all_id_post_ok() {
@@ -126,6 +140,25 @@ async fn test_create_on_query_endpoint() {
     );
 }
 
+#[actix_rt::test]
+async fn test_version_on_query_endpoint() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("VERSION")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let info: VersionInfo = ron::de::from_str(&body).unwrap();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.wql_revision, wql::GRAMMAR_REVISION);
+    assert_eq!(info.format, "ron");
+}
+
 #[ignore]
 #[actix_rt::test]
 async fn test_select_all_post_ok() {
@@ -181,6 +214,187 @@ async fn test_select_all_post_ok() {
     assert!(body.contains("Integer(123)"));
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_select_all_sorted_by_id_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_select_sorted_ids")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let mut ids = Vec::new();
+    for a in 0..6 {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("INSERT {{a: {},}} INTO test_select_sorted_ids", a))
+            .uri("/wql/tx")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        let body = resp.take_body().as_str().to_string();
+        let response: TxResponse = ron::de::from_str(&body).unwrap();
+        ids.push(response.uuid.unwrap());
+    }
+    ids.sort();
+
+    let mut pages = Vec::new();
+    for payload in &[
+        "Select * FROM test_select_sorted_ids",
+        "Select * FROM test_select_sorted_ids OFFSET 1 LIMIT 3",
+        "Select #{a,} FROM test_select_sorted_ids LIMIT 3 OFFSET 1",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/query")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        let mut returned = ids
+            .iter()
+            .filter_map(|id| body.find(&id.to_string()).map(|position| (position, *id)))
+            .collect::<Vec<(usize, Uuid)>>();
+        returned.sort();
+        pages.push(
+            returned
+                .into_iter()
+                .map(|(_, id)| id)
+                .collect::<Vec<Uuid>>(),
+        );
+    }
+
+    assert_eq!(pages[0], ids);
+    assert_eq!(pages[1], ids[1..4].to_vec());
+    assert_eq!(pages[2], pages[1]);
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_returning_proof_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_select_proof")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_select_proof")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "SELECT #{{a,}} FROM test_select_proof ID {} RETURNING PROOF",
+            uuid
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let inserted: HashMap<String, Types> = ron::de::from_str(&body).unwrap();
+    assert_eq!(inserted["a"], Types::Integer(123));
+    assert_eq!(inserted["_previous_hash"], Types::Nil);
+    assert!(matches!(&inserted["_hash"], Types::String(hash) if hash.len() == 8));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "UPDATE test_select_proof SET {{a: 12,}} INTO {}",
+            uuid
+        ))
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_select_proof RETURNING PROOF")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    let updated = &result[&uuid];
+    assert_eq!(updated["a"], Types::Integer(12));
+    assert_eq!(updated["_previous_hash"], inserted["_hash"]);
+    assert_ne!(updated["_hash"], inserted["_hash"]);
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_select_proof")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(!body.contains("_hash"));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_select_all_compact_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    for payload in &[
+        "CREATE ENTITY test_select_compact",
+        "INSERT {a: 4365, b: 76.3,} INTO test_select_compact",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("Select * FROM test_select_compact")
+        .uri("/wql/query?format=compact")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let compact = resp.take_body().as_str().to_string();
+    assert!(!compact.contains('\n'));
+    assert!(compact.contains("Integer(4365)"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("Select * FROM test_select_compact")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let pretty = resp.take_body().as_str().to_string();
+    assert!(pretty.contains('\n'));
+
+    let compact: BTreeMap<uuid::Uuid, HashMap<String, Types>> =
+        ron::de::from_str(&compact).unwrap();
+    let pretty: BTreeMap<uuid::Uuid, HashMap<String, Types>> = ron::de::from_str(&pretty).unwrap();
+    assert_eq!(compact, pretty);
+}
+
 #[ignore]
 #[actix_rt::test]
 async fn test_select_keys_post_ok() {
@@ -511,6 +725,141 @@ async fn test_select_when_all_id_post_ok() {
     assert!(!body.contains("41ede07f-e98b-41dd-9ff2-8dce99af4e96"));
 }
 
+#[actix_rt::test]
+async fn test_select_when_effective_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    let payload = format!(
+        "Select #{{f,}} FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN EFFECTIVE AT {}",
+        "2021-02-09T16:55:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let state: HashMap<String, Types> = ron::de::from_str(&body).unwrap();
+    assert_eq!(state.len(), 1);
+    assert_eq!(state["f"], Types::String("helloworld".to_string()));
+
+    let payload = format!(
+        "Select * FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN EFFECTIVE AT {}",
+        "2021-03-01T00:00:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let state: HashMap<String, Types> = ron::de::from_str(&body).unwrap();
+    assert_eq!(state["f"], Types::String("NAOMI".to_string()));
+
+    let payload = format!(
+        "Select * FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN EFFECTIVE AT {}",
+        "2021-02-09T16:00:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "{}");
+}
+
+#[actix_rt::test]
+async fn test_select_envelope_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    let payload = format!(
+        "Select #{{f,}} FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN EFFECTIVE AT {}",
+        "2021-02-09T16:55:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query?format=compact&envelope=true")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.starts_with("(response:{\"f\":String(\"helloworld\")},count:1,elapsed_ms:"));
+    assert!(body.ends_with(",truncated:false)"));
+}
+
+#[actix_rt::test]
+async fn test_select_accept_json_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    let payload = format!(
+        "Select #{{f,}} FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN EFFECTIVE AT {}",
+        "2021-02-09T16:55:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .header("Accept", "application/json")
+        .set_payload(payload.clone())
+        .uri("/wql/query?format=compact")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "{\"f\":{\"String\":\"helloworld\"}}");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .header("Accept", "application/x-ron")
+        .set_payload(payload)
+        .uri("/wql/query?format=compact")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "{\"f\":String(\"helloworld\")}");
+}
+
+#[actix_rt::test]
+async fn test_select_accept_csv_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    let payload = format!(
+        "Select #{{f,}} FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN EFFECTIVE AT {}",
+        "2021-02-09T16:55:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .header("Accept", "text/csv")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "f\r\nhelloworld\r\n");
+}
+
 #[actix_rt::test]
 async fn test_select_when_range_all_post_ok() {
     let mut app = test::init_service(App::new().configure(routes)).await;
@@ -525,10 +874,11 @@ async fn test_select_when_range_all_post_ok() {
         .uri("/wql/query")
         .to_request();
 
-    let mut resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&mut app, req).await;
 
     assert!(resp.status().is_success());
-    let body = resp.take_body().as_str().to_string();
+    let body = test::read_body(resp).await;
+    let body = std::str::from_utf8(&body).unwrap();
 
     assert!(body.contains("{\n \"2021-02-09T16:44:03.236333Z\":"));
     assert!(body.contains("\"f\": String(\"hello\")"));
@@ -538,6 +888,53 @@ async fn test_select_when_range_all_post_ok() {
     assert!(body.contains("\"f\": String(\"JULIA\")"));
 }
 
+#[actix_rt::test]
+async fn test_select_when_range_across_days_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    let payload = format!(
+        "Select * FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN START {} END {}",
+        "2021-01-07T00:00:00Z", "2021-02-09T16:50:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    let body = std::str::from_utf8(&body).unwrap();
+    let states: BTreeMap<DateTime<Utc>, HashMap<String, Types>> = ron::de::from_str(body).unwrap();
+
+    assert_eq!(states.len(), 1);
+    assert!(body.starts_with("{\n \"2021-02-09T16:44:03.236333Z\":"));
+    assert!(body.ends_with("},\n}"));
+}
+
+#[actix_rt::test]
+async fn test_select_when_range_without_states_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    let payload = format!(
+        "Select * FROM test_update ID fb1ccddb-2465-4504-a4a4-e28ee75c7981 WHEN START {} END {}",
+        "2020-12-30T00:00:00Z", "2021-01-08T23:00:00Z"
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    assert_eq!(body, "{}");
+}
+
 #[ignore]
 #[actix_rt::test]
 async fn test_check_encrypt_post_ok() {
@@ -579,6 +976,81 @@ async fn test_check_encrypt_post_ok() {
     assert!(body.contains("\"ssn\": false"));
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_check_batch_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_check_batch ENCRYPT #{pswd,}")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let mut uuids = Vec::new();
+    for pswd in &["first_password", "second_password"] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "INSERT {{a: 123, pswd: \"{}\",}} INTO test_check_batch",
+                pswd
+            ))
+            .uri("/wql/tx")
+            .to_request();
+
+        let mut resp_insert = test::call_service(&mut app, req).await;
+        let body = resp_insert.take_body().as_str().to_string();
+        let response: TxResponse = ron::de::from_str(&body).unwrap();
+        uuids.push(response.uuid.unwrap());
+    }
+    let missing = Uuid::new_v4();
+
+    let payload = format!(
+        "[
+        (entity: \"test_check_batch\", id: \"{}\", content: {{\"pswd\": \"first_password\"}}),
+        (entity: \"test_check_batch\", id: \"{}\", content: {{\"pswd\": \"first_password\"}}),
+        (entity: \"test_check_batch\", id: \"{}\", content: {{\"pswd\": \"first_password\"}}),
+    ]",
+        uuids[0], uuids[1], missing
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/check")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+
+    assert!(resp.status().is_success());
+    let slot = |id: &Uuid| {
+        body.split(&format!("\"{}\": ", id))
+            .nth(1)
+            .unwrap()
+            .to_string()
+    };
+    assert!(slot(&uuids[0]).starts_with("{\n \"pswd\": true,\n}"));
+    assert!(slot(&uuids[1]).starts_with("{\n \"pswd\": false,\n}"));
+    assert!(slot(&missing).contains("UuidNotCreatedForEntity"));
+
+    let payload = format!(
+        "[(entity: \"test_check_batch\", id: \"{}\", content: {{\"a\": \"123\"}}),]",
+        uuids[0]
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/check")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+
+    assert!(resp.status().is_success());
+    assert!(body.contains("CheckNonEncryptedKeys"));
+}
+
 trait BodyTest {
     fn as_str(&self) -> &str;
 }
@@ -597,3 +1069,383 @@ impl BodyTest for ResponseBody<Body> {
         }
     }
 }
+
+#[ignore]
+#[actix_rt::test]
+async fn test_batch_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_batch_ok")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_batch_ok")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let payload = format!(
+        "[\"Select * FROM test_batch_ok ID {}\", \"Select * FROM test_batch_unknown\", \"Select #{{a,}}\n FROM test_batch_ok\"]",
+        uuid
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/batch")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let first = body.find("\"b\": Float(12.3)").unwrap();
+    let second = body.find("EntityNotCreated").unwrap();
+    let third = body.rfind("\"a\": Integer(123)").unwrap();
+    assert!(body.starts_with('['));
+    assert!(body.ends_with(']'));
+    assert!(first < second && second < third);
+
+    let payload = format!(
+        "[\"Select #{{b,}} FROM test_batch_ok ID {}\", \"Select * FROM\"]",
+        uuid
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/batch")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"b\": Float(12.3)"));
+    assert!(body.contains("QueryFormat"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("Select * FROM test_batch_ok\nSelect #{a,} FROM test_batch_ok")
+        .uri("/wql/batch")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("Batch must be an array of queries"));
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_batch_with_tx_post_err() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("[\"CREATE ENTITY test_batch_tx\", \"SELECT * FROM test_batch_tx\"]")
+        .uri("/wql/batch")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("NonSelectQuery"));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_describe_entity_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_describe")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {a: 123, b: \"x\",} INTO test_describe",
+        "INSERT {a: 4.5, b: \"y\",} INTO test_describe",
+        "INSERT {a: 12,} INTO test_describe",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("DESCRIBE ENTITY test_describe")
+        .uri("/wql/query")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let description: BTreeMap<String, KeyDescription> = ron::de::from_str(&body).unwrap();
+    let mut a = BTreeMap::new();
+    a.insert("Integer".to_string(), 2);
+    a.insert("Float".to_string(), 1);
+    let mut b = BTreeMap::new();
+    b.insert("String".to_string(), 2);
+    assert_eq!(description["a"], KeyDescription::Observed(a));
+    assert_eq!(description["b"], KeyDescription::Observed(b));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_describe_entity_with_schema_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_describe_schema {age: Integer, tags: Vector<String>,}")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("DESCRIBE ENTITY test_describe_schema")
+        .uri("/wql/query")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let description: BTreeMap<String, KeyDescription> = ron::de::from_str(&body).unwrap();
+    assert_eq!(description.len(), 2);
+    assert_eq!(
+        description["age"],
+        KeyDescription::Declared("Integer".to_string())
+    );
+    assert_eq!(
+        description["tags"],
+        KeyDescription::Declared("Vector<String>".to_string())
+    );
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_exists_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_exists")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123,} INTO test_exists")
+        .uri("/wql/tx")
+        .to_request();
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("EXISTS {} IN test_exists", uuid))
+        .uri("/wql/query")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.take_body().as_str(), "true");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("EXISTS 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 IN test_exists")
+        .uri("/wql/query")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.take_body().as_str(), "false");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("EXISTS 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 IN test_exists_missing")
+        .uri("/wql/query")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_client_error());
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_query_with_params_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    for payload in &[
+        "CREATE ENTITY test_query_params",
+        "INSERT {name: \"julia, maria\", age: 34,} INTO test_query_params",
+        "INSERT {name: \"julia\", age: 12,} INTO test_query_params",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "(query: \"SELECT * FROM test_query_params WHERE { ?* test_query_params:name $1, (>= ?age $2), }\", params: [String(\"julia, maria\"), Integer(18)])",
+        )
+        .uri("/wql/query/params")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let states: BTreeMap<uuid::Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert_eq!(states.len(), 1);
+    assert!(states
+        .values()
+        .all(|state| state["name"] == Types::String("julia, maria".to_string())));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "(query: \"SELECT * FROM test_query_params WHERE { (>= ?age $1), }\", params: [Integer(18), Integer(2)])",
+        )
+        .uri("/wql/query/params")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("Param 2 is not used, the statement has no placeholder `$2`"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("(query: \"INSERT {a: $1,} INTO test_query_params\", params: [Integer(1)])")
+        .uri("/wql/query/params")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_client_error());
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_cached_select_invalidated_by_writes() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    for payload in &[
+        "CREATE ENTITY cached_a",
+        "CREATE ENTITY cached_b",
+        "INSERT {a: 1,} INTO cached_a",
+        "INSERT {b: 1,} INTO cached_b",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let count = |entity: &str| {
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("SELECT * FROM {} COUNT", entity))
+            .uri("/wql/query")
+            .to_request()
+    };
+    let mut resp = test::call_service(&mut app, count("cached_a")).await;
+    let first = resp.take_body().as_str().to_string();
+    assert!(first.contains("count: 1"));
+    let mut resp = test::call_service(&mut app, count("cached_a")).await;
+    assert_eq!(resp.take_body().as_str(), first);
+    let mut resp = test::call_service(&mut app, count("cached_b")).await;
+    assert!(resp.take_body().as_str().contains("count: 1"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 2,} INTO cached_a")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let mut resp = test::call_service(&mut app, count("cached_a")).await;
+    assert!(resp.take_body().as_str().contains("count: 2"));
+    let mut resp = test::call_service(&mut app, count("cached_b")).await;
+    assert!(resp.take_body().as_str().contains("count: 1"));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_cached_subquery_follows_inner_entity() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let user_id = "0b7a9cb0-3f5e-4d1a-9a55-1c1b0f1f6c3e";
+    for payload in &[
+        String::from("CREATE ENTITY cached_users"),
+        String::from("CREATE ENTITY cached_orders"),
+        format!(
+            "INSERT {{active: false,}} INTO cached_users WITH {}",
+            user_id
+        ),
+        format!("INSERT {{user_id: {},}} INTO cached_orders", user_id),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let orders = || {
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(
+                "SELECT * FROM cached_orders WHERE {
+                    ?* cached_orders:user_id ?user_id,
+                    (in ?user_id (SELECT #{id,} FROM cached_users WHERE {
+                        ?* cached_users:active true,
+                    })),
+                }",
+            )
+            .uri("/wql/query")
+            .to_request()
+    };
+    let mut resp = test::call_service(&mut app, orders()).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert!(result.is_empty());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "UPDATE cached_users SET {{active: true,}} INTO {}",
+            user_id
+        ))
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let mut resp = test::call_service(&mut app, orders()).await;
+    let body = resp.take_body().as_str().to_string();
+    let result: BTreeMap<Uuid, HashMap<String, Types>> = ron::de::from_str(&body).unwrap();
+    assert_eq!(result.len(), 1);
+
+    clear();
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use actix_web::{
+    error::BlockingError,
+    http::header,
+    web::{self, Bytes},
+    HttpResponse,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use serde::Deserialize;
+
+use crate::{
+    io::paths::{is_sharded, query_date_log},
+    model::error::{error_to_http, Error},
+};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Entity of the day log, required when `SHARD_LOGS` writes a day log per entity.
+#[derive(Debug, Deserialize)]
+pub struct RawLogQuery {
+    entity: Option<String>,
+}
+
+/// Streams the day log written at `date`, like `2023-06-01`, as it is stored. Day logs
+/// already compressed by the scheduler are streamed as `application/zstd`.
+pub async fn raw_log_handler(
+    date: web::Path<String>,
+    query: web::Query<RawLogQuery>,
+) -> HttpResponse {
+    match raw_log_controller(&date, query.entity.as_deref()) {
+        Ok(response) => response,
+        Err(e) => error_to_http(&e),
+    }
+}
+
+fn raw_log_controller(date: &str, entity: Option<&str>) -> Result<HttpResponse, Error> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        Error::QueryFormat(format!(
+            "Raw log date must be written as YYYY-MM-DD, like 2023-06-01. Found {}",
+            date
+        ))
+    })?;
+    let entity = match entity {
+        Some(entity) if entity.chars().all(|c| c.is_alphanumeric() || c == '_') => entity,
+        Some(entity) => {
+            return Err(Error::QueryFormat(format!(
+                "Entity name `{}` should only contain alphanumerics and `_`",
+                entity
+            )))
+        }
+        None if is_sharded() => {
+            return Err(Error::QueryFormat(String::from(
+                "Query parameter `entity` is required when SHARD_LOGS is true",
+            )))
+        }
+        None => "",
+    };
+    let date_log = query_date_log(entity, &DateTime::from_utc(day.and_hms(0, 0, 0), Utc));
+
+    let (file, path, content_type) = match File::open(&date_log) {
+        Ok(file) => (file, date_log, "text/plain; charset=utf-8"),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let compressed = date_log.replace(".log", ".zst");
+            match File::open(&compressed) {
+                Ok(file) if compressed != date_log => (file, compressed, "application/zstd"),
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => return Err(Error::DayLogNotFound(date.to_owned())),
+            }
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let length = file.metadata()?.len();
+    let file_name = Path::new(&path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_owned();
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .no_chunking(length)
+        .streaming(file_chunks(file)))
+}
+
+/// Chunks of `file` read in the blocking thread pool, so the file is never fully in memory.
+fn file_chunks(file: File) -> LocalBoxStream<'static, Result<Bytes, Error>> {
+    stream::try_unfold(file, |mut file| async move {
+        let (file, chunk) = web::block(move || {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let read = file.read(&mut chunk)?;
+            chunk.truncate(read);
+            Ok::<_, io::Error>((file, chunk))
+        })
+        .await
+        .map_err(|e| match e {
+            BlockingError::Error(e) => Error::Io(e),
+            BlockingError::Canceled => Error::Io(io::Error::other("Thread pool is gone")),
+        })?;
+
+        if chunk.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some((Bytes::from(chunk), file)))
+        }
+    })
+    .boxed_local()
+}
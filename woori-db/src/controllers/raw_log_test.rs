@@ -0,0 +1,62 @@
+use crate::{http::routes, model::error::Error};
+use actix_web::{http::header, test, App};
+
+#[actix_rt::test]
+async fn test_raw_log_get_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::get()
+        .uri("/raw-log/2021-01-08")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let expected = std::fs::read("data/2021_01_08.txt").unwrap();
+    let headers = resp.headers();
+    assert_eq!(
+        headers.get(header::CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(
+        headers.get(header::CONTENT_LENGTH).unwrap(),
+        &expected.len().to_string()
+    );
+    assert_eq!(
+        headers.get(header::CONTENT_DISPOSITION).unwrap(),
+        "attachment; filename=\"2021_01_08.txt\""
+    );
+
+    let body = test::read_body(resp).await;
+    assert_eq!(body, expected);
+}
+
+#[actix_rt::test]
+async fn test_raw_log_get_not_found() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::get()
+        .uri("/raw-log/2000-01-01")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status().as_u16(), 404);
+    let body = test::read_body(resp).await;
+    assert_eq!(
+        body,
+        Error::DayLogNotFound("2000-01-01".to_string()).to_string()
+    );
+}
+
+#[actix_rt::test]
+async fn test_raw_log_get_bad_request() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for uri in &[
+        "/raw-log/2021_01_08",
+        "/raw-log/2021-13-01",
+        "/raw-log/2021-01-08?entity=..%2Fsecret",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+
+        assert_eq!(resp.status().as_u16(), 400, "{}", uri);
+    }
+}
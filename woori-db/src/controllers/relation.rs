@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-use wql::{RelationType, ToSelect, Wql};
+use wql::{Function, JoinMode, RelationType, ToSelect, Types, Wql};
 
 use crate::{
     model::{error::Error, DataExecutor, DataLocalContext},
@@ -8,7 +8,7 @@ use crate::{
 };
 
 use super::{
-    clauses::select_where_controller,
+    clauses::{compare_values, select_where_controller},
     query::{
         select_all, select_all_id_when_controller, select_all_with_id, select_all_with_ids,
         select_args, select_keys_id_when_controller, select_keys_with_id, select_keys_with_ids,
@@ -125,14 +125,24 @@ pub async fn union(
 }
 
 pub async fn join(
+    mode: JoinMode,
     entity_a: (String, String),
+    function: Function,
     entity_b: (String, String),
     queries: Vec<Wql>,
     local_data: DataLocalContext,
+    actor: DataExecutor,
 ) -> Result<Response, Error> {
     let mut result = Vec::new();
-    let a = get_join_query_value(queries[0].clone(), local_data.clone()).await?;
-    let b = get_join_query_value(queries[1].clone(), local_data).await?;
+    let a = get_join_query_value(queries[0].clone(), local_data.clone(), actor.clone()).await?;
+    let b = get_join_query_value(queries[1].clone(), local_data, actor).await?;
+
+    if mode != JoinMode::Inner {
+        return outer_join(mode, &entity_a, &function, &entity_b, a, b);
+    }
+    if function != Function::Eq {
+        return comparison_join(&entity_a, &function, &entity_b, a, b);
+    }
 
     let b_hash = b
         .hash(&entity_b.1)
@@ -146,17 +156,162 @@ pub async fn join(
     }
 }
 
+/// Non-equi joins can't be hashed, so every state of `a` is compared with every
+/// state of `b`, which is `O(n * m)`. States missing the join key or with values
+/// that can't be compared are not joined.
+fn comparison_join(
+    entity_a: &(String, String),
+    function: &Function,
+    entity_b: &(String, String),
+    a: Response,
+    b: Response,
+) -> Result<Response, Error> {
+    let (a, b) = match (a.states(), b.states()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(Error::QueryFormat("Join query not supported".to_string())),
+    };
+
+    let mut result = Vec::new();
+    for state_a in &a {
+        let value_a = match state_a.get(&entity_a.1) {
+            Some(v) => v,
+            None => continue,
+        };
+        for state_b in &b {
+            let joined = matches!(state_b.get(&entity_b.1),
+                Some(value_b) if compare_values(value_a, function, value_b));
+            if !joined {
+                continue;
+            }
+            let mut state = state_a.clone();
+            for (k, v) in state_b.iter().filter(|(k, _)| *k != "tx_time") {
+                let entry_name = if state.contains_key(k) {
+                    format!("{}:{}", k, entity_b.0)
+                } else {
+                    k.to_owned()
+                };
+                state.insert(entry_name, v.to_owned());
+            }
+            result.push(state);
+        }
+    }
+
+    Ok(Response::Join(result))
+}
+
+/// Outer joins prefix every key by its source entity, `entity_a:key`, and fill the keys
+/// of the side without a match with `Types::Nil`. Equality joins are hashed, the other
+/// comparisons are `O(n * m)`. States missing the join key, or with a `Nil` join key
+/// in equality joins, don't match any state.
+fn outer_join(
+    mode: JoinMode,
+    entity_a: &(String, String),
+    function: &Function,
+    entity_b: &(String, String),
+    a: Response,
+    b: Response,
+) -> Result<Response, Error> {
+    let (a, b) = match (a.states(), b.states()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(Error::QueryFormat("Join query not supported".to_string())),
+    };
+    let keys_a = state_keys(&a);
+    let keys_b = state_keys(&b);
+
+    let mut b_hash: HashMap<&Types, Vec<usize>> = HashMap::new();
+    if function == &Function::Eq {
+        for (i, state) in b.iter().enumerate() {
+            match state.get(&entity_b.1) {
+                Some(Types::Nil) | None => (),
+                Some(value) => b_hash.entry(value).or_default().push(i),
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut matched_b = vec![false; b.len()];
+    for state_a in &a {
+        let matches = match state_a.get(&entity_a.1) {
+            None => Vec::new(),
+            Some(value) if function == &Function::Eq => {
+                b_hash.get(value).cloned().unwrap_or_default()
+            }
+            Some(value) => b
+                .iter()
+                .enumerate()
+                .filter(|(_, state_b)| {
+                    matches!(state_b.get(&entity_b.1),
+                        Some(value_b) if compare_values(value, function, value_b))
+                })
+                .map(|(i, _)| i)
+                .collect(),
+        };
+
+        if matches.is_empty() && mode != JoinMode::Right {
+            result.push(outer_join_state(
+                (&entity_a.0, &keys_a, Some(state_a)),
+                (&entity_b.0, &keys_b, None),
+            ));
+        }
+        for i in matches {
+            matched_b[i] = true;
+            result.push(outer_join_state(
+                (&entity_a.0, &keys_a, Some(state_a)),
+                (&entity_b.0, &keys_b, Some(&b[i])),
+            ));
+        }
+    }
+
+    if mode != JoinMode::Left {
+        b.iter()
+            .zip(matched_b)
+            .filter(|(_, matched)| !matched)
+            .for_each(|(state_b, _)| {
+                result.push(outer_join_state(
+                    (&entity_a.0, &keys_a, None),
+                    (&entity_b.0, &keys_b, Some(state_b)),
+                ))
+            });
+    }
+
+    Ok(Response::Join(result))
+}
+
+fn state_keys(states: &[HashMap<String, Types>]) -> BTreeSet<String> {
+    states
+        .iter()
+        .flat_map(|state| state.keys().cloned())
+        .collect()
+}
+
+type JoinSide<'a> = (
+    &'a str,
+    &'a BTreeSet<String>,
+    Option<&'a HashMap<String, Types>>,
+);
+
+fn outer_join_state(a: JoinSide, b: JoinSide) -> HashMap<String, Types> {
+    let mut state = HashMap::new();
+    for (entity, keys, side) in [a, b].iter() {
+        for key in keys.iter() {
+            let value = side.and_then(|s| s.get(key)).cloned().unwrap_or(Types::Nil);
+            state.insert(format!("{}:{}", entity, key), value);
+        }
+    }
+    state
+}
+
 async fn get_query_value(
     query: Wql,
     local_data: DataLocalContext,
     actor: DataExecutor,
 ) -> Result<Response, Error> {
     match query {
-        Wql::Select(entity, ToSelect::All, Some(uuid), _) => {
-            select_all_with_id(entity, uuid, local_data).await
+        Wql::Select(entity, ToSelect::All, Some(uuid), functions) => {
+            select_all_with_id(entity, uuid, local_data, functions).await
         }
-        Wql::Select(entity, ToSelect::Keys(keys), Some(uuid), _) => {
-            select_keys_with_id(entity, uuid, keys, local_data).await
+        Wql::Select(entity, ToSelect::Keys(keys), Some(uuid), functions) => {
+            select_keys_with_id(entity, uuid, keys, local_data, functions).await
         }
         Wql::SelectWhen(entity, ToSelect::All, Some(uuid), date) => {
             select_all_id_when_controller(entity, date, uuid, actor).await
@@ -168,7 +323,11 @@ async fn get_query_value(
     }
 }
 
-async fn get_join_query_value(query: Wql, local_data: DataLocalContext) -> Result<Response, Error> {
+async fn get_join_query_value(
+    query: Wql,
+    local_data: DataLocalContext,
+    actor: DataExecutor,
+) -> Result<Response, Error> {
     match query {
         Wql::Select(entity, ToSelect::All, None, functions) => {
             select_all(entity, local_data, functions).await
@@ -183,8 +342,15 @@ async fn get_join_query_value(query: Wql, local_data: DataLocalContext) -> Resul
             select_keys_with_ids(entity, keys, uuids, local_data, functions).await
         }
         Wql::SelectWhere(entity_name, args_to_select, clauses, functions) => {
-            select_where_controller(entity_name, args_to_select, clauses, local_data, functions)
-                .await
+            select_where_controller(
+                entity_name,
+                args_to_select,
+                clauses,
+                local_data,
+                functions,
+                actor,
+            )
+            .await
         }
         _ => Err(Error::QueryFormat(String::from(ERROR_JOIN))),
     }
@@ -437,6 +437,132 @@ async fn test_join2() {
     assert_eq!(body.matches("Char(\'d\')").count(), 4);
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_join_with_operator() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for req in inserts_operator() {
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload =
+        "JOIN (entity_AC:a > entity_BC:a) Select * FROM entity_AC | Select * FROM entity_BC"
+            .to_string();
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+
+    assert_eq!(body.matches("\"a:entity_BC\"").count(), 3);
+    assert!(body.contains("\"a:entity_BC\": Integer(100)"));
+    assert!(body.contains("\"a:entity_BC\": Integer(200)"));
+    assert!(!body.contains("\"a:entity_BC\": Integer(300)"));
+    assert!(!body.contains("\"tx_time:entity_BC\""));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_full_outer_join() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for req in inserts_operator() {
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload = "FULL OUTER JOIN (entity_AC:a < entity_BC:a) Select * FROM entity_AC | Select * FROM entity_BC";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+
+    assert_eq!(body.matches("\"entity_BC:a\"").count(), 4);
+    assert_eq!(body.matches("\"entity_AC:a\": Nil").count(), 1);
+    assert!(body.contains("\"entity_BC:a\": Integer(100)"));
+    assert!(body.contains("\"entity_AC:c\": Char('c')"));
+    assert!(body.contains("\"entity_AC:tx_time\""));
+    assert!(!body.contains("\"a\""));
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_left_join() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+
+    for req in inserts_operator() {
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let payload =
+        "LEFT JOIN (entity_AC:a, entity_BC:a) Select * FROM entity_AC | Select * FROM entity_BC";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+
+    assert_eq!(body.matches("\"entity_AC:a\"").count(), 2);
+    assert_eq!(body.matches("\"entity_BC:a\": Nil").count(), 2);
+    assert_eq!(body.matches("\"entity_BC:d\": Nil").count(), 2);
+}
+
+fn inserts_operator() -> Vec<Request> {
+    vec![
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("CREATE ENTITY {}", "entity_AC"))
+            .uri("/wql/tx")
+            .to_request(),
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("CREATE ENTITY {}", "entity_BC"))
+            .uri("/wql/tx")
+            .to_request(),
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("INSERT {{a: 123, b: 12.3,}} INTO {}", "entity_AC"))
+            .uri("/wql/tx")
+            .to_request(),
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("INSERT {{a: 235, c: 'c',}} INTO {}", "entity_AC"))
+            .uri("/wql/tx")
+            .to_request(),
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("INSERT {{a: 100, d: 'd',}} INTO {}", "entity_BC"))
+            .uri("/wql/tx")
+            .to_request(),
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("INSERT {{a: 200, d: 'e',}} INTO {}", "entity_BC"))
+            .uri("/wql/tx")
+            .to_request(),
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!("INSERT {{a: 300, d: 'f',}} INTO {}", "entity_BC"))
+            .uri("/wql/tx")
+            .to_request(),
+    ]
+}
+
 fn inserts() -> Vec<Request> {
     vec![
         test::TestRequest::post()
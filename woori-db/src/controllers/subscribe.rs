@@ -0,0 +1,31 @@
+use crate::{
+    actors::subscriptions::Subscriptions,
+    core::read_lock,
+    model::{
+        error::{error_to_http, Error},
+        DataLocalContext,
+    },
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+
+/// Opens a WebSocket that receives the state of each id of `entity` written by an insert or update.
+pub async fn subscribe_handler(
+    req: HttpRequest,
+    entity: web::Path<String>,
+    stream: web::Payload,
+    local_data: DataLocalContext,
+    subscriptions: web::Data<Subscriptions>,
+) -> HttpResponse {
+    let entity = entity.into_inner();
+    let created = match read_lock(&local_data) {
+        Ok(guard) => guard.contains_key(&entity),
+        Err(e) => return error_to_http(&e),
+    };
+    if !created {
+        return error_to_http(&Error::EntityNotCreated(entity));
+    }
+
+    ws::start(subscriptions.subscribe(entity), &req, stream)
+        .unwrap_or_else(HttpResponse::from_error)
+}
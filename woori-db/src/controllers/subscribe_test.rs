@@ -0,0 +1,55 @@
+use crate::http::routes;
+use actix_http::ws::Frame;
+use actix_web::{test, App};
+use futures::StreamExt;
+
+#[ignore]
+#[actix_rt::test]
+async fn test_subscribe_receives_inserts_ok() {
+    let mut srv = test::start(|| App::new().configure(routes));
+    let resp = srv
+        .post("/wql/tx")
+        .header("Content-Type", "application/wql")
+        .send_body("CREATE ENTITY subscribe_ok")
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let resp = srv
+        .post("/wql/tx")
+        .header("Content-Type", "application/wql")
+        .send_body("CREATE ENTITY subscribe_other")
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let mut framed = srv.ws_at("/subscribe/subscribe_ok").await.unwrap();
+    for insert in &[
+        "INSERT {a: 1,} INTO subscribe_other",
+        "INSERT {a: 123, b: 12.3,} INTO subscribe_ok",
+    ] {
+        let resp = srv
+            .post("/wql/tx")
+            .header("Content-Type", "application/wql")
+            .send_body(*insert)
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let frame = framed.next().await.unwrap().unwrap();
+    let body = match frame {
+        Frame::Text(text) => String::from_utf8(text.to_vec()).unwrap(),
+        frame => panic!("Expected a text frame, got {:?}", frame),
+    };
+    assert!(body.contains("tx_type: Insert"));
+    assert!(body.contains("entity: \"subscribe_ok\""));
+    assert!(body.contains("\"a\": Integer(123)"));
+    assert!(body.contains("\"b\": Float(12.3)"));
+}
+
+#[actix_rt::test]
+async fn test_subscribe_entity_not_created_err() {
+    let mut srv = test::start(|| App::new().configure(routes));
+    let framed = srv.ws_at("/subscribe/subscribe_not_created").await;
+    assert!(framed.is_err());
+}
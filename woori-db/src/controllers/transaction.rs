@@ -0,0 +1,464 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::Ordering,
+};
+
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use ron::ser::to_string_pretty;
+use uuid::Uuid;
+use wql::{Types, Wql};
+
+use crate::{
+    actors::{
+        encrypts::EncryptContent,
+        recovery::{LocalData, OffsetCounter},
+        state::MatchUpdate,
+        uniques::{CheckForUniqueKeys, RemoveUniqueValues, VerifyUniqueKeys},
+        wql::{
+            InsertEntityContent, UpdateContentEntityContent, UpdateSetEntityContent, WriteBlock,
+        },
+    },
+    core::{
+        pretty_config_inner, tx_time,
+        wql::{
+            insert_entity_content, replace_entity_content, update_content_entity_content,
+            update_content_state, update_set_entity_content,
+        },
+    },
+    io::{
+        paths::date_log,
+        write::{checksum, log_offset},
+    },
+    model::{
+        error::Error, DataAtomicUsize, DataEncryptContext, DataExecutor, DataLocalContext,
+        DataRegister, DataU32, DataUniquenessContext,
+    },
+    repository::local::{LocalContext, UniquenessContext},
+    schemas::tx::{
+        InsertEntityResponse, TransactionResponse, TxResponse, TxType, UpdateEntityResponse,
+    },
+};
+
+use super::tx::{append_only_controller, check_version, evaluate_expressions, validate_schemas};
+
+type Registry = (DataRegister, Vec<u8>);
+
+/// Registry write of a statement of a `BEGIN` block, built before anything is written.
+struct StagedWrite {
+    entity: String,
+    id: Uuid,
+    tx_type: TxType,
+    datetime: DateTime<Utc>,
+    /// Content of the statement, its unique values are registered on commit.
+    content: HashMap<String, Types>,
+    content_log: String,
+    state_log: String,
+    /// State kept in the registry of the entity id.
+    encoded: Vec<u8>,
+}
+
+/// Executes the statements of a `BEGIN ... COMMIT` block, the caller holds the write lock
+/// for the whole block. The new state of every statement is built against a staged view,
+/// which includes the changes of the previous statements in the block, and only when all
+/// of them are valid the unique values are registered, the day log entries appended and
+/// the registries swapped. If registering or appending fails, the unique values added by
+/// the block are removed, none of its entries is left in the day logs and the registries
+/// are left untouched.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn transaction_controller(
+    mut statements: Vec<Wql>,
+    req: &HttpRequest,
+    local_data: DataLocalContext,
+    uniqueness: DataUniquenessContext,
+    encryption: DataEncryptContext,
+    bytes_counter: DataAtomicUsize,
+    hashing_cost: DataU32,
+    actor: DataExecutor,
+) -> Result<TxResponse, Error> {
+    let registries = if let Ok(guard) = local_data.lock() {
+        guard.clone()
+    } else {
+        return Err(Error::LockData);
+    };
+    let uniques = if let Ok(guard) = uniqueness.lock() {
+        guard.clone()
+    } else {
+        return Err(Error::LockData);
+    };
+    validate_schemas(req, &mut statements, &actor).await?;
+    let staged = stage_statements(
+        statements,
+        req,
+        &registries,
+        &uniques,
+        &encryption,
+        *hashing_cost.into_inner(),
+        &actor,
+    )
+    .await?;
+
+    let mut registered = Vec::new();
+    let written = match commit(
+        &staged,
+        &registries,
+        &uniqueness,
+        &bytes_counter,
+        &actor,
+        &mut registered,
+    )
+    .await
+    {
+        Ok(written) => written,
+        Err(e) => {
+            for (entity, content) in registered {
+                actor
+                    .send(RemoveUniqueValues {
+                        entity,
+                        content,
+                        data: uniqueness.clone().into_inner(),
+                    })
+                    .await??;
+            }
+            return Err(e);
+        }
+    };
+
+    let local_data = {
+        let mut local_data = if let Ok(guard) = local_data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+        for ((entity, id), registry) in written {
+            if let Some(ids) = local_data.get_mut(&entity) {
+                ids.insert(id, registry);
+            }
+        }
+        local_data.clone()
+    };
+    actor.send(LocalData::new(local_data)).await??;
+
+    let responses = staged.into_iter().map(response).collect();
+    Ok(TransactionResponse::new(responses).into())
+}
+
+/// Builds the writes of the block, failing on the first invalid statement. Statements that
+/// change ids of an `APPEND ONLY` entity fail the block before anything is staged.
+async fn stage_statements(
+    statements: Vec<Wql>,
+    req: &HttpRequest,
+    registries: &LocalContext,
+    uniques: &UniquenessContext,
+    encryption: &DataEncryptContext,
+    hashing_cost: u32,
+    actor: &DataExecutor,
+) -> Result<Vec<StagedWrite>, Error> {
+    statements
+        .iter()
+        .try_for_each(|statement| append_only_controller(req, statement))?;
+    let mut staged: Vec<StagedWrite> = Vec::new();
+    let mut states: HashMap<(String, Uuid), HashMap<String, Types>> = HashMap::new();
+    let mut staged_uniques: HashSet<(String, String, String)> = HashSet::new();
+    for statement in statements {
+        let (entity, mut content, id) = match &statement {
+            Wql::Insert(entity, content, id) => (entity.to_owned(), content.to_owned(), *id),
+            Wql::UpdateContent(entity, content, id)
+            | Wql::UpdateSet(entity, content, id, _)
+            | Wql::Replace(entity, content, id)
+            | Wql::MatchUpdate(entity, content, id, _, _) => {
+                (entity.to_owned(), content.to_owned(), Some(*id))
+            }
+            _ => return Err(Error::SelectBadRequest),
+        };
+        let datetime = tx_time(&content)?;
+        if !registries.contains_key(&entity) {
+            return Err(Error::EntityNotCreated(entity));
+        }
+        let id = id.unwrap_or_else(Uuid::new_v4);
+        let mut state = match statement {
+            Wql::Insert(..) => HashMap::new(),
+            _ => staged_state(&entity, id, registries, &states)?,
+        };
+
+        let tx_type = match &statement {
+            Wql::Insert(..) => TxType::Insert,
+            Wql::UpdateContent(..) => TxType::UpdateContent,
+            Wql::Replace(..) => TxType::Replace,
+            _ => TxType::UpdateSet,
+        };
+        if let Wql::UpdateSet(_, _, _, version) = &statement {
+            if let Some((register, _)) = registries.get(&entity).and_then(|ids| ids.get(&id)) {
+                check_version(&entity, &id, version.as_ref(), register)?;
+            }
+        }
+        if let Wql::MatchUpdate(_, _, _, conditions, expressions) = &statement {
+            actor
+                .send(MatchUpdate {
+                    conditions: conditions.to_owned(),
+                    previous_state: state.clone(),
+                })
+                .await??;
+            content.extend(evaluate_expressions(expressions, &state)?);
+        }
+
+        let (content, encoded) = if let Wql::UpdateContent(..) = statement {
+            verify_update_content(&entity, &content, encryption)?;
+            content.insert("tx_time".to_owned(), Types::DateTime(datetime));
+            content.iter().try_for_each(|(k, v)| {
+                update_content_state(&mut state, k.to_owned(), v.to_owned())
+            })?;
+            let encoded = bincode::serialize(&state).unwrap();
+            (content, encoded)
+        } else {
+            let content = actor
+                .send(EncryptContent::new(
+                    &entity,
+                    content,
+                    encryption.clone().into_inner(),
+                    hashing_cost,
+                    datetime,
+                ))
+                .await??;
+            if let TxType::Replace = tx_type {
+                state = content.clone();
+            } else {
+                state.extend(content.clone());
+            }
+            let encoded = bincode::serialize(&content).unwrap();
+            (content, encoded)
+        };
+        verify_uniques(&entity, &content, uniques, &mut staged_uniques, actor).await?;
+
+        let content_log =
+            to_string_pretty(&content, pretty_config_inner()).map_err(Error::Serialization)?;
+        let state_log =
+            to_string_pretty(&state, pretty_config_inner()).map_err(Error::Serialization)?;
+        states.insert((entity.to_owned(), id), state);
+        staged.push(StagedWrite {
+            entity,
+            id,
+            tx_type,
+            datetime,
+            content,
+            content_log,
+            state_log,
+            encoded,
+        });
+    }
+    Ok(staged)
+}
+
+/// Registers the unique values of the staged writes, keeping the ones registered in
+/// `registered`, and appends them to the day logs with a single write per day log, all of
+/// them or none. Returns the registries to swap.
+async fn commit(
+    staged: &[StagedWrite],
+    registries: &LocalContext,
+    uniqueness: &DataUniquenessContext,
+    bytes_counter: &DataAtomicUsize,
+    actor: &DataExecutor,
+    registered: &mut Vec<(String, HashMap<String, Types>)>,
+) -> Result<HashMap<(String, Uuid), Registry>, Error> {
+    for write in staged {
+        actor
+            .send(CheckForUniqueKeys::new(
+                write.entity.to_owned(),
+                &write.content,
+                uniqueness.clone().into_inner(),
+            ))
+            .await??;
+        registered.push((write.entity.to_owned(), write.content.to_owned()));
+    }
+
+    let mut written: HashMap<(String, Uuid), Registry> = HashMap::new();
+    let mut logs: Vec<(String, usize, String)> = Vec::new();
+    for write in staged {
+        let key = (write.entity.to_owned(), write.id);
+        let previous_registry = written
+            .get(&key)
+            .or_else(|| {
+                registries
+                    .get(&write.entity)
+                    .and_then(|ids| ids.get(&write.id))
+            })
+            .map(|previous| to_string_pretty(previous, pretty_config_inner()))
+            .transpose()
+            .map_err(Error::Serialization)?
+            .unwrap_or_default();
+        let (date, entry) = log_entry(write, &previous_registry);
+
+        let file_name = date_log(&write.entity, &date);
+        let index = match logs.iter().position(|(log, _, _)| log == &file_name) {
+            Some(index) => index,
+            None => {
+                let offset = log_offset(&file_name);
+                logs.push((file_name.clone(), offset, String::new()));
+                logs.len() - 1
+            }
+        };
+        let (_, offset, buffer) = &mut logs[index];
+        let register = DataRegister {
+            offset: *offset + buffer.len(),
+            bytes_length: entry.len(),
+            file_name,
+            checksum: Some(checksum(entry.as_bytes())),
+        };
+        buffer.push_str(&entry);
+        written.insert(key, (register, write.encoded.to_owned()));
+    }
+
+    let created = actor.send(WriteBlock(logs.clone())).await??;
+    for ((_, _, buffer), is_empty) in logs.iter().zip(created) {
+        if is_empty {
+            bytes_counter.store(0, Ordering::SeqCst);
+        }
+        bytes_counter.fetch_add(buffer.len(), Ordering::SeqCst);
+    }
+    actor
+        .send(OffsetCounter::new(bytes_counter.load(Ordering::SeqCst)))
+        .await??;
+    Ok(written)
+}
+
+/// Day log entry of a staged write, the same one its single statement would append.
+fn log_entry(write: &StagedWrite, previous_registry: &str) -> (DateTime<Utc>, String) {
+    match write.tx_type {
+        TxType::Insert => {
+            let (date, _, entry) = insert_entity_content(&InsertEntityContent::new(
+                &write.entity,
+                &write.content_log,
+                Some(write.id),
+                write.datetime,
+            ));
+            (date, entry)
+        }
+        TxType::UpdateContent => update_content_entity_content(&UpdateContentEntityContent::new(
+            &write.entity,
+            &write.state_log,
+            &write.content_log,
+            write.id,
+            previous_registry,
+        )),
+        TxType::Replace => replace_entity_content(&UpdateSetEntityContent::new(
+            &write.entity,
+            &write.content_log,
+            &write.content_log,
+            write.id,
+            write.datetime,
+            previous_registry,
+        )),
+        _ => update_set_entity_content(&UpdateSetEntityContent::new(
+            &write.entity,
+            &write.state_log,
+            &write.content_log,
+            write.id,
+            write.datetime,
+            previous_registry,
+        )),
+    }
+}
+
+fn response(write: StagedWrite) -> TxResponse {
+    match write.tx_type {
+        TxType::Insert => {
+            let message = format!("Entity {} inserted with Uuid {}", &write.entity, &write.id);
+            InsertEntityResponse::new(write.entity, write.id, message).into()
+        }
+        tx_type => {
+            let action = if let TxType::Replace = tx_type {
+                "replaced"
+            } else {
+                "updated"
+            };
+            let message = format!(
+                "Entity {} with Uuid {} {}",
+                &write.entity, &write.id, action
+            );
+            UpdateEntityResponse::new(write.entity, write.id, write.state_log, message, tx_type)
+                .into()
+        }
+    }
+}
+
+/// State of the entity id with the changes of the previous statements of the block.
+fn staged_state(
+    entity: &str,
+    id: Uuid,
+    registries: &LocalContext,
+    states: &HashMap<(String, Uuid), HashMap<String, Types>>,
+) -> Result<HashMap<String, Types>, Error> {
+    if let Some(state) = states.get(&(entity.to_owned(), id)) {
+        return Ok(state.to_owned());
+    }
+
+    registries
+        .get(entity)
+        .and_then(|ids| ids.get(&id))
+        .map(|(_, state)| bincode::deserialize(state).unwrap_or_default())
+        .ok_or_else(|| Error::UuidNotCreatedForEntity(entity.to_owned(), id))
+}
+
+/// Unique values must not be in use and must not be repeated by statements of the block.
+async fn verify_uniques(
+    entity: &str,
+    content: &HashMap<String, Types>,
+    uniques: &UniquenessContext,
+    staged_uniques: &mut HashSet<(String, String, String)>,
+    actor: &DataExecutor,
+) -> Result<(), Error> {
+    let unique_keys = if let Some(unique_keys) = uniques.get(entity) {
+        unique_keys
+    } else {
+        return Ok(());
+    };
+    actor
+        .send(VerifyUniqueKeys {
+            entity: entity.to_owned(),
+            content: content.to_owned(),
+            uniqueness: std::sync::Arc::new(std::sync::Arc::new(std::sync::Mutex::new(
+                uniques.to_owned(),
+            ))),
+        })
+        .await??;
+
+    content
+        .iter()
+        .filter(|(k, _)| unique_keys.contains_key(*k))
+        .try_for_each(|(k, v)| {
+            if staged_uniques.insert((entity.to_owned(), k.to_owned(), format!("{:?}", v))) {
+                Ok(())
+            } else {
+                Err(Error::DuplicatedUnique(
+                    entity.to_owned(),
+                    k.to_owned(),
+                    v.to_owned(),
+                ))
+            }
+        })
+}
+
+/// `UPDATE CONTENT` can't change encrypted keys.
+fn verify_update_content(
+    entity: &str,
+    content: &HashMap<String, Types>,
+    encryption: &DataEncryptContext,
+) -> Result<(), Error> {
+    let encryption = if let Ok(guard) = encryption.lock() {
+        guard
+    } else {
+        return Err(Error::LockData);
+    };
+    let keys = encryption.get(entity).map_or_else(Vec::new, |encrypts| {
+        content
+            .keys()
+            .filter(|k| encrypts.contains(*k))
+            .map(ToOwned::to_owned)
+            .collect::<Vec<String>>()
+    });
+    if keys.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::UpdateContentEncryptKeys(keys))
+    }
+}
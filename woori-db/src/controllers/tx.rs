@@ -1,49 +1,69 @@
 use crate::core::tx_time;
-use crate::schemas::tx::{TxResponse, TxType};
+use crate::schemas::tx::{DryRunResponse, TxResponse, TxType, ValidateResponse};
 use crate::{
     actors::{
         encrypts::{CreateWithEncryption, EncryptContent, WriteWithEncryption},
         recovery::{LocalData, OffsetCounter},
-        state::{MatchUpdate, PreviousRegistry, State},
+        schemas::{BooleanParsing, CreateWithSchema, ValidateSchema, WriteWithSchema},
+        sequences::{sequence_id, NextSequence},
+        state::{match_conditions, PreviousRegistry, State},
+        subscriptions::{EntityChange, Subscriptions},
         uniques::{CreateWithUniqueKeys, WriteWithUniqueKeys},
-        wql::{DeleteId, InsertEntityContent, UpdateContentEntityContent, UpdateSetEntityContent},
+        wql::{
+            DeleteId, InsertEntityContent, ReplaceEntityContent, UpdateContentEntityContent,
+            UpdateSetEntityContent,
+        },
     },
-    core::{pretty_config_inner, wql::update_content_state},
+    core::{
+        cache::QueryCache,
+        pretty_config_inner,
+        query::{filter_keys_and_hash, query_pool},
+        wql::update_content_state,
+    },
+    io::paths::date_log,
     model::{
         error::error_to_http,
         wql::{InsertArgs, MatchUpdateArgs, UpdateArgs},
-        DataAtomicUsize, DataEncryptContext, DataExecutor, DataLocalContext, DataU32,
-        DataUniquenessContext,
+        DataAtomicUsize, DataEncryptContext, DataExecutor, DataI64, DataIdempotencyContext,
+        DataLocalContext, DataSchemaContext, DataSequenceContext, DataU32, DataUniquenessContext,
+        DataWriteLock,
     },
 };
 use crate::{
     actors::{
-        uniques::CheckForUniqueKeys,
-        wql::{CreateEntity, EvictEntity, EvictEntityId},
+        uniques::{CheckForUniqueKeys, ClearUniqueValues, SwapUniqueValues, VerifyUniqueKeys},
+        wql::{ClearEntity, CreateEntity, EvictEntity, EvictEntityId},
     },
-    schemas::tx::CreateEntityResponse,
+    schemas::tx::{CreateEntityResponse, ReindexEntityResponse},
 };
 use crate::{
     model::{error::Error, DataRegister},
     schemas::tx::InsertEntityResponse,
 };
 use crate::{
-    repository::local::LocalContext,
+    repository::local::{IdempotencyInfo, LocalContext},
     schemas::tx::{DeleteOrEvictEntityResponse, UpdateEntityResponse},
 };
 
-use actix_web::{HttpResponse, Responder};
+use super::{
+    audit::{audit, bearer_token, session_user_id},
+    clauses::select_where,
+    transaction::transaction_controller,
+};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
 use rayon::prelude::*;
 use ron::ser::to_string_pretty;
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     str::FromStr,
     sync::{atomic::Ordering, Arc, Mutex},
 };
 use uuid::Uuid;
-use wql::{Types, Wql};
+use wql::{Clause, EntitySchema, Expression, ToSelect, Types, Wql};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn wql_handler(
+    req: HttpRequest,
     body: String,
     local_data: DataLocalContext,
     uniqueness: DataUniquenessContext,
@@ -51,21 +71,168 @@ pub async fn wql_handler(
     bytes_counter: DataAtomicUsize,
     hashing_cost: DataU32,
     actor: DataExecutor,
+    idempotency: DataIdempotencyContext,
+    idempotency_ttl: DataI64,
 ) -> impl Responder {
+    let idempotency_key = idempotency_key(&req);
+
     let query = wql::Wql::from_str(&body);
+    let (mut query, dry_run) = match query {
+        Ok(Wql::DryRun(tx)) => (Ok(*tx), true),
+        Ok(Wql::Validate(tx)) => {
+            let validation = validate_controller(*tx, &req, &local_data, &actor).await;
+            let response = validation
+                .as_ref()
+                .map_or_else(error_to_http, |resp| HttpResponse::Ok().body(resp.write()));
+            audit(&req, &body, &response, validation.as_ref().err());
+            return response;
+        }
+        query => (query, false),
+    };
+    let _write_guard = if let Some(write_lock) = req.app_data::<DataWriteLock>() {
+        write_lock.lock().await
+    } else {
+        let e = Error::LockData;
+        let response = error_to_http(&e);
+        audit(&req, &body, &response, Some(&e));
+        return response;
+    };
+    // Looked up under the write lock, so a retry sent while the first request is still
+    // writing waits for its response instead of writing again.
+    if let Some(resp) = idempotency_key
+        .as_ref()
+        .and_then(|key| cached_response(&idempotency, key))
+    {
+        let response = HttpResponse::Ok().body(resp);
+        audit(&req, &body, &response, None);
+        return response;
+    }
+    if let Ok(query) = &mut query {
+        if let Err(e) = sequence_controller(&req, query, &local_data, &actor, dry_run).await {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            return response;
+        }
+        if let Err(e) = schema_controller(&req, query, &actor).await {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            return response;
+        }
+    }
+    let subscription_data = local_data.clone();
+    let written = query.as_ref().map(written_entities).unwrap_or_default();
     let response = match query {
-        Ok(Wql::CreateEntity(entity, uniques, encrypts)) => {
-            let _ = create_unique_controller(&entity, uniques, uniqueness, &actor).await;
-            let _ = create_encrypts_controller(&entity, encrypts, encryption, &actor).await;
-            create_controller(entity, local_data.into_inner(), bytes_counter, actor).await
+        Ok(Wql::Transaction(statements)) => {
+            transaction_controller(
+                statements,
+                &req,
+                local_data,
+                uniqueness,
+                encryption,
+                bytes_counter,
+                hashing_cost,
+                actor,
+            )
+            .await
+        }
+        Ok(query) => {
+            tx_controller(
+                query,
+                dry_run,
+                &req,
+                local_data,
+                uniqueness,
+                encryption,
+                bytes_counter,
+                hashing_cost,
+                actor,
+            )
+            .await
         }
-        Ok(Wql::Delete(entity, uuid)) => {
-            delete_controller(entity, uuid, local_data.into_inner(), bytes_counter, actor).await
+        Err(e) => Err(Error::QueryFormat(e)),
+    };
+    if let (false, Some(cache)) = (dry_run, req.app_data::<web::Data<QueryCache>>()) {
+        written.iter().for_each(|entity| cache.invalidate(entity));
+    }
+
+    match response {
+        Err(e) => {
+            let response = error_to_http(&e);
+            audit(&req, &body, &response, Some(&e));
+            response
+        }
+        Ok(resp) => {
+            if !dry_run {
+                publish_changes(&req, &resp, &subscription_data);
+            }
+            let resp_body = resp.write();
+            if let Some(key) = idempotency_key {
+                store_response(
+                    &idempotency,
+                    key,
+                    resp_body.clone(),
+                    *idempotency_ttl.into_inner(),
+                );
+            }
+            let response = HttpResponse::Ok().body(resp_body);
+            audit(&req, &body, &response, None);
+            response
+        }
+    }
+}
+
+/// Executes a single tx statement, `BEGIN` blocks are executed by `transaction_controller`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn tx_controller(
+    query: Wql,
+    dry_run: bool,
+    req: &HttpRequest,
+    local_data: DataLocalContext,
+    uniqueness: DataUniquenessContext,
+    encryption: DataEncryptContext,
+    bytes_counter: DataAtomicUsize,
+    hashing_cost: DataU32,
+    actor: DataExecutor,
+) -> Result<TxResponse, Error> {
+    append_only_controller(req, &query)?;
+    match query {
+        Wql::CreateEntity(entity, uniques, encrypts, schema) => {
+            if !dry_run {
+                let _ = create_unique_controller(&entity, uniques, uniqueness, &actor).await;
+                let _ = create_encrypts_controller(&entity, encrypts, encryption, &actor).await;
+            }
+            let response = create_controller(
+                entity.clone(),
+                local_data.clone().into_inner(),
+                bytes_counter,
+                actor.clone(),
+                dry_run,
+            )
+            .await;
+            if response.is_ok() && !dry_run {
+                let _ = create_schema_controller(&entity, schema, req, &actor).await;
+            }
+            response
         }
-        Ok(Wql::Insert(entity, content, uuid)) => {
+        Wql::Delete(entity, uuid) => {
+            delete_controller(
+                entity,
+                uuid,
+                local_data.clone().into_inner(),
+                bytes_counter,
+                actor,
+                dry_run,
+            )
+            .await
+        }
+        Wql::DeleteWhere(entity, clauses) => {
+            delete_where_controller(entity, clauses, local_data, bytes_counter, actor, dry_run)
+                .await
+        }
+        Wql::Insert(entity, content, uuid) => {
             insert_controller(
-                InsertArgs::new(entity, content, uuid),
-                local_data.into_inner(),
+                InsertArgs::new(entity, content, uuid, dry_run),
+                local_data.clone().into_inner(),
                 bytes_counter,
                 uniqueness,
                 encryption,
@@ -74,10 +241,10 @@ pub async fn wql_handler(
             )
             .await
         }
-        Ok(Wql::UpdateContent(entity, content, uuid)) => {
+        Wql::UpdateContent(entity, content, uuid) => {
             update_content_controller(
-                UpdateArgs::new(entity, content, uuid),
-                local_data.into_inner(),
+                UpdateArgs::new(entity, content, uuid, None, dry_run),
+                local_data.clone().into_inner(),
                 bytes_counter,
                 uniqueness,
                 encryption,
@@ -85,10 +252,10 @@ pub async fn wql_handler(
             )
             .await
         }
-        Ok(Wql::UpdateSet(entity, content, uuid)) => {
+        Wql::UpdateSet(entity, content, uuid, version) => {
             update_set_controller(
-                UpdateArgs::new(entity, content, uuid),
-                local_data.into_inner(),
+                UpdateArgs::new(entity, content, uuid, version, dry_run),
+                local_data.clone().into_inner(),
                 bytes_counter,
                 uniqueness,
                 encryption,
@@ -97,10 +264,22 @@ pub async fn wql_handler(
             )
             .await
         }
-        Ok(Wql::MatchUpdate(entity, content, uuid, conditions)) => {
+        Wql::Replace(entity, content, uuid) => {
+            replace_controller(
+                UpdateArgs::new(entity, content, uuid, None, dry_run),
+                local_data.clone().into_inner(),
+                bytes_counter,
+                uniqueness,
+                encryption,
+                hashing_cost,
+                actor,
+            )
+            .await
+        }
+        Wql::MatchUpdate(entity, content, uuid, conditions, expressions) => {
             match_update_set_controller(
-                MatchUpdateArgs::new(entity, content, uuid, conditions),
-                local_data.into_inner(),
+                MatchUpdateArgs::new(entity, content, uuid, conditions, expressions, dry_run),
+                local_data.clone().into_inner(),
                 bytes_counter,
                 uniqueness,
                 encryption,
@@ -109,16 +288,149 @@ pub async fn wql_handler(
             )
             .await
         }
-        Ok(Wql::Evict(entity, uuid)) => {
-            evict_controller(entity, uuid, local_data.into_inner(), bytes_counter, actor).await
+        Wql::Evict(entity, uuid) => {
+            evict_controller(
+                entity,
+                uuid,
+                local_data.clone().into_inner(),
+                bytes_counter,
+                actor,
+                dry_run,
+            )
+            .await
         }
-        Ok(_) => Err(Error::SelectBadRequest),
-        Err(e) => Err(Error::QueryFormat(e)),
+        Wql::Clear(entity) => {
+            #[cfg(not(debug_assertions))]
+            if !super::audit::session_roles(req).contains(&crate::auth::schemas::Role::Admin) {
+                return Err(Error::AuthorizationBadRequest);
+            }
+            clear_controller(
+                entity,
+                local_data.clone().into_inner(),
+                uniqueness,
+                bytes_counter,
+                actor,
+                dry_run,
+            )
+            .await
+        }
+        Wql::Reindex(entity) => {
+            #[cfg(not(debug_assertions))]
+            if !super::audit::session_roles(req).contains(&crate::auth::schemas::Role::Admin) {
+                return Err(Error::AuthorizationBadRequest);
+            }
+            reindex_controller(entity, local_data, uniqueness, actor).await
+        }
+        _ => Err(Error::SelectBadRequest),
+    }
+}
+
+/// Entities whose cached query responses a tx statement invalidates, the ones of all
+/// statements of a `BEGIN` block. Failed statements invalidate them as well.
+fn written_entities(query: &Wql) -> Vec<String> {
+    match query {
+        Wql::Transaction(statements) => statements.iter().flat_map(written_entities).collect(),
+        Wql::CreateEntity(entity, ..)
+        | Wql::Insert(entity, ..)
+        | Wql::UpdateContent(entity, ..)
+        | Wql::UpdateSet(entity, ..)
+        | Wql::Replace(entity, ..)
+        | Wql::MatchUpdate(entity, ..)
+        | Wql::Delete(entity, _)
+        | Wql::DeleteWhere(entity, _)
+        | Wql::Evict(entity, _)
+        | Wql::Clear(entity) => vec![entity.to_owned()],
+        _ => Vec::new(),
+    }
+}
+
+/// Rejects statements that change or remove ids of an entity created with `APPEND ONLY`,
+/// inserts are still allowed.
+pub(super) fn append_only_controller(req: &HttpRequest, query: &Wql) -> Result<(), Error> {
+    let entity = match query {
+        Wql::UpdateContent(entity, ..)
+        | Wql::UpdateSet(entity, ..)
+        | Wql::Replace(entity, ..)
+        | Wql::MatchUpdate(entity, ..)
+        | Wql::Delete(entity, _)
+        | Wql::DeleteWhere(entity, _)
+        | Wql::Evict(entity, _)
+        | Wql::Clear(entity) => entity,
+        _ => return Ok(()),
+    };
+    let schemas = match req
+        .app_data::<DataSchemaContext>()
+        .map(|schemas| schemas.lock())
+    {
+        Some(Ok(guard)) => guard,
+        Some(Err(_)) => return Err(Error::LockData),
+        None => return Ok(()),
+    };
+    if schemas.get(entity).is_some_and(|schema| schema.append_only) {
+        return Err(Error::ImmutableEntity(entity.to_owned()));
+    }
+    Ok(())
+}
+
+/// Sends the current state of the ids written by `response` to the subscribers of their entities.
+fn publish_changes(req: &HttpRequest, response: &TxResponse, local_data: &DataLocalContext) {
+    let subscriptions = match req.app_data::<web::Data<Subscriptions>>() {
+        Some(subscriptions) if subscriptions.has_subscribers() => subscriptions,
+        _ => return,
+    };
+    let local_data = match local_data.lock() {
+        Ok(guard) => guard,
+        Err(_) => return,
     };
 
-    match response {
-        Err(e) => error_to_http(&e),
-        Ok(resp) => HttpResponse::Ok().body(resp.write()),
+    let mut published = HashSet::new();
+    for (tx_type, entity, id) in response.written_ids() {
+        if !published.insert((entity, id)) {
+            continue;
+        }
+        if let Some((_, state)) = local_data.get(entity).and_then(|ids| ids.get(&id)) {
+            let state: HashMap<String, Types> = bincode::deserialize(state).unwrap_or_default();
+            subscriptions.publish(EntityChange {
+                tx_type,
+                entity: entity.to_owned(),
+                id,
+                state: filter_keys_and_hash(state, None, false),
+            });
+        }
+    }
+}
+
+/// `Idempotency-Key` header scoped by the session's user, or by the bearer token
+/// when there is no session for it.
+fn idempotency_key(req: &HttpRequest) -> Option<(String, String)> {
+    let key = req
+        .headers()
+        .get("Idempotency-Key")?
+        .to_str()
+        .ok()?
+        .to_owned();
+    let scope = session_user_id(req).map_or_else(|| bearer_token(req), |id| id.to_string());
+
+    Some((scope, key))
+}
+
+fn cached_response(idempotency: &DataIdempotencyContext, key: &(String, String)) -> Option<String> {
+    let idempotency = idempotency.lock().ok()?;
+    idempotency
+        .get(key)
+        .filter(|info| info.is_valid_date())
+        .map(IdempotencyInfo::response)
+}
+
+fn store_response(
+    idempotency: &DataIdempotencyContext,
+    key: (String, String),
+    response: String,
+    ttl: i64,
+) {
+    if let Ok(mut idempotency) = idempotency.lock() {
+        idempotency.retain(|_, info| info.is_valid_date());
+        idempotency.insert(key, IdempotencyInfo::new(response, ttl));
     }
 }
 
@@ -127,6 +439,7 @@ pub async fn create_controller(
     local_data: Arc<Arc<Mutex<LocalContext>>>,
     bytes_counter: DataAtomicUsize,
     actor: DataExecutor,
+    dry_run: bool,
 ) -> Result<TxResponse, Error> {
     let local_data = {
         let mut local_data = if let Ok(guard) = local_data.lock() {
@@ -137,6 +450,9 @@ pub async fn create_controller(
         if local_data.contains_key(&entity) {
             return Err(Error::EntityAlreadyCreated(entity));
         }
+        if dry_run {
+            return Ok(DryRunResponse::new(entity, Vec::new(), TxType::Create).into());
+        }
 
         local_data.insert(entity.clone(), BTreeMap::new());
         local_data.clone()
@@ -163,7 +479,12 @@ pub async fn evict_controller(
     local_data: Arc<Arc<Mutex<LocalContext>>>,
     bytes_counter: DataAtomicUsize,
     actor: DataExecutor,
+    dry_run: bool,
 ) -> Result<TxResponse, Error> {
+    if dry_run {
+        return evict_dry_run(entity, uuid, &local_data);
+    }
+
     if uuid.is_none() {
         let message = format!("Entity {} evicted", &entity);
         let (offset, is_empty) = actor.send(EvictEntity::new(&entity)).await??;
@@ -176,18 +497,26 @@ pub async fn evict_controller(
             .send(OffsetCounter::new(bytes_counter.load(Ordering::SeqCst)))
             .await??;
 
-        let local_data = {
+        let (local_data, evicted) = {
             let mut local_data = if let Ok(guard) = local_data.lock() {
                 guard
             } else {
                 return Err(Error::LockData);
             };
-            local_data.remove(&entity);
-            local_data.clone()
+            let evicted = local_data.remove(&entity);
+            (local_data.clone(), evicted)
         };
+        let affected_ids = evicted.map_or_else(Vec::new, |ids| ids.keys().cloned().collect());
 
         actor.send(LocalData::new(local_data)).await??;
-        Ok(DeleteOrEvictEntityResponse::new(entity, None, message, TxType::EvictEntityTree).into())
+        Ok(DeleteOrEvictEntityResponse::new(
+            entity,
+            None,
+            message,
+            TxType::EvictEntityTree,
+            affected_ids,
+        )
+        .into())
     } else {
         let id = uuid.unwrap();
         let (offset, is_empty) = actor.send(EvictEntityId::new(&entity, id)).await??;
@@ -215,7 +544,250 @@ pub async fn evict_controller(
         actor.send(LocalData::new(local_data)).await??;
 
         let message = format!("Entity {} with id {} evicted", &entity, &id);
-        Ok(DeleteOrEvictEntityResponse::new(entity, uuid, message, TxType::EvictEntity).into())
+        Ok(
+            DeleteOrEvictEntityResponse::new(entity, uuid, message, TxType::EvictEntity, vec![id])
+                .into(),
+        )
+    }
+}
+
+/// Evicts the ids of `entity` whose current state matches `clauses`, the same ids returned
+/// by `SELECT * FROM entity WHERE {...}`. Matching no id is a successful delete of zero ids.
+pub async fn delete_where_controller(
+    entity: String,
+    clauses: Vec<Clause>,
+    local_data: DataLocalContext,
+    bytes_counter: DataAtomicUsize,
+    actor: DataExecutor,
+    dry_run: bool,
+) -> Result<TxResponse, Error> {
+    let (states, _) = select_where(
+        entity.clone(),
+        ToSelect::All,
+        clauses,
+        local_data.clone(),
+        &HashMap::new(),
+        actor.clone(),
+    )
+    .await?;
+    let affected_ids = states.keys().copied().collect::<Vec<Uuid>>();
+    if dry_run {
+        return Ok(DryRunResponse::new(entity, affected_ids, TxType::EvictEntity).into());
+    }
+
+    if !affected_ids.is_empty() {
+        for id in &affected_ids {
+            let (offset, is_empty) = actor.send(EvictEntityId::new(&entity, *id)).await??;
+            if is_empty {
+                bytes_counter.store(0, Ordering::SeqCst);
+            }
+            bytes_counter.fetch_add(offset, Ordering::SeqCst);
+        }
+        actor
+            .send(OffsetCounter::new(bytes_counter.load(Ordering::SeqCst)))
+            .await??;
+
+        let local_data = {
+            let mut local_data = if let Ok(guard) = local_data.lock() {
+                guard
+            } else {
+                return Err(Error::LockData);
+            };
+            if let Some(ids) = local_data.get_mut(&entity) {
+                for id in &affected_ids {
+                    ids.remove(id);
+                }
+            }
+            local_data.clone()
+        };
+        actor.send(LocalData::new(local_data)).await??;
+    }
+
+    let message = format!("{} ids of entity {} evicted", affected_ids.len(), &entity);
+    Ok(
+        DeleteOrEvictEntityResponse::new(entity, None, message, TxType::EvictEntity, affected_ids)
+            .into(),
+    )
+}
+
+pub async fn clear_controller(
+    entity: String,
+    local_data: Arc<Arc<Mutex<LocalContext>>>,
+    uniqueness: DataUniquenessContext,
+    bytes_counter: DataAtomicUsize,
+    actor: DataExecutor,
+    dry_run: bool,
+) -> Result<TxResponse, Error> {
+    {
+        let local_data = if let Ok(guard) = local_data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+        match local_data.get(&entity) {
+            None => return Err(Error::EntityNotCreated(entity)),
+            Some(ids) if dry_run => {
+                let affected_ids = ids.keys().cloned().collect();
+                return Ok(DryRunResponse::new(entity, affected_ids, TxType::ClearEntity).into());
+            }
+            Some(_) => (),
+        }
+    }
+
+    let (offset, is_empty) = actor.send(ClearEntity::new(&entity)).await??;
+
+    if is_empty {
+        bytes_counter.store(0, Ordering::SeqCst);
+    }
+    bytes_counter.fetch_add(offset, Ordering::SeqCst);
+    actor
+        .send(OffsetCounter::new(bytes_counter.load(Ordering::SeqCst)))
+        .await??;
+
+    let (local_data, cleared) = {
+        let mut local_data = if let Ok(guard) = local_data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+        let cleared = local_data.insert(entity.clone(), BTreeMap::new());
+        (local_data.clone(), cleared)
+    };
+    let affected_ids = cleared.map_or_else(Vec::new, |ids| ids.keys().cloned().collect());
+    actor.send(LocalData::new(local_data)).await??;
+
+    actor
+        .send(ClearUniqueValues {
+            entity: entity.clone(),
+            data: uniqueness.into_inner(),
+        })
+        .await??;
+
+    let message = format!("Entity {} cleared", &entity);
+    Ok(
+        DeleteOrEvictEntityResponse::new(entity, None, message, TxType::ClearEntity, affected_ids)
+            .into(),
+    )
+}
+
+/// Rebuilds the unique values of `entity` from the current state of its ids, removing the
+/// ones left by updated, deleted or evicted ids.
+pub async fn reindex_controller(
+    entity: String,
+    local_data: DataLocalContext,
+    uniqueness: DataUniquenessContext,
+    actor: DataExecutor,
+) -> Result<TxResponse, Error> {
+    let registries = {
+        let local_data = if let Ok(guard) = local_data.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+        match local_data.get(&entity) {
+            Some(ids) => ids.clone(),
+            None => return Err(Error::EntityNotCreated(entity)),
+        }
+    };
+    let previous = {
+        let uniqueness = if let Ok(guard) = uniqueness.lock() {
+            guard
+        } else {
+            return Err(Error::LockData);
+        };
+        uniqueness.get(&entity).cloned().unwrap_or_default()
+    };
+
+    let values = query_pool().install(|| {
+        registries
+            .par_iter()
+            .filter_map(|(_, (_, state))| {
+                bincode::deserialize::<HashMap<String, Types>>(state).ok()
+            })
+            .fold(
+                || unique_keys(&previous),
+                |mut values, state| {
+                    for (key, key_values) in &mut values {
+                        if let Some(value) = state.get(key) {
+                            key_values.insert(format!("{:?}", value));
+                        }
+                    }
+                    values
+                },
+            )
+            .reduce(
+                || unique_keys(&previous),
+                |mut values, other| {
+                    for (key, key_values) in other {
+                        values.entry(key).or_default().extend(key_values);
+                    }
+                    values
+                },
+            )
+    });
+    let stale = previous
+        .into_iter()
+        .map(|(key, old_values)| {
+            let rebuilt = values.get(&key);
+            let stale = old_values
+                .into_iter()
+                .filter(|value| !rebuilt.is_some_and(|rebuilt| rebuilt.contains(value)))
+                .collect();
+            (key, stale)
+        })
+        .collect();
+
+    let indexed = values.values().map(HashSet::len).sum::<usize>();
+    actor
+        .send(SwapUniqueValues {
+            entity: entity.clone(),
+            values,
+            stale,
+            data: uniqueness.get_ref().clone(),
+        })
+        .await??;
+
+    let message = format!(
+        "Entity {} reindexed with {} unique values from {} ids",
+        &entity,
+        indexed,
+        registries.len()
+    );
+    Ok(ReindexEntityResponse::new(entity, message).into())
+}
+
+fn unique_keys(uniques: &HashMap<String, HashSet<String>>) -> HashMap<String, HashSet<String>> {
+    uniques
+        .keys()
+        .map(|key| (key.clone(), HashSet::new()))
+        .collect()
+}
+
+fn evict_dry_run(
+    entity: String,
+    uuid: Option<Uuid>,
+    local_data: &Mutex<LocalContext>,
+) -> Result<TxResponse, Error> {
+    let local_data = if let Ok(guard) = local_data.lock() {
+        guard
+    } else {
+        return Err(Error::LockData);
+    };
+    let ids = if let Some(ids) = local_data.get(&entity) {
+        ids
+    } else {
+        return Err(Error::EntityNotCreated(entity));
+    };
+
+    match uuid {
+        Some(id) if ids.contains_key(&id) => {
+            Ok(DryRunResponse::new(entity, vec![id], TxType::EvictEntity).into())
+        }
+        Some(id) => Err(Error::UuidNotCreatedForEntity(entity, id)),
+        None => {
+            let affected_ids = ids.keys().cloned().collect();
+            Ok(DryRunResponse::new(entity, affected_ids, TxType::EvictEntityTree).into())
+        }
     }
 }
 
@@ -273,6 +845,151 @@ pub async fn create_encrypts_controller(
     }
 }
 
+pub async fn create_schema_controller(
+    entity: &str,
+    schema: Option<EntitySchema>,
+    req: &HttpRequest,
+    actor: &DataExecutor,
+) -> Result<(), Error> {
+    let (schema, schemas) = match (schema, req.app_data::<DataSchemaContext>()) {
+        (Some(schema), Some(schemas)) => (schema, schemas.get_ref().clone()),
+        _ => return Ok(()),
+    };
+    actor
+        .send(WriteWithSchema {
+            entity: entity.to_owned(),
+            schema: schema.clone(),
+        })
+        .await??;
+    actor
+        .send(CreateWithSchema {
+            entity: entity.to_owned(),
+            schema,
+            data: schemas,
+        })
+        .await??;
+    Ok(())
+}
+
+/// Replaces `INSERT ... WITH NEXT` statements, also the ones of a `BEGIN` block, by inserts
+/// with the id of the next number of their entity sequence. `DRY RUN` doesn't reserve it.
+async fn sequence_controller(
+    req: &HttpRequest,
+    query: &mut Wql,
+    local_data: &DataLocalContext,
+    actor: &DataExecutor,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let statements = match query {
+        Wql::Transaction(statements) => statements.iter_mut().collect::<Vec<&mut Wql>>(),
+        query => vec![query],
+    };
+    for statement in statements {
+        if let Wql::InsertNext(entity, content) = statement {
+            let (entity, content) = (entity.to_owned(), std::mem::take(content));
+            let is_created = if let Ok(guard) = local_data.lock() {
+                guard.contains_key(&entity)
+            } else {
+                return Err(Error::LockData);
+            };
+            if !is_created {
+                return Err(Error::EntityNotCreated(entity));
+            }
+            let sequences = if let Some(sequences) = req.app_data::<DataSequenceContext>() {
+                sequences.get_ref().clone()
+            } else {
+                return Err(Error::LockData);
+            };
+
+            let number = actor
+                .send(NextSequence {
+                    entity: entity.clone(),
+                    reserve: !dry_run,
+                    data: sequences,
+                })
+                .await??;
+            *statement = Wql::Insert(entity, content, Some(sequence_id(number)));
+        }
+    }
+    Ok(())
+}
+
+/// Validates every statement of `VALIDATE` against the schema of its entity without writing
+/// them. Sequences of `INSERT ... WITH NEXT` are read but not reserved.
+async fn validate_controller(
+    mut query: Wql,
+    req: &HttpRequest,
+    local_data: &DataLocalContext,
+    actor: &DataExecutor,
+) -> Result<TxResponse, Error> {
+    sequence_controller(req, &mut query, local_data, actor, true).await?;
+    let mut statements = match query {
+        Wql::Transaction(statements) => statements,
+        query => vec![query],
+    };
+    validate_schemas(req, &mut statements, actor).await?;
+    Ok(ValidateResponse::new(statements.len()).into())
+}
+
+/// Validates all statements of a batch against their schemas, instead of stopping at the
+/// first one that violates them, so the error lists every offending statement.
+pub(crate) async fn validate_schemas(
+    req: &HttpRequest,
+    statements: &mut [Wql],
+    actor: &DataExecutor,
+) -> Result<(), Error> {
+    let mut violations = Vec::new();
+    for (position, statement) in statements.iter_mut().enumerate() {
+        match schema_controller(req, statement, actor).await {
+            Err(e) if e.is_schema_violation() => violations.push((position, e)),
+            result => result?,
+        }
+    }
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::SchemaViolations(violations))
+    }
+}
+
+/// Validates the content of inserts and updates against the schema declared
+/// for the entity, if there is one. Loose booleans of `Boolean` keys are converted
+/// in the query content unless `STRICT_BOOLEANS` is set.
+pub(crate) async fn schema_controller(
+    req: &HttpRequest,
+    query: &mut Wql,
+    actor: &DataExecutor,
+) -> Result<(), Error> {
+    let fill_defaults = matches!(query, Wql::Insert(..) | Wql::Replace(..));
+    let (entity, content) = match query {
+        Wql::Insert(entity, content, _)
+        | Wql::UpdateContent(entity, content, _)
+        | Wql::UpdateSet(entity, content, _, _)
+        | Wql::Replace(entity, content, _)
+        | Wql::MatchUpdate(entity, content, _, _, _) => (entity, content),
+        _ => return Ok(()),
+    };
+    let schemas = if let Some(schemas) = req.app_data::<DataSchemaContext>() {
+        schemas.get_ref().clone()
+    } else {
+        return Ok(());
+    };
+    let booleans = req
+        .app_data::<web::Data<BooleanParsing>>()
+        .map_or(BooleanParsing::Strict, |booleans| *booleans.get_ref());
+
+    *content = actor
+        .send(ValidateSchema {
+            entity: entity.to_owned(),
+            content: content.to_owned(),
+            schemas,
+            booleans,
+            fill_defaults,
+        })
+        .await??;
+    Ok(())
+}
+
 pub async fn insert_controller(
     args: InsertArgs,
     local_data: Arc<Arc<Mutex<LocalContext>>>,
@@ -283,7 +1000,6 @@ pub async fn insert_controller(
     actor: DataExecutor,
 ) -> Result<TxResponse, Error> {
     let datetime = tx_time(&args.content)?;
-    let mut offset = bytes_counter.load(Ordering::SeqCst);
     let encrypted_content = actor
         .send(EncryptContent::new(
             &args.entity,
@@ -308,7 +1024,18 @@ pub async fn insert_controller(
         }
     }
 
+    let uuid = args.uuid.unwrap_or_else(Uuid::new_v4);
     let uniqueness = uniqueness.into_inner();
+    if args.dry_run {
+        actor
+            .send(VerifyUniqueKeys {
+                entity: args.entity.to_owned(),
+                content: encrypted_content,
+                uniqueness,
+            })
+            .await??;
+        return Ok(DryRunResponse::new(args.entity, vec![uuid], TxType::Insert).into());
+    }
     actor
         .send(CheckForUniqueKeys::new(
             args.entity.to_owned(),
@@ -321,20 +1048,20 @@ pub async fn insert_controller(
         .send(InsertEntityContent::new(
             &args.entity,
             &content_log,
-            args.uuid,
+            Some(uuid),
             datetime,
         ))
         .await??;
 
     if content_value.3 {
         bytes_counter.store(0, Ordering::SeqCst);
-        offset = 0;
     }
 
     let local_data_register = DataRegister {
-        offset,
+        offset: content_value.4,
         bytes_length: content_value.2,
-        file_name: content_value.0.format("data/%Y_%m_%d.log").to_string(),
+        file_name: date_log(&args.entity, &content_value.0),
+        checksum: Some(content_value.5),
     };
 
     let local_data = {
@@ -364,6 +1091,7 @@ pub async fn insert_controller(
     Ok(InsertEntityResponse::new(args.entity, content_value.1, message).into())
 }
 
+/// Merges `args.content` into the state of the entity id.
 pub async fn update_set_controller(
     args: UpdateArgs,
     local_data: Arc<Arc<Mutex<LocalContext>>>,
@@ -373,8 +1101,61 @@ pub async fn update_set_controller(
     hashing_cost: DataU32,
     actor: DataExecutor,
 ) -> Result<TxResponse, Error> {
+    set_state_controller(
+        TxType::UpdateSet,
+        args,
+        local_data,
+        bytes_counter,
+        uniqueness,
+        encryption,
+        hashing_cost,
+        actor,
+    )
+    .await
+}
+
+/// Sets the state of the entity id to exactly `args.content`, unlike `UPDATE SET` keys
+/// absent from it are dropped.
+pub async fn replace_controller(
+    args: UpdateArgs,
+    local_data: Arc<Arc<Mutex<LocalContext>>>,
+    bytes_counter: DataAtomicUsize,
+    uniqueness: DataUniquenessContext,
+    encryption: DataEncryptContext,
+    hashing_cost: DataU32,
+    actor: DataExecutor,
+) -> Result<TxResponse, Error> {
+    set_state_controller(
+        TxType::Replace,
+        args,
+        local_data,
+        bytes_counter,
+        uniqueness,
+        encryption,
+        hashing_cost,
+        actor,
+    )
+    .await
+}
+
+/// Encrypts the content, registers its unique values, logs it and swaps the registry of the
+/// entity id, `TxType::Replace` replaces the state by the content and `TxType::UpdateSet`
+/// merges it. `IF VERSION` is checked before anything is written and again in the lock that
+/// swaps the registry, the write lock held by `wql_handler` keeps the registry between both
+/// checks.
+#[allow(clippy::too_many_arguments)]
+async fn set_state_controller(
+    tx_type: TxType,
+    args: UpdateArgs,
+    local_data: Arc<Arc<Mutex<LocalContext>>>,
+    bytes_counter: DataAtomicUsize,
+    uniqueness: DataUniquenessContext,
+    encryption: DataEncryptContext,
+    hashing_cost: DataU32,
+    actor: DataExecutor,
+) -> Result<TxResponse, Error> {
+    let replace = matches!(tx_type, TxType::Replace);
     let datetime = tx_time(&args.content)?;
-    let mut offset = bytes_counter.load(Ordering::SeqCst);
     let encrypted_content = actor
         .send(EncryptContent::new(
             &args.entity,
@@ -387,22 +1168,41 @@ pub async fn update_set_controller(
     let content_log = to_string_pretty(&encrypted_content, pretty_config_inner())
         .map_err(Error::Serialization)?;
 
-    {
+    let previous_entry = {
         let local_data = if let Ok(guard) = local_data.lock() {
             guard
         } else {
             return Err(Error::LockData);
         };
-        if !local_data.contains_key(&args.entity) {
+        let previous_entry = if let Some(id_to_registry) = local_data.get(&args.entity) {
+            if let Some(entry) = id_to_registry.get(&args.id) {
+                entry
+            } else {
+                return Err(Error::UuidNotCreatedForEntity(args.entity, args.id));
+            }
+        } else {
             return Err(Error::EntityNotCreated(args.entity));
-        } else if local_data.contains_key(&args.entity)
-            && !local_data.get(&args.entity).unwrap().contains_key(&args.id)
-        {
-            return Err(Error::UuidNotCreatedForEntity(args.entity, args.id));
-        }
-    }
+        };
+        check_version(
+            &args.entity,
+            &args.id,
+            args.version.as_ref(),
+            &previous_entry.0,
+        )?;
+        previous_entry.clone()
+    };
 
     let uniqueness = uniqueness.into_inner();
+    if args.dry_run {
+        actor
+            .send(VerifyUniqueKeys {
+                entity: args.entity.to_owned(),
+                content: encrypted_content,
+                uniqueness,
+            })
+            .await??;
+        return Ok(DryRunResponse::new(args.entity, vec![args.id], tx_type).into());
+    }
     actor
         .send(CheckForUniqueKeys {
             entity: args.entity.to_owned(),
@@ -411,48 +1211,40 @@ pub async fn update_set_controller(
         })
         .await??;
 
-    let previous_entry = {
-        let local_data = if let Ok(guard) = local_data.lock() {
-            guard
-        } else {
-            return Err(Error::LockData);
-        };
-        let previous_entry = local_data.get(&args.entity).unwrap().get(&args.id).unwrap();
-        previous_entry.clone()
+    let state = if replace {
+        encrypted_content
+    } else {
+        let mut previous_state: HashMap<String, Types> =
+            bincode::deserialize(&previous_entry.1).unwrap();
+        previous_state.extend(encrypted_content);
+        previous_state
     };
-
-    let mut previous_state: HashMap<String, Types> =
-        bincode::deserialize(&previous_entry.1.clone()).unwrap();
-    let encrypted_content_clone = encrypted_content.clone();
-    encrypted_content.into_iter().for_each(|(k, v)| {
-        let local_state = previous_state.entry(k).or_insert_with(|| v.clone());
-        *local_state = v;
-    });
-
     let state_log =
-        to_string_pretty(&previous_state, pretty_config_inner()).map_err(Error::Serialization)?;
+        to_string_pretty(&state, pretty_config_inner()).map_err(Error::Serialization)?;
 
-    let content_value = actor
-        .send(UpdateSetEntityContent::new(
-            &args.entity,
-            &state_log,
-            &content_log,
-            args.id,
-            datetime,
-            &to_string_pretty(&previous_entry, pretty_config_inner())
-                .map_err(Error::Serialization)?,
-        ))
-        .await??;
+    let content = UpdateSetEntityContent::new(
+        &args.entity,
+        &state_log,
+        &content_log,
+        args.id,
+        datetime,
+        &to_string_pretty(&previous_entry, pretty_config_inner()).map_err(Error::Serialization)?,
+    );
+    let content_value = if replace {
+        actor.send(ReplaceEntityContent(content)).await??
+    } else {
+        actor.send(content).await??
+    };
 
     if content_value.2 {
         bytes_counter.store(0, Ordering::SeqCst);
-        offset = 0;
     }
 
     let local_data_register = DataRegister {
-        offset,
+        offset: content_value.3,
         bytes_length: content_value.1,
-        file_name: content_value.0.format("data/%Y_%m_%d.log").to_string(),
+        file_name: date_log(&args.entity, &content_value.0),
+        checksum: Some(content_value.4),
     };
 
     let local_data = {
@@ -463,7 +1255,8 @@ pub async fn update_set_controller(
         };
         if let Some(map) = local_data.get_mut(&args.entity) {
             if let Some(reg) = map.get_mut(&args.id) {
-                let encoded: Vec<u8> = bincode::serialize(&encrypted_content_clone).unwrap();
+                check_version(&args.entity, &args.id, args.version.as_ref(), &reg.0)?;
+                let encoded: Vec<u8> = bincode::serialize(&state).unwrap();
                 *reg = (local_data_register, encoded);
             }
         }
@@ -475,11 +1268,40 @@ pub async fn update_set_controller(
     actor
         .send(OffsetCounter::new(bytes_counter.load(Ordering::SeqCst)))
         .await??;
-    let message = format!("Entity {} with Uuid {} updated", &args.entity, &args.id);
-    Ok(
-        UpdateEntityResponse::new(args.entity, args.id, state_log, message, TxType::UpdateSet)
-            .into(),
-    )
+    let action = if replace { "replaced" } else { "updated" };
+    let message = format!("Entity {} with Uuid {} {}", &args.entity, &args.id, action);
+    Ok(UpdateEntityResponse::new(args.entity, args.id, state_log, message, tx_type).into())
+}
+
+/// Values of the `MATCH UPDATE` expressions over the current state of the entity id.
+pub(crate) fn evaluate_expressions(
+    expressions: &HashMap<String, Expression>,
+    state: &HashMap<String, Types>,
+) -> Result<HashMap<String, Types>, Error> {
+    expressions
+        .iter()
+        .map(|(key, expression)| {
+            expression
+                .evaluate(state)
+                .map(|value| (key.to_owned(), value))
+                .map_err(Error::InvalidExpression)
+        })
+        .collect()
+}
+
+/// `IF VERSION` of `UPDATE SET` must be the version of the current registry of the entity.
+pub(crate) fn check_version(
+    entity: &str,
+    id: &Uuid,
+    version: Option<&String>,
+    register: &DataRegister,
+) -> Result<(), Error> {
+    match version {
+        Some(version) if version != &register.version() => {
+            Err(Error::VersionConflict(entity.to_owned(), id.to_owned()))
+        }
+        _ => Ok(()),
+    }
 }
 
 pub async fn update_content_controller(
@@ -491,7 +1313,6 @@ pub async fn update_content_controller(
     actor: DataExecutor,
 ) -> Result<TxResponse, Error> {
     let datetime = tx_time(&args.content)?;
-    let mut offset = bytes_counter.load(Ordering::SeqCst);
     if let Ok(guard) = encryption.lock() {
         if guard.contains_key(&args.entity) {
             let keys = args
@@ -526,6 +1347,16 @@ pub async fn update_content_controller(
     }
 
     let uniqueness = uniqueness.into_inner();
+    if args.dry_run {
+        actor
+            .send(VerifyUniqueKeys {
+                entity: args.entity.to_owned(),
+                content,
+                uniqueness,
+            })
+            .await??;
+        return Ok(DryRunResponse::new(args.entity, vec![args.id], TxType::UpdateContent).into());
+    }
     actor
         .send(CheckForUniqueKeys {
             entity: args.entity.to_owned(),
@@ -549,7 +1380,7 @@ pub async fn update_content_controller(
 
     content
         .into_iter()
-        .for_each(|(k, v)| update_content_state(&mut previous_state, k, v));
+        .try_for_each(|(k, v)| update_content_state(&mut previous_state, k, v))?;
 
     let state_log =
         to_string_pretty(&previous_state, pretty_config_inner()).map_err(Error::Serialization)?;
@@ -567,12 +1398,12 @@ pub async fn update_content_controller(
 
     if content_value.2 {
         bytes_counter.store(0, Ordering::SeqCst);
-        offset = 0;
     }
     let local_data_register = DataRegister {
-        offset,
+        offset: content_value.3,
         bytes_length: content_value.1,
-        file_name: content_value.0.format("data/%Y_%m_%d.log").to_string(),
+        file_name: date_log(&args.entity, &content_value.0),
+        checksum: Some(content_value.4),
     };
     let local_data = {
         let mut local_data = if let Ok(guard) = local_data.lock() {
@@ -612,10 +1443,10 @@ pub async fn delete_controller(
     local_data: Arc<Arc<Mutex<LocalContext>>>,
     bytes_counter: DataAtomicUsize,
     actor: DataExecutor,
+    dry_run: bool,
 ) -> Result<TxResponse, Error> {
     let uuid = Uuid::from_str(&id)?;
     let message = format!("Entity {} with Uuid {} deleted", &entity, id);
-    let mut offset = bytes_counter.load(Ordering::SeqCst);
 
     let previous_entry = {
         let local_data = if let Ok(guard) = local_data.lock() {
@@ -630,6 +1461,9 @@ pub async fn delete_controller(
         {
             return Err(Error::UuidNotCreatedForEntity(entity, uuid));
         }
+        if dry_run {
+            return Ok(DryRunResponse::new(entity, vec![uuid], TxType::Delete).into());
+        }
 
         let previous_entry = local_data.get(&entity).unwrap().get(&uuid).unwrap();
         previous_entry.clone().0
@@ -668,12 +1502,12 @@ pub async fn delete_controller(
 
     if content_value.2 {
         bytes_counter.store(0, Ordering::SeqCst);
-        offset = 0;
     }
     let local_data_register = DataRegister {
-        offset,
+        offset: content_value.3,
         bytes_length: content_value.1,
-        file_name: content_value.0.format("data/%Y_%m_%d.log").to_string(),
+        file_name: date_log(&entity, &content_value.0),
+        checksum: Some(content_value.4),
     };
 
     let local_data = {
@@ -698,11 +1532,17 @@ pub async fn delete_controller(
         .send(OffsetCounter::new(bytes_counter.load(Ordering::SeqCst)))
         .await??;
 
-    Ok(DeleteOrEvictEntityResponse::new(entity, Some(uuid), message, TxType::Delete).into())
+    Ok(
+        DeleteOrEvictEntityResponse::new(entity, Some(uuid), message, TxType::Delete, vec![uuid])
+            .into(),
+    )
 }
 
+/// The conditions are checked and the expressions evaluated against the registry read
+/// in the same lock section, and the write lock held by `wql_handler` keeps that registry
+/// until it is swapped, so two `MATCH UPDATE`s can't both match the same state.
 pub async fn match_update_set_controller(
-    args: MatchUpdateArgs,
+    mut args: MatchUpdateArgs,
     local_data: Arc<Arc<Mutex<LocalContext>>>,
     bytes_counter: DataAtomicUsize,
     uniqueness: DataUniquenessContext,
@@ -711,7 +1551,7 @@ pub async fn match_update_set_controller(
     actor: DataExecutor,
 ) -> Result<TxResponse, Error> {
     let datetime = tx_time(&args.content)?;
-    let previous_entry = {
+    let (previous_entry, mut previous_state, computed) = {
         let local_data = if let Ok(guard) = local_data.lock() {
             guard
         } else {
@@ -726,21 +1566,33 @@ pub async fn match_update_set_controller(
         }
 
         let previous_entry = local_data.get(&args.entity).unwrap().get(&args.id).unwrap();
-        previous_entry.clone()
+        let previous_state: HashMap<String, Types> =
+            bincode::deserialize(&previous_entry.1).unwrap();
+        let computed = match_conditions(&args.conditions, &previous_state)
+            .and_then(|()| evaluate_expressions(&args.expressions, &previous_state));
+        (previous_entry.clone(), previous_state, computed)
     };
 
-    let previous_entry: HashMap<String, Types> =
-        bincode::deserialize(&previous_entry.1.clone()).unwrap();
-    let mut previous_state = previous_entry.clone();
-
-    actor
-        .send(MatchUpdate {
-            conditions: args.conditions,
-            previous_state: previous_entry.clone(),
-        })
-        .await??;
-
-    let mut offset = bytes_counter.load(Ordering::SeqCst);
+    if args.dry_run {
+        return match computed {
+            Err(Error::FailedMatchCondition) => {
+                Ok(DryRunResponse::new(args.entity, Vec::new(), TxType::UpdateSet).into())
+            }
+            Err(e) => Err(e),
+            Ok(computed) => {
+                args.content.extend(computed);
+                actor
+                    .send(VerifyUniqueKeys {
+                        entity: args.entity.to_owned(),
+                        content: args.content,
+                        uniqueness: uniqueness.into_inner(),
+                    })
+                    .await??;
+                Ok(DryRunResponse::new(args.entity, vec![args.id], TxType::UpdateSet).into())
+            }
+        };
+    }
+    args.content.extend(computed?);
 
     let encrypted_content = actor
         .send(EncryptContent::new(
@@ -758,15 +1610,12 @@ pub async fn match_update_set_controller(
     actor
         .send(CheckForUniqueKeys {
             entity: args.entity.to_owned(),
-            content: args.content.to_owned(),
+            content: encrypted_content.to_owned(),
             uniqueness,
         })
         .await??;
 
-    args.content.into_iter().for_each(|(k, v)| {
-        let local_state = previous_state.entry(k).or_insert_with(|| v.clone());
-        *local_state = v;
-    });
+    previous_state.extend(encrypted_content.clone());
 
     let state_log =
         to_string_pretty(&previous_state, pretty_config_inner()).map_err(Error::Serialization)?;
@@ -785,12 +1634,12 @@ pub async fn match_update_set_controller(
 
     if content_value.2 {
         bytes_counter.store(0, Ordering::SeqCst);
-        offset = 0;
     }
     let local_data_register = DataRegister {
-        offset,
+        offset: content_value.3,
         bytes_length: content_value.1,
-        file_name: content_value.0.format("data/%Y_%m_%d.log").to_string(),
+        file_name: date_log(&args.entity, &content_value.0),
+        checksum: Some(content_value.4),
     };
 
     let local_data = {
@@ -801,6 +1650,10 @@ pub async fn match_update_set_controller(
         };
         if let Some(map) = local_data.get_mut(&args.entity) {
             if let Some(reg) = map.get_mut(&args.id) {
+                // The conditions matched this registry, any other write must fail the match.
+                if reg.0 != previous_entry.0 {
+                    return Err(Error::FailedMatchCondition);
+                }
                 let encoded: Vec<u8> = bincode::serialize(&encrypted_content).unwrap();
                 *reg = (local_data_register, encoded);
             }
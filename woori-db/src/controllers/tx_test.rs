@@ -1,7 +1,7 @@
 use crate::io::read;
 use crate::{http::routes, schemas::tx::TxResponse};
 use actix_http::body::ResponseBody;
-use actix_web::{body::Body, test, App};
+use actix_web::{body::Body, dev::Service, test, App};
 use uuid::Uuid;
 
 #[actix_rt::test]
@@ -19,7 +19,7 @@ async fn test_create_post_ok() {
     let body = body.as_ref().unwrap();
     assert_eq!(
         &Body::from(
-            "(\n tx_type: Create,\n entity: \"crete_test_ok\",\n uuid: None,\n state: \"\",\n message: \"Entity `crete_test_ok` created\",\n)"
+            "(\n tx_type: Create,\n entity: \"crete_test_ok\",\n uuid: None,\n state: \"\",\n message: \"Entity `crete_test_ok` created\",\n affected: 0,\n affected_ids: [],\n)"
         ),
         body
     );
@@ -337,6 +337,124 @@ async fn test_update_set_post_ok() {
     clear();
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_replace_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_replace")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_replace")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let payload = format!("REPLACE test_replace {{a: 12,}} INTO {}", uuid);
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(resp.status().is_success());
+    assert!(body.contains("tx_type: Replace"));
+    read::assert_content("REPLACE|");
+    read::assert_content("|test_replace|");
+
+    let payload = format!("SELECT * FROM test_replace ID {}", uuid);
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"a\": Integer(12)"));
+    assert!(!body.contains("\"b\""));
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_update_set_if_version_post() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_update_version")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_update_version")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "SELECT * FROM test_update_version ID {} WITH VERSION",
+            uuid
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let version = body
+        .split("\"_version\": String(\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_string();
+
+    let payload = format!(
+        "UPDATE test_update_version SET {{a: 12,}} INTO {} IF VERSION {}",
+        uuid, version
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload.clone())
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status().as_u16(), 412);
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("VersionConflict"));
+    clear();
+}
+
 #[ignore]
 #[actix_rt::test]
 async fn test_update_uniqueness_set_post_ok() {
@@ -633,7 +751,7 @@ async fn test_delete_without_update() {
 
     let mut resp = test::call_service(&mut app, req).await;
     let body = resp.take_body().as_str().to_string();
-    assert_eq!(body, format!("(\n tx_type: Delete,\n entity: \"test_delete\",\n uuid: Some(\"{}\"),\n state: \"\",\n message: \"Entity test_delete with Uuid {} deleted\",\n)", uuid.unwrap(), uuid.unwrap()));
+    assert_eq!(body, format!("(\n tx_type: Delete,\n entity: \"test_delete\",\n uuid: Some(\"{}\"),\n state: \"\",\n message: \"Entity test_delete with Uuid {} deleted\",\n affected: 1,\n affected_ids: [\n  \"{}\",\n ],\n)", uuid.unwrap(), uuid.unwrap(), uuid.unwrap()));
 
     assert!(resp.status().is_success());
 
@@ -840,6 +958,66 @@ async fn test_match_all_update_fake_key() {
     clear();
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_match_update_expression_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_match_expression")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {balance: 250, name: \"julia\",} INTO test_match_expression")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    for (payload, success) in &[
+        ("SET {balance: balance - 100,}", true),
+        ("SET {balance: name - 100,}", false),
+        ("SET {balance: balance / 0,}", false),
+    ] {
+        let payload = format!(
+            "MATCH ALL(balance >= 100) UPDATE test_match_expression {} INTO {}",
+            payload, uuid
+        );
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload)
+            .uri("/wql/tx")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        if *success {
+            assert!(resp.status().is_success());
+        } else {
+            assert!(resp.status().is_client_error());
+            let body = resp.take_body().as_str().to_string();
+            assert!(body.contains("InvalidExpression"));
+        }
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("SELECT * FROM test_match_expression ID {}", uuid))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"balance\": Integer(150)"));
+    clear();
+}
+
 #[actix_rt::test]
 async fn test_evict_entity_post_ok() {
     let mut app = test::init_service(App::new().configure(routes)).await;
@@ -929,6 +1107,207 @@ async fn test_evict_entity_id_post_ok() {
     clear();
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_clear_entity_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_clear UNIQUES #{a,}")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    for payload in &[
+        "INSERT {a: 123, b: 12.3,} INTO test_clear",
+        "INSERT {a: 321, b: 32.1,} INTO test_clear",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CLEAR ENTITY test_clear")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.affected, 2);
+    assert_eq!(response.affected_ids.len(), 2);
+    read::assert_content("CLEAR_ENTITY|");
+    read::assert_content("|test_clear;");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_clear")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(resp.status().is_success());
+    assert_eq!(body, "{}");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_clear")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_reindex_entity_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_reindex UNIQUES #{a,}")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 12.3,} INTO test_reindex")
+        .uri("/wql/tx")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+
+    let payload = format!(
+        "UPDATE test_reindex SET {{a: 321,}} INTO {}",
+        response.uuid.unwrap()
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("REINDEX ENTITY test_reindex")
+        .uri("/wql/tx")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("tx_type: ReindexEntity"));
+    assert!(body.contains("Entity test_reindex reindexed with 1 unique values from 1 ids"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123, b: 32.1,} INTO test_reindex")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 321, b: 32.1,} INTO test_reindex")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("REINDEX ENTITY test_reindex_missing")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_insert_with_next_post_ok() {
+    use crate::actors::sequences::sequence_id;
+
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_insert_next")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let mut numbers = Vec::new();
+    for payload in &[
+        "INSERT {a: 1,} INTO test_insert_next WITH NEXT",
+        "DRY RUN INSERT {a: 2,} INTO test_insert_next WITH NEXT",
+        "INSERT {a: 2,} INTO test_insert_next WITH NEXT",
+        "BEGIN INSERT {a: 3,} INTO test_insert_next WITH NEXT; INSERT {a: 4,} INTO test_insert_next WITH NEXT; COMMIT",
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(*payload)
+            .uri("/wql/tx")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        let body = resp.take_body().as_str().to_string();
+        let response: TxResponse = ron::de::from_str(&body).unwrap();
+        numbers.push(response.affected_ids);
+    }
+
+    assert_eq!(numbers[0], vec![sequence_id(1)]);
+    assert_eq!(numbers[1], vec![sequence_id(2)]);
+    assert_eq!(numbers[2], vec![sequence_id(2)]);
+    assert_eq!(numbers[3], vec![sequence_id(3), sequence_id(4)]);
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 5,} INTO test_insert_next WITH NEXT")
+        .uri("/wql/tx")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.uuid, Some(sequence_id(5)));
+    assert_eq!(response.number, Some(5));
+    assert!(body.contains("number: Some(5)"));
+    assert_eq!(read::sequences().unwrap().get("test_insert_next"), Some(&5));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "Select * FROM test_insert_next ID {}",
+            sequence_id(3)
+        ))
+        .uri("/wql/query")
+        .to_request();
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"a\": Integer(3)"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 1,} INTO test_insert_next_missing WITH NEXT")
+        .uri("/wql/tx")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    clear();
+}
+
 #[actix_rt::test]
 async fn test_insert_encrypt_post_ok() {
     let mut app = test::init_service(App::new().configure(routes)).await;
@@ -1065,6 +1444,753 @@ impl BodyTest for ResponseBody<Body> {
     }
 }
 
+#[ignore]
+#[actix_rt::test]
+async fn test_insert_with_idempotency_key_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_idempotency")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .header("Idempotency-Key", "insert-a-123")
+        .set_payload("INSERT {a: 123,} INTO test_idempotency")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let first = resp.take_body().as_str().to_string();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .header("Idempotency-Key", "insert-a-123")
+        .set_payload("INSERT {a: 123,} INTO test_idempotency")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let second = resp.take_body().as_str().to_string();
+    assert_eq!(first, second);
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_idempotency")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body.matches("\"a\": Integer(123)").count(), 1);
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_concurrent_idempotency_key_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_concurrent_idempotency")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let insert = || {
+        test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .header("Idempotency-Key", "insert-a-123")
+            .set_payload("INSERT {a: 123,} INTO test_concurrent_idempotency")
+            .uri("/wql/tx")
+            .to_request()
+    };
+    let first = app.call(insert());
+    let second = app.call(insert());
+    let (first, second) = futures::future::join(first, second).await;
+    let mut first = first.unwrap();
+    let mut second = second.unwrap();
+    assert!(first.status().is_success());
+    assert!(second.status().is_success());
+    assert_eq!(
+        first.take_body().as_str().to_string(),
+        second.take_body().as_str().to_string()
+    );
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_concurrent_idempotency")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body.matches("\"a\": Integer(123)").count(), 1);
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_dry_run_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_dry_run")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123,} INTO test_dry_run")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let payload = format!(
+        "DRY RUN MATCH ALL(a > 100) UPDATE test_dry_run SET {{a: 43,}} INTO {}",
+        uuid
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.affected_ids, vec![uuid]);
+
+    let payload = format!(
+        "DRY RUN MATCH ALL(a < 100) UPDATE test_dry_run SET {{a: 43,}} INTO {}",
+        uuid
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.affected_ids, Vec::new());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("DRY RUN DELETE {} FROM test_dry_run", uuid))
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("DRY RUN INSERT {a: 43,} INTO test_dry_run")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.affected_ids.len(), 1);
+    assert_ne!(response.affected_ids[0], uuid);
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_dry_run")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"a\": Integer(123)"));
+    assert!(!body.contains("Integer(43)"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_insert_with_schema_post_err() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_schema {name: String, age: Integer,} EXHAUSTIVE")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"julia\", age: 34,} INTO test_schema")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"julia\", age: \"34\",} INTO test_schema")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "(\n error_type: \"SchemaTypeMismatch\",\n error_message: \"Key `age` of entity `test_schema` must be of type Integer, found String\",\n)");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"julia\", city: \"Porto Alegre\",} INTO test_schema")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("KeyNotInSchema"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_append_only_post_err() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_append_only APPEND ONLY")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 123,} INTO test_append_only")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp_insert = test::call_service(&mut app, req).await;
+    assert!(resp_insert.status().is_success());
+    let body = resp_insert.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    for payload in &[
+        format!("UPDATE test_append_only SET {{a: 12,}} INTO {}", uuid),
+        format!("UPDATE test_append_only CONTENT {{a: 12,}} INTO {}", uuid),
+        format!("DELETE {} FROM test_append_only", uuid),
+        format!("EVICT {} FROM test_append_only", uuid),
+        format!(
+            "BEGIN INSERT {{a: 1,}} INTO test_append_only; UPDATE test_append_only SET {{a: 12,}} INTO {} COMMIT",
+            uuid
+        ),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(payload.to_owned())
+            .uri("/wql/tx")
+            .to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_client_error());
+        let body = resp.take_body().as_str().to_string();
+        assert_eq!(body, "(\n error_type: \"ImmutableEntity\",\n error_message: \"Entity test_append_only is APPEND ONLY, its ids can only be inserted\",\n)");
+    }
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {a: 12,} INTO test_append_only")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_insert_with_schema_defaults_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(
+            "CREATE ENTITY test_schema_defaults {name: String, age: Integer DEFAULT 18, score: Float DEFAULT,}",
+        )
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"julia\", score: Nil,} INTO test_schema_defaults")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("SELECT * FROM test_schema_defaults ID {}", uuid))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"age\": Integer(18)"));
+    assert!(body.contains("\"score\": Nil"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"maria\", age: 40,} INTO test_schema_defaults")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("SELECT * FROM test_schema_defaults ID {}", uuid))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"age\": Integer(40)"));
+    assert!(body.contains("\"score\": Float(0.0)"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_insert_lenient_boolean_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_lenient_boolean {active: Boolean, verified: Boolean,}")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {active: \"Yes\", verified: 0,} INTO test_lenient_boolean")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let uuid = response.uuid.unwrap();
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("SELECT * FROM test_lenient_boolean ID {}", uuid))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"active\": Boolean(true)"));
+    assert!(body.contains("\"verified\": Boolean(false)"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {active: \"maybe\",} INTO test_lenient_boolean")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("InvalidBoolean"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_transaction_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_transaction")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+    let from = Uuid::new_v4();
+    let to = Uuid::new_v4();
+    let payload = format!(
+        "BEGIN INSERT {{balance: 100,}} INTO test_transaction WITH {}; INSERT {{balance: 0,}} INTO test_transaction WITH {} COMMIT",
+        from, to
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let payload = format!(
+        "BEGIN MATCH ALL(balance >= 30) UPDATE test_transaction SET {{balance: 70,}} INTO {}; UPDATE test_transaction SET {{balance: 30,}} INTO {} COMMIT",
+        from, to
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.statements.map(|s| s.len()), Some(2));
+    assert_eq!(response.affected, 2);
+    assert_eq!(response.affected_ids, vec![from, to]);
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "SELECT * FROM test_transaction IDS IN #{{{}, {},}}",
+            from, to
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"balance\": Integer(70)"));
+    assert!(body.contains("\"balance\": Integer(30)"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_transaction_post_err() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_transaction_err")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+    let from = Uuid::new_v4();
+    let to = Uuid::new_v4();
+    let payload = format!(
+        "BEGIN INSERT {{balance: 100,}} INTO test_transaction_err WITH {}; INSERT {{balance: 0,}} INTO test_transaction_err WITH {} COMMIT",
+        from, to
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+
+    let payload = format!(
+        "BEGIN UPDATE test_transaction_err SET {{balance: 30,}} INTO {}; MATCH ALL(balance >= 30) UPDATE test_transaction_err SET {{balance: 0,}} INTO {} COMMIT",
+        to, to
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let payload = format!(
+        "BEGIN UPDATE test_transaction_err SET {{balance: 0,}} INTO {}; MATCH ALL(balance >= 1000) UPDATE test_transaction_err SET {{balance: 1000,}} INTO {} COMMIT",
+        to, from
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("FailedMatchCondition"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "SELECT * FROM test_transaction_err IDS IN #{{{}, {},}}",
+            from, to
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"balance\": Integer(100)"));
+    assert!(!body.contains("\"balance\": Integer(1000)"));
+    assert!(body.contains("\"balance\": Integer(0)"));
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_transaction_with_encrypts_and_uniques_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_transaction_encrypts ENCRYPT #{pswd,} UNIQUES #{name,}")
+        .uri("/wql/tx")
+        .to_request();
+
+    let _ = test::call_service(&mut app, req).await;
+    let id = Uuid::new_v4();
+    let payload = format!(
+        "BEGIN INSERT {{name: \"julia\", pswd: \"my-password\", count: 1,}} INTO test_transaction_encrypts WITH {}; UPDATE test_transaction_encrypts CONTENT {{count: 2,}} INTO {} COMMIT",
+        id, id
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let payload = format!(
+        "BEGIN INSERT {{name: \"naomi\", count: 1,}} INTO test_transaction_encrypts; MATCH ALL(count >= 100) UPDATE test_transaction_encrypts SET {{count: 0,}} INTO {} COMMIT",
+        id
+    );
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(payload)
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("INSERT {name: \"naomi\", count: 1,} INTO test_transaction_encrypts")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!(
+            "SELECT #{{name, count,}} FROM test_transaction_encrypts ID {}",
+            id
+        ))
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("\"count\": Integer(3)"));
+    read::assert_not_content("Integer(0)");
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_validate_schema_violations_post_err() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_validate {name: String, age: Integer,} EXHAUSTIVE")
+        .uri("/wql/tx")
+        .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let batch = "BEGIN INSERT {name: \"julia\", age: \"34\",} INTO test_validate; INSERT {name: \"otavio\", age: 29,} INTO test_validate; INSERT {name: \"naomi\", city: \"Porto Alegre\",} INTO test_validate; COMMIT";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("VALIDATE {}", batch))
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "(\n error_type: \"SchemaViolations\",\n error_message: \"2 statements do not match the schemas of their entities\",\n violations: [\n  (\n   statement: 0,\n   error_type: \"SchemaTypeMismatch\",\n   error_message: \"Key `age` of entity `test_validate` must be of type Integer, found String\",\n  ),\n  (\n   statement: 2,\n   error_type: \"KeyNotInSchema\",\n   error_message: \"Key `city` is not declared in the EXHAUSTIVE schema of entity `test_validate`\",\n  ),\n ],\n)");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(batch)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_client_error());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("statement: 0"));
+    assert!(body.contains("statement: 2"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("VALIDATE INSERT {name: \"otavio\", age: 29,} INTO test_validate")
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("tx_type: Validate"));
+    assert!(body.contains("1 statements match the schemas of their entities"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_validate")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert_eq!(body, "{}");
+
+    clear();
+}
+
+#[ignore]
+#[actix_rt::test]
+async fn test_delete_where_post_ok() {
+    let mut app = test::init_service(App::new().configure(routes)).await;
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("CREATE ENTITY test_delete_where")
+        .uri("/wql/tx")
+        .to_request();
+    let _ = test::call_service(&mut app, req).await;
+
+    let expired = [Uuid::new_v4(), Uuid::new_v4()];
+    let active = Uuid::new_v4();
+    for (id, status) in &[
+        (expired[0], "expired"),
+        (expired[1], "expired"),
+        (active, "active"),
+    ] {
+        let req = test::TestRequest::post()
+            .header("Content-Type", "application/wql")
+            .set_payload(format!(
+                "INSERT {{status: \"{}\",}} INTO test_delete_where WITH {}",
+                status, id
+            ))
+            .uri("/wql/tx")
+            .to_request();
+        let _ = test::call_service(&mut app, req).await;
+    }
+
+    let delete = "DELETE FROM test_delete_where WHERE { ?* test_delete_where:status \"expired\", }";
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(format!("DRY RUN {}", delete))
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains("affected: 2"));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(delete)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    let mut affected_ids = response.affected_ids;
+    affected_ids.sort();
+    let mut expected = expired.to_vec();
+    expected.sort();
+    assert_eq!(response.affected, 2);
+    assert_eq!(affected_ids, expected);
+    read::assert_content("EVICT_ENTITY_ID|");
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload("SELECT * FROM test_delete_where")
+        .uri("/wql/query")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    let body = resp.take_body().as_str().to_string();
+    assert!(body.contains(&active.to_string()));
+    assert!(!body.contains(&expired[0].to_string()));
+    assert!(!body.contains(&expired[1].to_string()));
+
+    let req = test::TestRequest::post()
+        .header("Content-Type", "application/wql")
+        .set_payload(delete)
+        .uri("/wql/tx")
+        .to_request();
+
+    let mut resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let body = resp.take_body().as_str().to_string();
+    let response: TxResponse = ron::de::from_str(&body).unwrap();
+    assert_eq!(response.affected, 0);
+
+    clear();
+}
+
 pub fn clear() {
     std::process::Command::new("rm")
         .arg("-rf")
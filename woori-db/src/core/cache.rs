@@ -0,0 +1,241 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use wql::{Clause, Function, Wql};
+
+use crate::schemas::query::Response as QueryResponse;
+
+const DEFAULT_CACHE_SIZE: usize = 1024;
+
+/// Responses of `SELECT`s over one entity, served again until the entity is written. Writes
+/// bump the version of their entity and drop only its cached responses.
+#[derive(Debug)]
+pub struct QueryCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    versions: HashMap<String, u64>,
+    entries: HashMap<String, CachedQuery>,
+    /// Keys in insertion order, the oldest entry is dropped when the cache is full.
+    order: VecDeque<String>,
+}
+
+#[derive(Debug)]
+struct CachedQuery {
+    entity: String,
+    query: Wql,
+    response: QueryResponse,
+}
+
+/// Normalized query and the version of its entity when the query started.
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    entity: String,
+    key: String,
+    version: u64,
+}
+
+impl QueryCache {
+    /// `capacity` is the number of cached responses, `0` disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Reads `QUERY_CACHE_SIZE`, defaults to 1024 responses.
+    pub fn from_env() -> Self {
+        let capacity = std::env::var("QUERY_CACHE_SIZE")
+            .ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_CACHE_SIZE);
+
+        Self::new(capacity)
+    }
+
+    fn state(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Key of `query`, `None` if the cache is disabled, the query is not a `SELECT` of the
+    /// current state of an entity or its result can change without a write to the entity.
+    /// Maps of the query are sorted, so the same query always has the same key.
+    pub fn key(&self, query: &Wql) -> Option<CacheKey> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let entity = match query {
+            Wql::SelectWhere(_, _, clauses, _) if clauses.iter().any(is_volatile) => return None,
+            Wql::Select(entity, ..) | Wql::SelectIds(entity, ..) | Wql::SelectWhere(entity, ..) => {
+                entity
+            }
+            _ => return None,
+        };
+        let key = serde_json::to_value(query)
+            .and_then(|value| serde_json::to_string(&value))
+            .ok()?;
+        let version = self.state().versions.get(entity).copied().unwrap_or(0);
+
+        Some(CacheKey {
+            entity: entity.to_owned(),
+            key,
+            version,
+        })
+    }
+
+    /// Cached response of `query`, compared with the cached query in case their keys match
+    /// but the queries don't, like floats that are not numbers.
+    pub fn get(&self, key: &CacheKey, query: &Wql) -> Option<QueryResponse> {
+        let state = self.state();
+        state
+            .entries
+            .get(&key.key)
+            .filter(|cached| &cached.query == query)
+            .map(|cached| cached.response.clone())
+    }
+
+    /// Caches `response` unless its entity was written since `key` was taken.
+    pub fn insert(&self, key: CacheKey, query: Wql, response: QueryResponse) {
+        let mut state = self.state();
+        if state.versions.get(&key.entity).copied().unwrap_or(0) != key.version {
+            return;
+        }
+        let cached = CachedQuery {
+            entity: key.entity,
+            query,
+            response,
+        };
+        if state.entries.insert(key.key.clone(), cached).is_none() {
+            state.order.push_back(key.key);
+        }
+        while state.entries.len() > self.capacity {
+            match state.order.pop_front() {
+                Some(oldest) => {
+                    state.entries.remove(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Drops the cached responses of `entity`, called after each transaction on it.
+    pub fn invalidate(&self, entity: &str) {
+        let mut state = self.state();
+        *state.versions.entry(entity.to_owned()).or_insert(0) += 1;
+        let cached = state.entries.len();
+        state.entries.retain(|_, cached| cached.entity != entity);
+        if state.entries.len() != cached {
+            let CacheState { entries, order, .. } = &mut *state;
+            order.retain(|key| entries.contains_key(key));
+        }
+    }
+}
+
+/// Clauses that depend on the clock or on other entities, the version of the queried
+/// entity doesn't tell when their result changes.
+fn is_volatile(clause: &Clause) -> bool {
+    match clause {
+        Clause::Predicate(Function::WithinLast(..))
+        | Clause::Predicate(Function::ComparedToNow(..))
+        | Clause::SubqueryIn(..) => true,
+        Clause::Or(_, clauses) => clauses.iter().any(is_volatile),
+        Clause::Not(clause) => is_volatile(clause),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn response(value: bool) -> QueryResponse {
+        QueryResponse::from(value)
+    }
+
+    fn is_cached(cache: &QueryCache, query: &str, expected: bool) -> bool {
+        let query = Wql::from_str(query).unwrap();
+        let key = cache.key(&query).unwrap();
+        match cache.get(&key, &query) {
+            Some(QueryResponse::Exists(value)) => value == expected,
+            _ => false,
+        }
+    }
+
+    fn cache(cache: &QueryCache, query: &str, value: bool) {
+        let query = Wql::from_str(query).unwrap();
+        let key = cache.key(&query).unwrap();
+        cache.insert(key, query, response(value));
+    }
+
+    #[test]
+    fn invalidates_only_written_entity() {
+        let queries = QueryCache::new(8);
+        cache(&queries, "SELECT * FROM a ORDER BY x :asc LIMIT 3", true);
+        cache(&queries, "SELECT * FROM b", false);
+        assert!(is_cached(
+            &queries,
+            "SELECT * FROM a LIMIT 3 ORDER BY x :asc",
+            true
+        ));
+        assert!(is_cached(&queries, "SELECT * FROM b", false));
+
+        queries.invalidate("a");
+        assert!(!is_cached(
+            &queries,
+            "SELECT * FROM a ORDER BY x :asc LIMIT 3",
+            true
+        ));
+        assert!(is_cached(&queries, "SELECT * FROM b", false));
+    }
+
+    #[test]
+    fn skips_responses_older_than_writes() {
+        let queries = QueryCache::new(8);
+        let query = Wql::from_str("SELECT * FROM a").unwrap();
+        let key = queries.key(&query).unwrap();
+        queries.invalidate("a");
+        queries.insert(key, query, response(true));
+        assert!(!is_cached(&queries, "SELECT * FROM a", true));
+    }
+
+    #[test]
+    fn bounded_and_selects_only() {
+        let queries = QueryCache::new(2);
+        cache(&queries, "SELECT * FROM a", true);
+        cache(&queries, "SELECT * FROM b", true);
+        cache(&queries, "SELECT * FROM c", true);
+        assert!(!is_cached(&queries, "SELECT * FROM a", true));
+        assert!(is_cached(&queries, "SELECT * FROM b", true));
+        assert!(is_cached(&queries, "SELECT * FROM c", true));
+
+        assert!(queries
+            .key(&Wql::from_str("DESCRIBE ENTITY a").unwrap())
+            .is_none());
+        assert!(QueryCache::new(0)
+            .key(&Wql::from_str("SELECT * FROM a").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn skips_clock_and_subquery_clauses() {
+        let queries = QueryCache::new(8);
+        for query in &[
+            "SELECT * FROM a WHERE { ?* a:created ?created, (?created WITHIN LAST 1h), }",
+            "SELECT * FROM a WHERE { ?* a:id ?id, (in ?id (SELECT #{id,} FROM b)), }",
+            "SELECT * FROM a WHERE { ?* a:ts ?ts, (< ?ts now()), }",
+            "SELECT * FROM a WHERE { ?* a:created ?created, (or (?created WITHIN LAST 1h) (== ?created 1)), }",
+        ] {
+            assert!(queries.key(&Wql::from_str(query).unwrap()).is_none());
+        }
+        assert!(queries
+            .key(&Wql::from_str("SELECT * FROM a WHERE { ?* a:b ?b, (== ?b 1), }").unwrap())
+            .is_some());
+    }
+}
@@ -1,24 +1,206 @@
 extern crate wql as ewql;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, OnceLock},
+};
 
+use actix_web::{http::header, HttpRequest};
 use chrono::{DateTime, Utc};
-use ewql::Types;
+use ewql::{HashAlgorithm, Types};
 use ron::ser::PrettyConfig;
+use serde::Serialize;
 
 use crate::model::error::Error;
+use serializer::{CsvSerializer, JsonSerializer, ResponseSerializer, RonSerializer};
 
+pub(crate) mod cache;
 pub(crate) mod query;
 pub(crate) mod registry;
+pub(crate) mod serializer;
+pub(crate) mod sorted;
 pub(crate) mod wql;
 
+static OUTPUT_INDENT_WIDTH: OnceLock<usize> = OnceLock::new();
+
 pub fn pretty_config_output() -> PrettyConfig {
+    let width = *OUTPUT_INDENT_WIDTH.get_or_init(output_indent_width_from_env);
+    pretty_config_with_indent(width)
+}
+
+fn pretty_config_with_indent(width: usize) -> PrettyConfig {
     PrettyConfig::new()
         .with_separate_tuple_members(true)
         .with_decimal_floats(true)
-        .with_indentor(" ".to_string())
+        .with_indentor(" ".repeat(width))
         .with_new_line("\n".to_string())
 }
 
+/// Reads `OUTPUT_INDENT_WIDTH`, the number of spaces of each indentation level of pretty
+/// printed responses. Default is `1`.
+pub fn output_indent_width_from_env() -> usize {
+    std::env::var("OUTPUT_INDENT_WIDTH")
+        .ok()
+        .and_then(|width| width.parse::<usize>().ok())
+        .unwrap_or(1)
+}
+
+/// Called at startup, so responses don't read the env to pretty print.
+pub fn init_output_indent_width(width: usize) {
+    if OUTPUT_INDENT_WIDTH.set(width).is_err() {
+        log::warn!("Output indent width was already initialized");
+    }
+}
+
+/// Layout of response bodies, `?format=compact` drops the pretty print whitespace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Pretty,
+    Compact,
+}
+
+impl OutputFormat {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let compact = req
+            .query_string()
+            .split('&')
+            .any(|param| param.eq_ignore_ascii_case("format=compact"));
+        if compact {
+            OutputFormat::Compact
+        } else {
+            OutputFormat::Pretty
+        }
+    }
+
+    pub fn ron<T: Serialize>(self, value: &T) -> Result<String, ron::Error> {
+        match self {
+            OutputFormat::Pretty => ron::ser::to_string_pretty(value, pretty_config_output()),
+            OutputFormat::Compact => ron::ser::to_string(value),
+        }
+    }
+
+    pub fn json<T: Serialize>(self, value: &T) -> Result<String, serde_json::Error> {
+        match self {
+            OutputFormat::Pretty => serde_json::to_string_pretty(value),
+            OutputFormat::Compact => serde_json::to_string(value),
+        }
+    }
+
+    /// Serializes `value` as the negotiated `MediaType`. CSV is only available for query
+    /// responses, other bodies are written as RON.
+    pub fn write<T: Serialize>(self, media: MediaType, value: &T) -> Result<String, Error> {
+        match media {
+            MediaType::Ron | MediaType::Csv => Ok(self.ron(value)?),
+            MediaType::Json => Ok(self.json(value)?),
+        }
+    }
+}
+
+/// Serialization of response bodies, negotiated with the `Accept` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaType {
+    Ron,
+    Json,
+    Csv,
+}
+
+impl MediaType {
+    /// `Accept: application/json` responds with JSON, `Accept: text/csv` with CSV and
+    /// `Accept: application/x-ron` or any other `Accept` responds with RON, with or without
+    /// feature `json`.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let accept = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .unwrap_or_default();
+        accept
+            .split(',')
+            .map(|media| media.split(';').next().unwrap_or_default().trim())
+            .find_map(|media| {
+                if media.eq_ignore_ascii_case("application/json") {
+                    Some(MediaType::Json)
+                } else if media.eq_ignore_ascii_case("text/csv") {
+                    Some(MediaType::Csv)
+                } else if media.eq_ignore_ascii_case("application/x-ron") {
+                    Some(MediaType::Ron)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(MediaType::Ron)
+    }
+
+    /// Media type of responses written inside another body, like the array of
+    /// `/wql/batch`, where CSV can't be nested and RON is used instead.
+    pub fn nested(self) -> Self {
+        match self {
+            MediaType::Csv => MediaType::Ron,
+            media => media,
+        }
+    }
+
+    /// Serializer of query responses, `CsvSerializer` reads `CSV_JOIN_SEPARATOR`.
+    pub fn serializer(self, format: OutputFormat) -> Box<dyn ResponseSerializer> {
+        match self {
+            MediaType::Ron => Box::new(RonSerializer(format)),
+            MediaType::Json => Box::new(JsonSerializer(format)),
+            MediaType::Csv => Box::new(CsvSerializer::from_env()),
+        }
+    }
+}
+
+/// `?envelope=true` wraps query responses with their metadata, see `Response::to_envelope`.
+pub fn envelope_requested(req: &HttpRequest) -> bool {
+    req.query_string()
+        .split('&')
+        .any(|param| param.eq_ignore_ascii_case("envelope=true"))
+}
+
+/// Reads `HASHING_ALGORITHM`, `bcrypt` or `argon2`, for encrypted keys and user passwords.
+/// Default is `bcrypt`, hashes of both algorithms can be verified with any of them set.
+pub fn hash_algorithm() -> HashAlgorithm {
+    match std::env::var("HASHING_ALGORITHM") {
+        Ok(algorithm) => algorithm.parse().unwrap_or_else(|e| {
+            log::warn!("{}, using bcrypt", e);
+            HashAlgorithm::Bcrypt
+        }),
+        Err(_) => HashAlgorithm::Bcrypt,
+    }
+}
+
+static RECOVER_POISONED_LOCKS: OnceLock<bool> = OnceLock::new();
+
+/// Reads `RECOVER_POISONED_LOCKS`, if queries recover locks poisoned by a panicked writer.
+pub fn recover_poisoned_locks_from_env() -> bool {
+    matches!(std::env::var("RECOVER_POISONED_LOCKS"), Ok(s) if s.eq_ignore_ascii_case("true"))
+}
+
+/// Called at startup, so `read_lock` doesn't read the env on every poisoned lock.
+pub fn init_recover_poisoned_locks(recover: bool) {
+    if RECOVER_POISONED_LOCKS.set(recover).is_err() {
+        log::warn!("Poisoned locks recovery was already initialized");
+    }
+}
+
+/// Locks a context read by queries. A lock poisoned by a panicked writer is a `LockData`
+/// error, unless `RECOVER_POISONED_LOCKS=true`, then the guard is recovered with a warning.
+/// Writers don't use it, so a write that panicked halfway is never built upon.
+pub fn read_lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, Error> {
+    let recover = *RECOVER_POISONED_LOCKS.get_or_init(recover_poisoned_locks_from_env);
+    lock_or_recover(mutex, recover)
+}
+
+fn lock_or_recover<T>(mutex: &Mutex<T>, recover: bool) -> Result<MutexGuard<'_, T>, Error> {
+    mutex.lock().or_else(|poisoned| {
+        if recover {
+            log::warn!("Recovering a poisoned lock for a read");
+            Ok(poisoned.into_inner())
+        } else {
+            Err(Error::LockData)
+        }
+    })
+}
+
 pub fn pretty_config_inner() -> PrettyConfig {
     PrettyConfig::new()
         .with_indentor("".to_string())
@@ -31,3 +213,59 @@ pub fn tx_time(content: &HashMap<String, Types>) -> Result<DateTime<Utc>, Error>
     }
     Ok(Utc::now())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn read_lock_recovers_poisoned_lock_when_enabled() {
+        let mutex = Arc::new(Mutex::new(vec![1]));
+        let writer = Arc::clone(&mutex);
+        let _ = std::thread::spawn(move || {
+            let _guard = writer.lock().unwrap();
+            panic!("writer panicked");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+
+        assert!(matches!(
+            lock_or_recover(&mutex, false),
+            Err(Error::LockData)
+        ));
+        assert_eq!(*lock_or_recover(&mutex, true).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn pretty_config_indents_with_width() {
+        let value = vec![1, 2];
+        let one = ron::ser::to_string_pretty(&value, pretty_config_with_indent(1)).unwrap();
+        let four = ron::ser::to_string_pretty(&value, pretty_config_with_indent(4)).unwrap();
+        assert_eq!(one, "[\n 1,\n 2,\n]");
+        assert_eq!(four, "[\n    1,\n    2,\n]");
+    }
+
+    #[test]
+    fn media_type_from_accept_header() {
+        use actix_web::test::TestRequest;
+
+        let media = |accept: &str| {
+            MediaType::from_request(
+                &TestRequest::default()
+                    .header(header::ACCEPT, accept)
+                    .to_http_request(),
+            )
+        };
+        assert_eq!(media("application/json"), MediaType::Json);
+        assert_eq!(media("text/html, application/json;q=0.9"), MediaType::Json);
+        assert_eq!(media("application/x-ron"), MediaType::Ron);
+        assert_eq!(media("text/csv; charset=utf-8"), MediaType::Csv);
+        assert_eq!(media("text/csv").nested(), MediaType::Ron);
+        assert_eq!(media("*/*"), MediaType::Ron);
+        assert_eq!(
+            MediaType::from_request(&TestRequest::default().to_http_request()),
+            MediaType::Ron
+        );
+    }
+}
@@ -1,42 +1,350 @@
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, HashMap, HashSet},
+    sync::OnceLock,
 };
 
-use rayon::prelude::*;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 use uuid::Uuid;
-use wql::{Algebra, Types};
+use wql::{Algebra, EntitySchema, ScalarArg, ScalarFunction, Types, Wql};
 
 use crate::{
-    model::DataRegister,
-    schemas::query::{CountResponse, Response as QueryResponse},
+    actors::history::previous_registry,
+    io::{read::read_log, write::checksum},
+    model::{error::Error, DataRegister},
+    repository::local::EncryptContext,
+    schemas::query::{CountResponse, KeyDescription, Response as QueryResponse},
 };
 
+static QUERY_POOL: OnceLock<ThreadPool> = OnceLock::new();
+
+/// Builds the pool that runs the parallel work on query results with `threads` threads,
+/// `0` is one thread per cpu. Called at startup so CPU-heavy selects don't compete with
+/// actix's runtime in rayon's global pool.
+pub fn init_query_pool(threads: usize) {
+    if QUERY_POOL.set(build_query_pool(threads)).is_err() {
+        log::warn!("Query thread pool was already initialized");
+    }
+}
+
+/// Pool of `init_query_pool`, built with one thread per cpu if it was not initialized.
+pub(crate) fn query_pool() -> &'static ThreadPool {
+    QUERY_POOL.get_or_init(|| build_query_pool(0))
+}
+
+fn build_query_pool(threads: usize) -> ThreadPool {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("woori-query-{}", i))
+        .build()
+        .expect("Failed to build the query thread pool")
+}
+
+/// Removes hashes and, when `omit_nulls` is set, `Nil` values from the entity map,
+/// keeping only `keys` if they are defined.
 pub(crate) fn filter_keys_and_hash(
     state: HashMap<String, Types>,
     keys: Option<HashSet<String>>,
+    omit_nulls: bool,
 ) -> HashMap<String, Types> {
-    let filtered = state.into_par_iter().filter(|(_, v)| !v.is_hash());
-    if let Some(keys) = keys {
-        filtered.filter(|(k, _)| keys.contains(k)).collect()
-    } else {
-        filtered.collect()
+    query_pool().install(|| {
+        let filtered = state
+            .into_par_iter()
+            .filter(|(_, v)| !(v.is_hash() || omit_nulls && v == &Types::Nil));
+        if let Some(keys) = keys {
+            filtered.filter(|(k, _)| keys.contains(k)).collect()
+        } else {
+            filtered.collect()
+        }
+    })
+}
+
+/// Value returned in place of the values of encrypted keys, see `mask_keys`.
+pub(crate) const MASKED_VALUE: &str = "***";
+
+/// Encrypted keys of the entities read by `query`, with the `key:entity` and `entity:key`
+/// names joins give to keys of both entities.
+pub(crate) fn encrypted_keys(query: &Wql, encryption: &EncryptContext) -> HashSet<String> {
+    query_entities(query)
+        .into_iter()
+        .filter_map(|entity| encryption.get(entity).map(|keys| (entity, keys)))
+        .flat_map(|(entity, keys)| {
+            keys.iter().flat_map(move |key| {
+                vec![
+                    key.to_owned(),
+                    format!("{}:{}", key, entity),
+                    format!("{}:{}", entity, key),
+                ]
+            })
+        })
+        .collect()
+}
+
+fn query_entities(query: &Wql) -> Vec<&String> {
+    match query {
+        Wql::Select(entity, ..)
+        | Wql::SelectIds(entity, ..)
+        | Wql::SelectWhen(entity, ..)
+        | Wql::SelectWhenRange(entity, ..)
+        | Wql::SelectWhenEffective(entity, ..)
+        | Wql::SelectWhere(entity, ..) => vec![entity],
+        Wql::RelationQuery(queries, ..) => queries.iter().flat_map(query_entities).collect(),
+        Wql::Join(_, (entity_a, _), _, (entity_b, _), _) => vec![entity_a, entity_b],
+        _ => Vec::new(),
+    }
+}
+
+/// Replaces the values of `keys` with `MASKED_VALUE`. Encrypted values are hashes, already
+/// removed by `filter_keys_and_hash`, so this keeps anything else stored at an encrypted key
+/// from being returned.
+pub(crate) fn mask_keys(state: &mut HashMap<String, Types>, keys: &HashSet<String>) {
+    state
+        .iter_mut()
+        .filter(|(k, _)| keys.contains(*k))
+        .for_each(|(_, v)| *v = Types::String(MASKED_VALUE.to_owned()));
+}
+
+/// Adds the values of scalar functions, indexed and literal projections to the entity map,
+/// keyed by their aliases. Arguments are evaluated against the original map and unknown keys
+/// resolve to `Nil`.
+pub(crate) fn project_scalars(
+    mut state: HashMap<String, Types>,
+    functions: &HashMap<String, Algebra>,
+) -> HashMap<String, Types> {
+    if let Some(Algebra::Project(projections)) = functions.get("PROJECT") {
+        let values = projections
+            .iter()
+            .map(|(alias, function)| (alias.to_owned(), scalar_value(&state, function)))
+            .collect::<Vec<(String, Types)>>();
+        state.extend(values);
+    }
+    state
+}
+
+fn scalar_value(state: &HashMap<String, Types>, function: &ScalarFunction) -> Types {
+    let arg_value = |arg: &ScalarArg| match arg {
+        ScalarArg::Key(key) => state.get(key).cloned().unwrap_or(Types::Nil),
+        ScalarArg::Value(value) => value.to_owned(),
+    };
+
+    match function {
+        ScalarFunction::Coalesce(args) => args
+            .iter()
+            .map(arg_value)
+            .find(|value| value != &Types::Nil)
+            .unwrap_or(Types::Nil),
+        ScalarFunction::NullIf(a, b) => {
+            let value = arg_value(a);
+            if value == arg_value(b) {
+                Types::Nil
+            } else {
+                value
+            }
+        }
+        ScalarFunction::Literal(value) => value.to_owned(),
+        ScalarFunction::Index(key, index) => match state.get(key) {
+            Some(Types::Vector(values)) => values.get(*index).cloned().unwrap_or(Types::Nil),
+            _ => Types::Nil,
+        },
+        ScalarFunction::Sort(arg) => match arg_value(arg) {
+            Types::Vector(mut values) => {
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                Types::Vector(values)
+            }
+            _ => Types::Nil,
+        },
+        ScalarFunction::Reverse(arg) => match arg_value(arg) {
+            Types::Vector(mut values) => {
+                values.reverse();
+                Types::Vector(values)
+            }
+            _ => Types::Nil,
+        },
+        ScalarFunction::Unique(arg) => match arg_value(arg) {
+            Types::Vector(values) => {
+                let mut seen = HashSet::new();
+                Types::Vector(
+                    values
+                        .into_iter()
+                        .filter(|value| seen.insert(value.clone()))
+                        .collect(),
+                )
+            }
+            _ => Types::Nil,
+        },
+        ScalarFunction::Flatten(arg) => match arg_value(arg) {
+            Types::Vector(values) => Types::Vector(
+                values
+                    .into_iter()
+                    .flat_map(|value| match value {
+                        Types::Vector(inner) => inner,
+                        value => vec![value],
+                    })
+                    .collect(),
+            ),
+            _ => Types::Nil,
+        },
+        ScalarFunction::Slice(arg, start, end) => match arg_value(arg) {
+            Types::Vector(values) => {
+                let clamp = |index: isize| index.clamp(0, values.len() as isize) as usize;
+                let (start, end) = (clamp(*start), clamp(*end));
+                Types::Vector(values[start..end.max(start)].to_vec())
+            }
+            _ => Types::Nil,
+        },
+        ScalarFunction::Red(arg) => match arg_value(arg) {
+            Types::Color(red, _, _) => Types::Integer(red as isize),
+            _ => Types::Nil,
+        },
+        ScalarFunction::Green(arg) => match arg_value(arg) {
+            Types::Color(_, green, _) => Types::Integer(green as isize),
+            _ => Types::Nil,
+        },
+        ScalarFunction::Blue(arg) => match arg_value(arg) {
+            Types::Color(_, _, blue) => Types::Integer(blue as isize),
+            _ => Types::Nil,
+        },
+        ScalarFunction::Cast(arg, type_name) => cast(arg_value(arg), type_name),
+        ScalarFunction::SumArray(arg) => sum_array(array_numbers(arg_value(arg))),
+        ScalarFunction::MaxArray(arg) => array_numbers(arg_value(arg))
+            .into_iter()
+            .max_by(|a, b| number(a).partial_cmp(&number(b)).unwrap_or(Ordering::Equal))
+            .unwrap_or(Types::Nil),
+        ScalarFunction::MinArray(arg) => array_numbers(arg_value(arg))
+            .into_iter()
+            .min_by(|a, b| number(a).partial_cmp(&number(b)).unwrap_or(Ordering::Equal))
+            .unwrap_or(Types::Nil),
+        ScalarFunction::AvgArray(arg) => {
+            let numbers = array_numbers(arg_value(arg));
+            if numbers.is_empty() {
+                Types::Nil
+            } else {
+                Types::Float(numbers.iter().map(number).sum::<f64>() / numbers.len() as f64)
+            }
+        }
+    }
+}
+
+/// `Integer` and `Float` elements of a `Vector` for the `*_ARRAY` projections, floats that
+/// are not a number and other elements are skipped. Values that are not a `Vector` have no
+/// numbers.
+fn array_numbers(value: Types) -> Vec<Types> {
+    match value {
+        Types::Vector(values) => values
+            .into_iter()
+            .filter(|value| match value {
+                Types::Integer(_) => true,
+                Types::Float(f) => !f.is_nan(),
+                _ => false,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn number(value: &Types) -> f64 {
+    match value {
+        Types::Integer(i) => *i as f64,
+        Types::Float(f) => *f,
+        _ => 0.0,
     }
 }
 
+/// `Integer` sum when all numbers are integers and it doesn't overflow, `Float` otherwise.
+fn sum_array(numbers: Vec<Types>) -> Types {
+    if numbers.is_empty() {
+        return Types::Nil;
+    }
+    numbers
+        .iter()
+        .try_fold(0_isize, |sum, value| match value {
+            Types::Integer(i) => sum.checked_add(*i),
+            _ => None,
+        })
+        .map_or_else(
+            || Types::Float(numbers.iter().map(number).sum()),
+            Types::Integer,
+        )
+}
+
+/// Converts `value` to the type named `type_name` for `CAST`, `Nil` when it can't be
+/// converted. Floats and `Precise` are truncated to `Integer`, text values become `String`
+/// and `String`s are parsed as any type. Hashes are never converted, so `CAST` can't expose
+/// them.
+fn cast(value: Types, type_name: &str) -> Types {
+    if value.type_name() == type_name {
+        return value;
+    }
+    let cast = match (value, type_name) {
+        (Types::Hash(_), _) | (Types::Nil, _) => None,
+        (Types::String(s), _) => Types::from_str_as(&s, type_name).ok(),
+        (value, "String") => cast_to_string(&value).map(Types::String),
+        (Types::Float(f), "Integer") => Some(f.trunc())
+            .filter(|f| *f >= isize::MIN as f64 && *f < isize::MAX as f64)
+            .map(|f| Types::Integer(f as isize)),
+        (Types::Precise(p), "Integer") => p
+            .split('.')
+            .next()
+            .and_then(|integer| integer.parse().ok())
+            .map(Types::Integer),
+        (Types::Boolean(b), "Integer") => Some(Types::Integer(b as isize)),
+        (Types::Integer(i), "Float") => Some(Types::Float(i as f64)),
+        (Types::Precise(p), "Float") => p.parse().ok().map(Types::Float),
+        (Types::Ratio { num, den }, "Float") => Some(Types::Float(num as f64 / den as f64)),
+        (Types::Percentage(p), "Float") => Some(Types::Float(p)),
+        (Types::Integer(i), "Precise") => Some(Types::Precise(i.to_string())),
+        (Types::Float(f), "Precise") if f.is_finite() => Some(Types::Precise(f.to_string())),
+        (Types::Integer(0), "Boolean") => Some(Types::Boolean(false)),
+        (Types::Integer(1), "Boolean") => Some(Types::Boolean(true)),
+        _ => None,
+    };
+    cast.unwrap_or(Types::Nil)
+}
+
+/// Text of the values `CAST` can turn into a `String`, written like they are inserted.
+pub(crate) fn cast_to_string(value: &Types) -> Option<String> {
+    Some(match value {
+        Types::Char(c) => c.to_string(),
+        Types::Integer(i) => i.to_string(),
+        Types::Float(f) => f.to_string(),
+        Types::Precise(p) => p.clone(),
+        Types::Boolean(b) => b.to_string(),
+        Types::Uuid(id) => id.to_string(),
+        Types::DateTime(date) => date.to_rfc3339(),
+        Types::IpAddr(ip) => ip.to_string(),
+        Types::Email(email) => email.clone(),
+        Types::Phone(phone) => phone.clone(),
+        Types::Percentage(p) => format!("{}%", p),
+        Types::Ratio { num, den } => format!("{}/{}", num, den),
+        Types::Color(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        _ => return None,
+    })
+}
+
+/// States of the valid `registries` from `offset` up to `limit`, kept in `ID` order so
+/// queries without `ORDER BY` always return the same order and pages.
 pub fn registries_to_states(
     registries: BTreeMap<Uuid, (DataRegister, Vec<u8>)>,
     keys: Option<HashSet<String>>,
+    functions: &HashMap<String, Algebra>,
     offset: usize,
     limit: usize,
-) -> BTreeMap<Uuid, HashMap<String, Types>> {
-    let mut states: BTreeMap<Uuid, HashMap<String, Types>> = BTreeMap::new();
-    for (uuid, (_, state)) in registries.into_iter().skip(offset).take(limit) {
-        let state: HashMap<String, Types> = bincode::deserialize(&state).unwrap();
-        let filtered = filter_keys_and_hash(state, keys.clone());
-        states.insert(uuid, filtered);
-    }
-    states
+) -> Result<BTreeMap<Uuid, HashMap<String, Types>>, Error> {
+    query_pool().install(|| {
+        let mut states: BTreeMap<Uuid, HashMap<String, Types>> = BTreeMap::new();
+        for (uuid, (registry, state)) in registries
+            .into_iter()
+            .filter(|(_, (_, state))| is_valid_registry(state, functions))
+            .skip(offset)
+            .take(limit)
+        {
+            let state: HashMap<String, Types> = bincode::deserialize(&state).unwrap();
+            let state = project_scalars(state, functions);
+            let filtered = filter_keys_and_hash(state, keys.clone(), omit_nulls(functions));
+            states.insert(uuid, with_proof(filtered, &registry, functions)?);
+        }
+        Ok(states)
+    })
 }
 
 pub(crate) fn get_limit_offset_count(
@@ -57,33 +365,115 @@ pub(crate) fn get_limit_offset_count(
     (limit, offset, count)
 }
 
+/// Whether the query has `OMIT NULLS`.
+pub(crate) fn omit_nulls(functions: &HashMap<String, Algebra>) -> bool {
+    matches!(functions.get("OMIT"), Some(Algebra::OmitNulls))
+}
+
+/// Key of the version token added by `WITH VERSION`.
+pub(crate) const VERSION_KEY: &str = "_version";
+
+/// Adds the version of `register` to the state if the query has `WITH VERSION`.
+pub(crate) fn with_version(
+    mut state: HashMap<String, Types>,
+    register: &DataRegister,
+    functions: &HashMap<String, Algebra>,
+) -> HashMap<String, Types> {
+    if let Some(Algebra::WithVersion) = functions.get("VERSION") {
+        state.insert(VERSION_KEY.to_string(), Types::String(register.version()));
+    }
+    state
+}
+
+/// Keys of the registry checksums added by `RETURNING PROOF`.
+pub(crate) const HASH_KEY: &str = "_hash";
+pub(crate) const PREVIOUS_HASH_KEY: &str = "_previous_hash";
+
+/// Adds the checksum of the registry bytes of `register` and the checksum of the registry
+/// it points to if the query has `RETURNING PROOF`. The previous checksum is the one stored
+/// in the registry itself, so changing an older registry breaks the chain. Inserts start the
+/// chain and their `_previous_hash` is `Nil`.
+pub(crate) fn with_proof(
+    mut state: HashMap<String, Types>,
+    register: &DataRegister,
+    functions: &HashMap<String, Algebra>,
+) -> Result<HashMap<String, Types>, Error> {
+    if let Some(Algebra::ReturningProof) = functions.get("PROOF") {
+        let log = read_log(register.to_owned())?;
+        let previous_hash = match previous_registry(&log)? {
+            Some(previous) => Types::String(format!("{:08x}", registry_checksum(previous)?)),
+            None => Types::Nil,
+        };
+        state.insert(
+            HASH_KEY.to_string(),
+            Types::String(format!("{:08x}", checksum(log.as_bytes()))),
+        );
+        state.insert(PREVIOUS_HASH_KEY.to_string(), previous_hash);
+    }
+    Ok(state)
+}
+
+/// Stored checksum of `register`, computed from its bytes for registries written before
+/// checksums existed.
+fn registry_checksum(register: DataRegister) -> Result<u32, Error> {
+    match register.checksum {
+        Some(checksum) => Ok(checksum),
+        None => Ok(checksum(read_log(register)?.as_bytes())),
+    }
+}
+
+/// Keys of the valid-time interval of an entity map, `valid_to` is exclusive.
+pub(crate) const VALID_FROM_KEY: &str = "valid_from";
+pub(crate) const VALID_TO_KEY: &str = "valid_to";
+
+/// Whether the valid-time interval of the state covers the `VALID AT` date, always `true`
+/// without `VALID AT`. Missing or `Nil` bounds are open and bounds that are not a
+/// `DateTime` never match.
+pub(crate) fn is_valid_at(
+    state: &HashMap<String, Types>,
+    functions: &HashMap<String, Algebra>,
+) -> bool {
+    let date = if let Some(Algebra::ValidAt(date)) = functions.get("VALID") {
+        date
+    } else {
+        return true;
+    };
+    let from = match state.get(VALID_FROM_KEY) {
+        None | Some(Types::Nil) => true,
+        Some(Types::DateTime(from)) => from <= date,
+        Some(_) => false,
+    };
+    let to = match state.get(VALID_TO_KEY) {
+        None | Some(Types::Nil) => true,
+        Some(Types::DateTime(to)) => date < to,
+        Some(_) => false,
+    };
+    from && to
+}
+
+/// `is_valid_at` for a serialized state, which is only deserialized with `VALID AT`.
+pub(crate) fn is_valid_registry(state: &[u8], functions: &HashMap<String, Algebra>) -> bool {
+    !functions.contains_key("VALID")
+        || matches!(
+            bincode::deserialize::<HashMap<String, Types>>(state),
+            Ok(state) if is_valid_at(&state, functions)
+        )
+}
+
+/// Keeps the first state of each `DEDUP` key value. Values are compared by the `Hash`
+/// and `Eq` of `Types`, which agree with the ordering of `ORDER BY`.
 pub(crate) fn dedup_states(
     states: BTreeMap<Uuid, HashMap<String, Types>>,
     functions: &HashMap<String, wql::Algebra>,
 ) -> BTreeMap<Uuid, HashMap<String, Types>> {
     if let Some(Algebra::Dedup(k)) = functions.get("DEDUP") {
-        let key = if k.starts_with("NIL(") {
-            String::from(&k[4..k.len() - 1])
-        } else {
-            k.to_owned()
-        };
+        let (key, skip_nil) = dedup_key(k);
 
-        let mut set: HashSet<String> = HashSet::new();
+        let mut set: HashSet<Types> = HashSet::new();
         let mut new_states: BTreeMap<Uuid, HashMap<String, Types>> = BTreeMap::new();
         for (id, state) in states {
-            let k_value = state.get(&key);
-
-            if k.starts_with("NIL(")
-                && k_value.is_some()
-                && k_value != Some(&Types::Nil)
-                && !set.contains(&format!("{:?}", k_value.unwrap()))
-            {
-                set.insert(format!("{:?}", k_value.unwrap()));
-                new_states.insert(id.to_owned(), state.to_owned());
-            } else if !k.starts_with("NIL(")
-                && !set.contains(&format!("{:?}", state.get(k).unwrap_or(&Types::Nil)))
-            {
-                set.insert(format!("{:?}", state.get(k).unwrap_or(&Types::Nil)));
+            let k_value = state.get(&key).unwrap_or(&Types::Nil);
+            if !(skip_nil && k_value == &Types::Nil) && set.insert(k_value.to_owned()) {
                 new_states.insert(id, state);
             }
         }
@@ -97,30 +487,18 @@ pub(crate) fn dedup_option_states(
     states: BTreeMap<Uuid, Option<HashMap<String, Types>>>,
     functions: &HashMap<String, wql::Algebra>,
 ) -> BTreeMap<Uuid, Option<HashMap<String, Types>>> {
-    let dedup = functions.get("DEDUP");
-    if let Some(Algebra::Dedup(k)) = dedup {
-        let key = if k.starts_with("NIL(") {
-            String::from(&k[4..k.len() - 1])
-        } else {
-            k.to_owned()
-        };
+    if let Some(Algebra::Dedup(k)) = functions.get("DEDUP") {
+        let (key, skip_nil) = dedup_key(k);
 
-        let mut set: HashSet<String> = HashSet::new();
+        let mut set: HashSet<Types> = HashSet::new();
         let mut new_states: BTreeMap<Uuid, Option<HashMap<String, Types>>> = BTreeMap::new();
-        for (id, state) in states.iter().filter(|(_, s)| s.is_some()) {
-            let some_state = state.clone().unwrap();
-            let k_value = some_state.get(&key);
-
-            if k.starts_with("NIL(")
-                && k_value.is_some()
-                && k_value != Some(&Types::Nil)
-                && !set.contains("")
-            {
-                set.insert(format!("{:?}", k_value.unwrap()));
-                new_states.insert(id.to_owned(), state.to_owned());
-            } else if !set.contains(&format!("{:?}", k_value.unwrap_or(&Types::Nil))) {
-                set.insert(format!("{:?}", k_value.unwrap_or(&Types::Nil)));
-                new_states.insert(id.to_owned(), state.to_owned());
+        for (id, state) in states {
+            let k_value = match &state {
+                Some(s) => s.get(&key).unwrap_or(&Types::Nil),
+                None => continue,
+            };
+            if !(skip_nil && k_value == &Types::Nil) && set.insert(k_value.to_owned()) {
+                new_states.insert(id, state);
             }
         }
         new_states
@@ -129,8 +507,64 @@ pub(crate) fn dedup_option_states(
     }
 }
 
+/// Key of `DEDUP key` and `DEDUP NIL(key)`, the latter also drops `Nil` values.
+fn dedup_key(k: &str) -> (String, bool) {
+    if k.starts_with("NIL(") {
+        (String::from(&k[4..k.len() - 1]), true)
+    } else {
+        (k.to_owned(), false)
+    }
+}
+
+/// Pseudo-key for `ORDER BY` that sorts states by the time their registries were written.
+pub(crate) const REGISTRY_TIME_KEY: &str = "_created";
+
+/// Registries needed to sort states with `ORDER BY _created`, empty for other orderings.
+pub(crate) fn registry_times<'a>(
+    registries: impl Iterator<Item = (&'a Uuid, &'a DataRegister)>,
+    functions: &HashMap<String, Algebra>,
+) -> BTreeMap<Uuid, DataRegister> {
+    match functions.get("ORDER") {
+        Some(Algebra::OrderBy(k, _)) if k == REGISTRY_TIME_KEY => registries
+            .map(|(id, registry)| (*id, registry.to_owned()))
+            .collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+fn compare_states(
+    a: &(Uuid, HashMap<String, Types>),
+    b: &(Uuid, HashMap<String, Types>),
+    key: &str,
+    registries: &BTreeMap<Uuid, DataRegister>,
+) -> Ordering {
+    if key == REGISTRY_TIME_KEY {
+        by_registry_time(&a.0, &b.0, registries)
+    } else {
+        a.1.get(key)
+            .partial_cmp(&b.1.get(key))
+            .unwrap_or(Ordering::Less)
+    }
+}
+
+/// Registries are written in order of `file_name` dates and then `offset`.
+fn by_registry_time(a: &Uuid, b: &Uuid, registries: &BTreeMap<Uuid, DataRegister>) -> Ordering {
+    registries.get(a).cmp(&registries.get(b))
+}
+
+/// Orders, groups and counts `states` as requested by `functions`, in the query pool.
 pub(crate) fn get_result_after_manipulation(
     states: BTreeMap<Uuid, HashMap<String, Types>>,
+    registries: &BTreeMap<Uuid, DataRegister>,
+    functions: &HashMap<String, wql::Algebra>,
+    should_count: bool,
+) -> QueryResponse {
+    query_pool().install(|| result_after_manipulation(states, registries, functions, should_count))
+}
+
+fn result_after_manipulation(
+    states: BTreeMap<Uuid, HashMap<String, Types>>,
+    registries: &BTreeMap<Uuid, DataRegister>,
     functions: &HashMap<String, wql::Algebra>,
     should_count: bool,
 ) -> QueryResponse {
@@ -141,17 +575,9 @@ pub(crate) fn get_result_after_manipulation(
             .map(|(id, state)| (id, state))
             .collect::<Vec<(Uuid, HashMap<String, Types>)>>();
         if ord == &wql::Order::Asc {
-            states.sort_by(|a, b| {
-                a.1.get(k)
-                    .partial_cmp(&b.1.get(k))
-                    .unwrap_or(Ordering::Less)
-            });
+            states.sort_by(|a, b| compare_states(a, b, k, registries));
         } else {
-            states.sort_by(|a, b| {
-                b.1.get(k)
-                    .partial_cmp(&a.1.get(k))
-                    .unwrap_or(Ordering::Less)
-            });
+            states.sort_by(|a, b| compare_states(b, a, k, registries));
         }
         if should_count {
             let size = states.len();
@@ -186,11 +612,7 @@ pub(crate) fn get_result_after_manipulation(
                 let group_states = group_states
                     .iter_mut()
                     .map(|(key, states)| {
-                        states.sort_by(|a, b| {
-                            a.1.get(k)
-                                .partial_cmp(&b.1.get(k))
-                                .unwrap_or(Ordering::Less)
-                        });
+                        states.sort_by(|a, b| compare_states(a, b, k, registries));
                         (key.to_owned(), states.to_owned())
                     })
                     .collect::<HashMap<String, Vec<(Uuid, HashMap<String, Types>)>>>();
@@ -200,11 +622,7 @@ pub(crate) fn get_result_after_manipulation(
                 let group_states = group_states
                     .iter_mut()
                     .map(|(key, states)| {
-                        states.sort_by(|a, b| {
-                            b.1.get(k)
-                                .partial_cmp(&a.1.get(k))
-                                .unwrap_or(Ordering::Less)
-                        });
+                        states.sort_by(|a, b| compare_states(b, a, k, registries));
                         (key.to_owned(), states.to_owned())
                     })
                     .collect::<HashMap<String, Vec<(Uuid, HashMap<String, Types>)>>>();
@@ -224,8 +642,21 @@ pub(crate) fn get_result_after_manipulation(
     }
 }
 
+/// `get_result_after_manipulation` for ids that may have no state.
 pub(crate) fn get_result_after_manipulation_for_options(
     states: BTreeMap<Uuid, Option<HashMap<String, Types>>>,
+    registries: &BTreeMap<Uuid, DataRegister>,
+    functions: &HashMap<String, wql::Algebra>,
+    should_count: bool,
+) -> QueryResponse {
+    query_pool().install(|| {
+        result_after_manipulation_for_options(states, registries, functions, should_count)
+    })
+}
+
+fn result_after_manipulation_for_options(
+    states: BTreeMap<Uuid, Option<HashMap<String, Types>>>,
+    registries: &BTreeMap<Uuid, DataRegister>,
     functions: &HashMap<String, wql::Algebra>,
     should_count: bool,
 ) -> QueryResponse {
@@ -241,17 +672,9 @@ pub(crate) fn get_result_after_manipulation_for_options(
             .map(|(id, s)| (id, s.unwrap()))
             .collect::<Vec<(Uuid, HashMap<String, Types>)>>();
         if ord == &wql::Order::Asc {
-            states.sort_by(|a, b| {
-                a.1.get(k)
-                    .partial_cmp(&b.1.get(k))
-                    .unwrap_or(Ordering::Less)
-            });
+            states.sort_by(|a, b| compare_states(a, b, k, registries));
         } else {
-            states.sort_by(|a, b| {
-                b.1.get(k)
-                    .partial_cmp(&a.1.get(k))
-                    .unwrap_or(Ordering::Less)
-            });
+            states.sort_by(|a, b| compare_states(b, a, k, registries));
         }
         if should_count {
             let size = states.len();
@@ -298,11 +721,7 @@ pub(crate) fn get_result_after_manipulation_for_options(
                 let group_states = group_states
                     .iter_mut()
                     .map(|(key, states)| {
-                        states.sort_by(|a, b| {
-                            a.1.get(k)
-                                .partial_cmp(&b.1.get(k))
-                                .unwrap_or(Ordering::Less)
-                        });
+                        states.sort_by(|a, b| compare_states(a, b, k, registries));
                         (key.to_owned(), states.to_owned())
                     })
                     .collect::<HashMap<String, Vec<(Uuid, HashMap<String, Types>)>>>();
@@ -312,11 +731,7 @@ pub(crate) fn get_result_after_manipulation_for_options(
                 let group_states = group_states
                     .iter_mut()
                     .map(|(key, states)| {
-                        states.sort_by(|a, b| {
-                            b.1.get(k)
-                                .partial_cmp(&a.1.get(k))
-                                .unwrap_or(Ordering::Less)
-                        });
+                        states.sort_by(|a, b| compare_states(b, a, k, registries));
                         (key.to_owned(), states.to_owned())
                     })
                     .collect::<HashMap<String, Vec<(Uuid, HashMap<String, Types>)>>>();
@@ -335,3 +750,455 @@ pub(crate) fn get_result_after_manipulation_for_options(
         states.into()
     }
 }
+
+/// Declared type of each key of `schema`, with the variants of `Enum` keys and the
+/// element type of `Vector` keys.
+pub(crate) fn describe_schema(schema: &EntitySchema) -> BTreeMap<String, KeyDescription> {
+    schema
+        .types
+        .iter()
+        .map(|(key, type_name)| {
+            let declared = if let Some(variants) = schema.enums.get(key) {
+                format!("Enum({})", variants.join(" | "))
+            } else if let Some(element) = schema.vectors.get(key) {
+                format!("Vector<{}>", element)
+            } else {
+                type_name.to_owned()
+            };
+            (key.to_owned(), KeyDescription::Declared(declared))
+        })
+        .collect()
+}
+
+/// Type names found for each key of `states` and how many states hold each of them.
+pub(crate) fn describe_states(
+    states: impl Iterator<Item = HashMap<String, Types>>,
+) -> BTreeMap<String, KeyDescription> {
+    let mut observed: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    for state in states {
+        for (key, value) in state {
+            *observed
+                .entry(key)
+                .or_default()
+                .entry(value.type_name().to_owned())
+                .or_insert(0) += 1;
+        }
+    }
+    observed
+        .into_iter()
+        .map(|(key, types)| (key, KeyDescription::Observed(types)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn float_states(values: &[f64]) -> BTreeMap<Uuid, HashMap<String, Types>> {
+        values
+            .iter()
+            .map(|v| {
+                let mut state = HashMap::new();
+                state.insert("f".to_string(), Types::Float(*v));
+                (Uuid::new_v4(), state)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn project_vector_indexes() {
+        let mut state = HashMap::new();
+        state.insert(
+            "coords".to_string(),
+            Types::Vector(vec![Types::Float(37.5), Types::Float(127.0)]),
+        );
+        state.insert("name".to_string(), Types::String("seoul".to_string()));
+        let mut functions = HashMap::new();
+        functions.insert(
+            "PROJECT".to_string(),
+            Algebra::Project(vec![
+                (
+                    "lat".to_string(),
+                    ScalarFunction::Index("coords".to_string(), 0),
+                ),
+                (
+                    "alt".to_string(),
+                    ScalarFunction::Index("coords".to_string(), 2),
+                ),
+                (
+                    "first".to_string(),
+                    ScalarFunction::Index("name".to_string(), 0),
+                ),
+            ]),
+        );
+
+        let state = project_scalars(state, &functions);
+
+        assert_eq!(state.get("lat"), Some(&Types::Float(37.5)));
+        assert_eq!(state.get("alt"), Some(&Types::Nil));
+        assert_eq!(state.get("first"), Some(&Types::Nil));
+    }
+
+    #[test]
+    fn project_vector_functions() {
+        let mut state = HashMap::new();
+        state.insert(
+            "tags".to_string(),
+            Types::Vector(vec![
+                Types::String("b".to_string()),
+                Types::String("c".to_string()),
+                Types::String("a".to_string()),
+                Types::String("b".to_string()),
+            ]),
+        );
+        state.insert("name".to_string(), Types::String("seoul".to_string()));
+        let tags = ScalarArg::Key("tags".to_string());
+        let mut functions = HashMap::new();
+        functions.insert(
+            "PROJECT".to_string(),
+            Algebra::Project(vec![
+                ("sorted".to_string(), ScalarFunction::Sort(tags.clone())),
+                (
+                    "reversed".to_string(),
+                    ScalarFunction::Reverse(tags.clone()),
+                ),
+                ("unique".to_string(), ScalarFunction::Unique(tags)),
+                (
+                    "name_sorted".to_string(),
+                    ScalarFunction::Sort(ScalarArg::Key("name".to_string())),
+                ),
+            ]),
+        );
+
+        let state = project_scalars(state, &functions);
+        let strings = |values: &[&str]| {
+            Types::Vector(
+                values
+                    .iter()
+                    .map(|v| Types::String(v.to_string()))
+                    .collect(),
+            )
+        };
+
+        assert_eq!(state.get("sorted"), Some(&strings(&["a", "b", "b", "c"])));
+        assert_eq!(state.get("reversed"), Some(&strings(&["b", "a", "c", "b"])));
+        assert_eq!(state.get("unique"), Some(&strings(&["b", "c", "a"])));
+        assert_eq!(state.get("tags"), Some(&strings(&["b", "c", "a", "b"])));
+        assert_eq!(state.get("name_sorted"), Some(&Types::Nil));
+    }
+
+    #[test]
+    fn project_color_components() {
+        let mut state = HashMap::new();
+        state.insert("color".to_string(), Types::Color(255, 136, 0));
+        state.insert("name".to_string(), Types::String("orange".to_string()));
+        let color = || ScalarArg::Key("color".to_string());
+        let mut functions = HashMap::new();
+        functions.insert(
+            "PROJECT".to_string(),
+            Algebra::Project(vec![
+                ("r".to_string(), ScalarFunction::Red(color())),
+                ("g".to_string(), ScalarFunction::Green(color())),
+                ("b".to_string(), ScalarFunction::Blue(color())),
+                (
+                    "name_red".to_string(),
+                    ScalarFunction::Red(ScalarArg::Key("name".to_string())),
+                ),
+            ]),
+        );
+
+        let state = project_scalars(state, &functions);
+
+        assert_eq!(state.get("r"), Some(&Types::Integer(255)));
+        assert_eq!(state.get("g"), Some(&Types::Integer(136)));
+        assert_eq!(state.get("b"), Some(&Types::Integer(0)));
+        assert_eq!(state.get("name_red"), Some(&Types::Nil));
+    }
+
+    #[test]
+    fn cast_conversions() {
+        let string = |s: &str| Types::String(s.to_string());
+        let cases = vec![
+            (Types::Integer(42), "String", string("42")),
+            (Types::Float(-2.9), "Integer", Types::Integer(-2)),
+            (Types::Float(f64::NAN), "Integer", Types::Nil),
+            (
+                Types::Precise("-12.75".to_string()),
+                "Integer",
+                Types::Integer(-12),
+            ),
+            (
+                Types::Precise("1.5".to_string()),
+                "Float",
+                Types::Float(1.5),
+            ),
+            (
+                Types::Integer(3),
+                "Precise",
+                Types::Precise("3".to_string()),
+            ),
+            (Types::Ratio { num: 1, den: 4 }, "Float", Types::Float(0.25)),
+            (string(" 7 "), "Integer", Types::Integer(7)),
+            (string("seven"), "Integer", Types::Nil),
+            (string("#ff8800"), "Color", Types::Color(255, 136, 0)),
+            (Types::Color(255, 136, 0), "String", string("#ff8800")),
+            (Types::Integer(1), "Boolean", Types::Boolean(true)),
+            (Types::Integer(2), "Boolean", Types::Nil),
+            (Types::Boolean(true), "Integer", Types::Integer(1)),
+            (Types::Hash("$2b$14$abc".to_string()), "String", Types::Nil),
+            (Types::Vector(vec![Types::Integer(1)]), "String", Types::Nil),
+            (Types::Nil, "String", Types::Nil),
+            (Types::Integer(5), "Integer", Types::Integer(5)),
+        ];
+
+        for (value, type_name, expected) in cases {
+            let cast = cast(value.clone(), type_name);
+            assert_eq!(cast, expected, "CAST({:?} AS {})", value, type_name);
+        }
+    }
+
+    #[test]
+    fn array_aggregates() {
+        let scores = |values: Vec<Types>| {
+            let mut state = HashMap::new();
+            state.insert("scores".to_string(), Types::Vector(values));
+            state
+        };
+        let aggregates = |state: &HashMap<String, Types>| {
+            let arg = || ScalarArg::Key("scores".to_string());
+            [
+                scalar_value(state, &ScalarFunction::SumArray(arg())),
+                scalar_value(state, &ScalarFunction::MaxArray(arg())),
+                scalar_value(state, &ScalarFunction::MinArray(arg())),
+                scalar_value(state, &ScalarFunction::AvgArray(arg())),
+            ]
+        };
+
+        let state = scores(vec![
+            Types::Integer(4),
+            Types::String("skip".to_string()),
+            Types::Integer(-2),
+            Types::Integer(10),
+        ]);
+        assert_eq!(
+            aggregates(&state),
+            [
+                Types::Integer(12),
+                Types::Integer(10),
+                Types::Integer(-2),
+                Types::Float(4.0)
+            ]
+        );
+
+        let state = scores(vec![
+            Types::Integer(1),
+            Types::Float(2.5),
+            Types::Float(f64::NAN),
+        ]);
+        assert_eq!(
+            aggregates(&state),
+            [
+                Types::Float(3.5),
+                Types::Float(2.5),
+                Types::Integer(1),
+                Types::Float(1.75)
+            ]
+        );
+
+        let state = scores(vec![Types::Integer(isize::MAX), Types::Integer(1)]);
+        assert_eq!(aggregates(&state)[0], Types::Float(isize::MAX as f64 + 1.0));
+
+        let nil = [Types::Nil, Types::Nil, Types::Nil, Types::Nil];
+        assert_eq!(aggregates(&scores(Vec::new())), nil);
+        assert_eq!(aggregates(&scores(vec![Types::Boolean(true)])), nil);
+        let mut state = HashMap::new();
+        state.insert("scores".to_string(), Types::Integer(3));
+        assert_eq!(aggregates(&state), nil);
+        assert_eq!(aggregates(&HashMap::new()), nil);
+    }
+
+    #[test]
+    fn project_flatten_and_slice() {
+        let integers =
+            |values: &[isize]| Types::Vector(values.iter().map(|v| Types::Integer(*v)).collect());
+        let mut state = HashMap::new();
+        state.insert(
+            "matrix".to_string(),
+            Types::Vector(vec![
+                integers(&[1, 2]),
+                Types::Integer(3),
+                Types::Vector(vec![integers(&[4]), Types::Integer(5)]),
+            ]),
+        );
+        state.insert("tags".to_string(), integers(&[1, 2, 3, 4]));
+        state.insert("name".to_string(), Types::String("seoul".to_string()));
+        let tags = ScalarArg::Key("tags".to_string());
+        let mut functions = HashMap::new();
+        functions.insert(
+            "PROJECT".to_string(),
+            Algebra::Project(vec![
+                (
+                    "flat".to_string(),
+                    ScalarFunction::Flatten(ScalarArg::Key("matrix".to_string())),
+                ),
+                (
+                    "first".to_string(),
+                    ScalarFunction::Slice(tags.clone(), 0, 3),
+                ),
+                (
+                    "clamped".to_string(),
+                    ScalarFunction::Slice(tags.clone(), -2, 10),
+                ),
+                ("empty".to_string(), ScalarFunction::Slice(tags, 3, 1)),
+                (
+                    "name_flat".to_string(),
+                    ScalarFunction::Flatten(ScalarArg::Key("name".to_string())),
+                ),
+                (
+                    "name_slice".to_string(),
+                    ScalarFunction::Slice(ScalarArg::Key("name".to_string()), 0, 1),
+                ),
+            ]),
+        );
+
+        let state = project_scalars(state, &functions);
+
+        assert_eq!(
+            state.get("flat"),
+            Some(&Types::Vector(vec![
+                Types::Integer(1),
+                Types::Integer(2),
+                Types::Integer(3),
+                integers(&[4]),
+                Types::Integer(5),
+            ]))
+        );
+        assert_eq!(state.get("first"), Some(&integers(&[1, 2, 3])));
+        assert_eq!(state.get("clamped"), Some(&integers(&[1, 2, 3, 4])));
+        assert_eq!(state.get("empty"), Some(&integers(&[])));
+        assert_eq!(state.get("name_flat"), Some(&Types::Nil));
+        assert_eq!(state.get("name_slice"), Some(&Types::Nil));
+    }
+
+    #[test]
+    fn dedup_and_order_agree_on_floats() {
+        let states = float_states(&[1.5, 0.0, -0.0, f64::NAN, 1.5, f64::NAN, 0.1 + 0.2, 0.3]);
+        let mut functions = HashMap::new();
+        functions.insert("DEDUP".to_string(), Algebra::Dedup("f".to_string()));
+
+        let deduped = dedup_states(states.clone(), &functions);
+
+        let mut sorted = states
+            .into_iter()
+            .collect::<Vec<(Uuid, HashMap<String, Types>)>>();
+        sorted.sort_by(|a, b| compare_states(a, b, "f", &BTreeMap::new()));
+        let mut distinct = sorted;
+        distinct.dedup_by(|a, b| compare_states(a, b, "f", &BTreeMap::new()) == Ordering::Equal);
+
+        assert_eq!(deduped.len(), 6);
+        assert_eq!(deduped.len(), distinct.len());
+    }
+
+    #[test]
+    fn dedup_nil_option_states() {
+        let mut states = BTreeMap::new();
+        for value in [Types::Integer(1), Types::Integer(1), Types::Nil].iter() {
+            let mut state = HashMap::new();
+            state.insert("a".to_string(), value.to_owned());
+            states.insert(Uuid::new_v4(), Some(state));
+        }
+        states.insert(Uuid::new_v4(), None);
+        let mut functions = HashMap::new();
+        functions.insert("DEDUP".to_string(), Algebra::Dedup("NIL(a)".to_string()));
+
+        let deduped = dedup_option_states(states, &functions);
+
+        assert_eq!(deduped.len(), 1);
+    }
+
+    #[test]
+    fn describe_states_counts_types() {
+        let states = [Types::Integer(1), Types::Integer(2), Types::Nil]
+            .iter()
+            .map(|value| {
+                let mut state = HashMap::new();
+                state.insert("a".to_string(), value.to_owned());
+                state.insert("b".to_string(), Types::String("b".to_string()));
+                state
+            })
+            .collect::<Vec<HashMap<String, Types>>>();
+
+        let description = describe_states(states.into_iter());
+
+        let mut a = BTreeMap::new();
+        a.insert("Integer".to_string(), 2);
+        a.insert("Nil".to_string(), 1);
+        let mut b = BTreeMap::new();
+        b.insert("String".to_string(), 3);
+        assert_eq!(description["a"], KeyDescription::Observed(a));
+        assert_eq!(description["b"], KeyDescription::Observed(b));
+    }
+
+    #[test]
+    fn describe_declared_schema() {
+        let mut schema = EntitySchema::default();
+        schema
+            .types
+            .insert("age".to_string(), "Integer".to_string());
+        schema
+            .types
+            .insert("status".to_string(), "String".to_string());
+        schema
+            .types
+            .insert("tags".to_string(), "Vector".to_string());
+        schema.enums.insert(
+            "status".to_string(),
+            vec!["Open".to_string(), "Closed".to_string()],
+        );
+        schema
+            .vectors
+            .insert("tags".to_string(), "String".to_string());
+
+        let description = describe_schema(&schema);
+
+        assert_eq!(
+            description["age"],
+            KeyDescription::Declared("Integer".to_string())
+        );
+        assert_eq!(
+            description["status"],
+            KeyDescription::Declared("Enum(Open | Closed)".to_string())
+        );
+        assert_eq!(
+            description["tags"],
+            KeyDescription::Declared("Vector<String>".to_string())
+        );
+    }
+
+    #[test]
+    fn encrypted_keys_of_joins() {
+        let mut encryption = EncryptContext::new();
+        encryption.insert(
+            "user".to_string(),
+            vec!["pswd".to_string()].into_iter().collect(),
+        );
+        let query = Wql::from_str(
+            "JOIN (user:id, profile:user_id) Select * FROM user | Select * FROM profile",
+        )
+        .unwrap();
+
+        let mut keys = encrypted_keys(&query, &encryption)
+            .into_iter()
+            .collect::<Vec<String>>();
+        keys.sort();
+        assert_eq!(keys, vec!["pswd", "pswd:user", "user:pswd"]);
+        assert!(encrypted_keys(&Wql::Version, &encryption).is_empty());
+    }
+
+    #[test]
+    fn query_pool_threads() {
+        assert_eq!(build_query_pool(2).current_num_threads(), 2);
+        assert!(query_pool().current_num_threads() > 0);
+    }
+}
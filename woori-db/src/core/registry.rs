@@ -3,6 +3,7 @@ use std::collections::BTreeMap;
 use uuid::Uuid;
 
 use crate::{
+    core::read_lock,
     model::{error::Error, DataLocalContext},
     repository::local::StateInfo,
 };
@@ -11,11 +12,7 @@ pub fn get_registries(
     entity: &str,
     local_data: &DataLocalContext,
 ) -> Result<BTreeMap<Uuid, StateInfo>, Error> {
-    let local_data = if let Ok(guard) = local_data.lock() {
-        guard
-    } else {
-        return Err(Error::LockData);
-    };
+    let local_data = read_lock(local_data)?;
     let registries = if let Some(id_to_registries) = local_data.get(entity) {
         id_to_registries
     } else {
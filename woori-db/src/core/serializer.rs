@@ -0,0 +1,343 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use uuid::Uuid;
+use wql::Types;
+
+use crate::{
+    core::{query::cast_to_string, MediaType, OutputFormat},
+    model::error::Error,
+    schemas::query::Response,
+};
+
+/// Writes query responses in the media type negotiated with the `Accept` header, see
+/// `MediaType::serializer`.
+pub trait ResponseSerializer {
+    fn serialize(&self, response: &Response) -> Result<String, Error>;
+}
+
+pub struct RonSerializer(pub OutputFormat);
+
+impl ResponseSerializer for RonSerializer {
+    fn serialize(&self, response: &Response) -> Result<String, Error> {
+        response.write(self.0, MediaType::Ron)
+    }
+}
+
+pub struct JsonSerializer(pub OutputFormat);
+
+impl ResponseSerializer for JsonSerializer {
+    fn serialize(&self, response: &Response) -> Result<String, Error> {
+        response.write(self.0, MediaType::Json)
+    }
+}
+
+/// Writes a row for each entity map, with a column for each of their keys in order.
+/// Ids, groups and dates of the response come first, as `_id`, `_group` and `_date`.
+/// `Vector`s and `Map`s are joined with `join_separator` and rejected without it.
+pub struct CsvSerializer {
+    pub join_separator: Option<String>,
+}
+
+/// Cells of a row by column name.
+type Row = BTreeMap<String, String>;
+
+impl CsvSerializer {
+    /// Reads `CSV_JOIN_SEPARATOR`, nested values are rejected when it's not set.
+    pub fn from_env() -> Self {
+        Self {
+            join_separator: std::env::var("CSV_JOIN_SEPARATOR")
+                .ok()
+                .filter(|separator| !separator.is_empty()),
+        }
+    }
+
+    fn cell(&self, key: &str, value: &Types) -> Result<String, Error> {
+        match (value, &self.join_separator) {
+            (Types::Nil, _) => Ok(String::new()),
+            (Types::String(s), _) | (Types::Hash(s), _) => Ok(s.clone()),
+            (Types::Point { x, y }, _) => Ok(format!("point({}, {})", x, y)),
+            (Types::Vector(values), Some(separator)) => Ok(values
+                .iter()
+                .map(|value| self.cell(key, value))
+                .collect::<Result<Vec<String>, Error>>()?
+                .join(separator)),
+            (Types::Map(map), Some(separator)) => {
+                let mut entries = map.iter().collect::<Vec<(&String, &Types)>>();
+                entries.sort_by_key(|(k, _)| *k);
+                Ok(entries
+                    .into_iter()
+                    .map(|(k, v)| Ok(format!("{}={}", k, self.cell(key, v)?)))
+                    .collect::<Result<Vec<String>, Error>>()?
+                    .join(separator))
+            }
+            (Types::Vector(_), None) | (Types::Map(_), None) => {
+                Err(Error::CsvUnavailable(format!(
+                    "Key `{}` has a {}, set CSV_JOIN_SEPARATOR to join its values in one cell",
+                    key,
+                    value.type_name()
+                )))
+            }
+            (value, _) => Ok(cast_to_string(value).unwrap_or_default()),
+        }
+    }
+
+    fn row(
+        &self,
+        meta: Vec<(&str, String)>,
+        state: Option<&HashMap<String, Types>>,
+    ) -> Result<Row, Error> {
+        let mut row = meta
+            .into_iter()
+            .map(|(column, cell)| (column.to_string(), cell))
+            .collect::<Row>();
+        let mut entries = state.into_iter().flatten().collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| *key);
+        for (key, value) in entries {
+            row.insert(key.clone(), self.cell(key, value)?);
+        }
+        Ok(row)
+    }
+
+    fn id_rows<'a>(
+        &self,
+        group: Option<&str>,
+        states: impl Iterator<Item = (&'a Uuid, Option<&'a HashMap<String, Types>>)>,
+    ) -> Result<Vec<Row>, Error> {
+        states
+            .map(|(id, state)| {
+                let mut meta = vec![("_id", id.to_string())];
+                if let Some(group) = group {
+                    meta.insert(0, ("_group", group.to_string()));
+                }
+                self.row(meta, state)
+            })
+            .collect()
+    }
+
+    /// Meta columns of the response and its rows.
+    fn rows(&self, response: &Response) -> Result<(Vec<&'static str>, Vec<Row>), Error> {
+        Ok(match response {
+            Response::Id(state)
+            | Response::Intersect(state)
+            | Response::Difference(state)
+            | Response::Union(state) => (vec![], vec![self.row(vec![], Some(state))?]),
+            Response::All(states) => (
+                vec!["_id"],
+                self.id_rows(None, states.iter().map(|(id, s)| (id, Some(s))))?,
+            ),
+            Response::Order(states) => (
+                vec!["_id"],
+                self.id_rows(None, states.iter().map(|(id, s)| (id, Some(s))))?,
+            ),
+            Response::OptionOrder(states) => (
+                vec!["_id"],
+                self.id_rows(None, states.iter().map(|(id, s)| (id, s.as_ref())))?,
+            ),
+            Response::OptionSelect(states) => (
+                vec!["_id"],
+                self.id_rows(None, states.iter().map(|(id, s)| (id, s.as_ref())))?,
+            ),
+            Response::GroupBy(groups) => {
+                let mut rows = Vec::new();
+                for (group, states) in groups.iter().collect::<BTreeMap<_, _>>() {
+                    let states = states.iter().map(|(id, s)| (id, Some(s)));
+                    rows.extend(self.id_rows(Some(group), states)?);
+                }
+                (vec!["_group", "_id"], rows)
+            }
+            Response::OrderedGroupBy(groups) => {
+                let mut rows = Vec::new();
+                for (group, states) in groups.iter().collect::<BTreeMap<_, _>>() {
+                    let states = states.iter().map(|(id, s)| (id, Some(s)));
+                    rows.extend(self.id_rows(Some(group), states)?);
+                }
+                (vec!["_group", "_id"], rows)
+            }
+            Response::OptionGroupBy(groups) => {
+                let mut rows = Vec::new();
+                for (group, states) in groups.iter().collect::<BTreeMap<_, _>>() {
+                    let states = states.iter().map(|(id, s)| (id, s.as_ref()));
+                    rows.extend(self.id_rows(Some(group), states)?);
+                }
+                (vec!["_group", "_id"], rows)
+            }
+            Response::TimeRange(states) => (
+                vec!["_date"],
+                states
+                    .iter()
+                    .map(|(date, s)| self.row(vec![("_date", date.to_rfc3339())], Some(s)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Response::DateSelect(states) => (
+                vec!["_date"],
+                states
+                    .iter()
+                    .collect::<BTreeMap<_, _>>()
+                    .into_iter()
+                    .map(|(date, s)| self.row(vec![("_date", date.clone())], Some(s)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Response::Join(states) => (
+                vec![],
+                states
+                    .iter()
+                    .map(|s| self.row(vec![], Some(s)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Response::CheckValues(checked) => (
+                vec![],
+                vec![checked
+                    .iter()
+                    .map(|(key, check)| (key.clone(), check.to_string()))
+                    .collect()],
+            ),
+            Response::WithCount(counted) => self.rows(counted.response())?,
+            Response::Exists(exists) => (vec!["exists"], vec![row_of("exists", exists)]),
+            Response::Count(count) => (vec!["count"], vec![row_of("count", count)]),
+            Response::Version(_) | Response::Describe(_) => {
+                return Err(Error::CsvUnavailable(String::from(
+                    "VERSION and DESCRIBE responses are only written as RON or JSON",
+                )))
+            }
+        })
+    }
+}
+
+fn row_of(column: &str, cell: impl ToString) -> Row {
+    let mut row = Row::new();
+    row.insert(column.to_string(), cell.to_string());
+    row
+}
+
+/// Quotes fields with `,`, `"` or line breaks, doubling their `"`, as in RFC 4180.
+fn escape(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl ResponseSerializer for CsvSerializer {
+    fn serialize(&self, response: &Response) -> Result<String, Error> {
+        let (meta, rows) = self.rows(response)?;
+        let keys = rows
+            .iter()
+            .flat_map(BTreeMap::keys)
+            .filter(|key| !meta.contains(&key.as_str()))
+            .collect::<BTreeSet<&String>>();
+        let columns = meta
+            .iter()
+            .copied()
+            .chain(keys.into_iter().map(String::as_str))
+            .collect::<Vec<&str>>();
+
+        if columns.is_empty() {
+            return Ok(String::new());
+        }
+        let header = columns
+            .iter()
+            .map(|column| escape(column))
+            .collect::<Vec<_>>();
+        let lines = std::iter::once(header.join(",")).chain(rows.iter().map(|row| {
+            columns
+                .iter()
+                .map(|column| {
+                    row.get(*column)
+                        .map_or_else(String::new, |cell| escape(cell))
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        }));
+        Ok(lines.map(|line| line + "\r\n").collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(entries: Vec<(&str, Types)>) -> HashMap<String, Types> {
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect()
+    }
+
+    fn csv(separator: Option<&str>, response: &Response) -> Result<String, Error> {
+        CsvSerializer {
+            join_separator: separator.map(str::to_string),
+        }
+        .serialize(response)
+    }
+
+    #[test]
+    fn columns_in_order_for_all_rows() {
+        let mut states = BTreeMap::new();
+        states.insert(
+            Uuid::from_u128(2),
+            state(vec![
+                ("name", Types::String("julia".to_string())),
+                ("age", Types::Integer(42)),
+            ]),
+        );
+        states.insert(
+            Uuid::from_u128(1),
+            state(vec![
+                ("note", Types::String("says \"hi\", twice".to_string())),
+                ("name", Types::String("ana".to_string())),
+                ("nickname", Types::Nil),
+            ]),
+        );
+        let response: Response = states.into();
+
+        assert_eq!(
+            csv(None, &response).unwrap(),
+            "_id,age,name,nickname,note\r\n\
+             00000000-0000-0000-0000-000000000001,,ana,,\"says \"\"hi\"\", twice\"\r\n\
+             00000000-0000-0000-0000-000000000002,42,julia,,\r\n"
+        );
+    }
+
+    #[test]
+    fn nested_values_need_a_join_separator() {
+        let mut map = HashMap::new();
+        map.insert("b".to_string(), Types::Integer(2));
+        map.insert("a".to_string(), Types::Integer(1));
+        let response = Response::from(state(vec![
+            (
+                "tags",
+                Types::Vector(vec![Types::String("x".to_string()), Types::Float(1.5)]),
+            ),
+            ("meta", Types::Map(map)),
+        ]));
+
+        assert!(matches!(
+            csv(None, &response),
+            Err(Error::CsvUnavailable(reason)) if reason.starts_with("Key `meta` has a Map")
+        ));
+        assert_eq!(
+            csv(Some("|"), &response).unwrap(),
+            "meta,tags\r\na=1|b=2,x|1.5\r\n"
+        );
+    }
+
+    #[test]
+    fn groups_and_counts() {
+        let mut group = BTreeMap::new();
+        group.insert(Uuid::nil(), state(vec![("a", Types::Boolean(true))]));
+        let mut groups = HashMap::new();
+        groups.insert("z".to_string(), group.clone());
+        groups.insert("y".to_string(), group);
+        let response: Response = groups.into();
+
+        assert_eq!(
+            csv(None, &response).unwrap(),
+            "_group,_id,a\r\n\
+             y,00000000-0000-0000-0000-000000000000,true\r\n\
+             z,00000000-0000-0000-0000-000000000000,true\r\n"
+        );
+        assert_eq!(csv(None, &Response::Count(3)).unwrap(), "count\r\n3\r\n");
+        assert!(csv(None, &Response::Version(Default::default())).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{ser::SerializeTuple, Serialize, Serializer};
+use wql::Types;
+
+/// Serializes a value with the keys of its `HashMap`s in order, including the ones inside
+/// `Types::Map`, so the same response is always written the same way.
+pub struct Sorted<'a, T: ?Sized>(pub &'a T);
+
+impl Serialize for Sorted<'_, Types> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Types::Vector(values) => {
+                serializer.serialize_newtype_variant("Types", 6, "Vector", &Sorted(values))
+            }
+            Types::Map(map) => {
+                serializer.serialize_newtype_variant("Types", 7, "Map", &Sorted(map))
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Sorted<'_, bool> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<V> Serialize for Sorted<'_, HashMap<String, V>>
+where
+    for<'b> Sorted<'b, V>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries = self.0.iter().collect::<Vec<(&String, &V)>>();
+        entries.sort_by_key(|(key, _)| *key);
+        serializer.collect_map(entries.into_iter().map(|(k, v)| (k, Sorted(v))))
+    }
+}
+
+impl<K: Serialize, V> Serialize for Sorted<'_, BTreeMap<K, V>>
+where
+    for<'b> Sorted<'b, V>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(k, v)| (k, Sorted(v))))
+    }
+}
+
+impl<T> Serialize for Sorted<'_, Vec<T>>
+where
+    for<'b> Sorted<'b, T>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(Sorted))
+    }
+}
+
+impl<T> Serialize for Sorted<'_, Option<T>>
+where
+    for<'b> Sorted<'b, T>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Some(value) => serializer.serialize_some(&Sorted(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<A: Serialize, B> Serialize for Sorted<'_, (A, B)>
+where
+    for<'b> Sorted<'b, B>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (a, b) = self.0;
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(a)?;
+        tuple.serialize_element(&Sorted(b))?;
+        tuple.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::core::OutputFormat;
+
+    #[test]
+    fn sorts_nested_maps() {
+        let mut inner = HashMap::new();
+        for key in &["z", "b", "m"] {
+            inner.insert(key.to_string(), Types::Integer(1));
+        }
+        let mut state = HashMap::new();
+        for key in &["c", "a", "b"] {
+            state.insert(key.to_string(), Types::Boolean(true));
+        }
+        state.insert("map".to_string(), Types::Map(inner.clone()));
+        state.insert(
+            "vec".to_string(),
+            Types::Vector(vec![Types::Map(inner), Types::Nil]),
+        );
+
+        let output = OutputFormat::Compact.ron(&Sorted(&state)).unwrap();
+        assert_eq!(
+            output,
+            "{\"a\":Boolean(true),\"b\":Boolean(true),\"c\":Boolean(true),\"map\":Map({\"b\":Integer(1),\"m\":Integer(1),\"z\":Integer(1)}),\"vec\":Vector([Map({\"b\":Integer(1),\"m\":Integer(1),\"z\":Integer(1)}),Nil])}"
+        );
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
         UpdateSetEntityContent,
     },
     core::pretty_config_inner,
-    model::wql::Action,
+    model::{error::Error, wql::Action},
 };
 use ron::ser::to_string_pretty;
 
@@ -24,6 +24,12 @@ pub fn evict_entity_content(entity: &str) -> String {
     format!("{}|{}|{};", Action::EvictEntity, date, entity)
 }
 
+pub fn clear_entity_content(entity: &str) -> String {
+    let date: DateTime<Utc> = Utc::now();
+    let date = to_string_pretty(&date, pretty_config_inner()).unwrap();
+    format!("{}|{}|{};", Action::ClearEntity, date, entity)
+}
+
 pub fn evict_entity_id_content(entity: &EvictEntityId) -> String {
     let date: DateTime<Utc> = Utc::now();
     let date = to_string_pretty(&date, pretty_config_inner()).unwrap();
@@ -53,12 +59,21 @@ pub fn insert_entity_content(content: &InsertEntityContent) -> (DateTime<Utc>, U
 }
 
 pub fn update_set_entity_content(content: &UpdateSetEntityContent) -> (DateTime<Utc>, String) {
+    set_entity_content(Action::UpdateSet, content)
+}
+
+/// Same registry as `UPDATE SET`, but `current_state` is exactly the content.
+pub fn replace_entity_content(content: &UpdateSetEntityContent) -> (DateTime<Utc>, String) {
+    set_entity_content(Action::Replace, content)
+}
+
+fn set_entity_content(action: Action, content: &UpdateSetEntityContent) -> (DateTime<Utc>, String) {
     let uuid = content.id;
     let date = content.datetime;
     let date_str = to_string_pretty(&date, pretty_config_inner()).unwrap();
     let log = format!(
         "{}|{}|{}|{}|{}|{}|{};",
-        Action::UpdateSet,
+        action,
         date_str,
         uuid.to_string(),
         content.name,
@@ -104,9 +119,16 @@ pub fn delete_entity_content(content: &DeleteId) -> (DateTime<Utc>, String) {
     (date, log)
 }
 
-pub fn update_content_state(previous_state: &mut HashMap<String, Types>, k: String, v: Types) {
+/// Merges `v` into the value of `k`, numbers are added and `Integer` additions that
+/// overflow `isize` return `Error::ArithmeticOverflow`. `Precise` values are added as
+/// decimals, also with `Integer`s.
+pub fn update_content_state(
+    previous_state: &mut HashMap<String, Types>,
+    k: String,
+    v: Types,
+) -> Result<(), Error> {
     let local_state = previous_state
-        .entry(k)
+        .entry(k.clone())
         .or_insert_with(|| v.default_values());
     match v {
         Types::Char(c) => {
@@ -114,12 +136,19 @@ pub fn update_content_state(previous_state: &mut HashMap<String, Types>, k: Stri
         }
         Types::Integer(i) => {
             if let Types::Integer(local) = *local_state {
-                *local_state = Types::Integer(local + i);
+                let sum = local
+                    .checked_add(i)
+                    .ok_or_else(|| Error::ArithmeticOverflow(k.clone(), local, i))?;
+                *local_state = Types::Integer(sum);
             }
 
             if let Types::Float(local) = *local_state {
                 *local_state = Types::Float(local + i as f64);
             }
+
+            if let Some(sum) = local_state.precise_add(&Types::Integer(i)) {
+                *local_state = sum;
+            }
         }
         Types::String(s) => {
             if let Types::String(local) = local_state {
@@ -163,12 +192,35 @@ pub fn update_content_state(previous_state: &mut HashMap<String, Types>, k: Stri
             *local_state = Types::Nil;
         }
         Types::Precise(p) => {
-            *local_state = Types::Precise(p);
+            let precise = Types::Precise(p);
+            *local_state = local_state.precise_add(&precise).unwrap_or(precise);
         }
         Types::DateTime(date) => {
             *local_state = Types::DateTime(date);
         }
+        Types::Point { x, y } => {
+            *local_state = Types::Point { x, y };
+        }
+        Types::IpAddr(ip) => {
+            *local_state = Types::IpAddr(ip);
+        }
+        Types::Percentage(p) => {
+            *local_state = Types::Percentage(p);
+        }
+        Types::Ratio { num, den } => {
+            *local_state = Types::Ratio { num, den };
+        }
+        Types::Email(email) => {
+            *local_state = Types::Email(email);
+        }
+        Types::Color(r, g, b) => {
+            *local_state = Types::Color(r, g, b);
+        }
+        Types::Phone(phone) => {
+            *local_state = Types::Phone(phone);
+        }
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -236,6 +288,22 @@ mod test {
         assert!(s.contains("reg"));
     }
 
+    #[test]
+    fn replace_entity_content_test() {
+        let entity = UpdateSetEntityContent::new(
+            "my-entity",
+            "state",
+            "log",
+            uuid::Uuid::new_v4(),
+            Utc::now(),
+            "reg",
+        );
+
+        let (_, s) = replace_entity_content(&entity);
+        assert!(s.starts_with("REPLACE|"));
+        assert!(s.ends_with("|my-entity|log|state|reg;"));
+    }
+
     #[test]
     fn update_content_entity_content_test() {
         let id = uuid::Uuid::new_v4();
@@ -255,6 +323,63 @@ mod test {
         assert!(s.contains("reg"));
     }
 
+    #[test]
+    fn update_content_state_integer_test() {
+        let mut state = HashMap::new();
+        state.insert("a".to_string(), Types::Integer(isize::MAX - 1));
+
+        update_content_state(&mut state, "a".to_string(), Types::Integer(1)).unwrap();
+        assert_eq!(state["a"], Types::Integer(isize::MAX));
+
+        update_content_state(&mut state, "b".to_string(), Types::Integer(isize::MIN)).unwrap();
+        assert_eq!(state["b"], Types::Integer(isize::MIN));
+    }
+
+    #[test]
+    fn update_content_state_integer_overflow_test() {
+        let mut state = HashMap::new();
+        state.insert("a".to_string(), Types::Integer(isize::MAX));
+        state.insert("b".to_string(), Types::Integer(isize::MIN));
+
+        let err = update_content_state(&mut state, "a".to_string(), Types::Integer(1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "(\n error_type: \"ArithmeticOverflow\",\n error_message: \"Adding 1 to key `a` with value {} overflows Integer\",\n)",
+                isize::MAX
+            )
+        );
+        assert!(update_content_state(&mut state, "b".to_string(), Types::Integer(-1)).is_err());
+        assert_eq!(state["a"], Types::Integer(isize::MAX));
+        assert_eq!(state["b"], Types::Integer(isize::MIN));
+    }
+
+    #[test]
+    fn update_content_state_precise_test() {
+        let mut state = HashMap::new();
+        state.insert(
+            "a".to_string(),
+            Types::Precise("99999999999999999999999999.9".to_string()),
+        );
+
+        update_content_state(&mut state, "a".to_string(), Types::Precise("0.1".to_string()))
+            .unwrap();
+        assert_eq!(
+            state["a"],
+            Types::Precise("100000000000000000000000000".to_string())
+        );
+
+        update_content_state(&mut state, "a".to_string(), Types::Integer(-1)).unwrap();
+        assert_eq!(
+            state["a"],
+            Types::Precise("99999999999999999999999999".to_string())
+        );
+
+        update_content_state(&mut state, "b".to_string(), Types::Precise("-2.50".to_string()))
+            .unwrap();
+        assert_eq!(state["b"], Types::Precise("-2.5".to_string()));
+    }
+
     #[test]
     fn delete_entity_test() {
         let id = uuid::Uuid::new_v4();
@@ -282,6 +407,16 @@ mod test {
         assert!(actual.contains("hello"))
     }
 
+    #[test]
+    fn clear_entity_test() {
+        let entity = "hello";
+
+        let actual = clear_entity_content(entity);
+
+        assert!(actual.starts_with("CLEAR_ENTITY|"));
+        assert!(actual.ends_with("|hello;"))
+    }
+
     #[test]
     fn evict_entity_id_test() {
         let uuid = Uuid::new_v4();
@@ -0,0 +1,288 @@
+use std::{
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{
+            HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        },
+        Method,
+    },
+    Error, HttpResponse,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+
+const DEFAULT_METHODS: &str = "GET,POST,PUT,DELETE";
+const DEFAULT_HEADERS: &str = "Authorization,Content-Type,Idempotency-Key";
+
+/// Allowed origins, methods and headers for browser clients. Requests from an origin
+/// that is not allowed pass through without CORS headers, so they stay same-origin.
+#[derive(Clone, Debug)]
+pub struct Cors {
+    origins: Vec<String>,
+    methods: Vec<String>,
+    headers: Vec<String>,
+}
+
+impl Cors {
+    /// Comma separated lists, `*` as origin allows any origin.
+    pub fn new(origins: &str, methods: &str, headers: &str) -> Self {
+        let split = |list: &str| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(ToOwned::to_owned)
+                .collect::<Vec<String>>()
+        };
+
+        Self {
+            origins: split(origins),
+            methods: split(methods)
+                .into_iter()
+                .map(|method| method.to_uppercase())
+                .collect(),
+            headers: split(headers),
+        }
+    }
+
+    /// Reads `CORS_ALLOWED_ORIGINS`, `CORS_ALLOWED_METHODS` and `CORS_ALLOWED_HEADERS`.
+    pub fn from_env() -> Self {
+        let origins = std::env::var("CORS_ALLOWED_ORIGINS").unwrap_or_default();
+        let methods =
+            std::env::var("CORS_ALLOWED_METHODS").unwrap_or_else(|_| DEFAULT_METHODS.to_owned());
+        let headers =
+            std::env::var("CORS_ALLOWED_HEADERS").unwrap_or_else(|_| DEFAULT_HEADERS.to_owned());
+
+        Self::new(&origins, &methods, &headers)
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|o| o == "*" || o == origin)
+    }
+
+    fn allows_preflight(&self, req: &ServiceRequest) -> bool {
+        let method = req
+            .headers()
+            .get(ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|method| method.to_str().ok())
+            .unwrap_or_default();
+        let headers = req
+            .headers()
+            .get(ACCESS_CONTROL_REQUEST_HEADERS)
+            .and_then(|headers| headers.to_str().ok())
+            .unwrap_or_default();
+
+        (method == "OPTIONS" || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method)))
+            && headers
+                .split(',')
+                .map(str::trim)
+                .filter(|header| !header.is_empty())
+                .all(|header| self.headers.iter().any(|h| h.eq_ignore_ascii_case(header)))
+    }
+
+    fn preflight(&self, origin: &str, req: &ServiceRequest) -> HttpResponse {
+        if !self.allows_origin(origin) || !self.allows_preflight(req) {
+            return HttpResponse::Forbidden().finish();
+        }
+
+        HttpResponse::NoContent()
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+            .header(ACCESS_CONTROL_ALLOW_METHODS, self.methods.join(", "))
+            .header(ACCESS_CONTROL_ALLOW_HEADERS, self.headers.join(", "))
+            .header(VARY, "Origin")
+            .finish()
+    }
+}
+
+impl<S, B> Transform<S> for Cors
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CorsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CorsMiddleware {
+            service,
+            cors: Rc::new(self.clone()),
+        })
+    }
+}
+
+pub struct CorsMiddleware<S> {
+    service: S,
+    cors: Rc<Cors>,
+}
+
+impl<S, B> Service for CorsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let origin = req
+            .headers()
+            .get(ORIGIN)
+            .and_then(|origin| origin.to_str().ok())
+            .map(ToOwned::to_owned);
+        let origin = match origin {
+            Some(origin) => origin,
+            None => return Box::pin(self.service.call(req)),
+        };
+
+        if req.method() == Method::OPTIONS
+            && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            let response = self.cors.preflight(&origin, &req);
+            return Box::pin(ok(req.into_response(response.into_body())));
+        }
+
+        let allowed = self.cors.allows_origin(&origin);
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            if allowed {
+                if let Ok(origin) = HeaderValue::from_str(&origin) {
+                    let headers = response.headers_mut();
+                    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                    headers.insert(VARY, HeaderValue::from_static("Origin"));
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+
+    fn cors() -> Cors {
+        Cors::new("http://woori.io", DEFAULT_METHODS, DEFAULT_HEADERS)
+    }
+
+    #[actix_rt::test]
+    async fn preflight_is_no_content_with_allowed_headers() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cors())
+                .route("/wql/tx", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/wql/tx")
+            .header(ORIGIN, "http://woori.io")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(
+                ACCESS_CONTROL_REQUEST_HEADERS,
+                "authorization, content-type",
+            )
+            .to_request();
+
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "http://woori.io"
+        );
+        assert_eq!(
+            headers.get(ACCESS_CONTROL_ALLOW_METHODS).unwrap(),
+            "GET, POST, PUT, DELETE"
+        );
+        assert_eq!(
+            headers.get(ACCESS_CONTROL_ALLOW_HEADERS).unwrap(),
+            "Authorization, Content-Type, Idempotency-Key"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn preflight_is_forbidden_for_other_origins_and_headers() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cors())
+                .route("/wql/tx", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/wql/tx")
+            .header(ORIGIN, "http://other.io")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(!resp.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        let req = test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/wql/tx")
+            .header(ORIGIN, "http://woori.io")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "X-Custom")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn allowed_origin_is_added_to_response() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cors())
+                .route("/wql/tx", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/wql/tx")
+            .header(ORIGIN, "http://woori.io")
+            .header("Content-Type", "application/wql")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(
+            resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "http://woori.io"
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/wql/tx")
+            .header(ORIGIN, "http://other.io")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        assert!(!resp.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[test]
+    fn same_origin_by_default() {
+        let cors = Cors::new("", DEFAULT_METHODS, DEFAULT_HEADERS);
+        assert!(!cors.allows_origin("http://woori.io"));
+
+        let cors = Cors::new("*", DEFAULT_METHODS, DEFAULT_HEADERS);
+        assert!(cors.allows_origin("http://woori.io"));
+    }
+}
@@ -1,23 +1,40 @@
+pub mod cors;
+pub mod tls;
+
 #[cfg(not(debug_assertions))]
 use crate::auth::{
     controllers as auth,
     io::read_admin_info,
-    middlewares::{history_validator, wql_validator},
+    middlewares::{
+        entities_validator, history_validator, raw_log_validator, subscribe_validator,
+        wql_validator,
+    },
+    models::SessionExpiration,
 };
 #[cfg(not(debug_assertions))]
 use actix_web_httpauth::middleware::HttpAuthentication;
 
 use crate::{
-    actors::{scheduler::Scheduler, wql::Executor},
-    controllers::entity_history,
-    io::read::{encryption, local_data, offset, unique_data},
-    repository::local::{LocalContext, SessionContext, UniquenessContext},
+    actors::{
+        audit::Auditor, scheduler::Scheduler, schemas::BooleanParsing,
+        sequences::with_local_sequences, subscriptions::Subscriptions, wql::Executor,
+    },
+    controllers::{entities, entity_history, raw_log, subscribe},
+    core::{cache::QueryCache, pretty_config_output},
+    io::{
+        durability::durability,
+        read::{encryption, local_data, offset, schemas, sequences, unique_data},
+    },
+    repository::local::{
+        IdempotencyContext, LocalContext, SequenceContext, SessionContext, UniquenessContext,
+    },
+    schemas::health::Health,
 };
 use crate::{
     controllers::{query, tx},
-    repository::local::EncryptContext,
+    repository::local::{EncryptContext, SchemaContext},
 };
-use actix::Actor;
+use actix::{Actor, SyncArbiter};
 use actix_web::{get, guard, web, HttpResponse, Responder};
 use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Mutex};
@@ -27,6 +44,21 @@ pub async fn ping() -> impl Responder {
     HttpResponse::Ok().body("pong!")
 }
 
+/// Reports the write durability of the day logs, configured with `WRITE_DURABILITY`.
+#[get("/healthz")]
+pub async fn healthz() -> impl Responder {
+    let durability = durability();
+    let health = Health {
+        status: "ok".to_owned(),
+        durability: durability.name().to_owned(),
+        batch_interval_ms: durability.batch_interval_ms(),
+    };
+    match ron::ser::to_string_pretty(&health, pretty_config_output()) {
+        Ok(body) => HttpResponse::Ok().body(body),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
 #[get("/~/ready")]
 pub async fn readiness() -> impl Responder {
     let process = std::process::Command::new("sh")
@@ -40,25 +72,58 @@ pub async fn readiness() -> impl Responder {
     }
 }
 
+/// Reads `SESSION_EXPIRATION_TIME`, `SESSION_MAX_EXPIRATION_TIME` and
+/// `SESSION_REJECT_EXCEEDING_EXPIRATION`.
+#[cfg(not(debug_assertions))]
+fn session_expiration() -> SessionExpiration {
+    let default = std::env::var("SESSION_EXPIRATION_TIME")
+        .ok()
+        .and_then(|time| time.parse::<i64>().ok())
+        .unwrap_or(3600);
+    let max = std::env::var("SESSION_MAX_EXPIRATION_TIME")
+        .ok()
+        .and_then(|time| time.parse::<i64>().ok())
+        .unwrap_or(86400);
+    let reject_exceeding = matches!(
+        std::env::var("SESSION_REJECT_EXCEEDING_EXPIRATION"),
+        Ok(s) if s.eq_ignore_ascii_case("true")
+    );
+
+    SessionExpiration::new(default, max, reject_exceeding)
+}
+
 pub fn routes(config: &mut web::ServiceConfig) {
     let local_context = local_data().map_or(LocalContext::new(), |map| map);
     let encrypt_context = encryption().map_or(EncryptContext::new(), |e| e);
+    let schema_context = schemas().map_or(SchemaContext::new(), |s| s);
     let uniqueness = unique_data().map_or(UniquenessContext::new(), |u| u);
+    let sequence_context = sequences().map_or(SequenceContext::new(), |s| s);
+    let sequence_context = with_local_sequences(sequence_context, &local_context);
     let wql_context = Arc::new(Mutex::new(local_context));
     let unique_context = Arc::new(Mutex::new(uniqueness));
     let encrypt_context = Arc::new(Mutex::new(encrypt_context));
+    let schema_context = Arc::new(Mutex::new(schema_context));
+    let sequence_context = Arc::new(Mutex::new(sequence_context));
     let write_offset = AtomicUsize::new(offset().map_or(0_usize, |o| o));
+    let write_lock = tokio::sync::Mutex::new(());
     let actor = Executor::new().start();
+    let auditor = SyncArbiter::start(1, || Auditor);
     let env_cost = std::env::var("HASHING_COST").unwrap_or_else(|_| "14".to_owned());
     let cost = env_cost.parse::<u32>().expect("HASHING_COST must be a u32");
 
     let session_context = Arc::new(Mutex::new(SessionContext::new()));
+    let idempotency_context = Arc::new(Mutex::new(IdempotencyContext::new()));
+    let idempotency_ttl_str =
+        std::env::var("IDEMPOTENCY_KEY_EXPIRATION_TIME").unwrap_or_else(|_| "86400".to_owned());
+    let idempotency_ttl = idempotency_ttl_str
+        .parse::<i64>()
+        .expect("IDEMPOTENCY_KEY_EXPIRATION_TIME must be an i64");
+    let boolean_parsing = BooleanParsing::from_env();
+    let subscriptions = Subscriptions::from_env();
+    let query_cache = QueryCache::from_env();
 
     #[cfg(not(debug_assertions))]
-    let exp_time_str =
-        std::env::var("SESSION_EXPIRATION_TIME").unwrap_or_else(|_| "3600".to_owned());
-    #[cfg(not(debug_assertions))]
-    let exp_time = exp_time_str.parse::<i64>().unwrap_or(3600);
+    let session_expiration = session_expiration();
 
     #[cfg(not(debug_assertions))]
     let admin_info = read_admin_info().unwrap();
@@ -69,19 +134,31 @@ pub fn routes(config: &mut web::ServiceConfig) {
     let wql_auth = HttpAuthentication::bearer(wql_validator);
     #[cfg(not(debug_assertions))]
     let history_auth = HttpAuthentication::bearer(history_validator);
+    #[cfg(not(debug_assertions))]
+    let subscribe_auth = HttpAuthentication::bearer(subscribe_validator);
+    #[cfg(not(debug_assertions))]
+    let entities_auth = HttpAuthentication::bearer(entities_validator);
+    #[cfg(not(debug_assertions))]
+    let raw_log_auth = HttpAuthentication::bearer(raw_log_validator);
 
     #[cfg(not(debug_assertions))]
     config
         .data(session_context)
         .data(wql_context)
         .data(actor)
+        .data(auditor)
+        .data(subscriptions)
+        .data(query_cache)
         .service(
             web::scope("/auth")
                 .data(admin_info)
-                .data(exp_time)
+                .data(session_expiration)
                 .route("/createUser", web::post().to(auth::create_user))
                 .route("/deleteUsers", web::post().to(auth::delete_users))
-                .route("/putUserSession", web::put().to(auth::put_user_session)),
+                .route("/putUserSession", web::put().to(auth::put_user_session))
+                .route("/sessions", web::get().to(auth::list_sessions))
+                .route("/sessions", web::delete().to(auth::revoke_session))
+                .route("/memory", web::get().to(auth::memory_usage)),
         )
         .service(
             web::scope("/wql")
@@ -89,16 +166,45 @@ pub fn routes(config: &mut web::ServiceConfig) {
                 .data(cost)
                 .data(unique_context)
                 .data(encrypt_context)
+                .data(schema_context)
+                .data(sequence_context)
+                .data(boolean_parsing)
                 .data(write_offset)
+                .data(write_lock)
+                .data(idempotency_context)
+                .data(idempotency_ttl)
                 .wrap(wql_auth)
                 .route("/tx", web::post().to(tx::wql_handler))
-                .route("/query", web::post().to(query::wql_handler)),
+                .route("/query", web::post().to(query::wql_handler))
+                .route("/query/params", web::post().to(query::params_handler))
+                .route("/batch", web::post().to(query::batch_handler))
+                .route("/check", web::post().to(query::check_batch_handler)),
         )
         .service(
             web::scope("/entity-history")
-                .wrap(history_auth)
+                .wrap(history_auth.clone())
                 .route("", web::post().to(entity_history::history_handler)),
         )
+        .service(
+            web::scope("/entity-by-hash")
+                .wrap(history_auth)
+                .route("", web::post().to(entity_history::entity_by_hash_handler)),
+        )
+        .service(
+            web::scope("/subscribe")
+                .wrap(subscribe_auth)
+                .route("/{entity}", web::get().to(subscribe::subscribe_handler)),
+        )
+        .service(
+            web::scope("/entities")
+                .wrap(entities_auth)
+                .route("", web::get().to(entities::entities_handler)),
+        )
+        .service(
+            web::scope("/raw-log")
+                .wrap(raw_log_auth)
+                .route("/{date}", web::get().to(raw_log::raw_log_handler)),
+        )
         .route("", web::get().to(HttpResponse::NotFound));
 
     #[cfg(debug_assertions)]
@@ -106,20 +212,42 @@ pub fn routes(config: &mut web::ServiceConfig) {
         .data(session_context)
         .data(wql_context)
         .data(actor)
+        .data(auditor)
+        .data(subscriptions)
+        .data(query_cache)
         .service(
             web::scope("/wql")
                 .guard(guard::Header("Content-Type", "application/wql"))
                 .data(cost)
                 .data(unique_context)
                 .data(encrypt_context)
+                .data(schema_context)
+                .data(sequence_context)
+                .data(boolean_parsing)
                 .data(write_offset)
+                .data(write_lock)
+                .data(idempotency_context)
+                .data(idempotency_ttl)
                 .route("/tx", web::post().to(tx::wql_handler))
-                .route("/query", web::post().to(query::wql_handler)),
+                .route("/query", web::post().to(query::wql_handler))
+                .route("/query/params", web::post().to(query::params_handler))
+                .route("/batch", web::post().to(query::batch_handler))
+                .route("/check", web::post().to(query::check_batch_handler)),
         )
         .route(
             "/entity-history",
             web::post().to(entity_history::history_handler),
         )
+        .route(
+            "/entity-by-hash",
+            web::post().to(entity_history::entity_by_hash_handler),
+        )
+        .route(
+            "/subscribe/{entity}",
+            web::get().to(subscribe::subscribe_handler),
+        )
+        .route("/entities", web::get().to(entities::entities_handler))
+        .route("/raw-log/{date}", web::get().to(raw_log::raw_log_handler))
         .route("", web::get().to(HttpResponse::NotFound));
 }
 
@@ -139,6 +267,24 @@ mod test {
         assert_eq!(&Body::from("pong!"), body)
     }
 
+    #[actix_rt::test]
+    async fn test_healthz_get() {
+        let mut app = test::init_service(App::new().service(healthz)).await;
+        let req = test::TestRequest::get().uri("/healthz").to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success());
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        let health = Health {
+            status: "ok".to_owned(),
+            durability: durability().name().to_owned(),
+            batch_interval_ms: durability().batch_interval_ms(),
+        };
+        let expected = ron::ser::to_string_pretty(&health, pretty_config_output()).unwrap();
+        assert!(expected.contains("status: \"ok\""));
+        assert_eq!(&Body::from(expected), body)
+    }
+
     #[actix_rt::test]
     async fn test_ready_get() {
         let mut app = test::init_service(App::new().service(readiness)).await;
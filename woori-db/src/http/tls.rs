@@ -0,0 +1,192 @@
+use std::task::{Context, Poll};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+
+use crate::model::error::{error_to_http, Error as WooriError};
+
+const FORWARDED_PROTO: &str = "X-Forwarded-Proto";
+/// Probes of the proxy or orchestrator, which usually don't go through TLS.
+const HEALTH_PATHS: [&str; 3] = ["/ping", "/healthz", "/~/ready"];
+
+/// Requests that must have arrived over TLS, checked with the `X-Forwarded-Proto` header
+/// set by the proxy that terminates TLS. Only a trusted proxy should be able to reach
+/// WooriDB, otherwise clients could set the header themselves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequireTls {
+    Off,
+    /// Only transactions at `/wql/tx`, reads are exempt.
+    Writes,
+    /// Every endpoint except the health checks.
+    All,
+}
+
+impl RequireTls {
+    /// Reads `REQUIRE_TLS`, `off`, `writes` or `all`, defaults to `off`.
+    pub fn from_env() -> Option<Self> {
+        let required = std::env::var("REQUIRE_TLS").unwrap_or_else(|_| "off".to_owned());
+        match required.to_lowercase().as_str() {
+            "off" => Some(RequireTls::Off),
+            "writes" => Some(RequireTls::Writes),
+            "all" => Some(RequireTls::All),
+            _ => None,
+        }
+    }
+
+    fn applies_to(self, path: &str) -> bool {
+        match self {
+            RequireTls::Off => false,
+            RequireTls::Writes => path.trim_end_matches('/') == "/wql/tx",
+            RequireTls::All => !HEALTH_PATHS.contains(&path),
+        }
+    }
+}
+
+/// If the first proxy forwarding the request received it over `https`.
+fn is_tls(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(FORWARDED_PROTO)
+        .and_then(|proto| proto.to_str().ok())
+        .and_then(|proto| proto.split(',').next())
+        .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+}
+
+impl<S, B> Transform<S> for RequireTls
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequireTlsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequireTlsMiddleware {
+            service,
+            require: *self,
+        })
+    }
+}
+
+pub struct RequireTlsMiddleware<S> {
+    service: S,
+    require: RequireTls,
+}
+
+impl<S, B> Service for RequireTlsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        if self.require.applies_to(req.path()) && !is_tls(&req) {
+            let response = error_to_http(&WooriError::TlsRequired);
+            return Box::pin(ok(req.into_response(response.into_body())));
+        }
+
+        Box::pin(self.service.call(req))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::{
+        body::{Body, ResponseBody},
+        http::StatusCode,
+        test, web, App, HttpResponse,
+    };
+
+    async fn status(require: RequireTls, path: &str, proto: Option<&str>) -> StatusCode {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(require)
+                .route("/wql/tx", web::post().to(HttpResponse::Ok))
+                .route("/wql/query", web::post().to(HttpResponse::Ok))
+                .route("/healthz", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let mut req = test::TestRequest::post().uri(path);
+        if let Some(proto) = proto {
+            req = req.header(FORWARDED_PROTO, proto);
+        }
+
+        test::call_service(&mut app, req.to_request())
+            .await
+            .status()
+    }
+
+    #[actix_rt::test]
+    async fn writes_require_tls() {
+        let writes = RequireTls::Writes;
+        assert_eq!(status(writes, "/wql/tx", None).await, StatusCode::FORBIDDEN);
+        assert_eq!(
+            status(writes, "/wql/tx", Some("http")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(writes, "/wql/tx", Some("HTTPS")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(writes, "/wql/tx", Some("http, https")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status(writes, "/wql/query", None).await, StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn all_exempts_only_health_checks() {
+        let all = RequireTls::All;
+        assert_eq!(
+            status(all, "/wql/query", Some("http")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(all, "/wql/query", Some("https")).await,
+            StatusCode::OK
+        );
+        assert_eq!(status(all, "/healthz", None).await, StatusCode::OK);
+        assert_eq!(
+            status(RequireTls::Off, "/wql/tx", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[actix_rt::test]
+    async fn forbidden_response_names_the_error() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RequireTls::Writes)
+                .route("/wql/tx", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let req = test::TestRequest::post().uri("/wql/tx").to_request();
+
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        match resp.take_body() {
+            ResponseBody::Other(body) => {
+                assert_eq!(body, Body::from(WooriError::TlsRequired.to_string()))
+            }
+            ResponseBody::Body(_) => panic!("expected the error body"),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::Error,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+    thread,
+    time::Duration,
+};
+
+static DURABILITY: OnceLock<Durability> = OnceLock::new();
+static PENDING_SYNCS: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+const DEFAULT_BATCH_INTERVAL: u64 = 100;
+
+/// How day logs are synced to disk after a transaction is written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Durability {
+    /// Writes are left in the OS buffers, a crash of the machine can lose the last ones.
+    Async,
+    /// Every write is synced before its transaction returns.
+    Sync,
+    /// Day logs written since the last batch are synced together every interval.
+    Batched(Duration),
+}
+
+impl Durability {
+    /// Reads `WRITE_DURABILITY`, `async`, `sync` or `batched`, defaults to `async`.
+    /// `WRITE_BATCH_INTERVAL` is the ms between syncs of `batched`, defaults to 100.
+    pub fn from_env() -> Option<Self> {
+        let level = std::env::var("WRITE_DURABILITY").unwrap_or_else(|_| "async".to_owned());
+        match level.to_lowercase().as_str() {
+            "async" => Some(Durability::Async),
+            "sync" => Some(Durability::Sync),
+            "batched" => {
+                let interval = match std::env::var("WRITE_BATCH_INTERVAL") {
+                    Ok(interval) => interval.parse::<u64>().ok().filter(|ms| *ms > 0)?,
+                    Err(_) => DEFAULT_BATCH_INTERVAL,
+                };
+                Some(Durability::Batched(Duration::from_millis(interval)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Durability::Async => "async",
+            Durability::Sync => "sync",
+            Durability::Batched(_) => "batched",
+        }
+    }
+
+    pub fn batch_interval_ms(&self) -> Option<u64> {
+        match self {
+            Durability::Batched(interval) => Some(interval.as_millis() as u64),
+            _ => None,
+        }
+    }
+}
+
+/// Called at startup, `batched` starts the thread syncing the pending day logs.
+pub fn init_durability(durability: Durability) {
+    if DURABILITY.set(start(durability)).is_err() {
+        log::warn!("Write durability was already initialized");
+    }
+}
+
+/// Durability of `init_durability`, read from the env if it was not initialized.
+pub(crate) fn durability() -> Durability {
+    *DURABILITY.get_or_init(|| start(Durability::from_env().unwrap_or(Durability::Async)))
+}
+
+fn start(durability: Durability) -> Durability {
+    if let Durability::Batched(interval) = durability {
+        thread::Builder::new()
+            .name("woori-log-sync".to_owned())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                if let Err(e) = sync_pending() {
+                    log::error!("Failed to sync day logs: {}", e);
+                }
+            })
+            .expect("Failed to start the day logs sync thread");
+    }
+    durability
+}
+
+fn pending_syncs() -> MutexGuard<'static, BTreeSet<PathBuf>> {
+    PENDING_SYNCS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Syncs `file`, just written to `path`, as `durability` requires. Returns whether it was
+/// synced by this call, `batched` only marks `path` to be synced by the next batch.
+pub(crate) fn persist(file: &File, path: &Path, durability: Durability) -> Result<bool, Error> {
+    match durability {
+        Durability::Async => Ok(false),
+        Durability::Sync => file.sync_data().map(|_| true),
+        Durability::Batched(_) => {
+            pending_syncs().insert(path.to_owned());
+            Ok(false)
+        }
+    }
+}
+
+/// Syncs the day logs written since the last batch. Returns how many were synced, the ones
+/// that failed are kept for the next batch.
+pub(crate) fn sync_pending() -> Result<usize, Error> {
+    let paths = std::mem::take(&mut *pending_syncs());
+    let mut synced = 0;
+    let mut error = None;
+    for path in paths {
+        match File::open(&path).and_then(|file| file.sync_data()) {
+            Ok(_) => synced += 1,
+            Err(e) => {
+                pending_syncs().insert(path);
+                error = Some(e);
+            }
+        }
+    }
+    error.map_or(Ok(synced), Err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::paths::{data_dir, data_file};
+    use std::{fs::OpenOptions, io::Write};
+
+    fn written(name: &str) -> (File, PathBuf) {
+        let path = PathBuf::from(data_file(&format!("{}.log", name)));
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"oh crazy log").unwrap();
+        (file, path)
+    }
+
+    #[test]
+    fn persist_respects_durability() {
+        let _ = std::fs::create_dir_all(data_dir());
+        let (file, path) = written("durability_async");
+        assert!(!persist(&file, &path, Durability::Async).unwrap());
+        assert!(!pending_syncs().contains(&path));
+
+        let (file, path) = written("durability_sync");
+        assert!(persist(&file, &path, Durability::Sync).unwrap());
+        assert!(!pending_syncs().contains(&path));
+
+        let (file, path) = written("durability_batched");
+        let batched = Durability::Batched(Duration::from_millis(DEFAULT_BATCH_INTERVAL));
+        assert!(!persist(&file, &path, batched).unwrap());
+        assert!(pending_syncs().contains(&path));
+        assert!(sync_pending().unwrap() >= 1);
+        assert!(!pending_syncs().contains(&path));
+
+        for name in &["durability_async", "durability_sync", "durability_batched"] {
+            let _ = std::fs::remove_file(data_file(&format!("{}.log", name)));
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod durability;
+pub(crate) mod paths;
 pub(crate) mod read;
 pub(crate) mod write;
@@ -0,0 +1,121 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Directory where WooriDB keeps its files, configured with the `DATA_DIR` env var.
+pub fn data_dir() -> String {
+    std::env::var("DATA_DIR").unwrap_or_else(|_| "data".to_owned())
+}
+
+/// Path of the file `name` inside the data directory.
+pub fn data_file(name: &str) -> String {
+    format!("{}/{}", data_dir(), name)
+}
+
+/// When the `SHARD_LOGS` env var is `true` each entity writes its day logs to
+/// `<DATA_DIR>/<entity>/` instead of sharing `<DATA_DIR>/`.
+pub fn is_sharded() -> bool {
+    matches!(std::env::var("SHARD_LOGS"), Ok(s) if s.eq_ignore_ascii_case("true"))
+}
+
+fn log_dir(entity: &str) -> String {
+    if is_sharded() {
+        data_file(entity)
+    } else {
+        data_dir()
+    }
+}
+
+/// Day log where the transactions of `entity` at `date` are written.
+pub fn date_log(entity: &str, date: &DateTime<Utc>) -> String {
+    format!("{}/{}", log_dir(entity), date.format("%Y_%m_%d.log"))
+}
+
+/// Day log read by `WHEN` and `AS OF` queries, tests read the `.txt` fixtures.
+pub fn query_date_log(entity: &str, date: &DateTime<Utc>) -> String {
+    #[cfg(test)]
+    let format = "%Y_%m_%d.txt";
+    #[cfg(not(test))]
+    let format = "%Y_%m_%d.log";
+    format!("{}/{}", log_dir(entity), date.format(format))
+}
+
+/// Day logs read by `WHEN START ... END` queries, one per day of the range.
+pub fn range_date_logs(
+    entity: &str,
+    start_date: DateTime<Utc>,
+    end_date: DateTime<Utc>,
+) -> Vec<String> {
+    let mut date_logs = Vec::new();
+    let mut day = start_date.date();
+    while day <= end_date.date() {
+        date_logs.push(query_date_log(entity, &day.and_hms(0, 0, 0)));
+        day = day.succ();
+    }
+    date_logs
+}
+
+/// Existing day logs of `entity` up to `end_date`, latest first, read by `WHEN EFFECTIVE AT`
+/// queries. Compressed day logs are included.
+pub fn date_logs_until(entity: &str, end_date: DateTime<Utc>) -> Vec<String> {
+    let mut days = glob::glob(&format!("{}/*", log_dir(entity)))
+        .map(|paths| {
+            paths
+                .filter_map(Result::ok)
+                .filter_map(|path| {
+                    let stem = path.file_stem()?.to_str()?.to_owned();
+                    NaiveDate::parse_from_str(&stem, "%Y_%m_%d").ok()
+                })
+                .filter(|day| *day <= end_date.naive_utc().date())
+                .collect::<Vec<NaiveDate>>()
+        })
+        .unwrap_or_default();
+    days.sort_unstable_by(|a, b| b.cmp(a));
+    days.dedup();
+
+    days.into_iter()
+        .map(|day| query_date_log(entity, &DateTime::from_utc(day.and_hms(0, 0, 0), Utc)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn date_log_test() {
+        let date = Utc.ymd(2014, 11, 28).and_hms(12, 0, 9);
+
+        assert_eq!(date_log("my_entity", &date), "data/2014_11_28.log");
+        assert_eq!(query_date_log("my_entity", &date), "data/2014_11_28.txt");
+        assert_eq!(data_file("local_data.log"), "data/local_data.log");
+    }
+
+    #[test]
+    fn range_date_logs_test() {
+        let start = Utc.ymd(2021, 2, 28).and_hms(23, 0, 0);
+        let end = Utc.ymd(2021, 3, 2).and_hms(1, 0, 0);
+
+        assert_eq!(
+            range_date_logs("my_entity", start, end),
+            vec![
+                "data/2021_02_28.txt",
+                "data/2021_03_01.txt",
+                "data/2021_03_02.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn date_logs_until_test() {
+        let end = Utc.ymd(2021, 2, 9).and_hms(0, 0, 0);
+
+        assert_eq!(
+            date_logs_until("my_entity", end),
+            vec!["data/2021_02_09.txt", "data/2021_01_08.txt"]
+        );
+        assert_eq!(
+            date_logs_until("my_entity", Utc.ymd(2021, 2, 8).and_hms(0, 0, 0)),
+            vec!["data/2021_01_08.txt"]
+        );
+    }
+}
@@ -6,8 +6,16 @@ use std::{
 
 use rayon::prelude::*;
 
-use crate::{actors::encrypts::WriteWithEncryption, model::DataRegister};
-use crate::{model::error, repository::local::LocalContext};
+use crate::{
+    actors::{encrypts::WriteWithEncryption, schemas::WriteWithSchema},
+    model::DataRegister,
+};
+use crate::{
+    model::error,
+    repository::local::{LocalContext, SchemaContext, SequenceContext},
+};
+
+use super::{paths::data_file, write::checksum};
 
 #[cfg(test)]
 pub fn assert_content(pat: &str) {
@@ -47,6 +55,18 @@ pub fn assert_uniques(pat: &str) {
     assert!(s.contains(pat));
 }
 
+#[cfg(test)]
+pub fn assert_audit(pat: &str) {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .open("data/audit.log")
+        .unwrap();
+    let mut s = String::new();
+    file.read_to_string(&mut s).unwrap();
+
+    assert!(s.contains(pat));
+}
+
 #[cfg(test)]
 pub fn assert_offset(pat: &str) {
     let mut file = OpenOptions::new()
@@ -138,9 +158,9 @@ pub fn read_date_log(date_log: String) -> Result<String, Error> {
 
 pub fn offset() -> Result<usize, error::Error> {
     #[cfg(not(feature = "test_read"))]
-    let path = "data/offset_counter.log";
+    let path = data_file("offset_counter.log");
     #[cfg(feature = "test_read")]
-    let path = "data/offset_counter.txt";
+    let path = data_file("offset_counter.txt");
     let mut file = OpenOptions::new().read(true).open(path)?;
     let mut s = String::new();
     file.read_to_string(&mut s)?;
@@ -151,9 +171,9 @@ pub fn offset() -> Result<usize, error::Error> {
 
 pub fn local_data() -> Result<LocalContext, error::Error> {
     #[cfg(not(feature = "test_read"))]
-    let path = "data/local_data.log";
+    let path = data_file("local_data.log");
     #[cfg(feature = "test_read")]
-    let path = "data/local_data.txt";
+    let path = data_file("local_data.txt");
     let mut file = OpenOptions::new().read(true).open(path)?;
     let mut s = String::new();
     file.read_to_string(&mut s)?;
@@ -163,14 +183,58 @@ pub fn local_data() -> Result<LocalContext, error::Error> {
         Err(_) => Err(error::Error::FailedToParseState),
     };
 
-    data
+    data.map(verify_registries)
+}
+
+/// Drops the registries whose bytes don't match their checksum, like the ones of a day log
+/// partially written by a crash, with a warning. Registries without checksum are kept.
+pub fn verify_registries(mut local_data: LocalContext) -> LocalContext {
+    let mut logs: HashMap<String, Option<Vec<u8>>> = HashMap::new();
+    for (entity, ids) in local_data.iter_mut() {
+        ids.retain(|id, (registry, _)| {
+            let expected = match registry.checksum {
+                Some(expected) => expected,
+                None => return true,
+            };
+            let log = logs
+                .entry(registry.file_name.clone())
+                .or_insert_with(|| read_date_log_bytes(&registry.file_name).ok());
+            let is_valid = log
+                .as_ref()
+                .and_then(|log| log.get(registry.offset..registry.offset + registry.bytes_length))
+                .is_some_and(|bytes| checksum(bytes) == expected);
+            if !is_valid {
+                log::warn!(
+                    "Registry of id {} in entity {} at {} offset {} is corrupt, skipping it",
+                    id,
+                    entity,
+                    registry.file_name,
+                    registry.offset
+                );
+            }
+            is_valid
+        });
+    }
+    local_data
+}
+
+fn read_date_log_bytes(date_log: &str) -> Result<Vec<u8>, Error> {
+    match std::fs::read(date_log) {
+        Ok(bytes) => Ok(bytes),
+        Err(_) => {
+            let file = OpenOptions::new()
+                .read(true)
+                .open(date_log.replace(".log", ".zst"))?;
+            zstd::stream::decode_all(BufReader::new(file))
+        }
+    }
 }
 
 pub fn unique_data() -> Result<BTreeMap<String, HashMap<String, HashSet<String>>>, error::Error> {
     #[cfg(not(feature = "test_read"))]
-    let path = "data/unique_data.log";
+    let path = data_file("unique_data.log");
     #[cfg(feature = "test_read")]
-    let path = "data/unique_data.txt";
+    let path = data_file("unique_data.txt");
     let mut file = OpenOptions::new().read(true).open(path)?;
     let mut s = String::new();
     file.read_to_string(&mut s)?;
@@ -186,9 +250,9 @@ pub fn unique_data() -> Result<BTreeMap<String, HashMap<String, HashSet<String>>
 
 pub fn encryption() -> Result<BTreeMap<String, HashSet<String>>, error::Error> {
     #[cfg(not(feature = "test_read"))]
-    let path = "data/encrypt.log";
+    let path = data_file("encrypt.log");
     #[cfg(feature = "test_read")]
-    let path = "data/encrypt.txt";
+    let path = data_file("encrypt.txt");
     let mut file = OpenOptions::new().read(true).open(path)?;
     let mut s = String::from('[');
     file.read_to_string(&mut s)?;
@@ -213,6 +277,36 @@ pub fn encryption() -> Result<BTreeMap<String, HashSet<String>>, error::Error> {
     Ok(data)
 }
 
+pub fn sequences() -> Result<SequenceContext, error::Error> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .open(data_file("sequences.log"))?;
+    let mut s = String::new();
+    file.read_to_string(&mut s)?;
+
+    ron::de::from_str(&s).map_err(|_| error::Error::FailedToParseState)
+}
+
+pub fn schemas() -> Result<SchemaContext, error::Error> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .open(data_file("schemas.log"))?;
+    let mut s = String::from('[');
+    file.read_to_string(&mut s)?;
+    s.push(']');
+    let s = s.replace(")(", "),(");
+
+    let data: Result<Vec<WriteWithSchema>, error::Error> = match ron::de::from_str(&s) {
+        Ok(x) => Ok(x),
+        Err(_) => Err(error::Error::FailedToParseState),
+    };
+
+    Ok(data?
+        .into_iter()
+        .map(|schema| (schema.entity, schema.schema))
+        .collect::<SchemaContext>())
+}
+
 #[cfg(test)]
 #[cfg(feature = "test_read")]
 mod test {
@@ -228,6 +322,7 @@ mod test {
             file_name: "data/read_test.log".to_string(),
             offset: 30,
             bytes_length: log_size - 58,
+            checksum: None,
         };
 
         let log = read_log(data).unwrap();
@@ -271,7 +366,7 @@ mod test {
         assert!(local_data.is_ok());
         assert_eq!(
                 format!("{:?}", local_data), 
-                "Ok({\"encrypt_ent\": {}, \"encrypt_ent2\": {}, \"hello\": {50e68bc1-0c3b-4ffc-93be-46e57f59b415: (DataRegister { file_name: \"2021_02_10.log\", offset: 447, bytes_length: 153 }, [0, 0, 0, 0, 0, 0, 0, 0])}, \"oh_yeah\": {27367bd0-1966-4005-a8b5-5e323e1c3524: (DataRegister { file_name: \"2021_02_10.log\", offset: 180, bytes_length: 247 }, [0, 0, 0, 0, 0, 0, 0, 0])}})"
+                "Ok({\"encrypt_ent\": {}, \"encrypt_ent2\": {}, \"hello\": {50e68bc1-0c3b-4ffc-93be-46e57f59b415: (DataRegister { file_name: \"2021_02_10.log\", offset: 447, bytes_length: 153, checksum: None }, [0, 0, 0, 0, 0, 0, 0, 0])}, \"oh_yeah\": {27367bd0-1966-4005-a8b5-5e323e1c3524: (DataRegister { file_name: \"2021_02_10.log\", offset: 180, bytes_length: 247, checksum: None }, [0, 0, 0, 0, 0, 0, 0, 0])}})"
             );
     }
 
@@ -288,3 +383,52 @@ mod test {
         assert!(body.contains("\"snn\": {}}}"));
     }
 }
+
+#[cfg(test)]
+mod checksum_test {
+    use std::{collections::BTreeMap, fs::OpenOptions};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::io::write::write_to_log;
+
+    #[test]
+    fn truncated_log_registry_is_skipped() {
+        let file_name = "data/checksum_test.log".to_string();
+        let _ = std::fs::remove_file(&file_name);
+        let mut ids = BTreeMap::new();
+        for log in &["INSERT|first;", "INSERT|second;"] {
+            let (offset, bytes_length, _) = write_to_log(&file_name, log).unwrap();
+            let registry = DataRegister {
+                file_name: file_name.clone(),
+                offset,
+                bytes_length,
+                checksum: Some(checksum(log.as_bytes())),
+            };
+            ids.insert(Uuid::new_v4(), (registry, Vec::new()));
+        }
+        let legacy = Uuid::new_v4();
+        let registry = DataRegister {
+            file_name: file_name.clone(),
+            offset: 0,
+            bytes_length: 1,
+            checksum: None,
+        };
+        ids.insert(legacy, (registry, Vec::new()));
+        let mut local_data = LocalContext::new();
+        local_data.insert("checksum_test".to_string(), ids.clone());
+
+        let file = OpenOptions::new().write(true).open(&file_name).unwrap();
+        file.set_len(20).unwrap();
+        let verified = verify_registries(local_data);
+        let _ = std::fs::remove_file(&file_name);
+
+        let verified = &verified["checksum_test"];
+        assert_eq!(verified.len(), 2);
+        assert!(verified.contains_key(&legacy));
+        assert!(verified
+            .values()
+            .any(|(registry, _)| registry.offset == 0 && registry.checksum.is_some()));
+    }
+}
@@ -1,27 +1,97 @@
-use chrono::{DateTime, Utc};
-use std::io::{Error, Seek, SeekFrom};
+use std::io::{Error, ErrorKind, Seek, SeekFrom};
 use std::path::Path;
-use std::{fs::OpenOptions, io::Write};
+use std::{
+    fs::{create_dir_all, remove_file, OpenOptions},
+    io::Write,
+};
 
-pub fn write_to_log(log: &str) -> Result<(usize, bool), Error> {
-    let utc: DateTime<Utc> = Utc::now();
-    let date_log = utc.format("data/%Y_%m_%d.log").to_string();
-    let is_empty = !Path::new(&date_log).exists();
+use super::{
+    durability::{durability, persist},
+    paths::data_file,
+};
 
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(date_log)?;
+/// Appends `log` to the day log `date_log` and syncs it as configured by `WRITE_DURABILITY`.
+/// Returns the offset where `log` was written, the written bytes and whether the day log
+/// was created by this write.
+pub fn write_to_log(date_log: &str, log: &str) -> Result<(usize, usize, bool), Error> {
+    let path = Path::new(date_log);
+    let is_empty = !path.exists();
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+
+    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+    let offset = file.metadata()?.len() as usize;
     let written_bytes = file.write(log.as_bytes())?;
     file.flush()?;
+    persist(&file, path, durability())?;
+
+    Ok((offset, written_bytes, is_empty))
+}
+
+/// Offset where the next entry of the day log `date_log` is appended.
+pub fn log_offset(date_log: &str) -> usize {
+    std::fs::metadata(date_log).map_or(0, |metadata| metadata.len() as usize)
+}
+
+/// Appends the entries of a `BEGIN` block, one buffer per day log written at the offset
+/// its registries were built with. If any append fails the day logs already written are
+/// truncated back, so either every entry of the block is on disk or none is. Returns
+/// whether each day log was created by the block.
+pub fn write_block(logs: &[(String, usize, String)]) -> Result<Vec<bool>, Error> {
+    let mut appended = Vec::new();
+    let result = logs.iter().try_for_each(|(date_log, offset, log)| {
+        let path = Path::new(date_log);
+        let is_empty = !path.exists();
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+        if file.metadata()?.len() as usize != *offset {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Day log {} changed while the block was written", date_log),
+            ));
+        }
+        appended.push((path, *offset, is_empty));
+        file.write_all(log.as_bytes())?;
+        file.flush()?;
+        persist(&file, path, durability()).map(|_| ())
+    });
+
+    if let Err(e) = result {
+        for (path, offset, is_empty) in appended.into_iter().rev() {
+            let rollback = if is_empty {
+                remove_file(path)
+            } else {
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .and_then(|file| file.set_len(offset as u64))
+            };
+            if let Err(rollback) = rollback {
+                log::error!("Failed to roll back day log {:?}: {}", path, rollback);
+            }
+        }
+        return Err(e);
+    }
+    Ok(appended
+        .into_iter()
+        .map(|(_, _, is_empty)| is_empty)
+        .collect())
+}
 
-    Ok((written_bytes, is_empty))
+/// Checksum of a registry appended to a day log, stored in its `DataRegister`.
+pub fn checksum(log: &[u8]) -> u32 {
+    crc32fast::hash(log)
 }
+
 pub fn write_to_uniques(log: &str) -> Result<(), Error> {
     let mut file = OpenOptions::new()
         .append(true)
         .create(true)
-        .open("data/uniques.log")?;
+        .open(data_file("uniques.log"))?;
 
     let _ = file.write(log.as_bytes())?;
     file.flush()?;
@@ -34,7 +104,7 @@ pub fn local_data(log: &str) -> Result<(), Error> {
         .write(true)
         .append(false)
         .create(true)
-        .open("data/local_data.log")?;
+        .open(data_file("local_data.log"))?;
 
     let _ = file.seek(SeekFrom::Start(0));
     file.write_all(log.as_bytes())?;
@@ -48,7 +118,7 @@ pub fn unique_data(log: &str) -> Result<(), Error> {
         .write(true)
         .append(false)
         .create(true)
-        .open("data/unique_data.log")?;
+        .open(data_file("unique_data.log"))?;
 
     let _ = file.seek(SeekFrom::Start(0));
     file.write_all(log.as_bytes())?;
@@ -57,12 +127,26 @@ pub fn unique_data(log: &str) -> Result<(), Error> {
     Ok(())
 }
 
+/// Replaces `sequences.log` by `log`, the last numbers assigned by `INSERT ... WITH NEXT`.
+pub fn write_sequences(log: &str) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(data_file("sequences.log"))?;
+
+    file.write_all(log.as_bytes())?;
+    file.flush()?;
+
+    Ok(())
+}
+
 pub fn offset_counter(log: usize) -> Result<(), Error> {
     let mut file = OpenOptions::new()
         .write(true)
         .append(false)
         .create(true)
-        .open("data/offset_counter.log")?;
+        .open(data_file("offset_counter.log"))?;
 
     let _ = file.seek(SeekFrom::Start(0));
     file.write_all(log.to_string().as_bytes())?;
@@ -75,7 +159,19 @@ pub fn write_to_encrypts(log: &str) -> Result<(), Error> {
     let mut file = OpenOptions::new()
         .append(true)
         .create(true)
-        .open("data/encrypt.log")?;
+        .open(data_file("encrypt.log"))?;
+
+    let _ = file.write(log.as_bytes())?;
+    file.flush()?;
+
+    Ok(())
+}
+
+pub fn write_to_schemas(log: &str) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(data_file("schemas.log"))?;
 
     let _ = file.write(log.as_bytes())?;
     file.flush()?;
@@ -83,11 +179,25 @@ pub fn write_to_encrypts(log: &str) -> Result<(), Error> {
     Ok(())
 }
 
+pub fn write_to_audit(log: &str) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(data_file("audit.log"))?;
+
+    file.write_all(format!("{}\n", log).as_bytes())?;
+    file.flush()?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::io::paths::date_log;
     use crate::io::read::{
-        assert_content, assert_local_data, assert_offset, assert_unique_data, assert_uniques,
+        assert_audit, assert_content, assert_local_data, assert_offset, assert_unique_data,
+        assert_uniques,
     };
     #[test]
     fn write_unique() {
@@ -97,10 +207,17 @@ mod test {
 
     #[test]
     fn write_log() {
-        let _ = write_to_log("oh crazy log");
+        let date_log = date_log("my_entity", &chrono::Utc::now());
+        let _ = write_to_log(&date_log, "oh crazy log");
         assert_content("oh crazy log");
     }
 
+    #[test]
+    fn write_audit() {
+        let _ = write_to_audit("oh crazy audit log");
+        assert_audit("oh crazy audit log");
+    }
+
     #[test]
     fn offset_counter_test() {
         let _ = offset_counter(5_usize);
@@ -118,4 +235,37 @@ mod test {
         let _ = unique_data("some crazy date here");
         assert_unique_data("some crazy date here");
     }
+
+    #[test]
+    fn write_block_rolls_back() {
+        let dir = "data/write_block_test";
+        let _ = std::fs::remove_dir_all(dir);
+        let existing = format!("{}/existing.log", dir);
+        let created = format!("{}/created.log", dir);
+        let _ = write_to_log(&existing, "before;");
+
+        create_dir_all(format!("{}/directory.log", dir)).unwrap();
+        let logs = vec![
+            (existing.clone(), log_offset(&existing), "entry;".to_owned()),
+            (created.clone(), 0, "entry;".to_owned()),
+            (format!("{}/directory.log", dir), 0, "entry;".to_owned()),
+        ];
+        assert!(write_block(&logs).is_err());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "before;");
+        assert!(!Path::new(&created).exists());
+
+        let stale = vec![(existing.clone(), 0, "entry;".to_owned())];
+        assert!(write_block(&stale).is_err());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "before;");
+
+        let logs = vec![
+            (existing.clone(), log_offset(&existing), "entry;".to_owned()),
+            (created.clone(), 0, "entry;".to_owned()),
+        ];
+        assert_eq!(write_block(&logs).unwrap(), vec![false, true]);
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "before;entry;");
+        assert_eq!(std::fs::read_to_string(&created).unwrap(), "entry;");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -14,7 +14,8 @@ mod model;
 mod repository;
 mod schemas;
 
-use http::{ping, readiness, routes};
+use http::{cors::Cors, healthz, ping, readiness, routes, tls::RequireTls};
+use io::durability::{init_durability, sync_pending, Durability};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -34,12 +35,36 @@ async fn main() -> std::io::Result<()> {
         .parse::<u64>()
         .expect("CLIENT_SHUTDOWN must be a u64");
 
+    let env_query_threads = std::env::var("QUERY_THREADS").unwrap_or_else(|_| "0".to_owned());
+    let query_threads = env_query_threads
+        .parse::<usize>()
+        .expect("QUERY_THREADS must be a usize");
+    core::query::init_query_pool(query_threads);
+    if let Ok(env_max_nesting_depth) = std::env::var("MAX_NESTING_DEPTH") {
+        let max_nesting_depth = env_max_nesting_depth
+            .parse::<usize>()
+            .expect("MAX_NESTING_DEPTH must be a usize");
+        wql::set_max_nesting_depth(max_nesting_depth);
+    }
+    let durability = Durability::from_env().expect(
+        "WRITE_DURABILITY must be async, sync or batched and WRITE_BATCH_INTERVAL a positive u64",
+    );
+    init_durability(durability);
+    core::init_recover_poisoned_locks(core::recover_poisoned_locks_from_env());
+    core::init_output_indent_width(core::output_indent_width_from_env());
+
+    let cors = Cors::from_env();
+    let require_tls = RequireTls::from_env().expect("REQUIRE_TLS must be off, writes or all");
+
     HttpServer::new(move || {
         App::new()
+            .wrap(require_tls)
+            .wrap(cors.clone())
             .wrap(Logger::default())
             .wrap(DefaultHeaders::new().header("x-request-id", uuid::Uuid::new_v4().to_string()))
             .wrap(Logger::new("IP:%a DATETIME:%t REQUEST:\"%r\" STATUS: %s DURATION:%T X-REQUEST-ID:%{x-request-id}o"))
             .service(ping)
+            .service(healthz)
             .service(readiness)
             .configure(routes)
             .route("", web::get().to(HttpResponse::NotFound))
@@ -49,5 +74,8 @@ async fn main() -> std::io::Result<()> {
     .workers(1)
     .bind(addr)?
     .run()
-    .await
+    .await?;
+
+    sync_pending()?;
+    Ok(())
 }
@@ -5,7 +5,7 @@ use std::io;
 use uuid::Uuid;
 use wql::Types;
 
-use crate::schemas::error::Response;
+use crate::schemas::error::{Response, Violation, ViolationsResponse};
 
 #[derive(Debug)]
 pub enum Error {
@@ -16,7 +16,6 @@ pub enum Error {
     EntityNotCreated(String),
     EntityNotCreatedWithUniqueness(String),
     Serialization(ron::Error),
-    #[cfg(feature = "json")]
     SerdeJson(serde_json::Error),
     UuidNotCreatedForEntity(String, Uuid),
     FailedToParseState,
@@ -40,8 +39,27 @@ pub enum Error {
     AuthorizationBadRequest,
     AuthenticationBadRequest,
     AuthenticationBadRequestBody(String),
+    TlsRequired,
     FailedToCreateUser,
     FailedToDeleteUsers,
+    SessionNotFound,
+    SessionExpirationTooLong(i64, i64),
+    SchemaTypeMismatch(String, String, String, String),
+    KeyNotInSchema(String, String),
+    EnumVariantNotAllowed(String, String, String, Vec<String>),
+    VectorElementTypeMismatch(String, String, String, String),
+    InvalidBoolean(String, String, String),
+    InvalidValidInterval(String, String, String),
+    InvalidExpression(String),
+    ArithmeticOverflow(String, isize, isize),
+    VerifyNonHashKey(String),
+    VersionConflict(String, Uuid),
+    ImmutableEntity(String),
+    RegistryHashNotFound(String, String),
+    DayLogNotFound(String),
+    CsvUnavailable(String),
+    /// Schema errors of the statements of a batch, with the position of each statement.
+    SchemaViolations(Vec<(usize, Error)>),
     Unknown,
 }
 
@@ -67,112 +85,131 @@ pub fn error_to_http(e: &Error) -> HttpResponse {
         | Error::CheckNonEncryptedKeys(_)
         | Error::FailedToCreateUser
         | Error::FailedToDeleteUsers
+        | Error::SchemaTypeMismatch(_, _, _, _)
+        | Error::KeyNotInSchema(_, _)
+        | Error::EnumVariantNotAllowed(_, _, _, _)
+        | Error::VectorElementTypeMismatch(_, _, _, _)
+        | Error::InvalidBoolean(_, _, _)
+        | Error::InvalidValidInterval(_, _, _)
+        | Error::SchemaViolations(_)
+        | Error::InvalidExpression(_)
+        | Error::ArithmeticOverflow(_, _, _)
+        | Error::VerifyNonHashKey(_)
+        | Error::SessionExpirationTooLong(_, _)
         | Error::KeyTxTimeNotAllowed => HttpResponse::BadRequest().body(e.to_string()),
-        Error::EntityAlreadyCreated(_) => HttpResponse::UnprocessableEntity().body(e.to_string()),
-        #[cfg(feature = "json")]
+        Error::EntityAlreadyCreated(_) | Error::ImmutableEntity(_) => {
+            HttpResponse::UnprocessableEntity().body(e.to_string())
+        }
+        Error::SessionNotFound | Error::RegistryHashNotFound(_, _) | Error::DayLogNotFound(_) => {
+            HttpResponse::NotFound().body(e.to_string())
+        }
+        Error::CsvUnavailable(_) => HttpResponse::NotAcceptable().body(e.to_string()),
         Error::SerdeJson(_) => HttpResponse::InternalServerError().body(e.to_string()),
-        Error::FailedMatchCondition => HttpResponse::PreconditionFailed().body(e.to_string()),
+        Error::FailedMatchCondition | Error::VersionConflict(_, _) => {
+            HttpResponse::PreconditionFailed().body(e.to_string())
+        }
         Error::NonSelectQuery => HttpResponse::MethodNotAllowed().body(e.to_string()),
         Error::LockData => HttpResponse::ServiceUnavailable().body(e.to_string()),
         Error::AdminNotConfigured | Error::AuthorizationBadRequest | Error::Unknown => {
             HttpResponse::Unauthorized().body(e.to_string())
         }
-        Error::AuthenticationBadRequest | Error::AuthenticationBadRequestBody(_) => {
-            HttpResponse::Forbidden().body(e.to_string())
-        }
+        Error::AuthenticationBadRequest
+        | Error::AuthenticationBadRequestBody(_)
+        | Error::TlsRequired => HttpResponse::Forbidden().body(e.to_string()),
         Error::SelectBadRequest => HttpResponse::MethodNotAllowed().body(e.to_string()),
     }
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Error {
+    /// If the error is a value that does not match the schema of its entity.
+    pub fn is_schema_violation(&self) -> bool {
+        matches!(
+            self,
+            Error::SchemaTypeMismatch(_, _, _, _)
+                | Error::KeyNotInSchema(_, _)
+                | Error::EnumVariantNotAllowed(_, _, _, _)
+                | Error::VectorElementTypeMismatch(_, _, _, _)
+                | Error::InvalidBoolean(_, _, _)
+                | Error::InvalidValidInterval(_, _, _)
+        )
+    }
+
+    /// Type and message of the error, as written in the response body.
+    pub fn response(&self) -> Response {
         match self {
             Error::QueryFormat(s) => {
-                Response::new(String::from("QueryFormat"), format!("{:?}", s)).write(f)
+                Response::new(String::from("QueryFormat"), format!("{:?}", s))
             }
             Error::InvalidQuery => {
                 Response::new(
                     String::from("InvalidQuery"), 
                     "Only single value queries are allowed, so key `ID` is required and keys `WHEN AT` are optional".to_string()
-                ).write(f)
+                )
             }
-            Error::Io(e) => Response::new(String::from("IO"), format!("{:?}", e)).write(f),
+            Error::Io(e) => Response::new(String::from("IO"), format!("{:?}", e)),
             Error::EntityAlreadyCreated(e) => Response::new(
                 String::from("EntityAlreadyCreated"),
                 format!("Entity `{}` already created", e),
-            )
-            .write(f),
+            ),
             Error::EntityNotCreated(e) => Response::new(
                 String::from("EntityNotCreated"),
                 format!("Entity `{}` not created", e),
-            )
-            .write(f),
+            ),
             Error::EntityNotCreatedWithUniqueness(e) => Response::new(
                 String::from("EntityNotCreatedWithUniqueness"),
                 format!("Entity `{}` not created", e),
-            )
-            .write(f),
+            ),
             Error::Serialization(e) => {
-                Response::new(String::from("Serialization"), format!("{:?}", e)).write(f)
+                Response::new(String::from("Serialization"), format!("{:?}", e))
             }
-            #[cfg(feature = "json")]
             Error::SerdeJson(e) => {
-                Response::new(String::from("SerdeJson"), format!("{:?}", e)).write(f)
+                Response::new(String::from("SerdeJson"), format!("{:?}", e))
             }
             Error::UuidNotCreatedForEntity(s, id) => Response::new(
                 String::from("UuidNotCreatedForEntity"),
                 format!("Uuid {:?} not created for entity {}", id, s),
-            )
-            .write(f),
+            ),
             Error::FailedToParseState => Response::new(
                 String::from("FailedToParseState"),
                 "Failed to parse state".to_string(),
-            )
-            .write(f),
+            ),
             Error::FailedToParseRegistry => Response::new(
                 String::from("FailedToParseRegistry"),
                 "Failed to parse registry".to_string(),
-            )
-            .write(f),
+            ),
             Error::DuplicatedUnique(entity, key, t) => Response::new(
                 String::from("DuplicatedUnique"),
                 format!(
                     "key `{}` in entity `{}` already contains value `{:?}`",
                     key, entity, t
                 ),
-            )
-            .write(f),
+            ),
             Error::UnknownCondition => Response::new(
                 String::from("UnknownCondition"),
                 "UNKNOWN MATCH CONDITION".to_string(),
-            )
-            .write(f),
+            ),
             Error::FailedMatchCondition => Response::new(
                 String::from("FailedMatchCondition"),
                 "One or more MATCH CONDITIONS failed".to_string(),
-            )
-            .write(f),
+            ),
             Error::SelectBadRequest => Response::new(
                 String::from("SelectBadRequest"),
                 "SELECT expressions are handled by `/wql/query` endpoint".to_string(),
-            )
-            .write(f),
+            ),
             Error::NonSelectQuery => Response::new(
                 String::from("NonSelectQuery"),
                 "Non-SELECT expressions are handled by `/wql/tx` endpoint".to_string(),
-            )
-            .write(f),
+            ),
             Error::ActixMailbox(r) => {
-                Response::new(String::from("ActixMailbox"), format!("{:?}", r)).write(f)
+                Response::new(String::from("ActixMailbox"), format!("{:?}", r))
             }
             Error::LockData => Response::new(
                 String::from("LockData"),
                 "System was not able to get a lock on data".to_string(),
-            )
-            .write(f),
-            Error::Ron(e) => Response::new(String::from("Ron"), format!("{:?}", e)).write(f),
+            ),
+            Error::Ron(e) => Response::new(String::from("Ron"), format!("{:?}", e)),
             Error::InvalidUuid(e) => {
-                Response::new(String::from("InvalidUuid"), format!("{:?}", e)).write(f)
+                Response::new(String::from("InvalidUuid"), format!("{:?}", e))
             }
             Error::UpdateContentEncryptKeys(keys) => Response::new(
                 String::from("UpdateContentEncryptKeys"),
@@ -180,68 +217,176 @@ impl std::fmt::Display for Error {
                     "Encrypted keys cannont be updated with UPDATE CONTENT: {:?}",
                     keys
                 ),
-            )
-            .write(f),
+            ),
             Error::CheckNonEncryptedKeys(keys) => Response::new(
                 String::from("CheckNonEncryptedKeys"),
                 format!("CHECK can only verify encrypted keys: {:?}", keys),
-            )
-            .write(f),
+            ),
             Error::DateTimeParse(e) => Response::new(
                 String::from("DateTimeParse"),
                 format!("Date parse error: {:?}", e),
-            )
-            .write(f),
+            ),
             Error::FailedToParseDate => Response::new(
                 String::from("FailedToParseDate"),
                 "Log date parse error".to_string(),
-            )
-            .write(f),
+            ),
             Error::AdminNotConfigured => Response::new(
                 String::from("AdminNotConfigured"),
                 "Admin credentials not configured".to_string(),
-            )
-            .write(f),
+            ),
             Error::AuthorizationBadRequest => Response::new(
                 String::from("AuthorizationBadRequest"),
                 "Bad request at authorizing endpoint".to_string(),
-            )
-            .write(f),
+            ),
             Error::AuthenticationBadRequest => Response::new(
                 String::from("AuthenticationBadRequest"),
                 "Bad request at authenticating endpoint".to_string(),
-            )
-            .write(f),
+            ),
             Error::AuthenticationBadRequestBody(error) => Response::new(
                 String::from("AuthenticationBadRequest"),
                 format!("Bad request: {}", error),
-            )
-            .write(f),
+            ),
             Error::FailedToCreateUser => Response::new(
                 String::from("FailedToCreateUser"),
                 "Failed to create requested user".to_string(),
-            )
-            .write(f),
+            ),
             Error::FailedToDeleteUsers => Response::new(
                 String::from("FailedToDeleteUsers"),
                 "Failed to delete requested users".to_string(),
-            )
-            .write(f),
+            ),
+            Error::SessionNotFound => Response::new(
+                String::from("SessionNotFound"),
+                "Session token not found".to_string(),
+            ),
+            Error::SessionExpirationTooLong(requested, max) => Response::new(
+                String::from("SessionExpirationTooLong"),
+                format!(
+                    "Session expiration of {} seconds exceeds the maximum of {} seconds",
+                    requested, max
+                ),
+            ),
             Error::Unknown => Response::new(
                 String::from("Unknown"),
                 "Request credentials failed".to_string(),
-            )
-            .write(f),
+            ),
             Error::KeyTxTimeNotAllowed => Response::new(
                 String::from("KeyTxTimeNotAllowed"),
                 "Key `tx_time` is not allowed".to_string(),
+            ),
+            Error::SchemaTypeMismatch(entity, key, expected, found) => Response::new(
+                String::from("SchemaTypeMismatch"),
+                format!(
+                    "Key `{}` of entity `{}` must be of type {}, found {}",
+                    key, entity, expected, found
+                ),
+            ),
+            Error::KeyNotInSchema(entity, key) => Response::new(
+                String::from("KeyNotInSchema"),
+                format!(
+                    "Key `{}` is not declared in the EXHAUSTIVE schema of entity `{}`",
+                    key, entity
+                ),
+            ),
+            Error::EnumVariantNotAllowed(entity, key, value, allowed) => Response::new(
+                String::from("EnumVariantNotAllowed"),
+                format!(
+                    "Value `{}` of key `{}` in entity `{}` is not one of the variants {:?}",
+                    value, key, entity, allowed
+                ),
+            ),
+            Error::InvalidBoolean(entity, key, value) => Response::new(
+                String::from("InvalidBoolean"),
+                format!(
+                    "Value `{}` of key `{}` in entity `{}` is not a boolean, expected true/false, 1/0 or yes/no",
+                    value, key, entity
+                ),
+            ),
+            Error::InvalidExpression(message) => {
+                Response::new(String::from("InvalidExpression"), message.to_owned())
+            }
+            Error::InvalidValidInterval(entity, from, to) => Response::new(
+                String::from("InvalidValidInterval"),
+                format!(
+                    "Key `valid_to` {} in entity `{}` must be after `valid_from` {}",
+                    to, entity, from
+                ),
+            ),
+            Error::VectorElementTypeMismatch(entity, key, expected, found) => Response::new(
+                String::from("VectorElementTypeMismatch"),
+                format!(
+                    "Elements of key `{}` in entity `{}` must be of type {}, found {}",
+                    key, entity, expected, found
+                ),
+            ),
+            Error::ArithmeticOverflow(key, value, increment) => Response::new(
+                String::from("ArithmeticOverflow"),
+                format!(
+                    "Adding {} to key `{}` with value {} overflows Integer",
+                    increment, key, value
+                ),
+            ),
+            Error::VersionConflict(entity, id) => Response::new(
+                String::from("VersionConflict"),
+                format!(
+                    "Entity {} with Uuid {} changed since the version in IF VERSION",
+                    entity, id
+                ),
+            ),
+            Error::ImmutableEntity(entity) => Response::new(
+                String::from("ImmutableEntity"),
+                format!(
+                    "Entity {} is APPEND ONLY, its ids can only be inserted",
+                    entity
+                ),
+            ),
+            Error::RegistryHashNotFound(entity, hash) => Response::new(
+                String::from("RegistryHashNotFound"),
+                format!("Entity {} has no registry with hash {}", entity, hash),
+            ),
+            Error::DayLogNotFound(date) => Response::new(
+                String::from("DayLogNotFound"),
+                format!("No day log was written on {}", date),
+            ),
+            Error::CsvUnavailable(reason) => Response::new(
+                String::from("CsvUnavailable"),
+                format!("Response can't be written as CSV. {}", reason),
+            ),
+            Error::SchemaViolations(violations) => Response::new(
+                String::from("SchemaViolations"),
+                format!(
+                    "{} statements do not match the schemas of their entities",
+                    violations.len()
+                ),
+            ),
+            Error::TlsRequired => Response::new(
+                String::from("TlsRequired"),
+                "Request must arrive over TLS, header `X-Forwarded-Proto` must be `https`"
+                    .to_string(),
+            ),
+            Error::VerifyNonHashKey(key) => Response::new(
+                String::from("VerifyNonHashKey"),
+                format!("VERIFY can only be applied to hashed keys, `{}` is not a Hash", key),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::SchemaViolations(violations) => ViolationsResponse::new(
+                self.response(),
+                violations
+                    .iter()
+                    .map(|(statement, e)| Violation::new(*statement, e.response()))
+                    .collect(),
             )
             .write(f),
+            _ => self.response().write(f),
         }
     }
 }
 
-#[cfg(feature = "json")]
 impl From<serde_json::Error> for Error {
     fn from(error: serde_json::Error) -> Self {
         Error::SerdeJson(error)
@@ -272,4 +417,8 @@ impl From<uuid::Error> for Error {
     }
 }
 
-impl error::ResponseError for Error {}
+impl error::ResponseError for Error {
+    fn error_response(&self) -> HttpResponse {
+        error_to_http(self)
+    }
+}
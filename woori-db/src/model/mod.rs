@@ -10,23 +10,48 @@ use std::{
 };
 
 use crate::{
-    actors::wql::Executor,
-    repository::local::{EncryptContext, LocalContext, UniquenessContext},
+    actors::{audit::Auditor, wql::Executor},
+    repository::local::{
+        EncryptContext, IdempotencyContext, LocalContext, SchemaContext, SequenceContext,
+        UniquenessContext,
+    },
 };
 
 pub type DataLocalContext = web::Data<Arc<Mutex<LocalContext>>>;
 pub type DataUniquenessContext = web::Data<Arc<Mutex<UniquenessContext>>>;
 pub type DataEncryptContext = web::Data<Arc<Mutex<EncryptContext>>>;
+pub type DataSchemaContext = web::Data<Arc<Mutex<SchemaContext>>>;
+pub type DataIdempotencyContext = web::Data<Arc<Mutex<IdempotencyContext>>>;
+pub type DataSequenceContext = web::Data<Arc<Mutex<SequenceContext>>>;
 pub type DataAtomicUsize = web::Data<AtomicUsize>;
 pub type DataU32 = web::Data<u32>;
 pub type DataI64 = web::Data<i64>;
 pub type DataExecutor = web::Data<Addr<Executor>>;
+pub type DataAuditor = web::Data<Addr<Auditor>>;
+/// Held by a tx from its validation until its registries are swapped, so the `.await`s in
+/// between can't interleave another write.
+pub type DataWriteLock = web::Data<tokio::sync::Mutex<()>>;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Derived ordering follows `file_name` and then `offset`, which is the order registries were written.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct DataRegister {
     pub file_name: String,
     pub offset: usize,
     pub bytes_length: usize,
+    /// CRC32 of the registry bytes, verified when `local_data` is loaded at startup.
+    /// Registries written before checksums existed have none.
+    #[serde(default)]
+    pub checksum: Option<u32>,
+}
+
+impl DataRegister {
+    /// Token returned by `SELECT ... WITH VERSION` and checked by `UPDATE ... IF VERSION`,
+    /// it changes every time a new registry is written for the entity. It is a CRC32 of the
+    /// registry position followed by its checksum, so it is stable across releases.
+    pub fn version(&self) -> String {
+        let position = crc32fast::hash(format!("{}:{}", self.file_name, self.offset).as_bytes());
+        format!("{:08x}{:08x}", position, self.checksum.unwrap_or_default())
+    }
 }
 
 impl Message for DataRegister {
@@ -41,3 +66,19 @@ impl Handler<DataRegister> for Executor {
         read_log(msg)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn version_is_stable() {
+        let register = DataRegister {
+            file_name: "data/2021_02_10.log".to_owned(),
+            offset: 479,
+            bytes_length: 150,
+            checksum: Some(1234),
+        };
+        assert_eq!(register.version(), "b45f29aa000004d2");
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use uuid::Uuid;
-use wql::{MatchCondition, Types};
+use wql::{Expression, MatchCondition, Types};
 
 #[derive(Debug, PartialEq)]
 pub enum Action {
@@ -10,9 +10,11 @@ pub enum Action {
     Read,
     UpdateSet,
     UpdateContent,
+    Replace,
     Delete,
     EvictEntity,
     EvictEntityId,
+    ClearEntity,
     Error,
 }
 
@@ -24,9 +26,11 @@ impl std::fmt::Display for Action {
             Action::Insert => write!(f, "INSERT"),
             Action::UpdateSet => write!(f, "UPDATE_SET"),
             Action::UpdateContent => write!(f, "UPDATE_CONTENT"),
+            Action::Replace => write!(f, "REPLACE"),
             Action::Delete => write!(f, "DELETE"),
             Action::EvictEntity => write!(f, "EVICT_ENTITY"),
             Action::EvictEntityId => write!(f, "EVICT_ENTITY_ID"),
+            Action::ClearEntity => write!(f, "CLEAR_ENTITY"),
             Action::Error => write!(f, "Error"),
         }
     }
@@ -41,8 +45,10 @@ impl From<String> for Action {
             "DELETE" => Action::Delete,
             "UPDATE_SET" => Action::UpdateSet,
             "UPDATE_CONTENT" => Action::UpdateContent,
+            "REPLACE" => Action::Replace,
             "EVICT_ENTITY" => Action::EvictEntity,
             "EVICT_ENTITY_ID" => Action::EvictEntityId,
+            "CLEAR_ENTITY" => Action::ClearEntity,
             _ => Action::Error,
         }
     }
@@ -53,6 +59,8 @@ pub struct MatchUpdateArgs {
     pub content: HashMap<String, Types>,
     pub id: uuid::Uuid,
     pub conditions: MatchCondition,
+    pub expressions: HashMap<String, Expression>,
+    pub dry_run: bool,
 }
 
 impl MatchUpdateArgs {
@@ -61,12 +69,16 @@ impl MatchUpdateArgs {
         content: HashMap<String, Types>,
         id: uuid::Uuid,
         conditions: MatchCondition,
+        expressions: HashMap<String, Expression>,
+        dry_run: bool,
     ) -> Self {
         Self {
             entity,
             content,
             id,
             conditions,
+            expressions,
+            dry_run,
         }
     }
 }
@@ -75,14 +87,24 @@ pub struct UpdateArgs {
     pub entity: String,
     pub content: HashMap<String, Types>,
     pub id: Uuid,
+    pub version: Option<String>,
+    pub dry_run: bool,
 }
 
 impl UpdateArgs {
-    pub fn new(entity: String, content: HashMap<String, Types>, id: Uuid) -> Self {
+    pub fn new(
+        entity: String,
+        content: HashMap<String, Types>,
+        id: Uuid,
+        version: Option<String>,
+        dry_run: bool,
+    ) -> Self {
         Self {
             entity,
             content,
             id,
+            version,
+            dry_run,
         }
     }
 }
@@ -91,14 +113,21 @@ pub struct InsertArgs {
     pub entity: String,
     pub content: HashMap<String, Types>,
     pub uuid: Option<Uuid>,
+    pub dry_run: bool,
 }
 
 impl InsertArgs {
-    pub fn new(entity: String, content: HashMap<String, Types>, uuid: Option<Uuid>) -> Self {
+    pub fn new(
+        entity: String,
+        content: HashMap<String, Types>,
+        uuid: Option<Uuid>,
+        dry_run: bool,
+    ) -> Self {
         Self {
             entity,
             content,
             uuid,
+            dry_run,
         }
     }
 }
@@ -121,5 +150,6 @@ mod test {
             Action::from(String::from("UPDATE_CONTENT")),
             Action::UpdateContent
         );
+        assert_eq!(Action::from(String::from("REPLACE")), Action::Replace);
     }
 }
@@ -4,23 +4,46 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use wql::EntitySchema;
+
 use crate::{auth::schemas::Role, model::DataRegister};
 
 pub type StateInfo = (DataRegister, Vec<u8>);
 pub type LocalContext = BTreeMap<String, BTreeMap<Uuid, StateInfo>>;
 pub type UniquenessContext = BTreeMap<String, HashMap<String, HashSet<String>>>;
 pub type EncryptContext = BTreeMap<String, HashSet<String>>;
+pub type SchemaContext = BTreeMap<String, EntitySchema>;
 pub type SessionContext = BTreeMap<String, SessionInfo>;
+pub type IdempotencyContext = BTreeMap<(String, String), IdempotencyInfo>;
+/// Last number assigned by `INSERT ... WITH NEXT` for each entity.
+pub type SequenceContext = BTreeMap<String, u64>;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SessionInfo {
+    user_id: Uuid,
     expiration: DateTime<Utc>,
     roles: Vec<Role>,
 }
 
 impl SessionInfo {
-    pub fn new(expiration: DateTime<Utc>, roles: Vec<Role>) -> Self {
-        Self { expiration, roles }
+    pub fn new(user_id: Uuid, expiration: DateTime<Utc>, roles: Vec<Role>) -> Self {
+        Self {
+            user_id,
+            expiration,
+            roles,
+        }
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        self.user_id
+    }
+
+    pub fn expiration(&self) -> DateTime<Utc> {
+        self.expiration
+    }
+
+    pub fn roles(&self) -> Vec<Role> {
+        self.roles.clone()
     }
 
     #[cfg(not(debug_assertions))]
@@ -34,3 +57,26 @@ impl SessionInfo {
         self.expiration > now
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyInfo {
+    expiration: DateTime<Utc>,
+    response: String,
+}
+
+impl IdempotencyInfo {
+    pub fn new(response: String, ttl: i64) -> Self {
+        Self {
+            expiration: Utc::now() + chrono::Duration::seconds(ttl),
+            response,
+        }
+    }
+
+    pub fn is_valid_date(&self) -> bool {
+        self.expiration > Utc::now()
+    }
+
+    pub fn response(&self) -> String {
+        self.response.clone()
+    }
+}
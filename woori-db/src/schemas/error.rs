@@ -22,3 +22,45 @@ impl Response {
         write!(f, "{}", output)
     }
 }
+
+/// Schema violation of the statement at position `statement` of a batch, counted from 0.
+#[derive(Debug, Clone, Serialize)]
+pub struct Violation {
+    statement: usize,
+    error_type: String,
+    error_message: String,
+}
+
+impl Violation {
+    pub fn new(statement: usize, response: Response) -> Self {
+        Self {
+            statement,
+            error_type: response.error_type,
+            error_message: response.error_message,
+        }
+    }
+}
+
+/// Every schema violation of a batch, so all offending statements can be fixed at once.
+#[derive(Debug, Clone, Serialize)]
+pub struct ViolationsResponse {
+    error_type: String,
+    error_message: String,
+    violations: Vec<Violation>,
+}
+
+impl ViolationsResponse {
+    pub fn new(response: Response, violations: Vec<Violation>) -> Self {
+        Self {
+            error_type: response.error_type,
+            error_message: response.error_message,
+            violations,
+        }
+    }
+
+    pub fn write(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = ron::ser::to_string_pretty(self, pretty_config_output())
+            .unwrap_or_else(|_| "SERVER ERROR".to_owned());
+        write!(f, "{}", output)
+    }
+}
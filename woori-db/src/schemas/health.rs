@@ -0,0 +1,9 @@
+use serde::Serialize;
+
+/// Response of `/healthz`, with the configured write durability.
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    pub status: String,
+    pub durability: String,
+    pub batch_interval_ms: Option<u64>,
+}
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
+use wql::Types;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct EntityHistoryInfo {
@@ -8,4 +10,23 @@ pub struct EntityHistoryInfo {
     pub entity_id: Uuid,
     pub start_datetime: Option<DateTime<Utc>>,
     pub end_datetime: Option<DateTime<Utc>>,
+    /// Returns only the last `n` registries of the id, read from the day logs. Start and
+    /// end dates are ignored.
+    #[serde(default)]
+    pub last_n: Option<usize>,
+}
+
+/// Body of `/entity-by-hash`, `hash` is a registry version like the `_version` returned
+/// by `WITH VERSION`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EntityByHashInfo {
+    pub entity_key: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashedRegistry {
+    pub entity_id: Uuid,
+    pub datetime: DateTime<Utc>,
+    pub state: HashMap<String, Types>,
 }
@@ -1,4 +1,5 @@
 pub mod error;
+pub mod health;
 pub mod history;
 pub mod query;
 pub mod tx;
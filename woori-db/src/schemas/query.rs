@@ -1,71 +1,40 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::{core::pretty_config_output, model::error::Error};
+use crate::{
+    core::{pretty_config_output, query::mask_keys, sorted::Sorted, MediaType, OutputFormat},
+    model::error::Error,
+};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use uuid::Uuid;
 use wql::Types;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct CountResponse {
-    response: Box<Response>,
-    count: usize,
+/// Body of `/wql/query/params`, the query and the values of its `$n` placeholders.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParameterizedQuery {
+    pub query: String,
+    #[serde(default)]
+    pub params: Vec<Types>,
 }
 
-#[derive(Serialize)]
-pub struct CountId {
-    response: HashMap<String, Types>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountAll {
-    response: BTreeMap<Uuid, HashMap<String, Types>>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountOrder {
-    response: Vec<(Uuid, HashMap<String, Types>)>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountGroupBy {
-    response: HashMap<String, BTreeMap<Uuid, HashMap<String, Types>>>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountOrderedGroupBy {
-    response: HashMap<String, Vec<(Uuid, HashMap<String, Types>)>>,
-    count: usize,
+/// Entry of `/wql/check`, like `CHECK {content} FROM entity ID id`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckEntry {
+    pub entity: String,
+    pub id: Uuid,
+    pub content: HashMap<String, String>,
 }
-#[derive(Serialize)]
-pub struct CountOptionOrder {
-    response: Vec<(Uuid, Option<HashMap<String, Types>>)>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountOptionGroupBy {
-    response: HashMap<String, BTreeMap<Uuid, Option<HashMap<String, Types>>>>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountOptionSelect {
-    response: BTreeMap<Uuid, Option<HashMap<String, Types>>>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountCheckValues {
-    response: HashMap<String, bool>,
-    count: usize,
-}
-#[derive(Serialize)]
-pub struct CountTimeRange {
-    response: BTreeMap<DateTime<Utc>, HashMap<String, Types>>,
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CountResponse {
+    response: Box<Response>,
     count: usize,
 }
 
+/// `COUNT` response with its entities, the response is serialized like `Payload`.
 #[derive(Serialize)]
-pub struct CountDateSelect {
-    response: HashMap<String, HashMap<String, Types>>,
+struct Counted<'a> {
+    response: Payload<'a>,
     count: usize,
 }
 
@@ -77,131 +46,73 @@ impl CountResponse {
         }
     }
 
-    pub fn to_response(&self) -> Result<String, Error> {
-        let count = self.count;
+    pub fn response(&self) -> &Response {
+        &self.response
+    }
+
+    pub fn to_response(&self, format: OutputFormat, media: MediaType) -> Result<String, Error> {
+        let resp = Counted {
+            response: Payload(&self.response),
+            count: self.count,
+        };
         match &*self.response {
-            Response::Id(state) => {
-                let resp = CountId {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::All(state) => {
-                let resp = CountAll {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::Order(state) => {
-                let resp = CountOrder {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::GroupBy(state) => {
-                let resp = CountGroupBy {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::OrderedGroupBy(state) => {
-                let resp = CountOrderedGroupBy {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::OptionOrder(state) => {
-                let resp = CountOptionOrder {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::OptionGroupBy(state) => {
-                let resp = CountOptionGroupBy {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::OptionSelect(state) => {
-                let resp = CountOptionSelect {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::CheckValues(state) => {
-                let resp = CountCheckValues {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::TimeRange(state) => {
-                let resp = CountTimeRange {
-                    count,
-                    response: state.to_owned(),
-                };
-                #[cfg(feature = "json")]
-                return Ok(serde_json::to_string(&resp)?);
-
-                #[cfg(not(feature = "json"))]
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
-            Response::DateSelect(state) => {
-                let resp = CountDateSelect {
-                    count,
-                    response: state.to_owned(),
-                };
-                Ok(ron::ser::to_string_pretty(&resp, pretty_config_output())?)
-            }
+            Response::DateSelect(_)
+            | Response::Id(_)
+            | Response::All(_)
+            | Response::Order(_)
+            | Response::GroupBy(_)
+            | Response::OrderedGroupBy(_)
+            | Response::OptionOrder(_)
+            | Response::OptionGroupBy(_)
+            | Response::OptionSelect(_)
+            | Response::CheckValues(_)
+            | Response::TimeRange(_) => format.write(media, &resp),
             _ => Err(Error::Unknown),
         }
     }
 }
 
+/// Query response with its metadata, requested with `?envelope=true`.
+#[derive(Serialize)]
+struct Envelope<'a> {
+    response: Payload<'a>,
+    count: usize,
+    elapsed_ms: u64,
+    truncated: bool,
+}
+
+/// Content of a `Response` serialized the same way as `Response::to_string`, without
+/// the variant name and with the keys of entity maps in order. `COUNT` responses are
+/// unwrapped as the envelope has the count.
+struct Payload<'a>(&'a Response);
+
+impl Serialize for Payload<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Response::Id(state)
+            | Response::Intersect(state)
+            | Response::Difference(state)
+            | Response::Union(state) => Sorted(state).serialize(serializer),
+            Response::All(state) => Sorted(state).serialize(serializer),
+            Response::Order(state) => Sorted(state).serialize(serializer),
+            Response::GroupBy(state) => Sorted(state).serialize(serializer),
+            Response::OrderedGroupBy(state) => Sorted(state).serialize(serializer),
+            Response::OptionOrder(state) => Sorted(state).serialize(serializer),
+            Response::OptionGroupBy(state) => Sorted(state).serialize(serializer),
+            Response::OptionSelect(state) => Sorted(state).serialize(serializer),
+            Response::CheckValues(state) => Sorted(state).serialize(serializer),
+            Response::TimeRange(state) => Sorted(state).serialize(serializer),
+            Response::WithCount(state) => Payload(&state.response).serialize(serializer),
+            Response::DateSelect(state) => Sorted(state).serialize(serializer),
+            Response::Join(state) => Sorted(state).serialize(serializer),
+            Response::Version(info) => info.serialize(serializer),
+            Response::Describe(description) => description.serialize(serializer),
+            Response::Exists(exists) => exists.serialize(serializer),
+            Response::Count(count) => count.serialize(serializer),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Response {
     Id(HashMap<String, Types>),
@@ -220,6 +131,104 @@ pub enum Response {
     WithCount(CountResponse),
     DateSelect(HashMap<String, HashMap<String, Types>>),
     Join(Vec<HashMap<String, Types>>),
+    Version(VersionInfo),
+    Describe(BTreeMap<String, KeyDescription>),
+    /// Result of `EXISTS <uuid> IN entity`.
+    Exists(bool),
+    /// Result of `SELECT COUNT(*)`.
+    Count(usize),
+}
+
+/// Type information of a key returned by `DESCRIBE ENTITY`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum KeyDescription {
+    /// Type declared in the entity schema, like `Integer`, `Vector<String>` or `Enum(A | B)`.
+    Declared(String),
+    /// Type names found in the current states and how many states hold each of them.
+    Observed(BTreeMap<String, usize>),
+}
+
+/// Build information returned by the `VERSION` statement.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct VersionInfo {
+    pub version: String,
+    pub features: Vec<String>,
+    pub format: String,
+    pub wql_revision: String,
+}
+
+impl Default for VersionInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VersionInfo {
+    pub fn new() -> Self {
+        let features = [
+            ("history", cfg!(feature = "history")),
+            ("json", cfg!(feature = "json")),
+            ("test_read", cfg!(feature = "test_read")),
+        ]
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| (*feature).to_string())
+        .collect();
+        let format = if cfg!(feature = "json") {
+            "json"
+        } else {
+            "ron"
+        };
+
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features,
+            format: format.to_string(),
+            wql_revision: wql::GRAMMAR_REVISION.to_string(),
+        }
+    }
+}
+
+/// Entries of a time range written as one chunk of a streamed response. Chunks
+/// concatenated in order, followed by `time_range_end`, give the same output as
+/// `Response::TimeRange(..).to_string()`.
+pub fn time_range_chunk(
+    states: &BTreeMap<DateTime<Utc>, HashMap<String, Types>>,
+    first: bool,
+) -> Result<String, Error> {
+    let map = ron::ser::to_string_pretty(states, pretty_config_output())?;
+    let entries = map.trim_start_matches('{').trim_end_matches('}').trim_end();
+    Ok(if first {
+        format!("{{{}", entries)
+    } else {
+        entries.to_owned()
+    })
+}
+
+pub fn time_range_end(empty: bool) -> &'static str {
+    if empty {
+        "{}"
+    } else {
+        "\n}"
+    }
+}
+
+impl From<BTreeMap<String, KeyDescription>> for Response {
+    fn from(description: BTreeMap<String, KeyDescription>) -> Self {
+        Self::Describe(description)
+    }
+}
+
+impl From<bool> for Response {
+    fn from(exists: bool) -> Self {
+        Self::Exists(exists)
+    }
+}
+
+impl From<VersionInfo> for Response {
+    fn from(info: VersionInfo) -> Self {
+        Self::Version(info)
+    }
 }
 
 impl From<CountResponse> for Response {
@@ -314,6 +323,10 @@ impl Response {
             | Response::GroupBy(_)
             | Response::OrderedGroupBy(_)
             | Response::Join(_)
+            | Response::Version(_)
+            | Response::Describe(_)
+            | Response::Exists(_)
+            | Response::Count(_)
             | Response::DateSelect(_) => {
                 return false;
             }
@@ -417,6 +430,19 @@ impl Response {
         true
     }
 
+    /// Flattens the states of multiple values responses, `None` for the others.
+    pub fn states(self) -> Option<Vec<HashMap<String, Types>>> {
+        match self {
+            Response::All(state) => Some(state.into_values().collect()),
+            Response::Order(state) => Some(state.into_iter().map(|(_, s)| s).collect()),
+            Response::OptionOrder(state) => {
+                Some(state.into_iter().filter_map(|(_, s)| s).collect())
+            }
+            Response::OptionSelect(state) => Some(state.into_values().flatten().collect()),
+            _ => None,
+        }
+    }
+
     pub fn hash(self, key: &str) -> Option<HashMap<Types, Vec<HashMap<String, Types>>>> {
         let mut hm = HashMap::new();
         match self {
@@ -467,6 +493,10 @@ impl Response {
             | Response::Union(_)
             | Response::GroupBy(_)
             | Response::OrderedGroupBy(_)
+            | Response::Version(_)
+            | Response::Describe(_)
+            | Response::Exists(_)
+            | Response::Count(_)
             | Response::DateSelect(_) => {
                 return None;
             }
@@ -475,50 +505,165 @@ impl Response {
         Some(hm)
     }
 
-    pub fn to_string(&self) -> Result<String, Error> {
+    /// Number of rows of the response, entities maps of all groups are counted and a
+    /// single entity map is one row.
+    pub fn rows(&self) -> usize {
         match self {
-            Response::Id(state) => Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?),
-            Response::Intersect(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::Difference(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::Union(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::CheckValues(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::TimeRange(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::All(state) => Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?),
-            Response::Order(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::GroupBy(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::OrderedGroupBy(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::OptionOrder(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::OptionGroupBy(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::WithCount(state) => state.to_response(),
-            Response::OptionSelect(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::DateSelect(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
-            Response::Join(state) => {
-                Ok(ron::ser::to_string_pretty(&state, pretty_config_output())?)
-            }
+            Response::Id(state)
+            | Response::Intersect(state)
+            | Response::Difference(state)
+            | Response::Union(state) => usize::from(!state.is_empty()),
+            Response::All(state) => state.len(),
+            Response::Order(state) => state.len(),
+            Response::GroupBy(state) => state.values().map(BTreeMap::len).sum(),
+            Response::OrderedGroupBy(state) => state.values().map(Vec::len).sum(),
+            Response::OptionOrder(state) => state.len(),
+            Response::OptionGroupBy(state) => state.values().map(BTreeMap::len).sum(),
+            Response::OptionSelect(state) => state.len(),
+            Response::CheckValues(state) => state.len(),
+            Response::TimeRange(state) => state.len(),
+            Response::WithCount(state) => state.count,
+            Response::DateSelect(state) => state.len(),
+            Response::Join(state) => state.len(),
+            Response::Version(_) => 1,
+            Response::Describe(description) => description.len(),
+            Response::Exists(_) => 1,
+            Response::Count(count) => *count,
+        }
+    }
+
+    /// Masks the values of `keys` in every entity map of the response, see `mask_keys`.
+    pub fn mask_keys(&mut self, keys: &HashSet<String>) {
+        if keys.is_empty() {
+            return;
+        }
+        match self {
+            Response::Id(state)
+            | Response::Intersect(state)
+            | Response::Difference(state)
+            | Response::Union(state) => mask_keys(state, keys),
+            Response::All(state) => state.values_mut().for_each(|s| mask_keys(s, keys)),
+            Response::Order(state) => state.iter_mut().for_each(|(_, s)| mask_keys(s, keys)),
+            Response::GroupBy(state) => state
+                .values_mut()
+                .flat_map(BTreeMap::values_mut)
+                .for_each(|s| mask_keys(s, keys)),
+            Response::OrderedGroupBy(state) => state
+                .values_mut()
+                .flatten()
+                .for_each(|(_, s)| mask_keys(s, keys)),
+            Response::OptionOrder(state) => state
+                .iter_mut()
+                .filter_map(|(_, s)| s.as_mut())
+                .for_each(|s| mask_keys(s, keys)),
+            Response::OptionGroupBy(state) => state
+                .values_mut()
+                .flat_map(BTreeMap::values_mut)
+                .flatten()
+                .for_each(|s| mask_keys(s, keys)),
+            Response::OptionSelect(state) => state
+                .values_mut()
+                .flatten()
+                .for_each(|s| mask_keys(s, keys)),
+            Response::TimeRange(state) => state.values_mut().for_each(|s| mask_keys(s, keys)),
+            Response::DateSelect(state) => state.values_mut().for_each(|s| mask_keys(s, keys)),
+            Response::Join(state) => state.iter_mut().for_each(|s| mask_keys(s, keys)),
+            Response::WithCount(state) => state.response.mask_keys(keys),
+            Response::CheckValues(_)
+            | Response::Version(_)
+            | Response::Describe(_)
+            | Response::Exists(_)
+            | Response::Count(_) => (),
         }
     }
+
+    /// Wraps the response with its number of rows, the query execution time and if the
+    /// rows reached the query's `LIMIT`, in which case more rows may exist.
+    pub fn to_envelope(
+        &self,
+        format: OutputFormat,
+        media: MediaType,
+        elapsed_ms: u64,
+        limit: Option<usize>,
+    ) -> Result<String, Error> {
+        let count = self.rows();
+        let envelope = Envelope {
+            response: Payload(self),
+            count,
+            elapsed_ms,
+            truncated: limit.is_some_and(|limit| count >= limit),
+        };
+        format.write(media, &envelope)
+    }
+
+    /// Writes the response with the `ResponseSerializer` of the negotiated `MediaType`.
+    pub fn to_string(&self, format: OutputFormat, media: MediaType) -> Result<String, Error> {
+        media.serializer(format).serialize(self)
+    }
+
+    /// Writes the response as RON or JSON, `MediaType::Csv` is written as RON.
+    pub(crate) fn write(&self, format: OutputFormat, media: MediaType) -> Result<String, Error> {
+        match self {
+            Response::WithCount(state) => state.to_response(format, media),
+            Response::Version(info) => format.write(media, info),
+            _ => format.write(media, &Payload(self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn envelope_truncated_at_limit() {
+        let mut state = BTreeMap::new();
+        state.insert(Uuid::nil(), HashMap::new());
+        let response: Response = state.into();
+
+        let envelope = response
+            .to_envelope(OutputFormat::Compact, MediaType::Ron, 3, Some(1))
+            .unwrap();
+        assert_eq!(
+            envelope,
+            "(response:{\"00000000-0000-0000-0000-000000000000\":{}},count:1,elapsed_ms:3,truncated:true)"
+        );
+
+        let envelope = response
+            .to_envelope(OutputFormat::Compact, MediaType::Ron, 3, Some(2))
+            .unwrap();
+        assert!(envelope.ends_with("count:1,elapsed_ms:3,truncated:false)"));
+    }
+
+    #[test]
+    fn envelope_count_of_groups() {
+        let mut group = BTreeMap::new();
+        group.insert(Uuid::nil(), HashMap::new());
+        let mut state = HashMap::new();
+        state.insert("a".to_string(), group.clone());
+        state.insert("b".to_string(), group);
+        let response: Response = CountResponse::new(2, state.into()).into();
+
+        assert_eq!(response.rows(), 2);
+    }
+
+    #[test]
+    fn mask_keys_of_groups() {
+        let mut entity = HashMap::new();
+        entity.insert("name".to_string(), Types::String("julia".to_string()));
+        entity.insert("pswd".to_string(), Types::String("plain".to_string()));
+        let mut group = BTreeMap::new();
+        group.insert(Uuid::nil(), Some(entity));
+        group.insert(Uuid::from_u128(1), None);
+        let mut state = HashMap::new();
+        state.insert("a".to_string(), group);
+        let mut response: Response = CountResponse::new(1, state.into()).into();
+
+        let keys = vec!["pswd".to_string()].into_iter().collect();
+        response.mask_keys(&keys);
+        assert_eq!(
+            response.to_string(OutputFormat::Compact, MediaType::Ron).unwrap(),
+            "(response:{\"a\":{\"00000000-0000-0000-0000-000000000000\":Some({\"name\":String(\"julia\"),\"pswd\":String(\"***\")}),\"00000000-0000-0000-0000-000000000001\":None}},count:1)"
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use crate::actors::sequences::sequence_number;
 #[cfg(not(feature = "json"))]
 use crate::core::pretty_config_output;
 use serde::{Deserialize, Serialize};
@@ -9,18 +10,33 @@ pub enum TxType {
     Insert,
     UpdateSet,
     UpdateContent,
+    Replace,
     Delete,
     EvictEntity,
     EvictEntityTree,
+    ClearEntity,
+    ReindexEntity,
+    Transaction,
+    Validate,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TxResponse {
     tx_type: TxType,
-    entity: String,
+    pub(crate) entity: String,
     pub(crate) uuid: Option<Uuid>,
     state: String,
     message: String,
+    /// Number of entity ids written or removed by the tx, or that would be with `DRY RUN`.
+    #[serde(default)]
+    pub(crate) affected: usize,
+    #[serde(default)]
+    pub(crate) affected_ids: Vec<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) statements: Option<Vec<TxResponse>>,
+    /// Number of the id assigned by `INSERT ... WITH NEXT`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) number: Option<u64>,
 }
 
 impl TxResponse {
@@ -31,6 +47,25 @@ impl TxResponse {
         ron::ser::to_string_pretty(self, pretty_config_output())
             .unwrap_or_else(|_| "SERVER ERROR".to_string())
     }
+
+    /// Ids whose state was written by an insert or update, including the ones written by the
+    /// statements of a transaction.
+    pub(crate) fn written_ids(&self) -> Vec<(TxType, &str, Uuid)> {
+        match self.tx_type {
+            TxType::Insert | TxType::UpdateSet | TxType::UpdateContent | TxType::Replace => self
+                .affected_ids
+                .iter()
+                .map(|id| (self.tx_type.clone(), self.entity.as_str(), *id))
+                .collect(),
+            TxType::Transaction => self
+                .statements
+                .iter()
+                .flatten()
+                .flat_map(TxResponse::written_ids)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +88,38 @@ impl From<CreateEntityResponse> for TxResponse {
             uuid: None,
             state: String::new(),
             message: tx.message,
+            affected: 0,
+            affected_ids: Vec::new(),
+            statements: None,
+            number: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexEntityResponse {
+    entity: String,
+    message: String,
+}
+
+impl ReindexEntityResponse {
+    pub fn new(entity: String, message: String) -> Self {
+        Self { entity, message }
+    }
+}
+
+impl From<ReindexEntityResponse> for TxResponse {
+    fn from(tx: ReindexEntityResponse) -> Self {
+        Self {
+            tx_type: TxType::ReindexEntity,
+            entity: tx.entity,
+            uuid: None,
+            state: String::new(),
+            message: tx.message,
+            affected: 0,
+            affected_ids: Vec::new(),
+            statements: None,
+            number: None,
         }
     }
 }
@@ -72,6 +139,10 @@ impl From<InsertEntityResponse> for TxResponse {
             uuid: Some(tx.uuid),
             state: String::new(),
             message: tx.message,
+            affected: 1,
+            affected_ids: vec![tx.uuid],
+            statements: None,
+            number: sequence_number(&tx.uuid),
         }
     }
 }
@@ -92,6 +163,7 @@ pub struct DeleteOrEvictEntityResponse {
     uuid: Option<Uuid>,
     message: String,
     tx_type: TxType,
+    affected_ids: Vec<Uuid>,
 }
 
 impl From<DeleteOrEvictEntityResponse> for TxResponse {
@@ -102,17 +174,29 @@ impl From<DeleteOrEvictEntityResponse> for TxResponse {
             uuid: tx.uuid,
             state: String::new(),
             message: tx.message,
+            affected: tx.affected_ids.len(),
+            affected_ids: tx.affected_ids,
+            statements: None,
+            number: None,
         }
     }
 }
 
 impl DeleteOrEvictEntityResponse {
-    pub fn new(entity: String, uuid: Option<Uuid>, message: String, tx_type: TxType) -> Self {
+    /// `affected_ids` are the removed ids, all ids of the entity when `uuid` is `None`.
+    pub fn new(
+        entity: String,
+        uuid: Option<Uuid>,
+        message: String,
+        tx_type: TxType,
+        affected_ids: Vec<Uuid>,
+    ) -> Self {
         Self {
             entity,
             uuid,
             message,
             tx_type,
+            affected_ids,
         }
     }
 }
@@ -134,6 +218,10 @@ impl From<UpdateEntityResponse> for TxResponse {
             uuid: Some(tx.uuid),
             state: tx.state,
             message: tx.message,
+            affected: 1,
+            affected_ids: vec![tx.uuid],
+            statements: None,
+            number: None,
         }
     }
 }
@@ -155,3 +243,107 @@ impl UpdateEntityResponse {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunResponse {
+    entity: String,
+    affected_ids: Vec<Uuid>,
+    tx_type: TxType,
+}
+
+impl From<DryRunResponse> for TxResponse {
+    fn from(tx: DryRunResponse) -> Self {
+        Self {
+            message: format!(
+                "Dry run: {} entities from `{}` would be affected",
+                tx.affected_ids.len(),
+                tx.entity
+            ),
+            tx_type: tx.tx_type,
+            entity: tx.entity,
+            uuid: None,
+            state: String::new(),
+            affected: tx.affected_ids.len(),
+            affected_ids: tx.affected_ids,
+            statements: None,
+            number: None,
+        }
+    }
+}
+
+impl DryRunResponse {
+    pub fn new(entity: String, affected_ids: Vec<Uuid>, tx_type: TxType) -> Self {
+        Self {
+            entity,
+            affected_ids,
+            tx_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionResponse {
+    statements: Vec<TxResponse>,
+}
+
+impl From<TransactionResponse> for TxResponse {
+    fn from(tx: TransactionResponse) -> Self {
+        let mut affected_ids: Vec<Uuid> = Vec::new();
+        for id in tx.statements.iter().flat_map(|s| &s.affected_ids) {
+            if !affected_ids.contains(id) {
+                affected_ids.push(*id);
+            }
+        }
+        Self {
+            tx_type: TxType::Transaction,
+            entity: String::new(),
+            uuid: None,
+            state: String::new(),
+            message: format!(
+                "Transaction with {} statements committed",
+                tx.statements.len()
+            ),
+            affected: affected_ids.len(),
+            affected_ids,
+            statements: Some(tx.statements),
+            number: None,
+        }
+    }
+}
+
+impl TransactionResponse {
+    pub fn new(statements: Vec<TxResponse>) -> Self {
+        Self { statements }
+    }
+}
+
+/// Statements of a `VALIDATE` that match the schemas of their entities.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidateResponse {
+    statements: usize,
+}
+
+impl From<ValidateResponse> for TxResponse {
+    fn from(tx: ValidateResponse) -> Self {
+        Self {
+            tx_type: TxType::Validate,
+            entity: String::new(),
+            uuid: None,
+            state: String::new(),
+            message: format!(
+                "Validate: {} statements match the schemas of their entities",
+                tx.statements
+            ),
+            affected: 0,
+            affected_ids: Vec::new(),
+            statements: None,
+            number: None,
+        }
+    }
+}
+
+impl ValidateResponse {
+    pub fn new(statements: usize) -> Self {
+        Self { statements }
+    }
+}
@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{ScalarArg, Types};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Operator {
+    pub(crate) fn parse(symbol: &str) -> Option<Operator> {
+        match symbol {
+            "+" => Some(Operator::Add),
+            "-" => Some(Operator::Sub),
+            "*" => Some(Operator::Mul),
+            "/" => Some(Operator::Div),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+        }
+    }
+}
+
+/// Value of `MATCH UPDATE ... SET {balance: balance - 100,}` computed from the current entity
+/// map. Operands are keys or numbers, and keys must hold an `Integer`, `Float` or `Precise`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Expression {
+    pub left: ScalarArg,
+    pub operator: Operator,
+    pub right: ScalarArg,
+}
+
+impl Expression {
+    /// `Integer`s with `Integer`s stay `Integer`, division truncates and overflows are errors.
+    /// `Float`s with any number become `Float`. `Precise` values only take `+` and `-` with
+    /// `Integer`s or `Precise`s.
+    pub fn evaluate(&self, state: &HashMap<String, Types>) -> Result<Types, String> {
+        let left = self.operand(&self.left, state)?;
+        let right = self.operand(&self.right, state)?;
+        if self.operator == Operator::Div && right.is_zero() {
+            return Err(format!("Expression `{}` divides by zero", self));
+        }
+
+        match (left, right) {
+            (Types::Integer(a), Types::Integer(b)) => match self.operator {
+                Operator::Add => a.checked_add(*b),
+                Operator::Sub => a.checked_sub(*b),
+                Operator::Mul => a.checked_mul(*b),
+                Operator::Div => a.checked_div(*b),
+            }
+            .map(Types::Integer)
+            .ok_or_else(|| format!("Expression `{}` overflows Integer", self)),
+            (Types::Precise(_), _) | (_, Types::Precise(_)) => match self.operator {
+                Operator::Add => left.precise_add(right),
+                Operator::Sub => left.precise_sub(right),
+                Operator::Mul | Operator::Div => None,
+            }
+            .ok_or_else(|| {
+                format!(
+                    "Expression `{}` can only add or subtract Precise and Integer values",
+                    self
+                )
+            }),
+            (a, b) => {
+                let (a, b) = (a.to_f64(), b.to_f64());
+                Ok(Types::Float(match self.operator {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                }))
+            }
+        }
+    }
+
+    fn operand<'a>(
+        &self,
+        arg: &'a ScalarArg,
+        state: &'a HashMap<String, Types>,
+    ) -> Result<&'a Types, String> {
+        let value = match arg {
+            ScalarArg::Key(key) => state.get(key).ok_or_else(|| {
+                format!(
+                    "Key `{}` of expression `{}` is not in the entity map",
+                    key, self
+                )
+            })?,
+            ScalarArg::Value(value) => value,
+        };
+        match value {
+            Types::Integer(_) | Types::Float(_) | Types::Precise(_) => Ok(value),
+            _ => Err(format!(
+                "Operands of expression `{}` must be Integer, Float or Precise, found {}",
+                self,
+                value.type_name()
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operand = |arg: &ScalarArg| match arg {
+            ScalarArg::Key(key) => key.to_owned(),
+            ScalarArg::Value(Types::Precise(p)) => format!("{}P", p),
+            ScalarArg::Value(Types::Integer(i)) => i.to_string(),
+            ScalarArg::Value(Types::Float(n)) => n.to_string(),
+            ScalarArg::Value(Types::Percentage(p)) => format!("{}%", p),
+            ScalarArg::Value(Types::Ratio { num, den }) => format!("{}/{}", num, den),
+            ScalarArg::Value(Types::Email(email)) => email.to_owned(),
+            ScalarArg::Value(Types::Phone(phone)) => phone.to_owned(),
+            ScalarArg::Value(Types::Color(r, g, b)) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            ScalarArg::Value(value) => format!("{:?}", value),
+        };
+        write!(
+            f,
+            "{} {} {}",
+            operand(&self.left),
+            self.operator.symbol(),
+            operand(&self.right)
+        )
+    }
+}
+
+impl Types {
+    fn is_zero(&self) -> bool {
+        match self {
+            Types::Integer(i) => *i == 0,
+            Types::Float(f) => *f == 0.0,
+            Types::Precise(p) => p.chars().all(|c| matches!(c, '0' | '.' | '-' | '+')),
+            _ => false,
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Types::Integer(i) => *i as f64,
+            Types::Float(f) => *f,
+            _ => f64::NAN,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn expression(left: ScalarArg, operator: Operator, right: ScalarArg) -> Expression {
+        Expression {
+            left,
+            operator,
+            right,
+        }
+    }
+
+    fn state() -> HashMap<String, Types> {
+        let mut state = HashMap::new();
+        state.insert("balance".to_string(), Types::Integer(250));
+        state.insert("rate".to_string(), Types::Float(0.5));
+        state.insert("total".to_string(), Types::Precise("10.25".to_string()));
+        state.insert("name".to_string(), Types::String("julia".to_string()));
+        state
+    }
+
+    #[test]
+    fn evaluate_numbers() {
+        let key = |k: &str| ScalarArg::Key(k.to_string());
+        let value = ScalarArg::Value;
+
+        assert_eq!(
+            expression(key("balance"), Operator::Sub, value(Types::Integer(100)))
+                .evaluate(&state()),
+            Ok(Types::Integer(150))
+        );
+        assert_eq!(
+            expression(key("balance"), Operator::Div, value(Types::Integer(100)))
+                .evaluate(&state()),
+            Ok(Types::Integer(2))
+        );
+        assert_eq!(
+            expression(key("balance"), Operator::Mul, key("rate")).evaluate(&state()),
+            Ok(Types::Float(125.0))
+        );
+        assert_eq!(
+            expression(key("total"), Operator::Add, value(Types::Integer(1))).evaluate(&state()),
+            Ok(Types::Precise("11.25".to_string()))
+        );
+    }
+
+    #[test]
+    fn evaluate_errors() {
+        let key = |k: &str| ScalarArg::Key(k.to_string());
+        let value = ScalarArg::Value;
+
+        assert_eq!(
+            expression(key("name"), Operator::Sub, value(Types::Integer(1))).evaluate(&state()),
+            Err(String::from(
+                "Operands of expression `name - 1` must be Integer, Float or Precise, found String"
+            ))
+        );
+        assert_eq!(
+            expression(key("age"), Operator::Add, value(Types::Integer(1))).evaluate(&state()),
+            Err(String::from(
+                "Key `age` of expression `age + 1` is not in the entity map"
+            ))
+        );
+        assert_eq!(
+            expression(key("balance"), Operator::Div, value(Types::Integer(0))).evaluate(&state()),
+            Err(String::from("Expression `balance / 0` divides by zero"))
+        );
+        assert_eq!(
+            expression(
+                value(Types::Integer(isize::MAX)),
+                Operator::Add,
+                key("balance")
+            )
+            .evaluate(&state()),
+            Err(format!(
+                "Expression `{} + balance` overflows Integer",
+                isize::MAX
+            ))
+        );
+        assert!(
+            expression(key("total"), Operator::Mul, value(Types::Integer(2)))
+                .evaluate(&state())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+/// Algorithm used to create `Types::Hash` values and user passwords. Hashes are encoded with
+/// their algorithm and parameters, `$2b$...` for bcrypt and `$argon2id$...` for argon2, so
+/// `verify_hash` works for hashes created by either of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Bcrypt,
+    Argon2,
+}
+
+impl HashAlgorithm {
+    /// `cost` is only used by bcrypt, argon2 uses argon2id with 19 MiB of memory and 2 iterations.
+    pub fn hash(self, value: &str, cost: Option<u32>) -> Result<String, String> {
+        match self {
+            HashAlgorithm::Bcrypt => bcrypt::hash(value, cost.unwrap_or(bcrypt::DEFAULT_COST))
+                .map_err(|e| format!("{:?}", e)),
+            HashAlgorithm::Argon2 => {
+                let config = argon2::Config {
+                    variant: argon2::Variant::Argon2id,
+                    mem_cost: 19456,
+                    time_cost: 2,
+                    ..argon2::Config::default()
+                };
+                // A v4 Uuid has 122 random bits, which is enough for a salt.
+                let salt = Uuid::new_v4();
+                argon2::hash_encoded(value.as_bytes(), salt.as_bytes(), &config)
+                    .map_err(|e| format!("{:?}", e))
+            }
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "bcrypt" => Ok(HashAlgorithm::Bcrypt),
+            "argon2" => Ok(HashAlgorithm::Argon2),
+            _ => Err(format!(
+                "Hashing algorithm `{}` is not valid, expected bcrypt or argon2",
+                s
+            )),
+        }
+    }
+}
+
+/// Checks `candidate` against a bcrypt or argon2 encoded `hash`, invalid hashes never match.
+pub fn verify_hash(candidate: &str, hash: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        argon2::verify_encoded(hash, candidate.as_bytes()).unwrap_or(false)
+    } else {
+        bcrypt::verify(candidate, hash).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn verify_both_algorithms() {
+        let bcrypt = HashAlgorithm::Bcrypt.hash("my_password", Some(4)).unwrap();
+        let argon2 = HashAlgorithm::Argon2.hash("my_password", Some(4)).unwrap();
+
+        assert!(bcrypt.starts_with("$2"));
+        assert!(argon2.starts_with("$argon2id$"));
+        assert!(verify_hash("my_password", &bcrypt));
+        assert!(verify_hash("my_password", &argon2));
+        assert!(!verify_hash("other_password", &bcrypt));
+        assert!(!verify_hash("other_password", &argon2));
+        assert!(!verify_hash("my_password", "not a hash"));
+    }
+
+    #[test]
+    fn parse_algorithm() {
+        assert_eq!("Argon2".parse(), Ok(HashAlgorithm::Argon2));
+        assert_eq!("bcrypt".parse(), Ok(HashAlgorithm::Bcrypt));
+        assert_eq!(
+            "md5".parse::<HashAlgorithm>(),
+            Err(String::from(
+                "Hashing algorithm `md5` is not valid, expected bcrypt or argon2"
+            ))
+        );
+    }
+}
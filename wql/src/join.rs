@@ -1,13 +1,39 @@
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-use crate::Wql;
+use crate::{Function, Wql};
 
-pub fn join(chars: &mut std::str::Chars) -> Result<Wql, String> {
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum JoinMode {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+/// Parses `LEFT JOIN`, `RIGHT JOIN` and `FULL JOIN`, the keyword `OUTER` is optional.
+pub fn outer_join(chars: &mut std::str::Chars, mode: JoinMode) -> Result<Wql, String> {
+    let mut keyword = chars.take_while(|c| !c.is_whitespace()).collect::<String>();
+    if keyword.eq_ignore_ascii_case("OUTER") {
+        keyword = chars.take_while(|c| !c.is_whitespace()).collect::<String>();
+    }
+
+    if keyword.eq_ignore_ascii_case("JOIN") {
+        join(chars, mode)
+    } else {
+        Err(String::from(
+            "Keywords `LEFT`, `RIGHT` and `FULL` must be followed by `JOIN` or `OUTER JOIN`",
+        ))
+    }
+}
+
+pub fn join(chars: &mut std::str::Chars, mode: JoinMode) -> Result<Wql, String> {
     let mut entity_a = (String::new(), String::new());
     let mut entity_b = (String::new(), String::new());
 
     let mut ent = String::new();
     let mut key = String::new();
+    let mut operator = String::new();
     let mut is_entity = true;
     loop {
         match chars.next() {
@@ -29,6 +55,14 @@ pub fn join(chars: &mut std::str::Chars) -> Result<Wql, String> {
                     ent = String::new();
                 }
             }
+            Some(c) if c == '<' || c == '>' || c == '=' || c == '!' => {
+                is_entity = true;
+                if entity_a.1.is_empty() {
+                    entity_a.1 = key;
+                    key = String::new();
+                }
+                operator.push(c);
+            }
             Some(',') => {
                 is_entity = true;
                 if entity_a.1.is_empty() {
@@ -47,6 +81,23 @@ pub fn join(chars: &mut std::str::Chars) -> Result<Wql, String> {
         }
     }
 
+    let function = if operator.is_empty() {
+        Function::Eq
+    } else {
+        match Function::from_str(&operator) {
+            Ok(f @ Function::Eq)
+            | Ok(f @ Function::NotEq)
+            | Ok(f @ Function::GEq)
+            | Ok(f @ Function::G)
+            | Ok(f @ Function::LEq)
+            | Ok(f @ Function::L) => f,
+            _ => return Err(format!(
+                "Join operator `{}` is not supported, use one of `==`, `!=`, `>=`, `>`, `<=`, `<`",
+                operator
+            )),
+        }
+    };
+
     let queries = chars
         .skip_while(|c| c == &'(' || c.is_whitespace())
         .take_while(|c| c != &')')
@@ -75,13 +126,13 @@ pub fn join(chars: &mut std::str::Chars) -> Result<Wql, String> {
 
     // WITH clause
 
-    Ok(Wql::Join(entity_a, entity_b, queries_wql))
+    Ok(Wql::Join(mode, entity_a, function, entity_b, queries_wql))
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::{ToSelect, Wql};
+    use crate::{Function, JoinMode, ToSelect, Wql};
     use std::collections::HashMap;
     use std::str::FromStr;
 
@@ -94,7 +145,9 @@ mod test {
         assert_eq!(
             wql.unwrap(),
             Wql::Join(
+                JoinMode::Inner,
                 ("entity_A".to_string(), "c".to_string()),
+                Function::Eq,
                 ("entity_B".to_string(), "c".to_string()),
                 vec![
                     Wql::Select("entity_A".to_string(), ToSelect::All, None, HashMap::new()),
@@ -103,4 +156,92 @@ mod test {
             )
         )
     }
+
+    #[test]
+    fn test_join_with_operator() {
+        let wql = Wql::from_str(
+            "JOIN (entity_A:time <= entity_B:deadline) Select * FROM entity_A | Select * FROM entity_B",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Join(
+                JoinMode::Inner,
+                ("entity_A".to_string(), "time".to_string()),
+                Function::LEq,
+                ("entity_B".to_string(), "deadline".to_string()),
+                vec![
+                    Wql::Select("entity_A".to_string(), ToSelect::All, None, HashMap::new()),
+                    Wql::Select("entity_B".to_string(), ToSelect::All, None, HashMap::new())
+                ]
+            )
+        )
+    }
+
+    #[test]
+    fn test_join_with_invalid_operator() {
+        let wql = Wql::from_str(
+            "JOIN (entity_A:time => entity_B:deadline) Select * FROM entity_A | Select * FROM entity_B",
+        );
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Join operator `=>` is not supported, use one of `==`, `!=`, `>=`, `>`, `<=`, `<`"
+            ))
+        )
+    }
+
+    #[test]
+    fn test_left_join() {
+        let wql = Wql::from_str(
+            "LEFT JOIN (entity_A:c, entity_B:c) Select * FROM entity_A | Select * FROM entity_B",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Join(
+                JoinMode::Left,
+                ("entity_A".to_string(), "c".to_string()),
+                Function::Eq,
+                ("entity_B".to_string(), "c".to_string()),
+                vec![
+                    Wql::Select("entity_A".to_string(), ToSelect::All, None, HashMap::new()),
+                    Wql::Select("entity_B".to_string(), ToSelect::All, None, HashMap::new())
+                ]
+            )
+        )
+    }
+
+    #[test]
+    fn test_right_outer_join() {
+        let wql = Wql::from_str(
+            "RIGHT OUTER JOIN (entity_A:c, entity_B:c) Select * FROM entity_A | Select * FROM entity_B",
+        );
+
+        assert!(matches!(wql, Ok(Wql::Join(JoinMode::Right, ..))));
+    }
+
+    #[test]
+    fn test_full_outer_join() {
+        let wql = Wql::from_str(
+            "FULL OUTER JOIN (entity_A:time < entity_B:deadline) Select * FROM entity_A | Select * FROM entity_B",
+        );
+
+        assert!(matches!(wql, Ok(Wql::Join(JoinMode::Full, _, Function::L, ..))));
+    }
+
+    #[test]
+    fn test_outer_join_without_join() {
+        let wql = Wql::from_str(
+            "FULL OUTER (entity_A:c, entity_B:c) Select * FROM entity_A | Select * FROM entity_B",
+        );
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Keywords `LEFT`, `RIGHT` and `FULL` must be followed by `JOIN` or `OUTER JOIN`"
+            ))
+        )
+    }
 }
@@ -1,11 +1,16 @@
 use crate::{
-    join::join,
-    logic::{read_args, read_map_as_str},
+    join::{join, outer_join, JoinMode},
+    logic::{parse_value, read_args, read_map_as_str, read_update_map},
     relation::{relation, Relation},
-    select::{select_all, select_args},
+    select::{select_all, select_args, select_count},
+    where_clause::where_clauses,
 };
 
-use super::{read_map, read_match_args, FromStr, MatchCondition, Uuid, Wql};
+use super::{
+    read_map, read_match_args, Clause, EntitySchema, FromStr, MatchCondition, Types, Uuid, Wql,
+    TYPE_NAMES,
+};
+use std::collections::BTreeMap;
 
 pub(crate) fn read_symbol(a: char, chars: &mut std::str::Chars) -> Result<Wql, String> {
     let symbol = chars.take_while(|c| !c.is_whitespace()).collect::<String>();
@@ -14,19 +19,205 @@ pub(crate) fn read_symbol(a: char, chars: &mut std::str::Chars) -> Result<Wql, S
         ('c', "REATE") | ('C', "REATE") => create_entity(chars),
         ('i', "NSERT") | ('I', "NSERT") => insert(chars),
         ('u', "PDATE") | ('U', "PDATE") => update(chars),
+        ('r', "EPLACE") | ('R', "EPLACE") => replace(chars),
         ('d', "ELETE") | ('D', "ELETE") => delete(chars),
         ('m', "ATCH") | ('M', "ATCH") => match_update(chars),
         ('e', "VICT") | ('E', "VICT") => evict(chars),
+        ('c', "LEAR") | ('C', "LEAR") => entity_name_only(chars, "CLEAR").map(Wql::Clear),
+        ('r', "EINDEX") | ('R', "EINDEX") => {
+            entity_name_only(chars, "REINDEX").map(Wql::Reindex)
+        }
         ('s', "ELECT") | ('S', "ELECT") => select(chars),
         ('c', "HECK") | ('C', "HECK") => check(chars),
         ('i', "NTERSECT") | ('I', "NTERSECT") => relation(chars, Relation::Intersect),
         ('d', "IFFERENCE") | ('D', "IFFERENCE") => relation(chars, Relation::Difference),
         ('u', "NION") | ('U', "NION") => relation(chars, Relation::Union),
-        ('j', "OIN") | ('J', "OIN") => join(chars),
+        ('j', "OIN") | ('J', "OIN") => join(chars, JoinMode::Inner),
+        ('l', "EFT") | ('L', "EFT") => outer_join(chars, JoinMode::Left),
+        ('r', "IGHT") | ('R', "IGHT") => outer_join(chars, JoinMode::Right),
+        ('f', "ULL") | ('F', "ULL") => outer_join(chars, JoinMode::Full),
+        ('d', "RY") | ('D', "RY") => dry_run(chars),
+        ('v', "ALIDATE") | ('V', "ALIDATE") => validate(chars),
+        ('b', "EGIN") | ('B', "EGIN") => transaction(chars),
+        ('v', "ERSION") | ('V', "ERSION") | ('p', "ING") | ('P', "ING") => version(chars),
+        ('d', "ESCRIBE") | ('D', "ESCRIBE") => describe(chars),
+        ('e', "XISTS") | ('E', "XISTS") => exists(chars),
         _ => Err(format!("Symbol `{}{}` not implemented", a, symbol)),
     }
 }
 
+fn version(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(String::from("VERSION does not take arguments"));
+    }
+    Ok(Wql::Version)
+}
+
+fn describe(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let entity_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    if entity_symbol.to_uppercase() != "ENTITY" {
+        return Err(String::from("Keyword ENTITY is required for DESCRIBE"));
+    }
+
+    let entity_name = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphanumeric() || c == &'_')
+        .collect::<String>();
+    if entity_name.is_empty() {
+        return Err(String::from("Entity name is required for DESCRIBE"));
+    }
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(String::from("DESCRIBE ENTITY only takes an entity name"));
+    }
+
+    Ok(Wql::Describe(entity_name))
+}
+
+fn exists(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let entity_id = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+    let id = Uuid::from_str(&entity_id)
+        .map_err(|_| format!("EXISTS requires a Uuid, found `{}`", entity_id))?;
+
+    let in_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+    if in_symbol.to_uppercase() != "IN" {
+        return Err(String::from("Keyword IN is required for EXISTS"));
+    }
+
+    let entity_name = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphanumeric() || c == &'_')
+        .collect::<String>();
+    if entity_name.is_empty() {
+        return Err(String::from("Entity name is required after IN"));
+    }
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(String::from("EXISTS only takes an Uuid and an entity name"));
+    }
+
+    Ok(Wql::Exists(entity_name, id))
+}
+
+fn transaction(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let block = chars.collect::<String>();
+    let block = block.trim_end();
+    let end = block.len().saturating_sub(6);
+    if !block
+        .get(end..)
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("COMMIT"))
+    {
+        return Err(String::from(
+            "Keyword COMMIT is required to end a BEGIN block",
+        ));
+    }
+
+    let statements = split_statements(&block[..end])
+        .iter()
+        .map(|statement| Wql::from_str(statement))
+        .collect::<Result<Vec<Wql>, String>>()?;
+    if statements.is_empty() {
+        return Err(String::from("BEGIN block requires at least one statement"));
+    }
+    if statements.iter().any(|statement| {
+        !matches!(
+            statement,
+            Wql::Insert(_, _, _)
+                | Wql::InsertNext(_, _)
+                | Wql::UpdateContent(_, _, _)
+                | Wql::UpdateSet(_, _, _, _)
+                | Wql::Replace(_, _, _)
+                | Wql::MatchUpdate(_, _, _, _, _)
+        )
+    }) {
+        return Err(String::from(
+            "BEGIN blocks only accept INSERT, UPDATE, REPLACE and MATCH UPDATE statements",
+        ));
+    }
+
+    Ok(Wql::Transaction(statements))
+}
+
+/// Splits the statements of a `BEGIN` block on `;`, ignoring the ones inside maps and strings.
+/// Escaped quotes don't end strings.
+fn split_statements(block: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+    let mut depth = 0_usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in block.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' | '(' if !in_string => depth += 1,
+            '}' | ')' if !in_string => depth = depth.saturating_sub(1),
+            ';' if !in_string && depth == 0 => {
+                statements.push(statement.trim().to_owned());
+                statement = String::new();
+                continue;
+            }
+            _ => (),
+        }
+        statement.push(c);
+    }
+    statements.push(statement.trim().to_owned());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+fn dry_run(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let run_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    if run_symbol.to_uppercase() != "RUN" {
+        return Err(String::from("Keyword RUN is required for DRY RUN"));
+    }
+
+    let tx = Wql::from_str(chars.collect::<String>().trim())?;
+    match tx {
+        Wql::CreateEntity(_, _, _, _)
+        | Wql::Insert(_, _, _)
+        | Wql::InsertNext(_, _)
+        | Wql::UpdateContent(_, _, _)
+        | Wql::UpdateSet(_, _, _, _)
+        | Wql::Replace(_, _, _)
+        | Wql::Delete(_, _)
+        | Wql::DeleteWhere(_, _)
+        | Wql::MatchUpdate(_, _, _, _, _)
+        | Wql::Evict(_, _)
+        | Wql::Clear(_) => Ok(Wql::DryRun(Box::new(tx))),
+        _ => Err(String::from("DRY RUN is only available for tx statements")),
+    }
+}
+
+fn validate(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let tx = Wql::from_str(chars.collect::<String>().trim())?;
+    match tx {
+        Wql::Insert(_, _, _)
+        | Wql::InsertNext(_, _)
+        | Wql::UpdateContent(_, _, _)
+        | Wql::UpdateSet(_, _, _, _)
+        | Wql::Replace(_, _, _)
+        | Wql::MatchUpdate(_, _, _, _, _)
+        | Wql::Transaction(_) => Ok(Wql::Validate(Box::new(tx))),
+        _ => Err(String::from(
+            "VALIDATE is only available for INSERT, UPDATE, REPLACE, MATCH UPDATE and BEGIN blocks",
+        )),
+    }
+}
+
 fn create_entity(chars: &mut std::str::Chars) -> Result<Wql, String> {
     let entity_symbol = chars.take_while(|c| !c.is_whitespace()).collect::<String>();
 
@@ -39,25 +230,231 @@ fn create_entity(chars: &mut std::str::Chars) -> Result<Wql, String> {
         .collect::<String>()
         .trim()
         .to_string();
+    let schema = create_schema(chars)?;
+    let schema = append_only(chars, schema)?;
 
     let next_symbol = chars.take_while(|c| !c.is_whitespace()).collect::<String>();
     if next_symbol.to_uppercase() == "UNIQUES" {
         let (uniques, encrypts) = create_uniques_and_encrypts(chars, "ENCRYPT")?;
 
-        Ok(Wql::CreateEntity(entity_name, uniques, encrypts))
+        Ok(Wql::CreateEntity(entity_name, uniques, encrypts, schema))
     } else if next_symbol.to_uppercase() == "ENCRYPT" {
         let (encrypts, uniques) = create_uniques_and_encrypts(chars, "UNIQUES")?;
 
-        Ok(Wql::CreateEntity(entity_name, uniques, encrypts))
+        Ok(Wql::CreateEntity(entity_name, uniques, encrypts, schema))
     } else if next_symbol.to_uppercase() == "ENCRYPTS" {
         Err(String::from("Correct wording is ENCRYPT"))
     } else if next_symbol.to_uppercase() == "UNIQUE" {
         Err(String::from("Correct wording is UNIQUES"))
     } else {
-        Ok(Wql::CreateEntity(entity_name, Vec::new(), Vec::new()))
+        Ok(Wql::CreateEntity(
+            entity_name,
+            Vec::new(),
+            Vec::new(),
+            schema,
+        ))
     }
 }
 
+/// Parses an optional `APPEND ONLY` after the entity name and schema, it is kept in the
+/// entity schema, which is empty if none was declared.
+fn append_only(
+    chars: &mut std::str::Chars,
+    schema: Option<EntitySchema>,
+) -> Result<Option<EntitySchema>, String> {
+    let rest = chars.as_str().trim_start();
+    let (symbol, tail) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+    if !symbol.eq_ignore_ascii_case("APPEND") {
+        return Ok(schema);
+    }
+    let tail = tail.trim_start();
+    let (symbol, tail) = tail.split_at(tail.find(char::is_whitespace).unwrap_or(tail.len()));
+    if !symbol.eq_ignore_ascii_case("ONLY") {
+        return Err(String::from("Keyword ONLY is required after APPEND"));
+    }
+    *chars = tail.trim_start().chars();
+
+    let mut schema = schema.unwrap_or_default();
+    schema.append_only = true;
+    Ok(Some(schema))
+}
+
+fn create_schema(chars: &mut std::str::Chars) -> Result<Option<EntitySchema>, String> {
+    let rest = chars.as_str().trim_start();
+    if !rest.starts_with('{') {
+        return Ok(None);
+    }
+    let end = rest
+        .find('}')
+        .ok_or_else(|| String::from("Schema should start with `{` and end with `}`"))?;
+    let mut types = BTreeMap::new();
+    let mut enums = BTreeMap::new();
+    let mut vectors = BTreeMap::new();
+    let mut defaults = BTreeMap::new();
+    for field in rest[1..end]
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+    {
+        let (field, default) = split_default(field);
+        let (key, type_name, constraint) = schema_field(field)?;
+        if let Some(default) = default {
+            let default = schema_default(&key, &type_name, constraint.as_ref(), default)?;
+            defaults.insert(key.clone(), default);
+        }
+        match constraint {
+            Some(FieldConstraint::Enum(variants)) => {
+                enums.insert(key.clone(), variants);
+            }
+            Some(FieldConstraint::Vector(element)) => {
+                vectors.insert(key.clone(), element);
+            }
+            None => (),
+        }
+        types.insert(key, type_name);
+    }
+
+    let rest = rest[end + 1..].trim_start();
+    let (symbol, tail) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+    let exhaustive = symbol.eq_ignore_ascii_case("EXHAUSTIVE");
+    *chars = if exhaustive { tail.trim_start() } else { rest }.chars();
+
+    Ok(Some(EntitySchema {
+        types,
+        exhaustive,
+        enums,
+        vectors,
+        defaults,
+        append_only: false,
+    }))
+}
+
+/// Splits `age: Integer DEFAULT 0` into the field and the default value, which is empty
+/// for a `DEFAULT` without value.
+fn split_default(field: &str) -> (&str, Option<&str>) {
+    let mut words = field.match_indices(char::is_whitespace).map(|(i, _)| i + 1);
+    let start = words.find(|&i| {
+        field[i..]
+            .split_whitespace()
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("DEFAULT"))
+    });
+    match start {
+        Some(i) => (field[..i].trim_end(), Some(field[i + 7..].trim())),
+        None => (field, None),
+    }
+}
+
+fn schema_default(
+    key: &str,
+    type_name: &str,
+    constraint: Option<&FieldConstraint>,
+    default: &str,
+) -> Result<Option<Types>, String> {
+    let mut chars = default.chars();
+    let value = match chars.next() {
+        Some(c) => parse_value(c, &mut chars)
+            .map_err(|e| format!("Default of schema key `{}` is not valid: {}", key, e))?,
+        None if matches!(constraint, Some(FieldConstraint::Enum(_))) => {
+            return Err(format!(
+                "Enum schema key `{}` requires a DEFAULT variant, like `{}: Enum(Open | Closed) DEFAULT \"Open\"`",
+                key, key
+            ))
+        }
+        None => return Ok(None),
+    };
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(format!(
+            "Default of schema key `{}` should be a single value, found `{}`",
+            key, default
+        ));
+    }
+    if value.type_name() != type_name {
+        return Err(format!(
+            "Default of schema key `{}` must be of type {}, found {}",
+            key,
+            type_name,
+            value.type_name()
+        ));
+    }
+    if let (Some(FieldConstraint::Enum(variants)), Types::String(variant)) = (constraint, &value) {
+        if !variants.contains(variant) {
+            return Err(format!(
+                "Default of schema key `{}` must be one of {:?}, found `{}`",
+                key, variants, variant
+            ));
+        }
+    }
+    Ok(Some(value))
+}
+
+enum FieldConstraint {
+    Enum(Vec<String>),
+    Vector(String),
+}
+
+/// Keys of the valid-time interval used by `SELECT ... VALID AT`.
+const VALID_TIME_KEYS: [&str; 2] = ["valid_from", "valid_to"];
+
+fn schema_field(field: &str) -> Result<(String, String, Option<FieldConstraint>), String> {
+    let (key, type_name) = field.split_once(':').ok_or_else(|| {
+        format!(
+            "Schema key `{}` requires a type, like `{}: Integer`",
+            field, field
+        )
+    })?;
+    let (key, type_name) = (key.trim(), type_name.trim());
+
+    if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(String::from("Key must be an alphanumeric value"));
+    }
+    if VALID_TIME_KEYS.contains(&key) && type_name != "DateTime" {
+        return Err(format!(
+            "Valid time key `{}` must be of type DateTime, found `{}`",
+            key, type_name
+        ));
+    }
+    if type_name.starts_with("Enum(") && type_name.ends_with(')') {
+        let variants = type_name[5..type_name.len() - 1]
+            .split('|')
+            .map(str::trim)
+            .map(String::from)
+            .collect::<Vec<String>>();
+        if variants.iter().any(|v| v.is_empty()) {
+            return Err(format!(
+                "Enum schema key `{}` requires non empty variants, like `{}: Enum(Open | Closed)`",
+                key, key
+            ));
+        }
+        return Ok((
+            key.to_owned(),
+            String::from("String"),
+            Some(FieldConstraint::Enum(variants)),
+        ));
+    }
+    if type_name.starts_with("Vector<") && type_name.ends_with('>') {
+        let element = type_name[7..type_name.len() - 1].trim();
+        if !TYPE_NAMES.contains(&element) {
+            return Err(format!(
+                "Type `{}` is not valid for the elements of schema key `{}`. Valid types are {:?}",
+                element, key, TYPE_NAMES
+            ));
+        }
+        return Ok((
+            key.to_owned(),
+            String::from("Vector"),
+            Some(FieldConstraint::Vector(element.to_owned())),
+        ));
+    }
+    if !TYPE_NAMES.contains(&type_name) {
+        return Err(format!(
+            "Type `{}` is not valid for schema key `{}`. Valid types are {:?}",
+            type_name, key, TYPE_NAMES
+        ));
+    }
+    Ok((key.to_owned(), type_name.to_owned(), None))
+}
+
 fn create_uniques_and_encrypts(
     chars: &mut std::str::Chars,
     next_element: &str,
@@ -94,7 +491,8 @@ fn select(chars: &mut std::str::Chars) -> Result<Wql, String> {
             Some(' ') => (),
             Some('*') => return select_all(chars),
             Some('#') => return select_args(chars),
-            _ => return Err(String::from("SELECT expression should be followed by `*` for ALL keys or `#{key_names...}` for some keys"))
+            Some('c') | Some('C') => return select_count(chars),
+            _ => return Err(String::from("SELECT expression should be followed by `*` for ALL keys, `#{key_names...}` for some keys or `COUNT(*)`"))
         }
     }
 }
@@ -106,7 +504,10 @@ fn delete(chars: &mut std::str::Chars) -> Result<Wql, String> {
         .trim()
         .to_string();
 
-    if entity_id.is_empty() || entity_id == "FROM" {
+    if entity_id.eq_ignore_ascii_case("FROM") {
+        return delete_where(chars);
+    }
+    if entity_id.is_empty() {
         return Err(String::from("Entity UUID is required for DELETE"));
     }
 
@@ -132,6 +533,42 @@ fn delete(chars: &mut std::str::Chars) -> Result<Wql, String> {
     Ok(Wql::Delete(entity_name, entity_id))
 }
 
+/// `DELETE FROM entity WHERE {...}`, the ids matching the clauses are evicted.
+fn delete_where(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let entity_name = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphanumeric() || c == &'_')
+        .collect::<String>();
+
+    if entity_name.is_empty() {
+        return Err(String::from("Entity name is required after FROM"));
+    }
+
+    let where_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    if where_symbol.is_empty() {
+        return Err(String::from("Entity UUID is required for DELETE"));
+    }
+    if where_symbol.to_uppercase() != "WHERE" {
+        return Err(String::from(
+            "Keyword WHERE is required for DELETE FROM entity",
+        ));
+    }
+
+    let clauses = where_clauses(&entity_name, chars)?;
+    if clauses.contains(&Clause::Error) {
+        return Err(String::from("DELETE WHERE clauses must be valid"));
+    }
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(String::from("DELETE WHERE only takes WHERE clauses"));
+    }
+
+    Ok(Wql::DeleteWhere(entity_name, clauses))
+}
+
 fn insert(chars: &mut std::str::Chars) -> Result<Wql, String> {
     let entity_map = read_map(chars)?;
     let entity_symbol = chars
@@ -174,6 +611,9 @@ fn insert(chars: &mut std::str::Chars) -> Result<Wql, String> {
         if entity_id.is_empty() {
             return Err(String::from("Entity UUID is required for INSERT WITH id"));
         }
+        if entity_id.eq_ignore_ascii_case("NEXT") {
+            return Ok(Wql::InsertNext(entity_name, entity_map));
+        }
 
         Ok(Wql::Insert(
             entity_name,
@@ -263,14 +703,73 @@ fn update(chars: &mut std::str::Chars) -> Result<Wql, String> {
 
     let uuid = Uuid::from_str(&uuid_str)
         .map_err(|e| format!("Couldn't create uuid from {}. Error: {:?}", uuid_str, e))?;
+    let version = update_version(chars)?;
 
-    match &entity_symbol.to_uppercase()[..] {
-        "SET" => Ok(Wql::UpdateSet(entity_name, entity_map, uuid)),
-        "CONTENT" => Ok(Wql::UpdateContent(entity_name, entity_map, uuid)),
+    match (&entity_symbol.to_uppercase()[..], version) {
+        ("SET", version) => Ok(Wql::UpdateSet(entity_name, entity_map, uuid, version)),
+        ("CONTENT", None) => Ok(Wql::UpdateContent(entity_name, entity_map, uuid)),
+        ("CONTENT", Some(_)) => Err(String::from("IF VERSION is only available for UPDATE SET")),
         _ => Err("Couldn't parse UPDATE query".to_string()),
     }
 }
 
+fn replace(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let entity_name = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphanumeric() || c == &'_')
+        .collect::<String>();
+
+    if entity_name.is_empty() {
+        return Err(String::from("Entity name is required for REPLACE"));
+    };
+
+    let entity_map = read_map(chars)?;
+
+    let into_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    if into_symbol.to_uppercase() != "INTO" {
+        return Err(String::from("Keyword INTO is required for REPLACE"));
+    };
+
+    let uuid_str = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphanumeric() || c == &'-')
+        .collect::<String>();
+    let uuid = Uuid::from_str(&uuid_str)
+        .map_err(|e| format!("Couldn't create uuid from {}. Error: {:?}", uuid_str, e))?;
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(String::from(
+            "REPLACE only takes an entity name, an entity map and an Uuid",
+        ));
+    }
+
+    Ok(Wql::Replace(entity_name, entity_map, uuid))
+}
+
+/// Reads the optional `IF VERSION <version>` after the uuid of an `UPDATE`.
+fn update_version(chars: &mut std::str::Chars) -> Result<Option<String>, String> {
+    let mut next_word = || {
+        chars
+            .skip_while(|c| c.is_whitespace())
+            .take_while(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    if !next_word().eq_ignore_ascii_case("IF") {
+        return Ok(None);
+    }
+    if !next_word().eq_ignore_ascii_case("VERSION") {
+        return Err(String::from("Keyword VERSION is required after IF"));
+    }
+    let version = next_word();
+    if version.is_empty() {
+        return Err(String::from("IF VERSION requires a version token"));
+    }
+    Ok(Some(version))
+}
+
 fn match_update(chars: &mut std::str::Chars) -> Result<Wql, String> {
     let match_arg_symbol = chars
         .skip_while(|c| c.is_whitespace())
@@ -321,7 +820,7 @@ fn match_update(chars: &mut std::str::Chars) -> Result<Wql, String> {
         ));
     };
 
-    let entity_map = read_map(chars)?;
+    let (entity_map, expressions) = read_update_map(chars)?;
 
     let into_symbol = chars
         .skip_while(|c| c.is_whitespace())
@@ -342,11 +841,42 @@ fn match_update(chars: &mut std::str::Chars) -> Result<Wql, String> {
         .map_err(|e| format!("Couldn't create uuid from {}, Error: {:?}", uuid_str, e))?;
 
     match &entity_symbol.to_uppercase()[..] {
-        "SET" => Ok(Wql::MatchUpdate(entity_name, entity_map, uuid, match_args?)),
+        "SET" => Ok(Wql::MatchUpdate(
+            entity_name,
+            entity_map,
+            uuid,
+            match_args?,
+            expressions,
+        )),
         _ => Err("Couldn't parse MATCH UPDATE query".to_string()),
     }
 }
 
+/// Entity name of `CLEAR ENTITY entity` and `REINDEX ENTITY entity`.
+fn entity_name_only(chars: &mut std::str::Chars, keyword: &str) -> Result<String, String> {
+    let entity_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    if entity_symbol.to_uppercase() != "ENTITY" {
+        return Err(format!("Keyword ENTITY is required for {}", keyword));
+    }
+
+    let entity_name = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| c.is_alphanumeric() || c == &'_')
+        .collect::<String>();
+    if entity_name.is_empty() {
+        return Err(format!("Entity name is required for {}", keyword));
+    }
+    if chars.any(|c| !c.is_whitespace()) {
+        return Err(format!("{} ENTITY only takes an entity name", keyword));
+    }
+
+    Ok(entity_name)
+}
+
 fn evict(chars: &mut std::str::Chars) -> Result<Wql, String> {
     let info = chars
         .take_while(|c| c.is_alphanumeric() || c == &'-' || c == &'_')
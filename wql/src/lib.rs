@@ -2,42 +2,97 @@ use chrono::{DateTime, Utc};
 use language_parser::read_symbol;
 use serde::{Deserialize, Serialize};
 use std::{cmp::Ordering, hash::Hash};
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+};
 use uuid::Uuid;
+mod expression;
+mod hash;
 mod join;
 mod language_parser;
 mod logic;
+mod params;
+mod precise;
 mod relation;
 mod select;
 #[cfg(test)]
 mod test;
 mod where_clause;
 
-pub use logic::parse_value as parse_types;
+pub use expression::{Expression, Operator};
+pub use hash::{verify_hash, HashAlgorithm};
+pub use join::JoinMode;
 use logic::{integer_decode, read_map, read_match_args};
+pub use logic::{parse_value as parse_types, set_max_nesting_depth, DEFAULT_MAX_NESTING_DEPTH};
+use precise::Decimal;
 pub use relation::{Relation, RelationType};
 pub use where_clause::{Clause, Function, Value};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Wql {
-    CreateEntity(String, Vec<String>, Vec<String>),
+    CreateEntity(String, Vec<String>, Vec<String>, Option<EntitySchema>),
     Insert(String, Entity, Option<Uuid>),
+    /// `INSERT {...} INTO entity WITH NEXT`, the id is the next number of the entity sequence.
+    InsertNext(String, Entity),
     UpdateContent(String, Entity, Uuid),
-    UpdateSet(String, Entity, Uuid),
+    /// `UPDATE entity SET {...} INTO <uuid> IF VERSION <version>`, the version is optional.
+    UpdateSet(String, Entity, Uuid, Option<String>),
+    /// `REPLACE entity {...} INTO <uuid>`, the entity map becomes exactly the content, keys
+    /// absent from it are dropped.
+    Replace(String, Entity, Uuid),
     Delete(String, String),
-    MatchUpdate(String, Entity, Uuid, MatchCondition),
+    /// `DELETE FROM entity WHERE {...}`, evicts the ids whose current state matches the clauses.
+    DeleteWhere(String, Vec<Clause>),
+    /// `MATCH ALL(...) UPDATE entity SET {...} INTO <uuid>`, the last field has the keys set
+    /// to expressions over the current entity map, like `{balance: balance - 100,}`.
+    MatchUpdate(
+        String,
+        Entity,
+        Uuid,
+        MatchCondition,
+        HashMap<String, Expression>,
+    ),
     Evict(String, Option<Uuid>),
+    /// `CLEAR ENTITY entity`, removes all ids of the entity but keeps it created.
+    Clear(String),
+    /// `REINDEX ENTITY entity`, rebuilds the unique values of the entity from its ids.
+    Reindex(String),
     Select(String, ToSelect, Option<Uuid>, HashMap<String, Algebra>),
     SelectWhen(String, ToSelect, Option<Uuid>, String),
     SelectWhenRange(String, Uuid, String, String),
+    /// `SELECT * FROM entity ID <uuid> WHEN EFFECTIVE AT <date>`, the last state of the id
+    /// registered before the date, even if it was registered on an earlier day.
+    SelectWhenEffective(String, ToSelect, Uuid, String),
     SelectIds(String, ToSelect, Vec<Uuid>, HashMap<String, Algebra>),
     SelectWhere(String, ToSelect, Vec<Clause>, HashMap<String, Algebra>),
     CheckValue(String, Uuid, HashMap<String, String>),
     RelationQuery(Vec<Wql>, Relation, RelationType),
-    Join((String, String), (String, String), Vec<Wql>),
+    Join(
+        JoinMode,
+        (String, String),
+        Function,
+        (String, String),
+        Vec<Wql>,
+    ),
+    DryRun(Box<Wql>),
+    /// `VALIDATE <tx>`, checks the contents of an insert, update or `BEGIN` block against
+    /// the entity schemas without writing them.
+    Validate(Box<Wql>),
+    Version,
+    /// `DESCRIBE ENTITY entity`, the declared schema or the types found in the entity states.
+    Describe(String),
+    /// `EXISTS <uuid> IN entity`, if the id is in the entity without reading its state.
+    Exists(String, Uuid),
+    Transaction(Vec<Wql>),
 }
 
-pub use select::{Algebra, Order};
+/// Revision of the WQL grammar supported by this parser, follows the `wql` crate version.
+pub const GRAMMAR_REVISION: &str = env!("CARGO_PKG_VERSION");
+
+pub use select::{Algebra, Order, Projection, ScalarArg, ScalarFunction};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ToSelect {
@@ -47,6 +102,28 @@ pub enum ToSelect {
 
 pub type Entity = HashMap<String, Types>;
 
+/// Types declared with `CREATE ENTITY <entity> {key: Type,}`. When `exhaustive`
+/// is set, keys not declared in the schema are rejected.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct EntitySchema {
+    pub types: BTreeMap<String, String>,
+    pub exhaustive: bool,
+    /// Allowed variants of `Enum(A | B)` keys, their type in `types` is `String`.
+    #[serde(default)]
+    pub enums: BTreeMap<String, Vec<String>>,
+    /// Element type of `Vector<Type>` keys, their type in `types` is `Vector`.
+    #[serde(default)]
+    pub vectors: BTreeMap<String, String>,
+    /// Values of `key: Type DEFAULT value` keys, set on inserts that omit the key. `None`
+    /// is a `DEFAULT` without value, the default of the key type on each insert.
+    #[serde(default)]
+    pub defaults: BTreeMap<String, Option<Types>>,
+    /// Set by `CREATE ENTITY <entity> APPEND ONLY`, ids of the entity can be inserted but
+    /// never updated, deleted or evicted.
+    #[serde(default)]
+    pub append_only: bool,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum MatchCondition {
     All(Vec<MatchCondition>),
@@ -57,6 +134,9 @@ pub enum MatchCondition {
     G(String, Types),
     LEq(String, Types),
     L(String, Types),
+    /// `last_seen OLDER THAN 30d`, the `DateTime` of the key is older than now minus the
+    /// duration. Values that are not a `DateTime` fail the condition.
+    OlderThan(String, std::time::Duration),
 }
 
 pub(crate) fn tokenize(wql: &str) -> std::str::Chars {
@@ -68,7 +148,9 @@ impl std::str::FromStr for Wql {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut tokens = tokenize(s.trim_start());
-        let wql = parse(tokens.next(), &mut tokens)?;
+        let mut wql =
+            parse(tokens.next(), &mut tokens).map_err(|e| logic::locate_unterminated(e, s))?;
+        wql.reject_placeholders()?;
         Ok(wql)
     }
 }
@@ -80,8 +162,7 @@ pub(crate) fn parse(c: Option<char>, chars: &mut std::str::Chars) -> Result<Wql,
     )
 }
 
-#[allow(clippy::derive_hash_xor_eq)] // for now
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Types {
     Char(char),
     Integer(isize),
@@ -95,32 +176,150 @@ pub enum Types {
     Precise(String),
     DateTime(DateTime<Utc>),
     Nil,
+    Point { x: f64, y: f64 },
+    IpAddr(IpAddr),
+    Percentage(f64),
+    Ratio { num: i64, den: i64 },
+    Email(String),
+    Color(u8, u8, u8),
+    Phone(String),
 }
 
 impl Types {
-    pub fn default_values(&self) -> Types {
+    /// Total order of floats that agrees with the `Hash` of `Types`, two floats are
+    /// `Equal` only if their `integer_decode` is the same, so `-0.0 < 0.0` and `NaN`
+    /// is equal to itself.
+    pub fn float_total_cmp(a: f64, b: f64) -> Ordering {
+        a.total_cmp(&b)
+    }
+
+    /// Creates a `Ratio` reduced by the GCD of `num` and `den`, with the sign in `num`.
+    /// Errors if `den` is zero or the reduced ratio doesn't fit in `i64`.
+    pub fn ratio(num: i64, den: i64) -> Result<Types, String> {
+        let (num, den) = reduce_ratio(num, den)
+            .ok_or_else(|| format!("Ratio `{}/{}` must have a non zero denominator", num, den))?;
+        Ok(Types::Ratio { num, den })
+    }
+
+    /// Creates an `Email` with the lowercased address, errors if it is not a valid email.
+    pub fn email(address: &str) -> Result<Types, String> {
+        logic::parse_email(address).map(Types::Email).ok_or_else(|| {
+            format!(
+                "Email value `{}` is not a valid email, like `name@example.com`",
+                address
+            )
+        })
+    }
+
+    /// Creates a `Phone` with the number normalized to E.164, errors if it is not an
+    /// international number.
+    pub fn phone(number: &str) -> Result<Types, String> {
+        logic::parse_phone(number).map(Types::Phone).ok_or_else(|| {
+            format!(
+                "Phone value `{}` must be an international number, like `+1 555 123 4567`",
+                number
+            )
+        })
+    }
+
+    /// Creates a `Color` from a hex RGB color, like `#ff8800`, errors if it is not one.
+    pub fn color(hex: &str) -> Result<Types, String> {
+        logic::parse_color(hex).ok_or_else(|| {
+            format!(
+                "Color value `{}` must be a hex RGB color, like `#ff8800`",
+                hex
+            )
+        })
+    }
+
+    /// Red, green and blue of a `Color` packed as `0xRRGGBB`, `None` for other types.
+    pub fn packed_color(&self) -> Option<u32> {
+        match self {
+            Types::Color(r, g, b) => Some(u32::from_be_bytes([0, *r, *g, *b])),
+            _ => None,
+        }
+    }
+
+    /// Part of an `Email` after the `@`, `None` for other types.
+    pub fn email_domain(&self) -> Option<&str> {
         match self {
-            Types::Char(_) => Types::Char(' '),
-            Types::Integer(_) => Types::Integer(0),
-            Types::String(_) => Types::String(String::new()),
-            Types::Uuid(_) => Types::Uuid(Uuid::new_v4()),
-            Types::Float(_) => Types::Float(0_f64),
-            Types::Boolean(_) => Types::Boolean(false),
-            Types::Vector(_) => Types::Vector(Vec::new()),
-            Types::Map(_) => Types::Map(HashMap::new()),
-            Types::Hash(_) => Types::Hash(String::new()),
-            Types::Precise(_) => Types::Precise(String::from("0")),
-            Types::DateTime(_) => Types::DateTime(Utc::now()),
-            Types::Nil => Types::Nil,
+            Types::Email(email) => email.rsplit_once('@').map(|(_, domain)| domain),
+            _ => None,
         }
     }
 
+    /// Adds a `Precise` to a `Precise` or `Integer` without converting them to `f64`.
+    /// `None` if none of them is `Precise` or the values are not decimals.
+    pub fn precise_add(&self, other: &Types) -> Option<Types> {
+        let (a, b) = self.precise_operands(other)?;
+        Some(Types::Precise(a.add(&b).to_string()))
+    }
+
+    /// Subtracts a `Precise` or `Integer` from a `Precise` or `Integer`, like `precise_add`.
+    pub fn precise_sub(&self, other: &Types) -> Option<Types> {
+        let (a, b) = self.precise_operands(other)?;
+        Some(Types::Precise(a.sub(&b).to_string()))
+    }
+
+    fn precise_operands(&self, other: &Types) -> Option<(Decimal, Decimal)> {
+        let decimal = |t: &Types| match t {
+            Types::Precise(p) => Decimal::parse(p),
+            Types::Integer(i) => Decimal::parse(&i.to_string()),
+            _ => None,
+        };
+        match (self, other) {
+            (Types::Precise(_), _) | (_, Types::Precise(_)) => {
+                Some((decimal(self)?, decimal(other)?))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn default_values(&self) -> Types {
+        Types::default_of(self.type_name()).unwrap_or(Types::Nil)
+    }
+
+    /// Default value of the type named `type_name`, `None` if it is not one of `TYPE_NAMES`.
+    /// `Uuid` and `DateTime` defaults are a new v4 `Uuid` and the current time.
+    pub fn default_of(type_name: &str) -> Option<Types> {
+        Some(match type_name {
+            "Char" => Types::Char(' '),
+            "Integer" => Types::Integer(0),
+            "String" => Types::String(String::new()),
+            "Uuid" => Types::Uuid(Uuid::new_v4()),
+            "Float" => Types::Float(0_f64),
+            "Boolean" => Types::Boolean(false),
+            "Vector" => Types::Vector(Vec::new()),
+            "Map" => Types::Map(HashMap::new()),
+            "Hash" => Types::Hash(String::new()),
+            "Precise" => Types::Precise(String::from("0")),
+            "DateTime" => Types::DateTime(Utc::now()),
+            "Nil" => Types::Nil,
+            "Point" => Types::Point { x: 0_f64, y: 0_f64 },
+            "IpAddr" => Types::IpAddr(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            "Percentage" => Types::Percentage(0_f64),
+            "Ratio" => Types::Ratio { num: 0, den: 1 },
+            "Email" => Types::Email(String::new()),
+            "Color" => Types::Color(0, 0, 0),
+            "Phone" => Types::Phone(String::new()),
+            _ => return None,
+        })
+    }
+
+    /// Hashes the value with bcrypt, like `to_hash_with(HashAlgorithm::Bcrypt, cost)`.
     pub fn to_hash(&self, cost: Option<u32>) -> Result<Types, String> {
-        use bcrypt::{hash, DEFAULT_COST};
+        self.to_hash_with(HashAlgorithm::Bcrypt, cost)
+    }
+
+    pub fn to_hash_with(
+        &self,
+        algorithm: HashAlgorithm,
+        cost: Option<u32>,
+    ) -> Result<Types, String> {
         let value = match self {
             Types::Char(c) => format!("{}", c),
             Types::Integer(i) => format!("{}", i),
-            Types::String(s) => s.to_string(),
+            Types::String(s) | Types::Email(s) | Types::Phone(s) => s.to_string(),
             Types::DateTime(date) => date.to_string(),
             Types::Uuid(id) => format!("{}", id),
             Types::Float(f) => format!("{:?}", integer_decode(f.to_owned())),
@@ -128,61 +327,207 @@ impl Types {
             Types::Vector(vec) => format!("{:?}", vec),
             Types::Map(map) => format!("{:?}", map),
             Types::Precise(p) => p.to_string(),
+            Types::Point { x, y } => format!("{:?}", (integer_decode(*x), integer_decode(*y))),
+            Types::IpAddr(ip) => ip.to_string(),
+            Types::Percentage(p) => format!("{}%", p),
+            Types::Color(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            Types::Ratio { num, den } => match reduce_ratio(*num, *den) {
+                Some((num, den)) => format!("{}/{}", num, den),
+                None => format!("{}/{}", num, den),
+            },
             Types::Hash(_) => return Err(String::from("Hash cannot be hashed")),
             Types::Nil => return Err(String::from("Nil cannot be hashed")),
         };
-        match hash(&value, cost.map_or(DEFAULT_COST, |c| c)) {
-            Ok(s) => Ok(Types::Hash(s)),
-            Err(e) => Err(format!("{:?}", e)),
-        }
+        algorithm.hash(&value, cost).map(Types::Hash)
+    }
+
+    /// Parses a raw string, like form data, as the type named `type_name`. Unlike
+    /// `parse_types`, the type is not inferred, so `"10"` can be an `Integer` or a
+    /// `Precise`. Strings are not quoted and `Hash` can't be parsed.
+    pub fn from_str_as(value: &str, type_name: &str) -> Result<Types, String> {
+        logic::parse_value_as(value, type_name)
     }
 
     pub fn is_hash(&self) -> bool {
         matches!(self, Types::Hash(_))
     }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Types::Char(_) => "Char",
+            Types::Integer(_) => "Integer",
+            Types::String(_) => "String",
+            Types::Uuid(_) => "Uuid",
+            Types::Float(_) => "Float",
+            Types::Boolean(_) => "Boolean",
+            Types::Vector(_) => "Vector",
+            Types::Map(_) => "Map",
+            Types::Hash(_) => "Hash",
+            Types::Precise(_) => "Precise",
+            Types::DateTime(_) => "DateTime",
+            Types::Nil => "Nil",
+            Types::Point { .. } => "Point",
+            Types::IpAddr(_) => "IpAddr",
+            Types::Percentage(_) => "Percentage",
+            Types::Ratio { .. } => "Ratio",
+            Types::Email(_) => "Email",
+            Types::Color(..) => "Color",
+            Types::Phone(_) => "Phone",
+        }
+    }
+
+    /// Approximate bytes used by the value, `size_of::<Types>()` plus the heap bytes of
+    /// strings, vectors and maps. Heap bytes are counted by length, not capacity, and
+    /// allocator overhead is ignored, so the same value always has the same estimate.
+    pub fn approx_size(&self) -> usize {
+        let heap = match self {
+            Types::String(s)
+            | Types::Hash(s)
+            | Types::Precise(s)
+            | Types::Email(s)
+            | Types::Phone(s) => s.len(),
+            Types::Vector(values) => values.iter().map(Types::approx_size).sum(),
+            Types::Map(map) => map
+                .iter()
+                .map(|(k, v)| std::mem::size_of::<String>() + k.len() + v.approx_size())
+                .sum(),
+            _ => 0,
+        };
+        std::mem::size_of::<Types>() + heap
+    }
 }
 
+pub(crate) const TYPE_NAMES: [&str; 19] = [
+    "Char",
+    "Integer",
+    "String",
+    "Uuid",
+    "Float",
+    "Boolean",
+    "Vector",
+    "Map",
+    "Hash",
+    "Precise",
+    "DateTime",
+    "Nil",
+    "Point",
+    "IpAddr",
+    "Percentage",
+    "Ratio",
+    "Email",
+    "Color",
+    "Phone",
+];
+
 impl Eq for Types {}
 impl PartialOrd for Types {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (self, other) {
             (Types::Integer(a), Types::Integer(b)) => Some(a.cmp(b)),
 
-            (Types::Float(a), Types::Float(b)) => Some(if a > b {
-                Ordering::Greater
-            } else {
-                Ordering::Less
-            }),
-            (Types::Integer(a), Types::Float(b)) => Some(if &(*a as f64) > b {
-                Ordering::Greater
-            } else {
-                Ordering::Less
-            }),
-            (Types::Float(a), Types::Integer(b)) => Some(if a > &(*b as f64) {
-                Ordering::Greater
-            } else {
-                Ordering::Less
-            }),
+            (Types::Float(a), Types::Float(b)) => Some(Types::float_total_cmp(*a, *b)),
+            (Types::Integer(a), Types::Float(b)) => Some(Types::float_total_cmp(*a as f64, *b)),
+            (Types::Float(a), Types::Integer(b)) => Some(Types::float_total_cmp(*a, *b as f64)),
             (Types::Char(a), Types::Char(b)) => Some(a.cmp(b)),
-            (Types::String(a), Types::String(b)) | (Types::Precise(a), Types::Precise(b)) => {
-                Some(a.cmp(b))
+            (Types::String(a), Types::String(b)) => Some(a.cmp(b)),
+            (Types::Email(a), Types::Email(b)) => Some(a.cmp(b)),
+            (Types::Phone(a), Types::Phone(b)) => Some(a.cmp(b)),
+            (Types::Color(..), Types::Color(..)) => {
+                Some(self.packed_color().cmp(&other.packed_color()))
+            }
+            (Types::Precise(a), Types::Precise(b)) => {
+                match (Decimal::parse(a), Decimal::parse(b)) {
+                    (Some(a), Some(b)) => Some(a.cmp(&b)),
+                    _ => Some(a.cmp(b)),
+                }
             }
             (Types::Uuid(a), Types::Uuid(b)) => Some(a.cmp(b)),
             (Types::Boolean(a), Types::Boolean(b)) => Some(a.cmp(b)),
             (Types::Vector(a), Types::Vector(b)) => Some(a.len().cmp(&b.len())),
+            (Types::Point { x: xa, y: ya }, Types::Point { x: xb, y: yb }) => {
+                Some(Types::float_total_cmp(*xa, *xb).then(Types::float_total_cmp(*ya, *yb)))
+            }
+            (Types::IpAddr(IpAddr::V4(a)), Types::IpAddr(IpAddr::V4(b))) => Some(a.cmp(b)),
+            (Types::IpAddr(IpAddr::V6(a)), Types::IpAddr(IpAddr::V6(b))) => Some(a.cmp(b)),
+            (Types::Percentage(a), Types::Percentage(b)) => Some(Types::float_total_cmp(*a, *b)),
+            (Types::Ratio { num: na, den: da }, Types::Ratio { num: nb, den: db }) => {
+                Some(ratio_cmp((*na, *da), (*nb, *db)))
+            }
+            (Types::Map(a), Types::Map(b)) => {
+                let a = sorted_entries(a);
+                let b = sorted_entries(b);
+                for ((key_a, value_a), (key_b, value_b)) in a.iter().zip(b.iter()) {
+                    match key_a.cmp(key_b) {
+                        Ordering::Equal if value_a == value_b => (),
+                        Ordering::Equal => match value_a.partial_cmp(value_b)? {
+                            Ordering::Equal => (),
+                            ordering => return Some(ordering),
+                        },
+                        ordering => return Some(ordering),
+                    }
+                }
+                Some(a.len().cmp(&b.len()))
+            }
             _ => None,
         }
     }
 }
 
-// UNSAFE
-#[allow(clippy::derive_hash_xor_eq)] // for now
+fn sorted_entries(map: &HashMap<String, Types>) -> Vec<(&String, &Types)> {
+    let mut entries = map.iter().collect::<Vec<(&String, &Types)>>();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Floats are equal by `integer_decode`, like their `Hash` and `Types::float_total_cmp`.
+impl PartialEq for Types {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Types::Char(a), Types::Char(b)) => a == b,
+            (Types::Integer(a), Types::Integer(b)) => a == b,
+            (Types::String(a), Types::String(b)) => a == b,
+            (Types::Email(a), Types::Email(b)) => a == b,
+            (Types::Phone(a), Types::Phone(b)) => a == b,
+            (Types::Color(ra, ga, ba), Types::Color(rb, gb, bb)) => (ra, ga, ba) == (rb, gb, bb),
+            (Types::Uuid(a), Types::Uuid(b)) => a == b,
+            (Types::Float(a), Types::Float(b)) => integer_decode(*a) == integer_decode(*b),
+            (Types::Boolean(a), Types::Boolean(b)) => a == b,
+            (Types::Vector(a), Types::Vector(b)) => a == b,
+            (Types::Map(a), Types::Map(b)) => a == b,
+            (Types::Hash(a), Types::Hash(b)) => a == b,
+            (Types::Precise(a), Types::Precise(b)) => {
+                match (Decimal::parse(a), Decimal::parse(b)) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => a == b,
+                }
+            }
+            (Types::DateTime(a), Types::DateTime(b)) => a == b,
+            (Types::Nil, Types::Nil) => true,
+            (Types::Point { x: xa, y: ya }, Types::Point { x: xb, y: yb }) => {
+                integer_decode(*xa) == integer_decode(*xb)
+                    && integer_decode(*ya) == integer_decode(*yb)
+            }
+            (Types::IpAddr(a), Types::IpAddr(b)) => a == b,
+            (Types::Percentage(a), Types::Percentage(b)) => {
+                integer_decode(*a) == integer_decode(*b)
+            }
+            (Types::Ratio { num: na, den: da }, Types::Ratio { num: nb, den: db }) => {
+                ratio_cmp((*na, *da), (*nb, *db)) == Ordering::Equal
+            }
+            _ => false,
+        }
+    }
+}
+
 impl Hash for Types {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         match self {
             Types::Char(t) => t.hash(state),
             Types::Integer(t) => t.hash(state),
             Types::String(t) => t.hash(state),
+            Types::Email(t) => t.hash(state),
+            Types::Phone(t) => t.hash(state),
+            Types::Color(..) => self.packed_color().hash(state),
             Types::Uuid(t) => t.hash(state),
             Types::Float(t) => {
                 let int_t = integer_decode(t.to_owned());
@@ -190,15 +535,62 @@ impl Hash for Types {
             }
             Types::Boolean(t) => t.hash(state),
             Types::Vector(t) => t.hash(state),
-            Types::Map(t) => t.into_iter().fold((), |acc, (k, v)| {
+            Types::Map(t) => sorted_entries(t).into_iter().for_each(|(k, v)| {
                 k.hash(state);
                 v.hash(state);
-                acc
             }),
             Types::Hash(t) => t.hash(state),
-            Types::Precise(t) => t.hash(state),
+            Types::Precise(t) => match Decimal::parse(t) {
+                Some(decimal) => decimal.to_string().hash(state),
+                None => t.hash(state),
+            },
             Types::DateTime(t) => t.hash(state),
             Types::Nil => "".hash(state),
+            Types::Point { x, y } => {
+                integer_decode(*x).hash(state);
+                integer_decode(*y).hash(state);
+            }
+            Types::IpAddr(IpAddr::V4(ip)) => ip.octets().hash(state),
+            Types::IpAddr(IpAddr::V6(ip)) => ip.octets().hash(state),
+            Types::Percentage(t) => integer_decode(*t).hash(state),
+            Types::Ratio { num, den } => {
+                reduce_ratio(*num, *den).unwrap_or((*num, *den)).hash(state)
+            }
         }
     }
 }
+
+/// Reduces `num/den` by their GCD and moves the sign to `num`, `None` if `den` is zero
+/// or the reduced ratio doesn't fit in `i64`, like `i64::MIN/-1`.
+fn reduce_ratio(num: i64, den: i64) -> Option<(i64, i64)> {
+    if den == 0 {
+        return None;
+    }
+    let (mut a, mut b) = (i128::from(num).abs(), i128::from(den).abs());
+    while b != 0 {
+        let rest = a % b;
+        a = b;
+        b = rest;
+    }
+    let sign = if den < 0 { -1 } else { 1 };
+    let num = i64::try_from(sign * i128::from(num) / a).ok()?;
+    let den = i64::try_from(i128::from(den).abs() / a).ok()?;
+    Some((num, den))
+}
+
+/// Compares two ratios by cross-multiplying them as `i128`, so no precision is lost.
+/// Denominators are positive after `Types::ratio`, negative ones are handled anyway.
+fn ratio_cmp((na, da): (i64, i64), (nb, db): (i64, i64)) -> Ordering {
+    let (na, da, nb, db) = (
+        i128::from(na),
+        i128::from(da),
+        i128::from(nb),
+        i128::from(db),
+    );
+    let ordering = (na * db).cmp(&(nb * da));
+    if (da < 0) != (db < 0) {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
@@ -1,7 +1,40 @@
 use chrono::{DateTime, Utc};
+use std::{
+    net::IpAddr,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    time::Duration,
+};
 use uuid::Uuid;
 
-use super::{FromStr, HashMap, MatchCondition, Types};
+use crate::params::placeholder;
+
+use super::{
+    Decimal, Entity, Expression, FromStr, HashMap, MatchCondition, Operator, Projection, ScalarArg,
+    ScalarFunction, Types, TYPE_NAMES,
+};
+
+/// Default of `set_max_nesting_depth`.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 128;
+
+static MAX_NESTING_DEPTH: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_NESTING_DEPTH);
+
+/// Sets how deep vector and map literals can be nested inside an entity map. Deeper literals
+/// are a parse error instead of overflowing the stack of the recursive readers.
+pub fn set_max_nesting_depth(depth: usize) {
+    MAX_NESTING_DEPTH.store(depth, AtomicOrdering::Relaxed);
+}
+
+fn check_nesting_depth(depth: usize) -> Result<(), String> {
+    let max = MAX_NESTING_DEPTH.load(AtomicOrdering::Relaxed);
+    if depth > max {
+        Err(format!(
+            "Vectors and maps cannot be nested deeper than {} levels",
+            max
+        ))
+    } else {
+        Ok(())
+    }
+}
 
 pub(crate) fn read_match_args(chars: &mut std::str::Chars) -> Result<Vec<MatchCondition>, String> {
     let base = chars
@@ -18,7 +51,10 @@ pub(crate) fn read_match_args(chars: &mut std::str::Chars) -> Result<Vec<MatchCo
                 .split(' ')
                 .filter(|f| !f.is_empty())
                 .collect::<Vec<&str>>();
-            let mut c = k[2].chars();
+            let mut c = k
+                .get(2)
+                .ok_or_else(|| String::from("Not able to parse match argument"))?
+                .chars();
             match k.get(1) {
                 Some(&"==") => Ok(MatchCondition::Eq(
                     k[0].to_string(),
@@ -68,6 +104,7 @@ pub(crate) fn read_match_args(chars: &mut std::str::Chars) -> Result<Vec<MatchCo
                         &mut c,
                     )?,
                 )),
+                Some(op) if op.eq_ignore_ascii_case("OLDER") => read_older_than(&k),
                 _ => Err(String::from("Unidentified Match Condition")),
             }
         })
@@ -79,8 +116,64 @@ pub(crate) fn read_match_args(chars: &mut std::str::Chars) -> Result<Vec<MatchCo
     Ok(conditions)
 }
 
+fn read_older_than(args: &[&str]) -> Result<MatchCondition, String> {
+    match args {
+        [key, _, than, age] if than.eq_ignore_ascii_case("THAN") => Ok(MatchCondition::OlderThan(
+            key.to_string(),
+            parse_duration(age)?,
+        )),
+        _ => Err(String::from(
+            "OLDER THAN requires a key and a duration, like `last_seen OLDER THAN 30d`",
+        )),
+    }
+}
+
+/// Amount followed by `s`, `m`, `h`, `d` or `w`, like `30d`.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let error = || {
+        format!(
+            "Duration `{}` should be an amount followed by s, m, h, d or w, like `30d`",
+            duration
+        )
+    };
+    let unit_start = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(error)?;
+    let (amount, unit) = duration.split_at(unit_start);
+    let amount = amount.parse::<u64>().map_err(|_| error())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(error()),
+    };
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(error)
+}
+
 pub(crate) fn read_map(chars: &mut std::str::Chars) -> Result<HashMap<String, Types>, String> {
+    read_entity_map(chars, false).map(|(entity, _)| entity)
+}
+
+/// Entity map of `MATCH UPDATE ... SET`, whose values can also be expressions over the
+/// current entity map, like `{balance: balance - 100,}`. Expressions are returned apart.
+pub(crate) fn read_update_map(
+    chars: &mut std::str::Chars,
+) -> Result<(Entity, HashMap<String, Expression>), String> {
+    read_entity_map(chars, true)
+}
+
+/// Reads an entity map, values are only read as expressions if `with_expressions` is set.
+fn read_entity_map(
+    chars: &mut std::str::Chars,
+    with_expressions: bool,
+) -> Result<(Entity, HashMap<String, Expression>), String> {
     let mut res: HashMap<String, Types> = HashMap::new();
+    let mut expressions: HashMap<String, Expression> = HashMap::new();
     let mut key: Option<String> = None;
     let mut val: Option<Types> = None;
     loop {
@@ -94,29 +187,37 @@ pub(crate) fn read_map(chars: &mut std::str::Chars) -> Result<HashMap<String, Ty
             }
         }
     }
+    scan_literal(chars.as_str(), '{')?;
 
     loop {
         match chars.next() {
-            Some('}') => return Ok(res),
+            Some('}') => return Ok((res, expressions)),
             Some('{') => {
                 if key.is_some() {
-                    val = Some(Types::Map(read_inner_map(chars)?));
+                    val = Some(Types::Map(read_inner_map(chars, 1)?));
                 } else {
                     return Err(String::from("Key must be an alphanumeric value"));
                 }
             }
             Some('[') => {
                 if key.is_some() {
-                    val = Some(Types::Vector(read_vec(chars)?));
+                    val = Some(Types::Vector(read_vec(chars, 1)?));
                 } else {
                     return Err(String::from("Key must be an alphanumeric value"));
                 }
             }
             Some(c) if !c.is_whitespace() && c != ',' => {
-                if key.is_some() {
-                    val = Some(parse_value(c, chars)?);
-                } else {
+                if key.is_none() {
                     key = Some(parse_key(c, chars));
+                } else if with_expressions {
+                    match read_expression(c, chars)? {
+                        Some(expression) => {
+                            expressions.insert(key.take().unwrap(), expression);
+                        }
+                        None => val = Some(parse_value(c, chars)?),
+                    }
+                } else {
+                    val = Some(parse_value(c, chars)?);
                 }
             }
             Some(c) if c.is_whitespace() || c == ',' => (),
@@ -131,6 +232,62 @@ pub(crate) fn read_map(chars: &mut std::str::Chars) -> Result<HashMap<String, Ty
     }
 }
 
+/// Reads `left operator right` if the value starting at `c` is followed by an operator,
+/// otherwise `chars` is left untouched. Operators must be surrounded by whitespaces.
+fn read_expression(c: char, chars: &mut std::str::Chars) -> Result<Option<Expression>, String> {
+    if c == '"' {
+        return Ok(None);
+    }
+    let rest = chars.as_str();
+    let token_end = |s: &str| {
+        s.find(|c: char| c.is_whitespace() || c == ',')
+            .unwrap_or(s.len())
+    };
+    let (left, rest) = rest.split_at(token_end(rest));
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace());
+    let (symbol, tail) = rest.split_at(token_end(rest));
+    let operator = match Operator::parse(symbol) {
+        Some(operator) if tail.starts_with(char::is_whitespace) => operator,
+        _ => return Ok(None),
+    };
+    let tail = tail.trim_start();
+    let (right, tail) = tail.split_at(token_end(tail));
+
+    let expression = Expression {
+        left: expression_operand(&format!("{}{}", c, left))?,
+        operator,
+        right: expression_operand(right)?,
+    };
+    *chars = tail.chars();
+    Ok(Some(expression))
+}
+
+fn expression_operand(token: &str) -> Result<ScalarArg, String> {
+    let error = || {
+        format!(
+            "Expression operand `{}` must be a key or an Integer, Float or Precise",
+            token
+        )
+    };
+    let mut chars = token.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            if token.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                Ok(ScalarArg::Key(token.to_owned()))
+            } else {
+                Err(error())
+            }
+        }
+        Some(c) => match parse_value(c, &mut chars) {
+            Ok(value @ Types::Integer(_))
+            | Ok(value @ Types::Float(_))
+            | Ok(value @ Types::Precise(_)) => Ok(ScalarArg::Value(value)),
+            _ => Err(error()),
+        },
+        None => Err(error()),
+    }
+}
+
 pub(crate) fn read_map_as_str(
     chars: &mut std::str::Chars,
 ) -> Result<HashMap<String, String>, String> {
@@ -149,6 +306,7 @@ pub(crate) fn read_map_as_str(
             }
         }
     }
+    scan_literal(chars.as_str(), '{')?;
 
     loop {
         match chars.next() {
@@ -174,7 +332,9 @@ pub(crate) fn read_map_as_str(
 
 pub(crate) fn read_inner_map(
     chars: &mut std::str::Chars,
+    depth: usize,
 ) -> Result<HashMap<String, Types>, String> {
+    check_nesting_depth(depth)?;
     let mut res: HashMap<String, Types> = HashMap::new();
     let mut key: Option<String> = None;
     let mut val: Option<Types> = None;
@@ -184,14 +344,14 @@ pub(crate) fn read_inner_map(
             Some('}') => return Ok(res),
             Some('{') => {
                 if key.is_some() {
-                    val = Some(Types::Map(read_inner_map(chars)?));
+                    val = Some(Types::Map(read_inner_map(chars, depth + 1)?));
                 } else {
                     return Err(String::from("Key must be an alphanumeric value"));
                 }
             }
             Some('[') => {
                 if key.is_some() {
-                    val = Some(Types::Vector(read_vec(chars)?));
+                    val = Some(Types::Vector(read_vec(chars, depth + 1)?));
                 } else {
                     return Err(String::from("Key must be an alphanumeric value"));
                 }
@@ -215,13 +375,16 @@ pub(crate) fn read_inner_map(
     }
 }
 
-fn read_vec(chars: &mut std::str::Chars) -> Result<Vec<Types>, String> {
+/// Reads a vector literal right after its `[`, `depth` is the nesting of the literal, `1`
+/// for a value of the entity map.
+fn read_vec(chars: &mut std::str::Chars, depth: usize) -> Result<Vec<Types>, String> {
+    check_nesting_depth(depth)?;
     let mut res: Vec<Types> = vec![];
     loop {
         match chars.next() {
             Some(']') => return Ok(res),
-            Some('[') => res.push(Types::Vector(read_vec(chars)?)),
-            Some('{') => res.push(Types::Map(read_inner_map(chars)?)),
+            Some('[') => res.push(Types::Vector(read_vec(chars, depth + 1)?)),
+            Some('{') => res.push(Types::Map(read_inner_map(chars, depth + 1)?)),
             Some(c) if !c.is_whitespace() && c != ',' => {
                 res.push(parse_value(c, chars)?);
             }
@@ -231,8 +394,11 @@ fn read_vec(chars: &mut std::str::Chars) -> Result<Vec<Types>, String> {
     }
 }
 
-pub(crate) fn read_select_args(chars: &mut std::str::Chars) -> Result<Vec<String>, String> {
+pub(crate) fn read_select_args(
+    chars: &mut std::str::Chars,
+) -> Result<(Vec<String>, Vec<Projection>), String> {
     let mut res = Vec::new();
+    let mut projections = Vec::new();
     if chars.next() != Some('{') {
         return Err(String::from(
             "SELECT arguments set should start with `#{` and end with `}`",
@@ -241,8 +407,13 @@ pub(crate) fn read_select_args(chars: &mut std::str::Chars) -> Result<Vec<String
 
     loop {
         match chars.next() {
-            Some('}') => return Ok(res),
+            Some('}') => return Ok((res, projections)),
             Some(c) if !c.is_whitespace() && c != ',' => {
+                if let Some((alias, function)) = read_projection(c, chars)? {
+                    res.push(alias.clone());
+                    projections.push((alias, function));
+                    continue;
+                }
                 let key_rest = chars
                     .take_while(|c| c.is_alphanumeric() || c == &'_')
                     .collect::<String>();
@@ -256,6 +427,247 @@ pub(crate) fn read_select_args(chars: &mut std::str::Chars) -> Result<Vec<String
     }
 }
 
+/// Projections following `SELECT *`, like `SELECT *, "v2" AS source FROM entity`.
+pub(crate) fn read_all_projections(chars: &mut std::str::Chars) -> Result<Vec<Projection>, String> {
+    let mut projections = Vec::new();
+    loop {
+        let rest = chars.as_str().trim_start();
+        if !rest.starts_with(',') {
+            return Ok(projections);
+        }
+        let mut projection_chars = rest[1..].trim_start().chars();
+        let projection = match projection_chars.next() {
+            Some(c) => read_projection(c, &mut projection_chars)?,
+            None => None,
+        };
+        match projection {
+            Some(projection) => projections.push(projection),
+            None => {
+                return Err(String::from(
+                    "Only literals and scalar functions with an alias can follow `SELECT *`, like `SELECT *, \"v2\" AS source FROM entity`",
+                ))
+            }
+        }
+        *chars = projection_chars;
+    }
+}
+
+/// Parses a scalar function or a literal projection starting at `c`, `None` when `c`
+/// starts a key.
+fn read_projection(c: char, chars: &mut std::str::Chars) -> Result<Option<Projection>, String> {
+    if let Some(function) = scalar_function_name(c, chars.as_str()) {
+        chars.take_while(|c| c != &'(').for_each(drop);
+        let args = read_scalar_args(chars)?;
+        let alias = read_alias(function, chars)?;
+        let function = match (function, args.len()) {
+            ("COALESCE", n) if n > 0 => ScalarFunction::Coalesce(args),
+            ("NULLIF", 2) => {
+                let mut args = args.into_iter();
+                ScalarFunction::NullIf(args.next().unwrap(), args.next().unwrap())
+            }
+            ("SORT", 1) => ScalarFunction::Sort(args.into_iter().next().unwrap()),
+            ("REVERSE", 1) => ScalarFunction::Reverse(args.into_iter().next().unwrap()),
+            ("UNIQUE", 1) => ScalarFunction::Unique(args.into_iter().next().unwrap()),
+            ("FLATTEN", 1) => ScalarFunction::Flatten(args.into_iter().next().unwrap()),
+            ("RED", 1) => ScalarFunction::Red(args.into_iter().next().unwrap()),
+            ("GREEN", 1) => ScalarFunction::Green(args.into_iter().next().unwrap()),
+            ("BLUE", 1) => ScalarFunction::Blue(args.into_iter().next().unwrap()),
+            ("SUM_ARRAY", 1) => ScalarFunction::SumArray(args.into_iter().next().unwrap()),
+            ("MAX_ARRAY", 1) => ScalarFunction::MaxArray(args.into_iter().next().unwrap()),
+            ("MIN_ARRAY", 1) => ScalarFunction::MinArray(args.into_iter().next().unwrap()),
+            ("AVG_ARRAY", 1) => ScalarFunction::AvgArray(args.into_iter().next().unwrap()),
+            ("SLICE", 3) => {
+                let mut args = args.into_iter();
+                let vector = args.next().unwrap();
+                match (args.next(), args.next()) {
+                    (
+                        Some(ScalarArg::Value(Types::Integer(start))),
+                        Some(ScalarArg::Value(Types::Integer(end))),
+                    ) => ScalarFunction::Slice(vector, start, end),
+                    _ => return Err(String::from("SLICE indexes must be integers")),
+                }
+            }
+            ("CAST", 3) => {
+                let mut args = args.into_iter();
+                let value = args.next().unwrap();
+                match (args.next(), args.next()) {
+                    (Some(ScalarArg::Key(keyword)), Some(ScalarArg::Key(type_name)))
+                        if keyword.eq_ignore_ascii_case("AS") =>
+                    {
+                        if type_name == "Hash" || !TYPE_NAMES.contains(&&type_name[..]) {
+                            return Err(format!(
+                                "CAST type `{}` must be one of {}, except Hash and Nil",
+                                type_name,
+                                TYPE_NAMES.join(", ")
+                            ));
+                        }
+                        ScalarFunction::Cast(value, type_name)
+                    }
+                    _ => return Err(String::from(CAST_USAGE)),
+                }
+            }
+            ("CAST", _) => return Err(String::from(CAST_USAGE)),
+            ("COALESCE", _) => return Err(String::from("COALESCE requires at least one argument")),
+            ("NULLIF", _) => return Err(String::from("NULLIF requires exactly two arguments")),
+            ("SLICE", _) => return Err(String::from("SLICE requires exactly three arguments")),
+            (name, _) => return Err(format!("{} requires exactly one argument", name)),
+        };
+        return Ok(Some((alias, function)));
+    }
+    if let Some(projection) = read_index_projection(c, chars)? {
+        return Ok(Some(projection));
+    }
+    read_literal_projection(c, chars)
+}
+
+/// Parses `coords[0] AS lat`, the element at an index of the `Vector` of a key.
+fn read_index_projection(
+    c: char,
+    chars: &mut std::str::Chars,
+) -> Result<Option<Projection>, String> {
+    if !(c.is_alphabetic() || c == '_') {
+        return Ok(None);
+    }
+    let rest = chars.as_str();
+    let (key_rest, tail) = rest.split_at(
+        rest.find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len()),
+    );
+    if !tail.starts_with('[') {
+        return Ok(None);
+    }
+    let key = format!("{}{}", c, key_rest);
+    let close = tail
+        .find(']')
+        .ok_or_else(|| format!("Index of key `{}` should end with `]`", key))?;
+    let index = tail[1..close].trim().parse::<usize>().map_err(|_| {
+        format!(
+            "Index of key `{}` must be a non negative integer, found `{}`",
+            key,
+            &tail[1..close]
+        )
+    })?;
+
+    *chars = tail[close + 1..].chars();
+    let alias = read_alias("Index", chars).map_err(|_| {
+        format!(
+            "Indexed projections require an alias, like `{}[{}] AS value`",
+            key, index
+        )
+    })?;
+    Ok(Some((alias, ScalarFunction::Index(key, index))))
+}
+
+/// Parses `"v2" AS source`, the value is parsed like insert values. Strings are always
+/// literals, other values only when followed by `AS`, as keys can't have an alias.
+fn read_literal_projection(
+    c: char,
+    chars: &mut std::str::Chars,
+) -> Result<Option<Projection>, String> {
+    let mut value_chars = chars.clone();
+    let value = match parse_value(c, &mut value_chars) {
+        Ok(value) => value,
+        Err(e) if c == '"' => return Err(e),
+        Err(_) => return Ok(None),
+    };
+    let rest = value_chars.as_str().trim_start();
+    let has_alias = rest.get(..3).is_some_and(|s| {
+        s[..2].eq_ignore_ascii_case("AS") && s.ends_with(char::is_whitespace)
+    });
+    if c != '"' && !has_alias {
+        return Ok(None);
+    }
+
+    *chars = value_chars;
+    let alias = read_alias("Literal", chars).map_err(|_| {
+        String::from("Literal projections require an alias, like `\"v2\" AS source`")
+    })?;
+    Ok(Some((alias, ScalarFunction::Literal(value))))
+}
+
+const SCALAR_FUNCTIONS: [&str; 15] = [
+    "COALESCE",
+    "NULLIF",
+    "SORT",
+    "REVERSE",
+    "UNIQUE",
+    "FLATTEN",
+    "SLICE",
+    "RED",
+    "GREEN",
+    "BLUE",
+    "CAST",
+    "SUM_ARRAY",
+    "MAX_ARRAY",
+    "MIN_ARRAY",
+    "AVG_ARRAY",
+];
+
+const CAST_USAGE: &str = "CAST requires a key or value and a type, like `CAST(age AS String)`";
+
+fn scalar_function_name(c: char, rest: &str) -> Option<&'static str> {
+    let token = format!("{}{}", c, rest).to_uppercase();
+    SCALAR_FUNCTIONS
+        .iter()
+        .find(|name| token.starts_with(&format!("{}(", name)))
+        .copied()
+}
+
+fn read_scalar_args(chars: &mut std::str::Chars) -> Result<Vec<ScalarArg>, String> {
+    let mut args = Vec::new();
+    loop {
+        match chars.next() {
+            Some(')') => return Ok(args),
+            Some(c) if c.is_whitespace() || c == ',' => (),
+            Some('"') => args.push(ScalarArg::Value(read_str(chars)?)),
+            Some(c) => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || c == ',' || c == ')')
+                    .unwrap_or(rest.len());
+                let token = format!("{}{}", c, &rest[..end]);
+                *chars = rest[end..].chars();
+
+                let is_key = (c.is_alphabetic() || c == '_')
+                    && token.chars().all(|c| c.is_alphanumeric() || c == '_')
+                    && token.parse::<bool>().is_err()
+                    && token.to_lowercase() != "nil";
+                if is_key {
+                    args.push(ScalarArg::Key(token));
+                } else {
+                    let mut token = token.chars();
+                    let first = token.next().unwrap_or_default();
+                    args.push(ScalarArg::Value(parse_value(first, &mut token)?));
+                }
+            }
+            None => {
+                return Err(String::from(
+                    "Scalar function arguments should start with `(` and end with `)`",
+                ))
+            }
+        }
+    }
+}
+
+fn read_alias(function: &str, chars: &mut std::str::Chars) -> Result<String, String> {
+    let rest = chars.as_str().trim_start();
+    let (symbol, tail) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+    let tail = tail.trim_start();
+    let end = tail
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(tail.len());
+
+    if !symbol.eq_ignore_ascii_case("AS") || end == 0 {
+        return Err(format!(
+            "{} requires an alias, like `{}(a, b) AS c`",
+            function, function
+        ));
+    }
+    let alias = tail[..end].to_string();
+    *chars = tail[end..].chars();
+    Ok(alias)
+}
+
 pub(crate) fn read_args(chars: &mut std::str::Chars) -> Result<Vec<String>, String> {
     let mut res = Vec::new();
     if chars.next() != Some('{') {
@@ -295,6 +707,14 @@ pub fn parse_value(c: char, chars: &mut std::str::Chars) -> Result<Types, String
     if c == '"' {
         return read_str(chars);
     }
+    if (c == 'p' || c == 'P') && chars.as_str().to_lowercase().starts_with("oint(") {
+        let point = chars.take_while(|c| c != &')').collect::<String>();
+        return parse_point(&format!("{}{})", c, point));
+    }
+    if (c == 'p' || c == 'P') && chars.as_str().to_lowercase().starts_with("hone(") {
+        let phone = chars.take_while(|c| c != &')').collect::<String>();
+        return Types::phone(&phone[5..]);
+    }
 
     let value = format!(
         "{}{}",
@@ -303,8 +723,29 @@ pub fn parse_value(c: char, chars: &mut std::str::Chars) -> Result<Types, String
             .take_while(|c| !c.is_whitespace() && c != &',')
             .collect::<String>()
     );
-    if value.ends_with('P') && value[..value.len() - 1].parse::<f64>().is_ok() {
-        Ok(Types::Precise(value[..value.len() - 1].to_string()))
+    if let Some(percentage) = value.strip_suffix('%') {
+        return parse_percentage(percentage)
+            .ok_or_else(|| format!("Percentage value `{}` must be from 0% to 100%", value));
+    }
+    if value.contains('@') {
+        return Types::email(&value);
+    }
+    if value.starts_with('#') {
+        return Types::color(&value);
+    }
+    if let Some((num, den)) = value.split_once('/') {
+        if let (Ok(num), Ok(den)) = (num.parse::<i64>(), den.parse::<i64>()) {
+            return Types::ratio(num, den);
+        }
+    }
+    if let Some(precise) = value.strip_suffix('P') {
+        if Decimal::parse(precise).is_none() {
+            return Err(format!(
+                "Precise value `{}` must be a decimal number, like `-12.34P`",
+                value
+            ));
+        }
+        Ok(Types::Precise(precise.to_string()))
     } else if value.parse::<isize>().is_ok() {
         Ok(Types::Integer(value.parse().unwrap()))
     } else if value.parse::<f64>().is_ok() {
@@ -319,11 +760,192 @@ pub fn parse_value(c: char, chars: &mut std::str::Chars) -> Result<Types, String
         Ok(Types::Char(value.chars().nth(1).unwrap()))
     } else if value.parse::<DateTime<Utc>>().is_ok() {
         Ok(Types::DateTime(value.parse::<DateTime<Utc>>().unwrap()))
+    } else if let Ok(ip) = value.parse::<IpAddr>() {
+        Ok(Types::IpAddr(ip))
+    } else if let Some(placeholder) = placeholder(&value)? {
+        Ok(placeholder)
     } else {
         Err(format!("Value Type could not be created from {}", value))
     }
 }
 
+/// Parses a raw `value` as the type named `type_name`, so `10` can be an `Integer`, a
+/// `Float` or a `Precise`. Strings are not quoted, vectors and maps use WQL syntax.
+pub(crate) fn parse_value_as(value: &str, type_name: &str) -> Result<Types, String> {
+    let error = || format!("`{}` could not be parsed as {}", value, type_name);
+    let trimmed = value.trim();
+    match type_name {
+        "Char" => {
+            let mut chars = trimmed.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Types::Char(c)),
+                _ => Err(error()),
+            }
+        }
+        "Integer" => trimmed.parse().map(Types::Integer).map_err(|_| error()),
+        "String" => Ok(Types::String(value.to_owned())),
+        "Uuid" => Uuid::from_str(trimmed)
+            .map(Types::Uuid)
+            .map_err(|_| error()),
+        "Float" => trimmed.parse().map(Types::Float).map_err(|_| error()),
+        "Boolean" => trimmed.parse().map(Types::Boolean).map_err(|_| error()),
+        "Vector" | "Map" => {
+            let mut chars = trimmed.chars();
+            let parsed = match (type_name, chars.next()) {
+                ("Vector", Some('[')) => read_vec(&mut chars, 1).map(Types::Vector),
+                ("Map", Some('{')) => read_inner_map(&mut chars, 1).map(Types::Map),
+                _ => return Err(error()),
+            };
+            match parsed {
+                Ok(parsed) if chars.as_str().trim().is_empty() => Ok(parsed),
+                _ => Err(error()),
+            }
+        }
+        "Hash" => Err(String::from(
+            "Hash values are created by ENCRYPTS and cannot be parsed",
+        )),
+        "Precise" => {
+            let precise = trimmed.strip_suffix('P').unwrap_or(trimmed);
+            Decimal::parse(precise)
+                .map(|_| Types::Precise(precise.to_string()))
+                .ok_or_else(error)
+        }
+        "DateTime" => trimmed
+            .parse::<DateTime<Utc>>()
+            .map(Types::DateTime)
+            .map_err(|_| error()),
+        "Nil" if trimmed.eq_ignore_ascii_case("nil") => Ok(Types::Nil),
+        "Nil" => Err(error()),
+        "Point" if trimmed.to_lowercase().starts_with("point(") => {
+            parse_point(trimmed).map_err(|_| error())
+        }
+        "Point" => parse_point(&format!("point({})", trimmed)).map_err(|_| error()),
+        "IpAddr" => trimmed.parse().map(Types::IpAddr).map_err(|_| error()),
+        "Percentage" => {
+            parse_percentage(trimmed.strip_suffix('%').unwrap_or(trimmed)).ok_or_else(error)
+        }
+        "Email" => Types::email(trimmed),
+        "Color" => Types::color(trimmed),
+        "Phone" => Types::phone(trimmed),
+        "Ratio" => {
+            let (num, den) = trimmed.split_once('/').ok_or_else(error)?;
+            match (num.trim().parse(), den.trim().parse()) {
+                (Ok(num), Ok(den)) => Types::ratio(num, den),
+                _ => Err(error()),
+            }
+        }
+        _ => Err(format!(
+            "Type `{}` is not valid. Valid types are {:?}",
+            type_name, TYPE_NAMES
+        )),
+    }
+}
+
+/// Parses the number of a percentage, without its `%`, it must be from 0 to 100.
+fn parse_percentage(value: &str) -> Option<Types> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|p| (0_f64..=100_f64).contains(p))
+        .map(Types::Percentage)
+}
+
+/// Lowercases and validates an email address, like the regex
+/// `^[a-z0-9._%+-]+@[a-z0-9-]+(\.[a-z0-9-]+)*\.[a-z]{2,}$`, domain labels can't start or
+/// end with `-`.
+pub(crate) fn parse_email(address: &str) -> Option<String> {
+    let address = address.to_lowercase();
+    let (local, domain) = address.split_once('@')?;
+    let is_local_char = |c: char| c.is_ascii_alphanumeric() || "._%+-".contains(c);
+    if local.is_empty() || !local.chars().all(is_local_char) {
+        return None;
+    }
+    let labels = domain.split('.').collect::<Vec<&str>>();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    let top_level = labels.last()?;
+    if labels.len() < 2
+        || !valid_labels
+        || top_level.len() < 2
+        || !top_level.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    Some(address)
+}
+
+/// Normalizes an international phone number to E.164, a `+` followed by 7 to 15 digits
+/// and no leading zero. `00` can replace the `+` and spaces, `-`, `.` and parentheses
+/// between digits are dropped, like in `+1 (555) 123-4567`.
+pub(crate) fn parse_phone(number: &str) -> Option<String> {
+    let number = number.trim();
+    let digits = number
+        .strip_prefix('+')
+        .or_else(|| number.strip_prefix("00"))?;
+    let digits = digits
+        .chars()
+        .filter(|c| !" -.()".contains(*c))
+        .collect::<String>();
+    if !(7..=15).contains(&digits.len())
+        || digits.starts_with('0')
+        || !digits.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(format!("+{}", digits))
+}
+
+/// Parses a `#` followed by the red, green and blue hex digits, like `#ff8800` or `#FF8800`.
+pub(crate) fn parse_color(hex: &str) -> Option<Types> {
+    let digits = hex.strip_prefix('#')?;
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let component = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some(Types::Color(component(0)?, component(2)?, component(4)?))
+}
+
+/// Parses `point(x, y)`, coordinates can be separated by `,` or whitespaces.
+pub(crate) fn parse_point(value: &str) -> Result<Types, String> {
+    let error = || {
+        format!(
+            "Point should be written as `point(x, y)`, like `point(12.3, 45.6)`. Found {}",
+            value
+        )
+    };
+    if !value.to_lowercase().starts_with("point(") || !value.ends_with(')') {
+        return Err(error());
+    }
+
+    let coordinates = value[6..value.len() - 1]
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|c| !c.is_empty())
+        .map(str::parse::<f64>)
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| error())?;
+    match coordinates[..] {
+        [x, y] => Ok(Types::Point { x, y }),
+        _ => Err(error()),
+    }
+}
+
+/// Parses a CIDR subnet like `10.0.0.0/8` or `2001:db8::/32` into its network address
+/// and prefix length. The prefix can't be longer than the address.
+pub(crate) fn parse_subnet(value: &str) -> Option<(IpAddr, u8)> {
+    let (network, prefix) = value.split_once('/')?;
+    let network = network.parse::<IpAddr>().ok()?;
+    let prefix = prefix.parse::<u8>().ok()?;
+    let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+    if prefix > max_prefix {
+        return None;
+    }
+    Some((network, prefix))
+}
+
 pub(crate) fn parse_str_value(c: char, chars: &mut std::str::Chars) -> String {
     format!(
         "{}{}",
@@ -336,6 +958,7 @@ pub(crate) fn parse_str_value(c: char, chars: &mut std::str::Chars) -> String {
 }
 
 pub(crate) fn read_str(chars: &mut std::str::Chars) -> Result<Types, String> {
+    let start = chars.as_str();
     let result = chars.try_fold((false, String::new()), |(last_was_escape, mut s), c| {
         if last_was_escape {
             // Supported escape characters, per https://github.com/edn-format/edn#strings
@@ -363,12 +986,73 @@ pub(crate) fn read_str(chars: &mut std::str::Chars) -> Result<Types, String> {
     match result {
         // An Ok means we actually finished parsing *without* seeing the end of the string, so that's
         // an error.
-        Ok(_) => Err("Unterminated string".to_string()),
+        Ok(_) => Err(unterminated("string", start)),
         Err(Err(e)) => Err(e),
         Err(Ok(string)) => Ok(Types::String(string)),
     }
 }
 
+/// Scans the map or vector opened by `opener` right before `rest` up to its closing char, so
+/// a literal missing it is reported instead of reading the rest of the statement as values.
+/// Strings and char values are skipped, brackets inside them don't open literals.
+fn scan_literal(rest: &str, opener: char) -> Result<(), String> {
+    let mut open = vec![(opener, rest)];
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let after = &rest[i + c.len_utf8()..];
+        match (open.last().map(|(opener, _)| *opener), c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some('"'), '"') | (Some('{'), '}') | (Some('['), ']') => {
+                open.pop();
+            }
+            (Some('"'), _) => (),
+            (_, '\'') if after.chars().nth(1) == Some('\'') => {
+                chars.nth(1);
+            }
+            (_, '"') | (_, '{') | (_, '[') => open.push((c, after)),
+            _ => (),
+        }
+        if open.is_empty() {
+            return Ok(());
+        }
+    }
+    match open.pop() {
+        Some(('"', start)) => Err(unterminated("string", start)),
+        Some(('[', start)) => Err(unterminated("vector", start)),
+        Some((_, start)) => Err(unterminated("map", start)),
+        None => Ok(()),
+    }
+}
+
+/// Error of a literal opened right before `rest` and never closed. The statement isn't known
+/// by the readers, so the position is counted back from its end until `locate_unterminated`
+/// counts it from its start.
+fn unterminated(construct: &str, rest: &str) -> String {
+    format!(
+        "Unterminated {} starting at char -{}",
+        construct,
+        rest.chars().count() + 1
+    )
+}
+
+/// Counts the position of an unterminated literal error from the start of `statement`.
+pub(crate) fn locate_unterminated(error: String, statement: &str) -> String {
+    let located = error
+        .strip_prefix("Unterminated ")
+        .and_then(|e| e.split_once(" starting at char -"))
+        .and_then(|(construct, from_end)| Some((construct, from_end.parse::<usize>().ok()?)));
+    match located {
+        Some((construct, from_end)) => format!(
+            "Unterminated {} starting at char {}",
+            construct,
+            (statement.chars().count() + 1).saturating_sub(from_end)
+        ),
+        None => error,
+    }
+}
+
 pub(crate) fn read_uuids(chars: &mut std::str::Chars) -> Result<Vec<Uuid>, String> {
     let mut uuids = Vec::new();
     let mut uuid = String::new();
@@ -0,0 +1,335 @@
+use crate::{
+    logic::locate_unterminated, parse, tokenize, Algebra, Clause, Expression, Function,
+    MatchCondition, ScalarArg, ScalarFunction, Types, Wql,
+};
+
+/// `$n` placeholders are parsed as `Types::Hash("$n")`, a value no WQL literal produces, and
+/// replaced by the params once the statement is parsed. Params are never read as WQL.
+/// Indexes that don't fit a `usize` are an error, they could never be bound or rejected.
+pub(crate) fn placeholder(value: &str) -> Result<Option<Types>, String> {
+    let index = match value.strip_prefix('$') {
+        Some(index) if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) => index,
+        _ => return Ok(None),
+    };
+    index
+        .parse::<usize>()
+        .map(|_| Some(Types::Hash(value.to_owned())))
+        .map_err(|_| format!("Placeholder `{}` index is too large", value))
+}
+
+fn placeholder_index(value: &Types) -> Option<usize> {
+    match value {
+        Types::Hash(hash) => hash.strip_prefix('$')?.parse().ok(),
+        _ => None,
+    }
+}
+
+impl Wql {
+    /// Parses a statement with `$1`, `$2`, ... placeholders and binds them to `params`, `$1`
+    /// being the first param. Every param must be used by a placeholder.
+    pub fn from_str_with_params(s: &str, params: &[Types]) -> Result<Wql, String> {
+        let mut tokens = tokenize(s.trim_start());
+        let mut wql = parse(tokens.next(), &mut tokens).map_err(|e| locate_unterminated(e, s))?;
+        let mut bound = vec![false; params.len()];
+        wql.visit_values(&mut |value| {
+            if let Some(index) = placeholder_index(value) {
+                let param = index
+                    .checked_sub(1)
+                    .and_then(|i| params.get(i))
+                    .ok_or_else(|| {
+                        format!(
+                            "Placeholder `${}` has no param, {} params were given",
+                            index,
+                            params.len()
+                        )
+                    })?;
+                *value = param.to_owned();
+                bound[index - 1] = true;
+            }
+            Ok(())
+        })?;
+        if let Some(unused) = bound.iter().position(|bound| !bound) {
+            return Err(format!(
+                "Param {} is not used, the statement has no placeholder `${}`",
+                unused + 1,
+                unused + 1
+            ));
+        }
+        Ok(wql)
+    }
+
+    /// Errors if the statement still has `$n` placeholders, they require params.
+    pub(crate) fn reject_placeholders(&mut self) -> Result<(), String> {
+        self.visit_values(&mut |value| match placeholder_index(value) {
+            Some(index) => Err(format!(
+                "Placeholder `${}` requires params to be bound",
+                index
+            )),
+            None => Ok(()),
+        })
+    }
+
+    /// Visits the values parsed from WQL literals of inserts, updates, `MATCH` conditions,
+    /// `WHERE` clauses and projections, including the elements of vectors and maps.
+    fn visit_values<F>(&mut self, f: &mut F) -> Result<(), String>
+    where
+        F: FnMut(&mut Types) -> Result<(), String>,
+    {
+        match self {
+            Wql::Insert(_, content, _)
+            | Wql::InsertNext(_, content)
+            | Wql::UpdateContent(_, content, _)
+            | Wql::UpdateSet(_, content, _, _)
+            | Wql::Replace(_, content, _) => content.values_mut().try_for_each(|v| visit(v, f)),
+            Wql::MatchUpdate(_, content, _, condition, expressions) => {
+                content.values_mut().try_for_each(|v| visit(v, f))?;
+                visit_condition(condition, f)?;
+                expressions
+                    .values_mut()
+                    .try_for_each(|expression| visit_expression(expression, f))
+            }
+            Wql::Select(_, _, _, functions) | Wql::SelectIds(_, _, _, functions) => {
+                functions.values_mut().try_for_each(|a| visit_algebra(a, f))
+            }
+            Wql::SelectWhere(_, _, clauses, functions) => {
+                clauses.iter_mut().try_for_each(|c| visit_clause(c, f))?;
+                functions.values_mut().try_for_each(|a| visit_algebra(a, f))
+            }
+            Wql::DeleteWhere(_, clauses) => clauses.iter_mut().try_for_each(|c| visit_clause(c, f)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn visit<F>(value: &mut Types, f: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut Types) -> Result<(), String>,
+{
+    match value {
+        Types::Vector(values) => values.iter_mut().try_for_each(|v| visit(v, f)),
+        Types::Map(map) => map.values_mut().try_for_each(|v| visit(v, f)),
+        _ => f(value),
+    }
+}
+
+fn visit_condition<F>(condition: &mut MatchCondition, f: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut Types) -> Result<(), String>,
+{
+    match condition {
+        MatchCondition::All(conditions) | MatchCondition::Any(conditions) => conditions
+            .iter_mut()
+            .try_for_each(|c| visit_condition(c, f)),
+        MatchCondition::Eq(_, value)
+        | MatchCondition::NotEq(_, value)
+        | MatchCondition::GEq(_, value)
+        | MatchCondition::G(_, value)
+        | MatchCondition::LEq(_, value)
+        | MatchCondition::L(_, value) => visit(value, f),
+        MatchCondition::OlderThan(_, _) => Ok(()),
+    }
+}
+
+fn visit_expression<F>(expression: &mut Expression, f: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut Types) -> Result<(), String>,
+{
+    visit_arg(&mut expression.left, f)?;
+    visit_arg(&mut expression.right, f)
+}
+
+fn visit_arg<F>(arg: &mut ScalarArg, f: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut Types) -> Result<(), String>,
+{
+    match arg {
+        ScalarArg::Value(value) => visit(value, f),
+        ScalarArg::Key(_) => Ok(()),
+    }
+}
+
+fn visit_algebra<F>(algebra: &mut Algebra, f: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut Types) -> Result<(), String>,
+{
+    let projections = match algebra {
+        Algebra::Project(projections) => projections,
+        _ => return Ok(()),
+    };
+    projections
+        .iter_mut()
+        .try_for_each(|(_, function)| match function {
+            ScalarFunction::Coalesce(args) => args.iter_mut().try_for_each(|a| visit_arg(a, f)),
+            ScalarFunction::NullIf(a, b) => {
+                visit_arg(a, f)?;
+                visit_arg(b, f)
+            }
+            ScalarFunction::Sort(arg)
+            | ScalarFunction::Reverse(arg)
+            | ScalarFunction::Unique(arg)
+            | ScalarFunction::Flatten(arg)
+            | ScalarFunction::Slice(arg, _, _)
+            | ScalarFunction::Red(arg)
+            | ScalarFunction::Green(arg)
+            | ScalarFunction::Blue(arg)
+            | ScalarFunction::Cast(arg, _)
+            | ScalarFunction::SumArray(arg)
+            | ScalarFunction::MaxArray(arg)
+            | ScalarFunction::MinArray(arg)
+            | ScalarFunction::AvgArray(arg) => visit_arg(arg, f),
+            ScalarFunction::Literal(value) => visit(value, f),
+            ScalarFunction::Index(_, _) => Ok(()),
+        })
+}
+
+fn visit_clause<F>(clause: &mut Clause, f: &mut F) -> Result<(), String>
+where
+    F: FnMut(&mut Types) -> Result<(), String>,
+{
+    match clause {
+        Clause::ContainsKeyValue(_, _, value) | Clause::SimpleComparisonFunction(_, _, value) => {
+            visit(value, f)
+        }
+        Clause::ComplexComparisonFunctions(_, _, values) => {
+            values.iter_mut().try_for_each(|v| visit(v, f))
+        }
+        Clause::Predicate(Function::Arithmetic(expression, _, other)) => {
+            visit_expression(expression, f)?;
+            visit_arg(other, f)
+        }
+        Clause::Or(_, clauses) => clauses.iter_mut().try_for_each(|c| visit_clause(c, f)),
+        Clause::Not(clause) => visit_clause(clause, f),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn bind_where_params() {
+        let params = vec![
+            Types::String("julia, \"maria\" )}".to_string()),
+            Types::Integer(34),
+        ];
+        let wql = Wql::from_str_with_params(
+            "SELECT * FROM my_entity WHERE { ?* my_entity:name $1, (>= ?age $2), }",
+            &params,
+        );
+
+        match wql {
+            Ok(Wql::SelectWhere(_, _, clauses, _)) => {
+                assert_eq!(
+                    clauses[0],
+                    Clause::ContainsKeyValue(
+                        "my_entity".to_string(),
+                        "name".to_string(),
+                        params[0].clone()
+                    )
+                );
+                assert_eq!(
+                    clauses[1],
+                    Clause::SimpleComparisonFunction(
+                        crate::Function::GEq,
+                        "?age".to_string(),
+                        Types::Integer(34)
+                    )
+                );
+            }
+            other => panic!("expected SELECT WHERE, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bind_insert_params() {
+        let wql = Wql::from_str_with_params(
+            "INSERT {a: $1, b: [1, $2,],} INTO my_entity",
+            &[Types::Boolean(true), Types::Nil],
+        )
+        .unwrap();
+
+        match wql {
+            Wql::Insert(_, content, _) => {
+                assert_eq!(content["a"], Types::Boolean(true));
+                assert_eq!(
+                    content["b"],
+                    Types::Vector(vec![Types::Integer(1), Types::Nil])
+                );
+            }
+            other => panic!("expected INSERT, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bind_arithmetic_params() {
+        let wql = Wql::from_str_with_params(
+            "SELECT * FROM my_entity WHERE { ?* my_entity:price ?price, (?price * $1 < $2), }",
+            &[Types::Float(1.2), Types::Integer(100)],
+        );
+
+        match wql {
+            Ok(Wql::SelectWhere(_, _, clauses, _)) => assert_eq!(
+                clauses[1],
+                Clause::Predicate(Function::Arithmetic(
+                    Expression {
+                        left: ScalarArg::Key("?price".to_string()),
+                        operator: crate::Operator::Mul,
+                        right: ScalarArg::Value(Types::Float(1.2)),
+                    },
+                    Box::new(Function::L),
+                    ScalarArg::Value(Types::Integer(100))
+                ))
+            ),
+            other => panic!("expected SELECT WHERE, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn params_count_errors() {
+        assert_eq!(
+            Wql::from_str_with_params(
+                "SELECT * FROM my_entity WHERE { (== ?a $2), }",
+                &[Types::Integer(1)]
+            )
+            .err(),
+            Some(String::from(
+                "Placeholder `$2` has no param, 1 params were given"
+            ))
+        );
+        assert_eq!(
+            Wql::from_str_with_params(
+                "SELECT * FROM my_entity WHERE { (== ?a $1), }",
+                &[Types::Integer(1), Types::Integer(2)]
+            )
+            .err(),
+            Some(String::from(
+                "Param 2 is not used, the statement has no placeholder `$2`"
+            ))
+        );
+        assert_eq!(
+            Wql::from_str("SELECT * FROM my_entity WHERE { (== ?a $1), }").err(),
+            Some(String::from("Placeholder `$1` requires params to be bound"))
+        );
+    }
+
+    #[test]
+    fn overflowing_placeholder_errors() {
+        let error = Some(String::from(
+            "Placeholder `$99999999999999999999999` index is too large",
+        ));
+        assert_eq!(
+            Wql::from_str("INSERT {a: $99999999999999999999999,} INTO my_entity").err(),
+            error
+        );
+        assert_eq!(
+            Wql::from_str_with_params(
+                "INSERT {a: $99999999999999999999999,} INTO my_entity",
+                &[Types::Integer(1)]
+            )
+            .err(),
+            error
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::cmp::Ordering;
+
+/// Decimal number of a `Types::Precise`, parsed from its string without going through
+/// `f64`. `integer` has no leading zeros and `fraction` has no trailing zeros, so zero is
+/// two empty strings and never negative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Decimal {
+    negative: bool,
+    integer: String,
+    fraction: String,
+}
+
+impl Decimal {
+    /// Parses `[+-]digits[.digits]`, like `-12.50`, anything else is `None`.
+    pub(crate) fn parse(value: &str) -> Option<Decimal> {
+        let (negative, unsigned) = match value.as_bytes().first()? {
+            b'-' => (true, &value[1..]),
+            b'+' => (false, &value[1..]),
+            _ => (false, value),
+        };
+        let (integer, fraction) = match unsigned.split_once('.') {
+            Some((integer, fraction)) if !fraction.is_empty() => (integer, fraction),
+            Some(_) => return None,
+            None => (unsigned, ""),
+        };
+        if integer.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+
+        Some(Decimal::new(
+            negative,
+            integer.trim_start_matches('0').to_string(),
+            fraction.trim_end_matches('0').to_string(),
+        ))
+    }
+
+    fn new(negative: bool, integer: String, fraction: String) -> Decimal {
+        let is_zero = integer.is_empty() && fraction.is_empty();
+        Decimal {
+            negative: negative && !is_zero,
+            integer,
+            fraction,
+        }
+    }
+
+    fn cmp_magnitude(&self, other: &Decimal) -> Ordering {
+        self.integer
+            .len()
+            .cmp(&other.integer.len())
+            .then_with(|| self.integer.cmp(&other.integer))
+            .then_with(|| self.fraction.cmp(&other.fraction))
+    }
+
+    /// Digits of `integer` and `fraction` padded to `scale` fraction digits, least
+    /// significant first.
+    fn digits(&self, scale: usize) -> Vec<u8> {
+        let mut digits = self
+            .integer
+            .bytes()
+            .chain(self.fraction.bytes())
+            .map(|d| d - b'0')
+            .collect::<Vec<u8>>();
+        digits.resize(self.integer.len() + scale, 0);
+        digits.reverse();
+        digits
+    }
+
+    fn from_digits(negative: bool, mut digits: Vec<u8>, scale: usize) -> Decimal {
+        digits.reverse();
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        let to_string = |digits: &[u8]| {
+            digits
+                .iter()
+                .map(|d| (d + b'0') as char)
+                .collect::<String>()
+        };
+
+        Decimal::new(
+            negative,
+            to_string(integer).trim_start_matches('0').to_string(),
+            to_string(fraction).trim_end_matches('0').to_string(),
+        )
+    }
+
+    fn add_magnitude(&self, other: &Decimal, negative: bool) -> Decimal {
+        let scale = self.fraction.len().max(other.fraction.len());
+        let (a, b) = (self.digits(scale), other.digits(scale));
+        let mut carry = 0;
+        let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+        for i in 0..a.len().max(b.len()) {
+            let digit = a.get(i).unwrap_or(&0) + b.get(i).unwrap_or(&0) + carry;
+            sum.push(digit % 10);
+            carry = digit / 10;
+        }
+        sum.push(carry);
+        Decimal::from_digits(negative, sum, scale)
+    }
+
+    /// `self - other` for `self` with a larger magnitude than `other`.
+    fn sub_magnitude(&self, other: &Decimal, negative: bool) -> Decimal {
+        let scale = self.fraction.len().max(other.fraction.len());
+        let (a, b) = (self.digits(scale), other.digits(scale));
+        let mut borrow = 0;
+        let mut difference = Vec::with_capacity(a.len());
+        for (i, digit) in a.iter().enumerate() {
+            let subtrahend = b.get(i).unwrap_or(&0) + borrow;
+            if *digit >= subtrahend {
+                difference.push(digit - subtrahend);
+                borrow = 0;
+            } else {
+                difference.push(digit + 10 - subtrahend);
+                borrow = 1;
+            }
+        }
+        Decimal::from_digits(negative, difference, scale)
+    }
+
+    pub(crate) fn add(&self, other: &Decimal) -> Decimal {
+        if self.negative == other.negative {
+            return self.add_magnitude(other, self.negative);
+        }
+        match self.cmp_magnitude(other) {
+            Ordering::Less => other.sub_magnitude(self, other.negative),
+            _ => self.sub_magnitude(other, self.negative),
+        }
+    }
+
+    pub(crate) fn sub(&self, other: &Decimal) -> Decimal {
+        let negated = Decimal::new(
+            !other.negative,
+            other.integer.clone(),
+            other.fraction.clone(),
+        );
+        self.add(&negated)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let integer = if self.integer.is_empty() {
+            "0"
+        } else {
+            &self.integer
+        };
+        if self.negative {
+            write!(f, "-")?;
+        }
+        if self.fraction.is_empty() {
+            write!(f, "{}", integer)
+        } else {
+            write!(f, "{}.{}", integer, self.fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::parse(value).unwrap()
+    }
+
+    #[test]
+    fn parse_decimals() {
+        assert_eq!(decimal("007.50").to_string(), "7.5");
+        assert_eq!(decimal("-0.0").to_string(), "0");
+        assert_eq!(decimal("+12").to_string(), "12");
+        assert!(Decimal::parse("1e10").is_none());
+        assert!(Decimal::parse("NaN").is_none());
+        assert!(Decimal::parse(".5").is_none());
+        assert!(Decimal::parse("5.").is_none());
+        assert!(Decimal::parse("-").is_none());
+    }
+
+    #[test]
+    fn compare_decimals() {
+        assert!(decimal("10") > decimal("9"));
+        assert!(decimal("-10") < decimal("-9"));
+        assert!(decimal("0.5") > decimal("0.05"));
+        assert!(decimal("-0.1") < decimal("0"));
+        assert_eq!(decimal("1.50").cmp(&decimal("1.5")), Ordering::Equal);
+        assert!(
+            decimal("98347883122138743294728345738925783257325789353593473247832493483478935673.2")
+                > decimal(
+                    "98347883122138743294728345738925783257325789353593473247832493483478935673.19"
+                )
+        );
+    }
+
+    #[test]
+    fn add_and_sub_decimals() {
+        assert_eq!(decimal("9.99").add(&decimal("0.01")).to_string(), "10");
+        assert_eq!(decimal("1.5").add(&decimal("-2.25")).to_string(), "-0.75");
+        assert_eq!(decimal("-1.5").add(&decimal("-2.5")).to_string(), "-4");
+        assert_eq!(decimal("10").sub(&decimal("9.5")).to_string(), "0.5");
+        assert_eq!(decimal("0.1").sub(&decimal("0.1")).to_string(), "0");
+        assert_eq!(
+            decimal("99999999999999999999999999999999")
+                .add(&decimal("1"))
+                .to_string(),
+            "100000000000000000000000000000000"
+        );
+    }
+}
@@ -1,9 +1,21 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, str::FromStr};
 
 use uuid::Uuid;
 
-const ALGEBRA: [&str; 6] = ["DEDUP", "GROUP", "ORDER", "OFFSET", "LIMIT", "COUNT"];
+const ALGEBRA: [&str; 10] = [
+    "DEDUP",
+    "GROUP",
+    "ORDER",
+    "OFFSET",
+    "LIMIT",
+    "COUNT",
+    "OMIT",
+    "WITH",
+    "VALID",
+    "RETURNING",
+];
 const OPERATORS: [&str; 10] = [
     "ID", "IDS", "WHERE", "WHEN", "DEDUP", "GROUP", "ORDER", "OFFSET", "LIMIT", "COUNT",
 ];
@@ -34,25 +46,148 @@ pub enum Algebra {
     Limit(usize),
     Offset(usize),
     Count,
+    /// `SELECT COUNT(*)` returns only the number of matching entities, without them.
+    CountOnly,
+    Project(Vec<Projection>),
+    /// `OMIT NULLS` drops keys whose value is `Types::Nil` from the result.
+    OmitNulls,
+    /// `WITH VERSION` adds the version token of the entity registry as key `_version`.
+    WithVersion,
+    /// `RETURNING PROOF` adds the checksum of each entity registry and of the registry it
+    /// points to as keys `_hash` and `_previous_hash`.
+    ReturningProof,
+    /// `VALID AT date` keeps entity maps whose `valid_from`/`valid_to` interval covers the date.
+    ValidAt(DateTime<Utc>),
 }
 
-use crate::where_clause::where_selector;
+/// Alias and scalar function of a projection.
+pub type Projection = (String, ScalarFunction);
+
+/// Scalar functions evaluated for each entity map in `SELECT #{COALESCE(a, b) AS c,}`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ScalarFunction {
+    Coalesce(Vec<ScalarArg>),
+    NullIf(ScalarArg, ScalarArg),
+    /// Constant value added to every entity map, `SELECT *, "v2" AS source`.
+    Literal(Types),
+    /// Element of the `Vector` of a key, `SELECT #{coords[0] AS lat,}`. Out of range
+    /// indexes and values that are not a `Vector` are `Nil`.
+    Index(String, usize),
+    /// `SORT(tags) AS sorted_tags`, the `Vector` sorted by the `Types` order.
+    Sort(ScalarArg),
+    /// `REVERSE(tags) AS reversed_tags`, the `Vector` in reverse order.
+    Reverse(ScalarArg),
+    /// `UNIQUE(tags) AS unique_tags`, the `Vector` keeping only the first occurrence of each
+    /// value.
+    Unique(ScalarArg),
+    /// `FLATTEN(matrix) AS cells`, the `Vector` with the elements of its inner `Vector`s in
+    /// their place, one level deep.
+    Flatten(ScalarArg),
+    /// `SLICE(tags, 0, 3) AS first_tags`, the elements from the first index up to, not
+    /// including, the second. Indexes are clamped to the `Vector`.
+    Slice(ScalarArg, isize, isize),
+    /// `RED(color) AS red`, the red component of a `Color` as an `Integer` from 0 to 255.
+    Red(ScalarArg),
+    /// `GREEN(color) AS green`, the green component of a `Color`.
+    Green(ScalarArg),
+    /// `BLUE(color) AS blue`, the blue component of a `Color`.
+    Blue(ScalarArg),
+    /// `CAST(age AS String) AS age_str`, the value converted to the type named by the
+    /// second field, `Nil` when it can't be converted.
+    Cast(ScalarArg, String),
+    /// `SUM_ARRAY(scores) AS total`, the sum of the `Integer` and `Float` elements of a
+    /// `Vector`, other elements are skipped. `Nil` when there are no numbers.
+    SumArray(ScalarArg),
+    /// `MAX_ARRAY(scores) AS best`, the largest number of a `Vector`.
+    MaxArray(ScalarArg),
+    /// `MIN_ARRAY(scores) AS worst`, the smallest number of a `Vector`.
+    MinArray(ScalarArg),
+    /// `AVG_ARRAY(scores) AS average`, the mean of the numbers of a `Vector` as a `Float`.
+    AvgArray(ScalarArg),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum ScalarArg {
+    Key(String),
+    Value(Types),
+}
+
+use crate::{
+    where_clause::{parse_date, where_selector},
+    Types,
+};
 
 use super::{
-    logic::{read_select_args, read_uuids},
+    logic::{read_all_projections, read_select_args, read_uuids},
     ToSelect, Wql,
 };
 
 pub(crate) fn select_all(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let projections = read_all_projections(chars)?;
     let arg = ToSelect::All;
-    select_body(arg, chars)
+
+    let wql = select_body(arg, chars)?;
+    if projections.is_empty() {
+        Ok(wql)
+    } else {
+        with_projections(wql, projections)
+    }
 }
 
 pub(crate) fn select_args(chars: &mut std::str::Chars) -> Result<Wql, String> {
-    let args: Vec<String> = read_select_args(chars)?;
+    let (args, projections) = read_select_args(chars)?;
     let arg = ToSelect::Keys(args);
 
-    select_body(arg, chars)
+    let wql = select_body(arg, chars)?;
+    if projections.is_empty() {
+        Ok(wql)
+    } else {
+        with_projections(wql, projections)
+    }
+}
+
+/// `SELECT COUNT(*) FROM entity`, optionally with `WHERE {...}` clauses. Always a
+/// `SelectWhere`, without clauses every entity map matches.
+pub(crate) fn select_count(chars: &mut std::str::Chars) -> Result<Wql, String> {
+    let count = chars.take_while(|c| c != &')').collect::<String>();
+    if !count.eq_ignore_ascii_case("OUNT(*") {
+        return Err(String::from("SELECT COUNT must be followed by `(*)`"));
+    }
+
+    match select_body(ToSelect::All, chars)? {
+        Wql::Select(entity, arg, None, mut functions) => {
+            functions.insert("COUNT".to_string(), Algebra::CountOnly);
+            Ok(Wql::SelectWhere(entity, arg, Vec::new(), functions))
+        }
+        Wql::SelectWhere(entity, arg, clauses, mut functions) => {
+            functions.insert("COUNT".to_string(), Algebra::CountOnly);
+            Ok(Wql::SelectWhere(entity, arg, clauses, functions))
+        }
+        _ => Err(String::from(
+            "COUNT(*) is only available for SELECT FROM entity and WHERE clauses",
+        )),
+    }
+}
+
+fn with_projections(wql: Wql, projections: Vec<Projection>) -> Result<Wql, String> {
+    let project = Algebra::Project(projections);
+    match wql {
+        Wql::Select(entity, arg, uuid, mut functions) => {
+            functions.insert("PROJECT".to_string(), project);
+            Ok(Wql::Select(entity, arg, uuid, functions))
+        }
+        Wql::SelectIds(entity, arg, uuids, mut functions) => {
+            functions.insert("PROJECT".to_string(), project);
+            Ok(Wql::SelectIds(entity, arg, uuids, functions))
+        }
+        Wql::SelectWhere(entity, arg, clauses, mut functions) => {
+            functions.insert("PROJECT".to_string(), project);
+            Ok(Wql::SelectWhere(entity, arg, clauses, functions))
+        }
+        _ => Err(String::from(
+            "Scalar functions COALESCE and NULLIF are not available with WHEN",
+        )),
+    }
 }
 
 fn select_body(arg: ToSelect, chars: &mut std::str::Chars) -> Result<Wql, String> {
@@ -99,7 +234,12 @@ fn select_body(arg: ToSelect, chars: &mut std::str::Chars) -> Result<Wql, String
             return when_selector(entity_name, arg, uuid.ok(), chars);
         }
 
-        Ok(Wql::Select(entity_name, arg, uuid.ok(), HashMap::new()))
+        Ok(Wql::Select(
+            entity_name,
+            arg,
+            uuid.ok(),
+            algebra_functions(next_symbol, chars)?,
+        ))
     } else if next_symbol == "IDS" {
         let in_symbol = chars
             .skip_while(|c| c.is_whitespace())
@@ -199,9 +339,32 @@ pub fn algebra_functions(
                     functions.insert("LIMIT".to_string(), Algebra::Limit(value))
                 }
                 "COUNT" => functions.insert("COUNT".to_string(), Algebra::Count),
+                "OMIT" if next_value.eq_ignore_ascii_case("NULLS") => {
+                    functions.insert("OMIT".to_string(), Algebra::OmitNulls)
+                }
+                "OMIT" => return Err(String::from("OMIT must be followed by NULLS")),
+                "WITH" if next_value.eq_ignore_ascii_case("VERSION") => {
+                    functions.insert("VERSION".to_string(), Algebra::WithVersion)
+                }
+                "WITH" => return Err(String::from("WITH must be followed by VERSION")),
+                "VALID" if next_value.eq_ignore_ascii_case("AT") => {
+                    let date = chars
+                        .skip_while(|c| c.is_whitespace())
+                        .take_while(|c| !c.is_whitespace())
+                        .collect::<String>();
+                    let date = parse_date(date.trim_matches('"')).ok_or_else(|| {
+                        String::from("VALID AT requires a date, like 2023-06-01 or 2023-06-01T00:00:00Z")
+                    })?;
+                    functions.insert("VALID".to_string(), Algebra::ValidAt(date))
+                }
+                "VALID" => return Err(String::from("VALID must be followed by AT")),
+                "RETURNING" if next_value.eq_ignore_ascii_case("PROOF") => {
+                    functions.insert("PROOF".to_string(), Algebra::ReturningProof)
+                }
+                "RETURNING" => return Err(String::from("RETURNING must be followed by PROOF")),
                 _ => {
                     return Err(String::from(
-                        "Available functions are DEDUP, GROUP BY, ORDER BY, OFFSET, LIMIT, COUNT",
+                        "Available functions are DEDUP, GROUP BY, ORDER BY, OFFSET, LIMIT, COUNT, OMIT NULLS, WITH VERSION, VALID AT, RETURNING PROOF",
                     ))
                 }
             };
@@ -215,7 +378,7 @@ pub fn algebra_functions(
             break;
         } else {
             return Err(String::from(
-                "Available functions are DEDUP, GROUP BY, ORDER BY, OFFSET, LIMIT, COUNT",
+                "Available functions are DEDUP, GROUP BY, ORDER BY, OFFSET, LIMIT, COUNT, OMIT NULLS, WITH VERSION, VALID AT, RETURNING PROOF",
             ));
         }
     }
@@ -239,6 +402,9 @@ fn when_selector(
     {
         return when_time_range(entity_name, uuid, chars);
     }
+    if next_symbol == "EFFECTIVE" {
+        return when_effective(entity_name, arg, uuid, chars);
+    }
     if next_symbol.to_uppercase() != "AT" {
         return Err(String::from("Keyword AT is required after WHEN"));
     };
@@ -251,6 +417,29 @@ fn when_selector(
     Ok(Wql::SelectWhen(entity_name, arg, uuid, date))
 }
 
+fn when_effective(
+    entity_name: String,
+    arg: ToSelect,
+    uuid: Option<Uuid>,
+    chars: &mut std::str::Chars,
+) -> Result<Wql, String> {
+    let uuid = uuid.ok_or_else(|| String::from("ID is required for WHEN EFFECTIVE"))?;
+    let next_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+    if !next_symbol.eq_ignore_ascii_case("AT") {
+        return Err(String::from("Keyword AT is required after WHEN EFFECTIVE"));
+    };
+
+    let date = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    Ok(Wql::SelectWhenEffective(entity_name, arg, uuid, date))
+}
+
 fn when_time_range(
     entity_name: String,
     uuid: Uuid,
@@ -277,12 +466,6 @@ fn when_time_range(
         .take_while(|c| !c.is_whitespace())
         .collect::<String>();
 
-    if !end_date.starts_with(&start_date[0..10]) {
-        return Err(String::from(
-            "START date and END date should be the same date.",
-        ));
-    }
-
     Ok(Wql::SelectWhenRange(
         entity_name,
         uuid,
@@ -343,6 +526,387 @@ mod test {
         );
     }
 
+    #[test]
+    fn select_coalesce_and_nullif() {
+        let wql = Wql::from_str(
+            "SelEct #{name, COALESCE(nickname, name, \"anon\") AS display, nullif(status, \"unknown\") AS status,} FROM my_entity",
+        );
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                (
+                    "display".to_string(),
+                    crate::ScalarFunction::Coalesce(vec![
+                        crate::ScalarArg::Key("nickname".to_string()),
+                        crate::ScalarArg::Key("name".to_string()),
+                        crate::ScalarArg::Value(crate::Types::String("anon".to_string())),
+                    ]),
+                ),
+                (
+                    "status".to_string(),
+                    crate::ScalarFunction::NullIf(
+                        crate::ScalarArg::Key("status".to_string()),
+                        crate::ScalarArg::Value(crate::Types::String("unknown".to_string())),
+                    ),
+                ),
+            ]),
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select(
+                "my_entity".to_string(),
+                ToSelect::Keys(vec![
+                    "name".to_string(),
+                    "display".to_string(),
+                    "status".to_string()
+                ]),
+                None,
+                hm
+            )
+        );
+    }
+
+    #[test]
+    fn select_literal_projection() {
+        let wql = Wql::from_str("SelEct *, \"ordersv2\" AS source, 2 as version FROM orders");
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                (
+                    "source".to_string(),
+                    crate::ScalarFunction::Literal(crate::Types::String("ordersv2".to_string())),
+                ),
+                (
+                    "version".to_string(),
+                    crate::ScalarFunction::Literal(crate::Types::Integer(2)),
+                ),
+            ]),
+        );
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("orders".to_string(), ToSelect::All, None, hm.clone())
+        );
+
+        let wql = Wql::from_str("SelEct #{id, \"ordersv2\" AS source, 2 AS version,} FROM orders");
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select(
+                "orders".to_string(),
+                ToSelect::Keys(vec![
+                    "id".to_string(),
+                    "source".to_string(),
+                    "version".to_string()
+                ]),
+                None,
+                hm
+            )
+        );
+    }
+
+    #[test]
+    fn select_literal_projection_errors() {
+        let wql = Wql::from_str("SelEct #{id, \"ordersv2\",} FROM orders");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Literal projections require an alias, like `\"v2\" AS source`"
+            ))
+        );
+
+        let wql = Wql::from_str("SelEct *, id FROM orders");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Only literals and scalar functions with an alias can follow `SELECT *`, like `SELECT *, \"v2\" AS source FROM entity`"
+            ))
+        );
+    }
+
+    #[test]
+    fn select_index_projection() {
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                (
+                    "lat".to_string(),
+                    crate::ScalarFunction::Index("coords".to_string(), 0),
+                ),
+                (
+                    "lng".to_string(),
+                    crate::ScalarFunction::Index("coords".to_string(), 1),
+                ),
+            ]),
+        );
+
+        let wql = Wql::from_str("SelEct #{name, coords[0] AS lat, coords[1] as lng,} FROM places");
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select(
+                "places".to_string(),
+                ToSelect::Keys(vec![
+                    "name".to_string(),
+                    "lat".to_string(),
+                    "lng".to_string()
+                ]),
+                None,
+                hm.clone()
+            )
+        );
+
+        let wql = Wql::from_str("SelEct *, coords[0] AS lat, coords[ 1 ] AS lng FROM places");
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("places".to_string(), ToSelect::All, None, hm)
+        );
+    }
+
+    #[test]
+    fn select_vector_functions() {
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                (
+                    "sorted_tags".to_string(),
+                    crate::ScalarFunction::Sort(crate::ScalarArg::Key("tags".to_string())),
+                ),
+                (
+                    "reversed_tags".to_string(),
+                    crate::ScalarFunction::Reverse(crate::ScalarArg::Key("tags".to_string())),
+                ),
+                (
+                    "unique_tags".to_string(),
+                    crate::ScalarFunction::Unique(crate::ScalarArg::Key("tags".to_string())),
+                ),
+            ]),
+        );
+
+        let wql = Wql::from_str(
+            "SELECT *, SORT(tags) AS sorted_tags, reverse(tags) AS reversed_tags, UNIQUE(tags) AS unique_tags FROM t",
+        );
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("t".to_string(), ToSelect::All, None, hm)
+        );
+
+        let wql = Wql::from_str("SELECT #{SORT(tags, names) AS sorted,} FROM t");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("SORT requires exactly one argument"))
+        );
+    }
+
+    #[test]
+    fn select_flatten_and_slice() {
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                (
+                    "cells".to_string(),
+                    crate::ScalarFunction::Flatten(crate::ScalarArg::Key("matrix".to_string())),
+                ),
+                (
+                    "first_tags".to_string(),
+                    crate::ScalarFunction::Slice(crate::ScalarArg::Key("tags".to_string()), 0, 3),
+                ),
+                (
+                    "last_tags".to_string(),
+                    crate::ScalarFunction::Slice(crate::ScalarArg::Key("tags".to_string()), -2, 10),
+                ),
+            ]),
+        );
+
+        let wql = Wql::from_str(
+            "SELECT *, FLATTEN(matrix) AS cells, slice(tags, 0, 3) AS first_tags, SLICE(tags, -2, 10) AS last_tags FROM t",
+        );
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("t".to_string(), ToSelect::All, None, hm)
+        );
+
+        let wql = Wql::from_str("SELECT #{SLICE(tags, 1) AS sliced,} FROM t");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("SLICE requires exactly three arguments"))
+        );
+        let wql = Wql::from_str("SELECT #{SLICE(tags, start, 3) AS sliced,} FROM t");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("SLICE indexes must be integers"))
+        );
+        let wql = Wql::from_str("SELECT #{FLATTEN() AS flat,} FROM t");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("FLATTEN requires exactly one argument"))
+        );
+    }
+
+    #[test]
+    fn select_color_components() {
+        use crate::Types;
+        let color = || crate::ScalarArg::Key("color".to_string());
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                ("r".to_string(), crate::ScalarFunction::Red(color())),
+                ("g".to_string(), crate::ScalarFunction::Green(color())),
+                (
+                    "b".to_string(),
+                    crate::ScalarFunction::Blue(crate::ScalarArg::Value(Types::Color(1, 2, 3))),
+                ),
+            ]),
+        );
+
+        let wql = Wql::from_str(
+            "SELECT #{RED(color) AS r, green(color) AS g, BLUE(#010203) AS b,} FROM t",
+        );
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select(
+                "t".to_string(),
+                ToSelect::Keys(vec!["r".to_string(), "g".to_string(), "b".to_string()]),
+                None,
+                hm
+            )
+        );
+
+        let wql = Wql::from_str("SELECT #{RED(color, 1) AS r,} FROM t");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("RED requires exactly one argument"))
+        );
+    }
+
+    #[test]
+    fn select_cast() {
+        use crate::{ScalarArg, ScalarFunction, Types};
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                (
+                    "age_str".to_string(),
+                    ScalarFunction::Cast(ScalarArg::Key("age".to_string()), "String".to_string()),
+                ),
+                (
+                    "ten".to_string(),
+                    ScalarFunction::Cast(
+                        ScalarArg::Value(Types::String("10".to_string())),
+                        "Precise".to_string(),
+                    ),
+                ),
+            ]),
+        );
+
+        let wql = Wql::from_str(
+            "SELECT *, CAST(age AS String) AS age_str, cast(\"10\" as Precise) AS ten FROM t",
+        );
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("t".to_string(), ToSelect::All, None, hm)
+        );
+
+        let wql = Wql::from_str("SELECT #{CAST(age AS Hash) AS a,} FROM t");
+        assert!(wql
+            .err()
+            .unwrap()
+            .starts_with("CAST type `Hash` must be one of Char, Integer, String"));
+        for query in &[
+            "SELECT #{CAST(age String) AS a,} FROM t",
+            "SELECT #{CAST(age AS Nil) AS a,} FROM t",
+            "SELECT #{CAST(age TO String) AS a,} FROM t",
+        ] {
+            assert_eq!(
+                Wql::from_str(query).err(),
+                Some(String::from(
+                    "CAST requires a key or value and a type, like `CAST(age AS String)`"
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn select_array_aggregates() {
+        use crate::{ScalarArg, ScalarFunction};
+        let scores = || ScalarArg::Key("scores".to_string());
+        let mut hm = HashMap::new();
+        hm.insert(
+            "PROJECT".to_string(),
+            crate::Algebra::Project(vec![
+                ("total".to_string(), ScalarFunction::SumArray(scores())),
+                ("best".to_string(), ScalarFunction::MaxArray(scores())),
+                ("worst".to_string(), ScalarFunction::MinArray(scores())),
+                ("average".to_string(), ScalarFunction::AvgArray(scores())),
+            ]),
+        );
+
+        let wql = Wql::from_str(
+            "SELECT *, SUM_ARRAY(scores) AS total, max_array(scores) AS best, MIN_ARRAY(scores) AS worst, AVG_ARRAY(scores) AS average FROM t",
+        );
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("t".to_string(), ToSelect::All, None, hm)
+        );
+
+        let wql = Wql::from_str("SELECT #{SUM_ARRAY(scores, bonus) AS total,} FROM t");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("SUM_ARRAY requires exactly one argument"))
+        );
+    }
+
+    #[test]
+    fn select_index_projection_errors() {
+        let wql = Wql::from_str("SelEct #{coords[0],} FROM places");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Indexed projections require an alias, like `coords[0] AS value`"
+            ))
+        );
+
+        let wql = Wql::from_str("SelEct #{coords[-1] AS lat,} FROM places");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Index of key `coords` must be a non negative integer, found `-1`"
+            ))
+        );
+    }
+
+    #[test]
+    fn select_scalar_function_errors() {
+        let wql = Wql::from_str("SelEct #{COALESCE(nickname, name),} FROM my_entity");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "COALESCE requires an alias, like `COALESCE(a, b) AS c`"
+            ))
+        );
+
+        let wql = Wql::from_str("SelEct #{NULLIF(status) AS s,} FROM my_entity");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("NULLIF requires exactly two arguments"))
+        );
+
+        let wql = Wql::from_str(
+            "SelEct #{NULLIF(status, 3) AS s,} FROM my_entity WHEN AT 2020-01-01T00:00:00Z",
+        );
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Scalar functions COALESCE and NULLIF are not available with WHEN"
+            ))
+        );
+    }
+
     #[test]
     fn select_args() {
         let wql = Wql::from_str("SelEct #{hello,world, by_me,} FROM my_entity");
@@ -495,6 +1059,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn when_effective_at() {
+        let wql = Wql::from_str("SelEct #{a,b,} FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 WHEN effective AT 2020-01-01T00:00:00Z");
+        let uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").unwrap();
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhenEffective(
+                "my_entity".to_string(),
+                ToSelect::Keys(vec!["a".to_string(), "b".to_string()]),
+                uuid,
+                "2020-01-01T00:00:00Z".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn when_effective_errors() {
+        let wql = Wql::from_str("SelEct * FROM my_entity WHEN EFFECTIVE AT 2020-01-01T00:00:00Z");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("ID is required for WHEN EFFECTIVE"))
+        );
+
+        let wql = Wql::from_str("SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 WHEN EFFECTIVE 2020-01-01T00:00:00Z");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Keyword AT is required after WHEN EFFECTIVE"))
+        );
+    }
+
     #[test]
     fn when_range_all() {
         let wql = Wql::from_str("SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 WHEN START 2020-01-01T00:00:00Z END 2020-01-01T03:00:00Z");
@@ -510,6 +1104,21 @@ mod test {
         );
     }
 
+    #[test]
+    fn when_range_across_days() {
+        let wql = Wql::from_str("SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 WHEN START 2020-01-01T00:00:00Z END 2020-12-31T03:00:00Z");
+        let uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").unwrap();
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhenRange(
+                "my_entity".to_string(),
+                uuid,
+                "2020-01-01T00:00:00Z".to_string(),
+                "2020-12-31T03:00:00Z".to_string()
+            )
+        );
+    }
+
     #[test]
     fn when_range_args_err() {
         let wql = Wql::from_str("SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 WHEN START 2020-01-01T00:00:00Z 2020-01-01T03:00:00Z");
@@ -526,7 +1135,7 @@ mod test {
 #[cfg(test)]
 mod functions_test {
     use super::*;
-    use crate::{ToSelect, Wql};
+    use crate::{Clause, ToSelect, Value, Wql};
 
     use std::str::FromStr;
 
@@ -547,6 +1156,23 @@ mod functions_test {
         );
     }
 
+    #[test]
+    fn select_all_offset_limit_any_order() {
+        let wql = Wql::from_str("SelEct * FROM my_entity offset 5 limit 3");
+        let hm: HashMap<String, Algebra> = vec![
+            ("LIMIT".to_string(), Algebra::Limit(3)),
+            ("OFFSET".to_string(), Algebra::Offset(5)),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("my_entity".to_string(), ToSelect::All, None, hm)
+        );
+    }
+
     #[test]
     fn select_all_order_by() {
         let wql = Wql::from_str("SelEct * FROM my_entity ORDER BY key_1 :asc");
@@ -596,6 +1222,188 @@ mod functions_test {
         );
     }
 
+    #[test]
+    fn select_count_only() {
+        let mut hm = HashMap::new();
+        hm.insert("COUNT".to_string(), Algebra::CountOnly);
+
+        assert_eq!(
+            Wql::from_str("SELECT count(*) FROM my_entity").unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                Vec::new(),
+                hm.clone()
+            )
+        );
+        assert_eq!(
+            Wql::from_str("SELECT COUNT(*) FROM my_entity WHERE { ?* my_entity:a ?a, }").unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![Clause::ValueAttribution(
+                    "my_entity".to_string(),
+                    "a".to_string(),
+                    Value("?a".to_string())
+                )],
+                hm
+            )
+        );
+        assert_eq!(
+            Wql::from_str("SELECT COUNT FROM my_entity").err(),
+            Some(String::from("SELECT COUNT must be followed by `(*)`"))
+        );
+        assert_eq!(
+            Wql::from_str("SELECT COUNT(*) FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1")
+                .err(),
+            Some(String::from(
+                "COUNT(*) is only available for SELECT FROM entity and WHERE clauses"
+            ))
+        );
+    }
+
+    #[test]
+    fn select_all_omit_nulls() {
+        let wql = Wql::from_str("SelEct * FROM my_entity omit nulls LIMIT 3");
+        let hm: HashMap<String, Algebra> = vec![
+            ("OMIT".to_string(), Algebra::OmitNulls),
+            ("LIMIT".to_string(), Algebra::Limit(3)),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("my_entity".to_string(), ToSelect::All, None, hm)
+        );
+    }
+
+    #[test]
+    fn select_id_omit_nulls() {
+        let wql = Wql::from_str(
+            "SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 OMIT NULLS",
+        );
+        let uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").unwrap();
+        let hm: HashMap<String, Algebra> = vec![("OMIT".to_string(), Algebra::OmitNulls)]
+            .iter()
+            .cloned()
+            .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("my_entity".to_string(), ToSelect::All, Some(uuid), hm)
+        );
+    }
+
+    #[test]
+    fn select_id_with_version() {
+        let wql = Wql::from_str(
+            "SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 with version",
+        );
+        let uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").unwrap();
+        let hm: HashMap<String, Algebra> = vec![("VERSION".to_string(), Algebra::WithVersion)]
+            .iter()
+            .cloned()
+            .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("my_entity".to_string(), ToSelect::All, Some(uuid), hm)
+        );
+    }
+
+    #[test]
+    fn select_with_without_version() {
+        let wql = Wql::from_str("SelEct * FROM my_entity WITH keys");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("WITH must be followed by VERSION"))
+        );
+    }
+
+    #[test]
+    fn select_returning_proof() {
+        let wql = Wql::from_str("SelEct * FROM my_entity LIMIT 2 returning proof");
+        let hm: HashMap<String, Algebra> = [
+            ("LIMIT".to_string(), Algebra::Limit(2)),
+            ("PROOF".to_string(), Algebra::ReturningProof),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("my_entity".to_string(), ToSelect::All, None, hm)
+        );
+        assert_eq!(
+            Wql::from_str("SelEct * FROM my_entity RETURNING keys").err(),
+            Some(String::from("RETURNING must be followed by PROOF"))
+        );
+    }
+
+    #[test]
+    fn select_omit_without_nulls() {
+        let wql = Wql::from_str("SelEct * FROM my_entity OMIT keys");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("OMIT must be followed by NULLS"))
+        );
+    }
+
+    #[test]
+    fn select_all_valid_at() {
+        let wql = Wql::from_str("SelEct * FROM my_entity VALID AT \"2023-06-01\" LIMIT 3");
+        let date = "2023-06-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let hm: HashMap<String, Algebra> = vec![
+            ("VALID".to_string(), Algebra::ValidAt(date)),
+            ("LIMIT".to_string(), Algebra::Limit(3)),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Select("my_entity".to_string(), ToSelect::All, None, hm)
+        );
+    }
+
+    #[test]
+    fn select_where_valid_at_date_time() {
+        let wql = Wql::from_str(
+            "SelEct * FROM my_entity WHERE { ?* my_entity:a 1, } valid at 2023-06-01T12:30:00+02:00",
+        );
+        let date = "2023-06-01T10:30:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        match wql.unwrap() {
+            Wql::SelectWhere(_, _, _, functions) => {
+                assert_eq!(functions.get("VALID"), Some(&Algebra::ValidAt(date)))
+            }
+            _ => panic!("expected SelectWhere"),
+        }
+    }
+
+    #[test]
+    fn select_valid_at_errors() {
+        let wql = Wql::from_str("SelEct * FROM my_entity VALID 2023-06-01");
+        assert_eq!(
+            wql.err(),
+            Some(String::from("VALID must be followed by AT"))
+        );
+
+        let wql = Wql::from_str("SelEct * FROM my_entity VALID AT yesterday");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "VALID AT requires a date, like 2023-06-01 or 2023-06-01T00:00:00Z"
+            ))
+        );
+    }
+
     #[test]
     fn select_all_ids_order() {
         let wql = Wql::from_str("SelEct * FROM my_entity IDS IN #{2df2b8cf-49da-474d-8a00-c596c0bb6fd1, 53315090-e14d-4738-a4d2-f1ec2a93664c,} ORDER BY my_key :desc DEDUP ley");
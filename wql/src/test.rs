@@ -41,7 +41,7 @@ mod test_create {
 
         assert_eq!(
             wql.unwrap(),
-            Wql::CreateEntity(String::from("entity"), Vec::new(), Vec::new())
+            Wql::CreateEntity(String::from("entity"), Vec::new(), Vec::new(), None)
         );
     }
 
@@ -58,7 +58,8 @@ mod test_create {
                     "ssn".to_string(),
                     "something".to_string()
                 ],
-                Vec::new()
+                Vec::new(),
+                None
             )
         );
     }
@@ -77,6 +78,7 @@ mod test_create {
                     "ssn".to_string(),
                     "something".to_string()
                 ],
+                None
             )
         );
     }
@@ -93,6 +95,7 @@ mod test_create {
                 String::from("entity"),
                 vec!["name".to_string(), "ssn".to_string(),],
                 vec!["password".to_string(), "something".to_string()],
+                None
             )
         );
     }
@@ -133,8 +136,264 @@ mod test_create {
                 String::from("entity"),
                 vec!["name".to_string(), "ssn".to_string(),],
                 vec!["password".to_string(), "something".to_string()],
+                None
+            )
+        );
+    }
+    #[test]
+    fn create_entity_with_schema() {
+        let wql = Wql::from_str(
+            "CREATE ENTITY entity {name: String, age: Integer,} EXHAUSTIVE UNIQUES #{name,}",
+        );
+        let mut types = std::collections::BTreeMap::new();
+        types.insert("name".to_string(), "String".to_string());
+        types.insert("age".to_string(), "Integer".to_string());
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::CreateEntity(
+                String::from("entity"),
+                vec!["name".to_string()],
+                Vec::new(),
+                Some(EntitySchema {
+                    types,
+                    exhaustive: true,
+                    enums: std::collections::BTreeMap::new(),
+                    vectors: std::collections::BTreeMap::new(),
+                    defaults: std::collections::BTreeMap::new(),
+                    append_only: false,
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn create_entity_with_non_exhaustive_schema() {
+        let wql = Wql::from_str("CREATE ENTITY entity {age: Integer,}");
+        let mut types = std::collections::BTreeMap::new();
+        types.insert("age".to_string(), "Integer".to_string());
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::CreateEntity(
+                String::from("entity"),
+                Vec::new(),
+                Vec::new(),
+                Some(EntitySchema {
+                    types,
+                    exhaustive: false,
+                    enums: std::collections::BTreeMap::new(),
+                    vectors: std::collections::BTreeMap::new(),
+                    defaults: std::collections::BTreeMap::new(),
+                    append_only: false,
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn create_entity_with_enum_schema() {
+        let wql = Wql::from_str("CREATE ENTITY entity {status: Enum(Open | Closed),}");
+        let mut types = std::collections::BTreeMap::new();
+        types.insert("status".to_string(), "String".to_string());
+        let mut enums = std::collections::BTreeMap::new();
+        enums.insert(
+            "status".to_string(),
+            vec!["Open".to_string(), "Closed".to_string()],
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::CreateEntity(
+                String::from("entity"),
+                Vec::new(),
+                Vec::new(),
+                Some(EntitySchema {
+                    types,
+                    exhaustive: false,
+                    enums,
+                    vectors: std::collections::BTreeMap::new(),
+                    defaults: std::collections::BTreeMap::new(),
+                    append_only: false,
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn create_entity_with_vector_schema() {
+        let wql = Wql::from_str("CREATE ENTITY entity {tags: Vector<String>,}");
+        let mut types = std::collections::BTreeMap::new();
+        types.insert("tags".to_string(), "Vector".to_string());
+        let mut vectors = std::collections::BTreeMap::new();
+        vectors.insert("tags".to_string(), "String".to_string());
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::CreateEntity(
+                String::from("entity"),
+                Vec::new(),
+                Vec::new(),
+                Some(EntitySchema {
+                    types,
+                    exhaustive: false,
+                    enums: std::collections::BTreeMap::new(),
+                    vectors,
+                    defaults: std::collections::BTreeMap::new(),
+                    append_only: false,
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn create_entity_with_defaults_schema() {
+        let wql = Wql::from_str(
+            "CREATE ENTITY entity {age: Integer DEFAULT 18, status: Enum(Open | Closed) default \"Open\", id: Uuid DEFAULT, name: String,}",
+        );
+        let mut defaults = std::collections::BTreeMap::new();
+        defaults.insert("age".to_string(), Some(Types::Integer(18)));
+        defaults.insert(
+            "status".to_string(),
+            Some(Types::String("Open".to_string())),
+        );
+        defaults.insert("id".to_string(), None);
+
+        match wql.unwrap() {
+            Wql::CreateEntity(_, _, _, Some(schema)) => {
+                assert_eq!(schema.defaults, defaults);
+                assert_eq!(schema.types["age"], "Integer");
+                assert_eq!(schema.types["status"], "String");
+                assert_eq!(schema.types["id"], "Uuid");
+                assert_eq!(schema.types["name"], "String");
+            }
+            _ => panic!("expected CREATE ENTITY with schema"),
+        }
+    }
+
+    #[test]
+    fn create_entity_append_only() {
+        let wql = Wql::from_str("CREATE ENTITY events APPEND ONLY");
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::CreateEntity(
+                String::from("events"),
+                Vec::new(),
+                Vec::new(),
+                Some(EntitySchema {
+                    append_only: true,
+                    ..EntitySchema::default()
+                })
             )
         );
+
+        match Wql::from_str(
+            "CREATE ENTITY events {at: DateTime,} EXHAUSTIVE append only UNIQUES #{id,}",
+        )
+        .unwrap()
+        {
+            Wql::CreateEntity(_, uniques, _, Some(schema)) => {
+                assert_eq!(uniques, vec!["id".to_string()]);
+                assert_eq!(schema.types["at"], "DateTime");
+                assert!(schema.exhaustive);
+                assert!(schema.append_only);
+            }
+            _ => panic!("expected CREATE ENTITY with schema"),
+        }
+        assert_eq!(
+            Wql::from_str("CREATE ENTITY events APPEND").err(),
+            Some(String::from("Keyword ONLY is required after APPEND"))
+        );
+    }
+
+    #[test]
+    fn create_entity_with_invalid_defaults() {
+        assert_eq!(
+            Wql::from_str("CREATE ENTITY entity {age: Integer DEFAULT 1.5,}").err(),
+            Some(String::from(
+                "Default of schema key `age` must be of type Integer, found Float"
+            ))
+        );
+        assert_eq!(
+            Wql::from_str("CREATE ENTITY entity {age: Integer DEFAULT 1 2,}").err(),
+            Some(String::from(
+                "Default of schema key `age` should be a single value, found `1 2`"
+            ))
+        );
+        assert_eq!(
+            Wql::from_str("CREATE ENTITY entity {status: Enum(Open | Closed) DEFAULT \"Done\",}")
+                .err(),
+            Some(String::from(
+                "Default of schema key `status` must be one of [\"Open\", \"Closed\"], found `Done`"
+            ))
+        );
+        assert!(
+            Wql::from_str("CREATE ENTITY entity {status: Enum(Open | Closed) DEFAULT,}")
+                .err()
+                .unwrap()
+                .starts_with("Enum schema key `status` requires a DEFAULT variant")
+        );
+    }
+
+    #[test]
+    fn create_entity_with_invalid_vector_element() {
+        let wql = Wql::from_str("CREATE ENTITY entity {tags: Vector<Text>,}");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Type `Text` is not valid for the elements of schema key `tags`. Valid types are [\"Char\", \"Integer\", \"String\", \"Uuid\", \"Float\", \"Boolean\", \"Vector\", \"Map\", \"Hash\", \"Precise\", \"DateTime\", \"Nil\", \"Point\", \"IpAddr\", \"Percentage\", \"Ratio\", \"Email\", \"Color\", \"Phone\"]"
+            ))
+        );
+    }
+
+    #[test]
+    fn create_entity_with_empty_enum_variant() {
+        let wql = Wql::from_str("CREATE ENTITY entity {status: Enum(Open | ),}");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Enum schema key `status` requires non empty variants, like `status: Enum(Open | Closed)`"
+            ))
+        );
+    }
+
+    #[test]
+    fn create_entity_with_schema_unknown_type() {
+        let wql = Wql::from_str("CREATE ENTITY entity {age: Number,}");
+
+        assert!(wql
+            .err()
+            .unwrap()
+            .starts_with("Type `Number` is not valid for schema key `age`"));
+    }
+
+    #[test]
+    fn create_entity_with_valid_time_schema() {
+        let wql = Wql::from_str("CREATE ENTITY entity {valid_from: DateTime, valid_to: DateTime,}");
+        assert!(wql.is_ok());
+
+        let wql = Wql::from_str("CREATE ENTITY entity {valid_from: DateTime, valid_to: String,}");
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Valid time key `valid_to` must be of type DateTime, found `String`"
+            ))
+        );
+    }
+
+    #[test]
+    fn create_entity_with_schema_missing_type() {
+        let wql = Wql::from_str("CREATE ENTITY entity {age,}");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Schema key `age` requires a type, like `age: Integer`"
+            ))
+        );
     }
 }
 
@@ -174,6 +433,50 @@ mod test_delete {
         );
     }
 
+    #[test]
+    fn delete_where() {
+        let wql = Wql::from_str(
+            "DELETE FROM my_entity WHERE { ?* my_entity:status \"expired\", (> ?age 30), }",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::DeleteWhere(
+                "my_entity".to_string(),
+                vec![
+                    Clause::ContainsKeyValue(
+                        "my_entity".to_string(),
+                        "status".to_string(),
+                        Types::String("expired".to_string())
+                    ),
+                    Clause::SimpleComparisonFunction(
+                        Function::G,
+                        "?age".to_string(),
+                        Types::Integer(30)
+                    ),
+                ]
+            )
+        )
+    }
+
+    #[test]
+    fn delete_where_errors() {
+        assert_eq!(
+            Wql::from_str("DELETE FROM my_entity LIMIT 3").err(),
+            Some(String::from(
+                "Keyword WHERE is required for DELETE FROM entity"
+            ))
+        );
+        assert_eq!(
+            Wql::from_str("DELETE FROM my_entity WHERE { ?* my_entity:a ?a, wrong, }").err(),
+            Some(String::from("DELETE WHERE clauses must be valid"))
+        );
+        assert_eq!(
+            Wql::from_str("DELETE FROM my_entity WHERE { ?* my_entity:a 1, } LIMIT 3").err(),
+            Some(String::from("DELETE WHERE only takes WHERE clauses"))
+        );
+    }
+
     #[test]
     fn delete_missing_entity() {
         let wql = Wql::from_str("DELETE this-is-an-uuid FROM");
@@ -226,197 +529,350 @@ mod test_insert {
     }
 
     #[test]
-    fn insert_missing_into() {
-        let wql = Wql::from_str(
-            "INSERT {
-            a: 123,
-        } INTRO my_entity",
-        );
+    fn insert_non_decimal_precise() {
+        let wql = Wql::from_str("INSERT {a: 1e10P,} INTO my_entity");
 
         assert_eq!(
             wql.err(),
-            Some(String::from("Keyword INTO is required for INSERT"))
+            Some(String::from(
+                "Precise value `1e10P` must be a decimal number, like `-12.34P`"
+            ))
         );
     }
 
     #[test]
-    fn insert_missing_entity_name() {
+    fn insert_point() {
         let wql = Wql::from_str(
             "INSERT {
-            a: 123,
-        } INTO ",
+            a: point(12.3, -45.6),
+            b: [Point(1 2),],
+        } INTO my_entity",
         );
 
-        assert_eq!(
-            wql.err(),
-            Some(String::from("Entity name is required after INTO"))
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Point { x: 12.3, y: -45.6 });
+        hm.insert(
+            "b".to_string(),
+            Types::Vector(vec![Types::Point { x: 1.0, y: 2.0 }]),
         );
-    }
 
-    fn hashmap() -> Entity {
-        let mut hm = HashMap::new();
-        hm.insert("a".to_string(), Types::Integer(123));
-        hm.insert("b".to_string(), Types::Float(12.3));
-        hm.insert("c".to_string(), Types::Char('d'));
-        hm.insert("d".to_string(), Types::Boolean(true));
-        hm.insert("e".to_string(), Types::Boolean(false));
-        hm.insert("f".to_string(), Types::String("hello".to_string()));
-        hm.insert("g".to_string(), Types::Nil);
-        hm
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
     }
-}
-
-#[cfg(test)]
-mod test_update {
-    use std::str::FromStr;
-
-    use super::*;
 
     #[test]
-    fn update_set_entity() {
-        let wql = Wql::from_str(
-            "UPDATE this_entity 
-        SET {
-            a: 123,
-            g: NiL
-        } 
-        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
-        );
+    fn insert_point_err() {
+        let wql = Wql::from_str("INSERT {a: point(12.3),} INTO my_entity");
 
         assert_eq!(
-            wql.unwrap(),
-            Wql::UpdateSet(
-                "this_entity".to_string(),
-                hashmap(),
-                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap()
-            )
+            wql.err(),
+            Some(String::from(
+                "Point should be written as `point(x, y)`, like `point(12.3, 45.6)`. Found point(12.3)"
+            ))
         );
     }
 
     #[test]
-    fn update_content_entity() {
+    fn insert_ip_addr() {
         let wql = Wql::from_str(
-            "UPDATE this_entity 
-        Content {
-            a: 123,
-            g: NiL
-        } 
-        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+            "INSERT {
+            a: 10.0.0.1,
+            b: 2001:db8::1,
+        } INTO my_entity",
         );
 
-        assert_eq!(
-            wql.unwrap(),
-            Wql::UpdateContent(
-                "this_entity".to_string(),
-                hashmap(),
-                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap()
-            )
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::IpAddr("10.0.0.1".parse().unwrap()));
+        hm.insert(
+            "b".to_string(),
+            Types::IpAddr("2001:db8::1".parse().unwrap()),
         );
+
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
     }
 
     #[test]
-    fn update_set_missing_entity() {
+    fn insert_percentage() {
         let wql = Wql::from_str(
-            "UPDATE 
-        SET {
-            a: 123,
-            g: NiL
-        } 
-        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+            "INSERT {
+            a: 80%,
+            b: 0.5%,
+        } INTO my_entity",
         );
 
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Percentage(80.0));
+        hm.insert("b".to_string(), Types::Percentage(0.5));
+
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
+    }
+
+    #[test]
+    fn insert_percentage_out_of_range() {
+        let wql = Wql::from_str("INSERT {a: 120%,} INTO my_entity");
+
         assert_eq!(
             wql.err(),
-            Some(String::from("Entity name is required for UPDATE"))
+            Some(String::from(
+                "Percentage value `120%` must be from 0% to 100%"
+            ))
         );
+        assert!(Wql::from_str("INSERT {a: -1%,} INTO my_entity").is_err());
     }
 
-    fn hashmap() -> Entity {
-        let mut hm = HashMap::new();
-        hm.insert("a".to_string(), Types::Integer(123));
-        hm.insert("g".to_string(), Types::Nil);
-        hm
+    #[test]
+    fn insert_ratio() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 1/3,
+            b: 4/-6,
+        } INTO my_entity",
+        );
+
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Ratio { num: 1, den: 3 });
+        hm.insert("b".to_string(), Types::Ratio { num: -2, den: 3 });
+
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
     }
 
     #[test]
-    fn update_entity_misspelled_action() {
+    fn insert_ratio_zero_denominator() {
+        let wql = Wql::from_str("INSERT {a: 1/0,} INTO my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Ratio `1/0` must have a non zero denominator"))
+        );
+    }
+
+    #[test]
+    fn insert_email() {
+        let wql = Wql::from_str("INSERT {a: Julia.Naomi+db@Example.COM,} INTO my_entity");
+
+        let mut hm = HashMap::new();
+        hm.insert(
+            "a".to_string(),
+            Types::Email("julia.naomi+db@example.com".to_string()),
+        );
+
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
+    }
+
+    #[test]
+    fn insert_invalid_email() {
+        let wql = Wql::from_str("INSERT {a: julia@example,} INTO my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Email value `julia@example` is not a valid email, like `name@example.com`"
+            ))
+        );
+        for email in &["@example.com", "a@b@example.com", "a@-b.com", "a b@c.com"] {
+            assert!(Types::email(email).is_err(), "{}", email);
+        }
+    }
+
+    #[test]
+    fn insert_phone() {
         let wql = Wql::from_str(
-            "UPDATE this_entity 
-        TO {
-            a: 123,
-            g: NiL
-        } 
-        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+            "INSERT {a: phone(+1 555-123.4567), b: PHONE(0044 20 7946 0958),} INTO my_entity",
         );
 
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Phone("+15551234567".to_string()));
+        hm.insert("b".to_string(), Types::Phone("+442079460958".to_string()));
+
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
+    }
+
+    #[test]
+    fn insert_invalid_phone() {
+        let wql = Wql::from_str("INSERT {a: phone(555 123 4567),} INTO my_entity");
+
         assert_eq!(
             wql.err(),
             Some(String::from(
-                "UPDATE type is required after entity. Keywords are SET or CONTENT"
+                "Phone value `555 123 4567` must be an international number, like `+1 555 123 4567`"
             ))
         );
+        for phone in &[
+            "+",
+            "+123456",
+            "+1234567890123456",
+            "+0 555 123 4567",
+            "+1 555 CALL",
+        ] {
+            assert!(Types::phone(phone).is_err(), "{}", phone);
+        }
     }
 
     #[test]
-    fn update_entity_missing_into() {
+    fn insert_color() {
+        let wql = Wql::from_str("INSERT {a: #FF8800, b: #0a0b0c,} INTO my_entity");
+
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Color(255, 136, 0));
+        hm.insert("b".to_string(), Types::Color(10, 11, 12));
+
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
+    }
+
+    #[test]
+    fn insert_invalid_color() {
+        let wql = Wql::from_str("INSERT {a: #ff880,} INTO my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Color value `#ff880` must be a hex RGB color, like `#ff8800`"
+            ))
+        );
+        for color in &["#", "#gg8800", "#ff88000", "ff8800", "#ff 880"] {
+            assert!(Types::color(color).is_err(), "{}", color);
+        }
+    }
+
+    #[test]
+    fn insert_deeply_nested_values() {
+        let depth = DEFAULT_MAX_NESTING_DEPTH;
+        let vector = format!("{}1 {}", "[".repeat(depth), "]".repeat(depth));
+        let wql = Wql::from_str(&format!("INSERT {{a: {},}} INTO my_entity", vector));
+        assert_eq!(wql.err(), None);
+
+        let error = Some(format!(
+            "Vectors and maps cannot be nested deeper than {} levels",
+            depth
+        ));
+        let vector = format!("{}1 {}", "[".repeat(100_000), "]".repeat(100_000));
+        let wql = Wql::from_str(&format!("INSERT {{a: {},}} INTO my_entity", vector));
+        assert_eq!(wql.err(), error);
+
+        let map = format!("{}1{}", "{a: ".repeat(100_000), ",}".repeat(100_000));
+        let wql = Wql::from_str(&format!("INSERT {{a: {},}} INTO my_entity", map));
+        assert_eq!(wql.err(), error);
+
+        let mixed = format!("{}1{}", "[{a: ".repeat(50_000), ",}]".repeat(50_000));
+        let wql = Wql::from_str(&format!(
+            "MATCH ALL(a > 0) UPDATE my_entity SET {{a: {},}} INTO 2df2b8cf-49da-474d-8a00-c596c0bb6fd1",
+            mixed
+        ));
+        assert_eq!(wql.err(), error);
+    }
+
+    #[test]
+    fn insert_missing_into() {
         let wql = Wql::from_str(
-            "UPDATE this_entity 
-        SET {
+            "INSERT {
             a: 123,
-            g: NiL
-        } 
-        to d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        } INTRO my_entity",
         );
 
         assert_eq!(
             wql.err(),
-            Some(String::from("Keyword INTO is required for UPDATE"))
+            Some(String::from("Keyword INTO is required for INSERT"))
         );
     }
 
     #[test]
-    fn update_entity_missing_uuid() {
+    fn insert_missing_entity_name() {
         let wql = Wql::from_str(
-            "UPDATE this_entity 
-        SET {
+            "INSERT {
             a: 123,
-            g: NiL
-        } 
-        into Some-crazy-id",
+        } INTO ",
         );
 
-        assert!(wql
-            .err()
-            .unwrap()
-            .starts_with("Couldn\'t create uuid from Some-crazy-id"));
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Entity name is required after INTO"))
+        );
+    }
+
+    #[test]
+    fn insert_unterminated_literals() {
+        let cases = [
+            (
+                "INSERT {a: \"unterminated INTO foo",
+                "Unterminated string starting at char 12",
+            ),
+            (
+                "INSERT {a: 1, b: [1, 2 INTO foo",
+                "Unterminated vector starting at char 18",
+            ),
+            (
+                "INSERT {a: 1, b: {c: 2, INTO foo",
+                "Unterminated map starting at char 18",
+            ),
+            ("INSERT {a: 1 INTO foo", "Unterminated map starting at char 8"),
+            (
+                "  UPDATE foo SET {a: \"x\\\"} INTO 2df2b8cf-49da-474d-8a00-c596c0bb6fd1",
+                "Unterminated string starting at char 22",
+            ),
+        ];
+        for (wql, error) in cases.iter() {
+            assert_eq!(Wql::from_str(wql).err(), Some(error.to_string()), "{}", wql);
+        }
+
+        let wql = Wql::from_str("INSERT {a: \"{[\", b: ']', c: [\"]\",],} INTO foo").unwrap();
+        match wql {
+            Wql::Insert(_, content, _) => {
+                assert_eq!(content["a"], Types::String("{[".to_string()));
+                assert_eq!(content["b"], Types::Char(']'));
+            }
+            other => panic!("expected INSERT, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_statements_error() {
+        let insert = "INSERT {a: \"hello\", b: [1, 2, {c: 'd',},], e: {f: [3,],},} INTO my_entity";
+        let map_end = insert.find(" INTO").unwrap() - 1;
+        for (end, _) in insert.char_indices().filter(|(i, _)| *i > 7 && *i < map_end) {
+            let error = Wql::from_str(&insert[..end]).err().unwrap();
+            assert!(error.starts_with("Unterminated "), "{}", error);
+        }
+
+        for wql in &[
+            insert,
+            "UPDATE my_entity CONTENT {a: 1, b: [1,],} INTO 2df2b8cf-49da-474d-8a00-c596c0bb6fd1",
+            "MATCH ALL(a > 100, b <= 20.0) UPDATE my_entity SET {a: a - 1, b: \"x\",} INTO 2df2b8cf-49da-474d-8a00-c596c0bb6fd1",
+            "CHECK {pswd: \"my-password\", ssn: 3948453,} FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1",
+            "SELECT #{a, COALESCE(b, \"x\") AS c, tags[0] AS t,} FROM my_entity WHERE { ?* my_entity:name \"julia\", (>= ?age 3), (or (== ?a 1) (in ?b [1, 2,])),}",
+        ] {
+            for (end, _) in wql.char_indices() {
+                let truncated = &wql[..end];
+                assert!(
+                    std::panic::catch_unwind(|| Wql::from_str(truncated)).is_ok(),
+                    "{}",
+                    truncated
+                );
+            }
+        }
+    }
+
+    fn hashmap() -> Entity {
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Integer(123));
+        hm.insert("b".to_string(), Types::Float(12.3));
+        hm.insert("c".to_string(), Types::Char('d'));
+        hm.insert("d".to_string(), Types::Boolean(true));
+        hm.insert("e".to_string(), Types::Boolean(false));
+        hm.insert("f".to_string(), Types::String("hello".to_string()));
+        hm.insert("g".to_string(), Types::Nil);
+        hm
     }
 }
 
 #[cfg(test)]
-mod test_match {
-    use super::*;
+mod test_update {
     use std::str::FromStr;
 
-    #[test]
-    fn test_read_match_args() {
-        let mut args = "(a == 1, b != 2, c > 3, d >= 4, e < 5, f <= 6)".chars();
-        let actual = read_match_args(&mut args).unwrap();
-        let expected = vec![
-            MatchCondition::Eq("a".to_string(), Types::Integer(1)),
-            MatchCondition::NotEq("b".to_string(), Types::Integer(2)),
-            MatchCondition::G("c".to_string(), Types::Integer(3)),
-            MatchCondition::GEq("d".to_string(), Types::Integer(4)),
-            MatchCondition::L("e".to_string(), Types::Integer(5)),
-            MatchCondition::LEq("f".to_string(), Types::Integer(6)),
-        ];
-        assert_eq!(actual, expected);
-    }
+    use super::*;
 
     #[test]
-    fn match_update_set_entity() {
+    fn update_set_entity() {
         let wql = Wql::from_str(
-            " MATCH ALL(a == 1, b >= 3, c != \"hello\", d < 7,)
-        UPDATE this_entity 
+            "UPDATE this_entity 
         SET {
             a: 123,
             g: NiL
@@ -426,62 +882,61 @@ mod test_match {
 
         assert_eq!(
             wql.unwrap(),
-            Wql::MatchUpdate(
+            Wql::UpdateSet(
                 "this_entity".to_string(),
                 hashmap(),
                 Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap(),
-                MatchCondition::All(vec![
-                    MatchCondition::Eq("a".to_string(), Types::Integer(1)),
-                    MatchCondition::GEq("b".to_string(), Types::Integer(3)),
-                    MatchCondition::NotEq("c".to_string(), Types::String("hello".to_string())),
-                    MatchCondition::L("d".to_string(), Types::Integer(7)),
-                ])
+                None
             )
         );
     }
 
     #[test]
-    fn match_update_missing_logical_arg() {
+    fn update_set_entity_if_version() {
         let wql = Wql::from_str(
-            " MATCH (a == 1, b >= 3, c != \"hello\", d < 7)
-        UPDATE this_entity 
-        SET {
-            a: 123,
-            g: NiL
-        } 
-        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+            "UPDATE this_entity SET {a: 123, g: NiL,} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536 IF VERSION 3f2a9c41d07b58e6",
         );
 
         assert_eq!(
-            wql.err().unwrap(),
-            String::from("MATCH requires ALL or ANY symbols")
+            wql.unwrap(),
+            Wql::UpdateSet(
+                "this_entity".to_string(),
+                hashmap(),
+                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap(),
+                Some("3f2a9c41d07b58e6".to_string())
+            )
         );
     }
 
     #[test]
-    fn match_update_missing_update_key() {
+    fn update_if_without_version() {
         let wql = Wql::from_str(
-            " MATCH Any(a == 1, b >= 3, c != \"hello\", d < 7)
-        this_entity 
-        SET {
-            a: 123,
-            g: NiL
-        } 
-        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+            "UPDATE this_entity SET {a: 123,} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536 IF 3f2a9c41d07b58e6",
         );
 
         assert_eq!(
-            wql.err().unwrap(),
-            String::from("UPDATE keyword is required for MATCH UPDATE")
+            wql.err(),
+            Some(String::from("Keyword VERSION is required after IF"))
         );
     }
 
     #[test]
-    fn match_update_missing_entity_name() {
+    fn update_content_if_version() {
         let wql = Wql::from_str(
-            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
-        UPDATE 
-        SET {
+            "UPDATE this_entity CONTENT {a: 123,} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536 IF VERSION 3f2a9c41d07b58e6",
+        );
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("IF VERSION is only available for UPDATE SET"))
+        );
+    }
+
+    #[test]
+    fn update_content_entity() {
+        let wql = Wql::from_str(
+            "UPDATE this_entity 
+        Content {
             a: 123,
             g: NiL
         } 
@@ -489,17 +944,20 @@ mod test_match {
         );
 
         assert_eq!(
-            wql.err().unwrap(),
-            String::from("Entity name is required for MATCH UPDATE")
+            wql.unwrap(),
+            Wql::UpdateContent(
+                "this_entity".to_string(),
+                hashmap(),
+                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap()
+            )
         );
     }
 
     #[test]
-    fn match_update_missing_set() {
+    fn update_set_missing_entity() {
         let wql = Wql::from_str(
-            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
-        UPDATE this_entity 
-        {
+            "UPDATE 
+        SET {
             a: 123,
             g: NiL
         } 
@@ -507,537 +965,1600 @@ mod test_match {
         );
 
         assert_eq!(
-            wql.err().unwrap(),
-            String::from("MATCH UPDATE type is required after entity. Keyword is SET")
+            wql.err(),
+            Some(String::from("Entity name is required for UPDATE"))
         );
     }
 
+    fn hashmap() -> Entity {
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Integer(123));
+        hm.insert("g".to_string(), Types::Nil);
+        hm
+    }
+
     #[test]
-    fn match_update_missing_content() {
+    fn update_entity_misspelled_action() {
         let wql = Wql::from_str(
-            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
-        UPDATE this_entity 
-        SET 
+            "UPDATE this_entity 
+        TO {
+            a: 123,
+            g: NiL
+        } 
         INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.err().unwrap(),
-            String::from("Entity map should start with `{` and end with `}`")
+            wql.err(),
+            Some(String::from(
+                "UPDATE type is required after entity. Keywords are SET or CONTENT"
+            ))
         );
     }
 
     #[test]
-    fn match_update_missing_into() {
+    fn update_entity_missing_into() {
         let wql = Wql::from_str(
-            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
-        UPDATE this_entity 
+            "UPDATE this_entity 
         SET {
             a: 123,
             g: NiL
         } 
-        d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        to d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.err().unwrap(),
-            String::from("Keyword INTO is required for MATCH UPDATE")
+            wql.err(),
+            Some(String::from("Keyword INTO is required for UPDATE"))
         );
     }
 
     #[test]
-    fn match_update_missing_id() {
+    fn update_entity_missing_uuid() {
         let wql = Wql::from_str(
-            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
-        UPDATE this_entity 
+            "UPDATE this_entity 
         SET {
             a: 123,
             g: NiL
         } 
-        INTO",
+        into Some-crazy-id",
         );
 
         assert!(wql
             .err()
             .unwrap()
-            .starts_with("Couldn\'t create uuid from "));
+            .starts_with("Couldn\'t create uuid from Some-crazy-id"));
     }
 
-    fn hashmap() -> Entity {
-        let mut hm = HashMap::new();
-        hm.insert("a".to_string(), Types::Integer(123));
-        hm.insert("g".to_string(), Types::Nil);
-        hm
+    #[test]
+    fn replace_entity() {
+        let wql = Wql::from_str(
+            "REPLACE this_entity {
+            a: 123,
+            g: NiL
+        } INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Replace(
+                "this_entity".to_string(),
+                hashmap(),
+                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap()
+            )
+        );
+        assert!(matches!(
+            Wql::from_str("DRY RUN replace this_entity {a: 123,} into d6ca73c0-41ff-4975-8a60-fc4a061ce536"),
+            Ok(Wql::DryRun(tx)) if matches!(*tx, Wql::Replace(..))
+        ));
+    }
+
+    #[test]
+    fn replace_errors() {
+        assert_eq!(
+            Wql::from_str("REPLACE {a: 123,} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536").err(),
+            Some(String::from("Entity name is required for REPLACE"))
+        );
+        assert_eq!(
+            Wql::from_str("REPLACE this_entity {a: 123,} TO d6ca73c0-41ff-4975-8a60-fc4a061ce536")
+                .err(),
+            Some(String::from("Keyword INTO is required for REPLACE"))
+        );
+        assert_eq!(
+            Wql::from_str(
+                "REPLACE this_entity {a: 123,} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536 IF VERSION 1"
+            )
+            .err(),
+            Some(String::from(
+                "REPLACE only takes an entity name, an entity map and an Uuid"
+            ))
+        );
     }
 }
 
 #[cfg(test)]
-mod evict {
-    use std::str::FromStr;
-
+mod test_match {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
-    fn evict_entity() {
-        let wql = Wql::from_str("EVICT my_entity");
-
-        assert_eq!(wql.unwrap(), Wql::Evict(String::from("my_entity"), None));
+    fn test_read_match_args() {
+        let mut args = "(a == 1, b != 2, c > 3, d >= 4, e < 5, f <= 6)".chars();
+        let actual = read_match_args(&mut args).unwrap();
+        let expected = vec![
+            MatchCondition::Eq("a".to_string(), Types::Integer(1)),
+            MatchCondition::NotEq("b".to_string(), Types::Integer(2)),
+            MatchCondition::G("c".to_string(), Types::Integer(3)),
+            MatchCondition::GEq("d".to_string(), Types::Integer(4)),
+            MatchCondition::L("e".to_string(), Types::Integer(5)),
+            MatchCondition::LEq("f".to_string(), Types::Integer(6)),
+        ];
+        assert_eq!(actual, expected);
     }
 
     #[test]
-    fn evict_entity_with_dash() {
-        let wql = Wql::from_str("EVICT my-entity");
+    fn read_match_older_than() {
+        let mut args = "(last_seen OLDER THAN 30d, pinged older than 90s)".chars();
+        let actual = read_match_args(&mut args).unwrap();
+        let expected = vec![
+            MatchCondition::OlderThan(
+                "last_seen".to_string(),
+                std::time::Duration::from_secs(30 * 24 * 60 * 60),
+            ),
+            MatchCondition::OlderThan("pinged".to_string(), std::time::Duration::from_secs(90)),
+        ];
+        assert_eq!(actual, expected);
 
+        let mut args = "(last_seen OLDER 30d)".chars();
         assert_eq!(
-            wql.err(),
-            Some(String::from("Entity name cannot contain `-`"))
+            read_match_args(&mut args).err(),
+            Some(String::from(
+                "OLDER THAN requires a key and a duration, like `last_seen OLDER THAN 30d`"
+            ))
         );
+        for duration in &["30", "d", "30y", "-3d", "99999999999999999999w"] {
+            let args = format!("(last_seen OLDER THAN {})", duration);
+            let mut args = args.chars();
+            assert_eq!(
+                read_match_args(&mut args).err(),
+                Some(format!(
+                    "Duration `{}` should be an amount followed by s, m, h, d or w, like `30d`",
+                    duration
+                ))
+            );
+        }
     }
 
     #[test]
-    fn evict_entity_from_id() {
-        let wql = Wql::from_str("EVICT d6ca73c0-41ff-4975-8a60-fc4a061ce536 FROM my_entity");
+    fn match_update_set_entity() {
+        let wql = Wql::from_str(
+            " MATCH ALL(a == 1, b >= 3, c != \"hello\", d < 7,)
+        UPDATE this_entity 
+        SET {
+            a: 123,
+            g: NiL
+        } 
+        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        );
 
         assert_eq!(
             wql.unwrap(),
-            Wql::Evict(
-                String::from("my_entity"),
-                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").ok()
+            Wql::MatchUpdate(
+                "this_entity".to_string(),
+                hashmap(),
+                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap(),
+                MatchCondition::All(vec![
+                    MatchCondition::Eq("a".to_string(), Types::Integer(1)),
+                    MatchCondition::GEq("b".to_string(), Types::Integer(3)),
+                    MatchCondition::NotEq("c".to_string(), Types::String("hello".to_string())),
+                    MatchCondition::L("d".to_string(), Types::Integer(7)),
+                ]),
+                HashMap::new()
             )
         );
     }
 
     #[test]
-    fn evict_entity_without_from() {
-        let wql = Wql::from_str("EVICT d6ca73c0-41ff-4975-8a60-fc4a061ce536 my_entity");
+    fn match_update_set_expressions() {
+        let wql = Wql::from_str(
+            "MATCH ALL(balance >= 100,) UPDATE accounts SET {balance: balance - 100, fee: 1.5 * rate, note: \"a - b\",} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        );
+        let mut expressions = HashMap::new();
+        expressions.insert(
+            "balance".to_string(),
+            Expression {
+                left: ScalarArg::Key("balance".to_string()),
+                operator: Operator::Sub,
+                right: ScalarArg::Value(Types::Integer(100)),
+            },
+        );
+        expressions.insert(
+            "fee".to_string(),
+            Expression {
+                left: ScalarArg::Value(Types::Float(1.5)),
+                operator: Operator::Mul,
+                right: ScalarArg::Key("rate".to_string()),
+            },
+        );
+        let mut content = HashMap::new();
+        content.insert("note".to_string(), Types::String("a - b".to_string()));
 
         assert_eq!(
-            wql.err(),
-            Some(String::from("Keyword FROM is required to EVICT an UUID"))
+            wql.unwrap(),
+            Wql::MatchUpdate(
+                "accounts".to_string(),
+                content,
+                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap(),
+                MatchCondition::All(vec![MatchCondition::GEq(
+                    "balance".to_string(),
+                    Types::Integer(100)
+                ),]),
+                expressions
+            )
         );
     }
 
     #[test]
-    fn evict_entity_without_entity_name() {
-        let wql = Wql::from_str("EVICT d6ca73c0-41ff-4975-8a60-fc4a061ce536 FROM");
+    fn match_update_set_expression_with_string() {
+        let wql = Wql::from_str(
+            "MATCH ALL(balance >= 100,) UPDATE accounts SET {balance: balance - \"100\",} INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        );
 
         assert_eq!(
             wql.err(),
-            Some(String::from("Entity name is required for EVICT"))
+            Some(String::from(
+                "Expression operand `\"100\"` must be a key or an Integer, Float or Precise"
+            ))
         );
     }
-}
-
-#[cfg(test)]
-mod test_data_sructures {
-    use super::*;
 
     #[test]
-    fn insert_vec() {
+    fn match_update_missing_logical_arg() {
         let wql = Wql::from_str(
-            "INSERT {
+            " MATCH (a == 1, b >= 3, c != \"hello\", d < 7)
+        UPDATE this_entity 
+        SET {
             a: 123,
-            b: [12.3, 34, \"hello\",]
-        } INTO my_entity",
+            g: NiL
+        } 
+        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.unwrap(),
-            Wql::Insert("my_entity".to_string(), hashmap(), None)
+            wql.err().unwrap(),
+            String::from("MATCH requires ALL or ANY symbols")
         );
     }
 
     #[test]
-    fn insert_time() {
-        use chrono::{DateTime, Utc};
+    fn match_update_missing_update_key() {
         let wql = Wql::from_str(
-            "INSERT {
-            time: 2014-11-28T12:00:09Z,
-        } INTO my_entity",
+            " MATCH Any(a == 1, b >= 3, c != \"hello\", d < 7)
+        this_entity 
+        SET {
+            a: 123,
+            g: NiL
+        } 
+        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
-        let hm: HashMap<String, Types> = vec![(
-            "time".to_string(),
-            Types::DateTime("2014-11-28T12:00:09Z".parse::<DateTime<Utc>>().unwrap()),
-        )]
-        .iter()
-        .cloned()
-        .collect();
-        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
+        assert_eq!(
+            wql.err().unwrap(),
+            String::from("UPDATE keyword is required for MATCH UPDATE")
+        );
     }
 
     #[test]
-    fn insert_vec_in_vec() {
+    fn match_update_missing_entity_name() {
         let wql = Wql::from_str(
-            "INSERT {
+            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
+        UPDATE 
+        SET {
             a: 123,
-            b: [12.3, 34, [\"hello\"]]
-        } INTO my_entity",
+            g: NiL
+        } 
+        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.unwrap(),
-            Wql::Insert("my_entity".to_string(), hashmap2(), None)
+            wql.err().unwrap(),
+            String::from("Entity name is required for MATCH UPDATE")
         );
     }
 
     #[test]
-    fn insert_vec_err() {
+    fn match_update_missing_set() {
         let wql = Wql::from_str(
-            "INSERT {
+            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
+        UPDATE this_entity 
+        {
             a: 123,
-            b: [12.3, 34, \"hello\", nkjsld,]
-        } INTO my_entity",
+            g: NiL
+        } 
+        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.err(),
-            Some(String::from("Value Type could not be created from nkjsld"))
+            wql.err().unwrap(),
+            String::from("MATCH UPDATE type is required after entity. Keyword is SET")
         );
     }
 
     #[test]
-    fn insert_with_err() {
+    fn match_update_missing_content() {
         let wql = Wql::from_str(
-            "INSERT {
-            a: 123,
-            b: [12.3, 34, \"hello\",]
-        } INTO my_entity
-        ID 555555-5555-444444",
+            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
+        UPDATE this_entity 
+        SET 
+        INTO d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.err(),
-            Some(String::from(
-                "Keyword WITH is required for INSERT with Uuid"
-            ))
+            wql.err().unwrap(),
+            String::from("Entity map should start with `{` and end with `}`")
         );
     }
 
     #[test]
-    fn insert_vec_with_map() {
+    fn match_update_missing_into() {
         let wql = Wql::from_str(
-            "INSERT {
+            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
+        UPDATE this_entity 
+        SET {
             a: 123,
-            b: { a: 12.3, b: 34, }
-        } INTO my_entity",
+            g: NiL
+        } 
+        d6ca73c0-41ff-4975-8a60-fc4a061ce536",
         );
 
         assert_eq!(
-            wql.unwrap(),
-            Wql::Insert("my_entity".to_string(), hashmap3(), None)
+            wql.err().unwrap(),
+            String::from("Keyword INTO is required for MATCH UPDATE")
         );
     }
 
     #[test]
-    fn insert_vec_with_map_and_id() {
-        let uuid = Uuid::parse_str("13ca62fc-241b-4af6-87c3-0ae4015f9967").ok();
+    fn match_update_missing_id() {
         let wql = Wql::from_str(
-            "INSERT {
+            " MATCH All(a == 1, b >= 3, c != \"hello\", d < 7)
+        UPDATE this_entity 
+        SET {
             a: 123,
-            b: { a: 12.3, b: 34, }
-        } INTO my_entity
-          WITH 13ca62fc-241b-4af6-87c3-0ae4015f9967",
+            g: NiL
+        } 
+        INTO",
         );
 
-        assert_eq!(
-            wql.unwrap(),
-            Wql::Insert("my_entity".to_string(), hashmap3(), uuid)
-        );
+        assert!(wql
+            .err()
+            .unwrap()
+            .starts_with("Couldn\'t create uuid from "));
     }
 
-    fn hashmap() -> HashMap<String, Types> {
+    fn hashmap() -> Entity {
         let mut hm = HashMap::new();
         hm.insert("a".to_string(), Types::Integer(123));
-        hm.insert(
-            "b".to_string(),
-            Types::Vector(vec![
-                Types::Float(12.3),
-                Types::Integer(34),
-                Types::String("hello".to_string()),
-            ]),
-        );
+        hm.insert("g".to_string(), Types::Nil);
         hm
     }
-
-    fn hashmap2() -> HashMap<String, Types> {
-        let mut hm = HashMap::new();
-        hm.insert("a".to_string(), Types::Integer(123));
-        hm.insert(
-            "b".to_string(),
-            Types::Vector(vec![
-                Types::Float(12.3),
-                Types::Integer(34),
-                Types::Vector(vec![Types::String("hello".to_string())]),
-            ]),
-        );
-        hm
-    }
-
-    fn hashmap3() -> HashMap<String, Types> {
-        let mut inner_map = HashMap::new();
-        inner_map.insert("a".to_string(), Types::Float(12.3));
-        inner_map.insert("b".to_string(), Types::Integer(34));
-        let mut hm = HashMap::new();
-        hm.insert("a".to_string(), Types::Integer(123));
-        hm.insert("b".to_string(), Types::Map(inner_map));
-        hm
-    }
-}
+}
 
 #[cfg(test)]
-mod check {
-    use std::collections::HashMap;
+mod evict {
     use std::str::FromStr;
 
     use super::*;
 
     #[test]
-    fn check_encrypt_values() {
-        let wql = Wql::from_str(
-            "CHECK {
-            ssn: 123,
-            pswd: \"my-password\"
-        } FROM my_entity ID d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+    fn evict_entity() {
+        let wql = Wql::from_str("EVICT my_entity");
+
+        assert_eq!(wql.unwrap(), Wql::Evict(String::from("my_entity"), None));
+    }
+
+    #[test]
+    fn evict_entity_with_dash() {
+        let wql = Wql::from_str("EVICT my-entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Entity name cannot contain `-`"))
         );
+    }
 
-        let uuid = Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap();
+    #[test]
+    fn evict_entity_from_id() {
+        let wql = Wql::from_str("EVICT d6ca73c0-41ff-4975-8a60-fc4a061ce536 FROM my_entity");
 
         assert_eq!(
             wql.unwrap(),
-            Wql::CheckValue("my_entity".to_string(), uuid, hashmap())
+            Wql::Evict(
+                String::from("my_entity"),
+                Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").ok()
+            )
         );
     }
 
-    fn hashmap() -> HashMap<String, String> {
-        let mut hm = HashMap::new();
-        hm.insert("ssn".to_string(), "123".to_string());
-        hm.insert("pswd".to_string(), "my-password".to_string());
-        hm
+    #[test]
+    fn evict_entity_without_from() {
+        let wql = Wql::from_str("EVICT d6ca73c0-41ff-4975-8a60-fc4a061ce536 my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Keyword FROM is required to EVICT an UUID"))
+        );
+    }
+
+    #[test]
+    fn evict_entity_without_entity_name() {
+        let wql = Wql::from_str("EVICT d6ca73c0-41ff-4975-8a60-fc4a061ce536 FROM");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Entity name is required for EVICT"))
+        );
     }
 }
 
 #[cfg(test)]
-mod test_where {
+mod clear {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
-    fn where_ok() {
-        let query = "Select * FROM my_entity WherE {
-            (in ?id 32434 45345 345346436),
-            (between ?age 30 35),
-        }";
-        let wql = Wql::from_str(query);
+    fn clear_entity() {
+        let wql = Wql::from_str("CLEAR ENTITY my_entity");
+
+        assert_eq!(wql.unwrap(), Wql::Clear(String::from("my_entity")));
+    }
+
+    #[test]
+    fn dry_run_clear_entity() {
+        let wql = Wql::from_str("DRY RUN clear entity my_entity");
 
         assert_eq!(
             wql.unwrap(),
-            Wql::SelectWhere(
-                "my_entity".to_string(),
-                ToSelect::All,
-                vec![
-                    Clause::ComplexComparisonFunctions(
-                        where_clause::Function::In,
-                        "?id".to_string(),
-                        vec![
-                            Types::Integer(32434),
-                            Types::Integer(45345),
-                            Types::Integer(345346436),
-                        ]
-                    ),
-                    Clause::ComplexComparisonFunctions(
-                        where_clause::Function::Between,
-                        "?age".to_string(),
-                        vec![Types::Integer(30), Types::Integer(35),]
-                    )
-                ],
-                HashMap::new()
-            )
-        )
+            Wql::DryRun(Box::new(Wql::Clear(String::from("my_entity"))))
+        );
     }
 
     #[test]
-    fn or_clause() {
-        let query = "Select * FROM my_entity WherE {
-            ?* my_entity:a ?a,
-            ?* my_entity:c ?c,
-            (== ?a 123),
-            (or
-                (>= c 4300.0)
-                (< c 6.9)
-            ),
-        }";
-        let wql = Wql::from_str(query);
+    fn clear_without_entity_keyword() {
+        let wql = Wql::from_str("CLEAR my_entity");
 
         assert_eq!(
-            wql.unwrap(),
-            Wql::SelectWhere(
-                "my_entity".to_string(),
-                ToSelect::All,
-                vec![
-                    Clause::ValueAttribution(
-                        "my_entity".to_string(),
-                        "a".to_string(),
-                        Value("?a".to_string())
-                    ),
-                    Clause::ValueAttribution(
-                        "my_entity".to_string(),
-                        "c".to_string(),
-                        Value("?c".to_string())
-                    ),
-                    Clause::SimpleComparisonFunction(
-                        Function::Eq,
-                        "?a".to_string(),
-                        Types::Integer(123)
-                    ),
-                    Clause::Or(
-                        Function::Or,
-                        vec![
-                            Clause::SimpleComparisonFunction(
-                                Function::GEq,
-                                "c".to_string(),
-                                Types::Float(4300.0)
-                            ),
-                            Clause::SimpleComparisonFunction(
-                                Function::L,
-                                "c".to_string(),
-                                Types::Float(6.9)
-                            )
-                        ]
-                    )
-                ],
-                HashMap::new()
-            )
-        )
+            wql.err(),
+            Some(String::from("Keyword ENTITY is required for CLEAR"))
+        );
     }
 
     #[test]
-    fn select_where_groupby() {
-        let query = "Select * FROM my_entity WHERE {
-            ?* my_entity:name \"julia\",
-            ?* my_entity:id 349875325,
-        } GROUP BY amazing_key";
-        let wql = Wql::from_str(query);
-        let hm: HashMap<String, Algebra> = vec![(
-            "GROUP".to_string(),
-            Algebra::GroupBy(String::from("amazing_key")),
-        )]
-        .iter()
-        .cloned()
-        .collect();
+    fn clear_with_dash() {
+        let wql = Wql::from_str("CLEAR ENTITY my-entity");
 
         assert_eq!(
-            wql.unwrap(),
-            Wql::SelectWhere(
-                "my_entity".to_string(),
-                ToSelect::All,
-                vec![
-                    Clause::ContainsKeyValue(
-                        "my_entity".to_string(),
-                        "name".to_string(),
-                        Types::String("julia".to_string())
-                    ),
-                    Clause::ContainsKeyValue(
-                        "my_entity".to_string(),
-                        "id".to_string(),
-                        Types::Integer(349875325)
-                    ),
-                ],
-                hm
-            )
-        )
+            wql.err(),
+            Some(String::from("CLEAR ENTITY only takes an entity name"))
+        );
     }
 }
 
 #[cfg(test)]
-mod diff_intersect {
-    use std::str::FromStr;
-
+mod reindex {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
-    fn intersect_key() {
-        let f_uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").ok();
-        let s_uuid = Uuid::from_str("49dab8cf-2df2-474d-6fd1-c596c0bb8a00").ok();
-        let query = "INTERSECT KEY SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 | SelEct * FROM my_entity ID 49dab8cf-2df2-474d-6fd1-c596c0bb8a00";
-        let wql = Wql::from_str(query);
-        assert_eq!(
-            wql.unwrap(),
-            Wql::RelationQuery(
-                vec![
-                    Wql::Select(
-                        "my_entity".to_string(),
-                        ToSelect::All,
-                        f_uuid,
-                        HashMap::new()
-                    ),
-                    Wql::Select(
-                        "my_entity".to_string(),
-                        ToSelect::All,
-                        s_uuid,
-                        HashMap::new()
-                    ),
-                ],
-                Relation::Intersect,
-                RelationType::Key
-            )
-        );
+    fn reindex_entity() {
+        let wql = Wql::from_str("reindex ENTITY my_entity");
+
+        assert_eq!(wql.unwrap(), Wql::Reindex(String::from("my_entity")));
     }
 
     #[test]
-    fn diff_key_value() {
-        let f_uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").ok();
-        let s_uuid = Uuid::from_str("49dab8cf-2df2-474d-6fd1-c596c0bb8a00").ok();
-        let query = "DIFFERENCE KEY-VALUE SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 | SelEct * FROM my_entity ID 49dab8cf-2df2-474d-6fd1-c596c0bb8a00 WHEN AT 2020-01-01T00:00:00Z";
-        let wql = Wql::from_str(query);
+    fn reindex_errors() {
         assert_eq!(
-            wql.unwrap(),
-            Wql::RelationQuery(
-                vec![
-                    Wql::Select(
-                        "my_entity".to_string(),
-                        ToSelect::All,
-                        f_uuid,
-                        HashMap::new()
-                    ),
-                    Wql::SelectWhen(
-                        "my_entity".to_string(),
-                        ToSelect::All,
-                        s_uuid,
-                        "2020-01-01T00:00:00Z".to_string()
-                    ),
-                ],
-                Relation::Difference,
-                RelationType::KeyValue
-            )
+            Wql::from_str("REINDEX my_entity").err(),
+            Some(String::from("Keyword ENTITY is required for REINDEX"))
+        );
+        assert_eq!(
+            Wql::from_str("REINDEX ENTITY").err(),
+            Some(String::from("Entity name is required for REINDEX"))
+        );
+        assert_eq!(
+            Wql::from_str("REINDEX ENTITY my_entity now").err(),
+            Some(String::from("REINDEX ENTITY only takes an entity name"))
+        );
+        assert_eq!(
+            Wql::from_str("DRY RUN REINDEX ENTITY my_entity").err(),
+            Some(String::from("DRY RUN is only available for tx statements"))
         );
     }
+}
+
+#[cfg(test)]
+mod test_data_sructures {
+    use super::*;
 
     #[test]
-    fn union_key() {
-        let f_uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").ok();
-        let s_uuid = Uuid::from_str("49dab8cf-2df2-474d-6fd1-c596c0bb8a00").ok();
-        let query = "UNION KEY SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 | SelEct * FROM my_entity ID 49dab8cf-2df2-474d-6fd1-c596c0bb8a00 WHEN AT 2020-01-01T00:00:00Z";
+    fn insert_vec() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: [12.3, 34, \"hello\",]
+        } INTO my_entity",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Insert("my_entity".to_string(), hashmap(), None)
+        );
+    }
+
+    #[test]
+    fn insert_time() {
+        use chrono::{DateTime, Utc};
+        let wql = Wql::from_str(
+            "INSERT {
+            time: 2014-11-28T12:00:09Z,
+        } INTO my_entity",
+        );
+
+        let hm: HashMap<String, Types> = vec![(
+            "time".to_string(),
+            Types::DateTime("2014-11-28T12:00:09Z".parse::<DateTime<Utc>>().unwrap()),
+        )]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(wql.unwrap(), Wql::Insert("my_entity".to_string(), hm, None));
+    }
+
+    #[test]
+    fn insert_vec_in_vec() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: [12.3, 34, [\"hello\"]]
+        } INTO my_entity",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Insert("my_entity".to_string(), hashmap2(), None)
+        );
+    }
+
+    #[test]
+    fn insert_vec_err() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: [12.3, 34, \"hello\", nkjsld,]
+        } INTO my_entity",
+        );
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Value Type could not be created from nkjsld"))
+        );
+    }
+
+    #[test]
+    fn insert_with_err() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: [12.3, 34, \"hello\",]
+        } INTO my_entity
+        ID 555555-5555-444444",
+        );
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "Keyword WITH is required for INSERT with Uuid"
+            ))
+        );
+    }
+
+    #[test]
+    fn insert_vec_with_map() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: { a: 12.3, b: 34, }
+        } INTO my_entity",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Insert("my_entity".to_string(), hashmap3(), None)
+        );
+    }
+
+    #[test]
+    fn insert_vec_with_map_and_id() {
+        let uuid = Uuid::parse_str("13ca62fc-241b-4af6-87c3-0ae4015f9967").ok();
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: { a: 12.3, b: 34, }
+        } INTO my_entity
+          WITH 13ca62fc-241b-4af6-87c3-0ae4015f9967",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::Insert("my_entity".to_string(), hashmap3(), uuid)
+        );
+    }
+
+    #[test]
+    fn insert_with_next() {
+        let wql = Wql::from_str(
+            "INSERT {
+            a: 123,
+            b: { a: 12.3, b: 34, }
+        } INTO my_entity
+          WITH next",
+        );
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::InsertNext("my_entity".to_string(), hashmap3())
+        );
+
+        let wql = Wql::from_str("BEGIN INSERT {a: 1,} INTO my_entity WITH NEXT; COMMIT");
+        assert!(matches!(wql, Ok(Wql::Transaction(_))));
+        let wql = Wql::from_str("DRY RUN INSERT {a: 1,} INTO my_entity WITH NEXT");
+        assert!(matches!(wql, Ok(Wql::DryRun(_))));
+    }
+
+    fn hashmap() -> HashMap<String, Types> {
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Integer(123));
+        hm.insert(
+            "b".to_string(),
+            Types::Vector(vec![
+                Types::Float(12.3),
+                Types::Integer(34),
+                Types::String("hello".to_string()),
+            ]),
+        );
+        hm
+    }
+
+    fn hashmap2() -> HashMap<String, Types> {
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Integer(123));
+        hm.insert(
+            "b".to_string(),
+            Types::Vector(vec![
+                Types::Float(12.3),
+                Types::Integer(34),
+                Types::Vector(vec![Types::String("hello".to_string())]),
+            ]),
+        );
+        hm
+    }
+
+    fn hashmap3() -> HashMap<String, Types> {
+        let mut inner_map = HashMap::new();
+        inner_map.insert("a".to_string(), Types::Float(12.3));
+        inner_map.insert("b".to_string(), Types::Integer(34));
+        let mut hm = HashMap::new();
+        hm.insert("a".to_string(), Types::Integer(123));
+        hm.insert("b".to_string(), Types::Map(inner_map));
+        hm
+    }
+}
+
+#[cfg(test)]
+mod check {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn check_encrypt_values() {
+        let wql = Wql::from_str(
+            "CHECK {
+            ssn: 123,
+            pswd: \"my-password\"
+        } FROM my_entity ID d6ca73c0-41ff-4975-8a60-fc4a061ce536",
+        );
+
+        let uuid = Uuid::from_str("d6ca73c0-41ff-4975-8a60-fc4a061ce536").unwrap();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::CheckValue("my_entity".to_string(), uuid, hashmap())
+        );
+    }
+
+    fn hashmap() -> HashMap<String, String> {
+        let mut hm = HashMap::new();
+        hm.insert("ssn".to_string(), "123".to_string());
+        hm.insert("pswd".to_string(), "my-password".to_string());
+        hm
+    }
+}
+
+#[cfg(test)]
+mod test_where {
+    use super::*;
+
+    #[test]
+    fn where_ok() {
+        let query = "Select * FROM my_entity WherE {
+            (in ?id 32434 45345 345346436),
+            (between ?age 30 35),
+        }";
         let wql = Wql::from_str(query);
+
         assert_eq!(
             wql.unwrap(),
-            Wql::RelationQuery(
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
                 vec![
-                    Wql::Select(
-                        "my_entity".to_string(),
-                        ToSelect::All,
-                        f_uuid,
-                        HashMap::new()
-                    ),
-                    Wql::SelectWhen(
-                        "my_entity".to_string(),
-                        ToSelect::All,
-                        s_uuid,
-                        "2020-01-01T00:00:00Z".to_string()
+                    Clause::ComplexComparisonFunctions(
+                        where_clause::Function::In,
+                        "?id".to_string(),
+                        vec![
+                            Types::Integer(32434),
+                            Types::Integer(45345),
+                            Types::Integer(345346436),
+                        ]
                     ),
+                    Clause::ComplexComparisonFunctions(
+                        where_clause::Function::Between,
+                        "?age".to_string(),
+                        vec![Types::Integer(30), Types::Integer(35),]
+                    )
                 ],
-                Relation::Union,
-                RelationType::Key
+                HashMap::new()
             )
+        )
+    }
+
+    #[test]
+    fn or_clause() {
+        let query = "Select * FROM my_entity WherE {
+            ?* my_entity:a ?a,
+            ?* my_entity:c ?c,
+            (== ?a 123),
+            (or
+                (>= c 4300.0)
+                (< c 6.9)
+            ),
+        }";
+        let wql = Wql::from_str(query);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "a".to_string(),
+                        Value("?a".to_string())
+                    ),
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "c".to_string(),
+                        Value("?c".to_string())
+                    ),
+                    Clause::SimpleComparisonFunction(
+                        Function::Eq,
+                        "?a".to_string(),
+                        Types::Integer(123)
+                    ),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::SimpleComparisonFunction(
+                                Function::GEq,
+                                "c".to_string(),
+                                Types::Float(4300.0)
+                            ),
+                            Clause::SimpleComparisonFunction(
+                                Function::L,
+                                "c".to_string(),
+                                Types::Float(6.9)
+                            )
+                        ]
+                    )
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn select_where_groupby() {
+        let query = "Select * FROM my_entity WHERE {
+            ?* my_entity:name \"julia\",
+            ?* my_entity:id 349875325,
+        } GROUP BY amazing_key";
+        let wql = Wql::from_str(query);
+        let hm: HashMap<String, Algebra> = vec![(
+            "GROUP".to_string(),
+            Algebra::GroupBy(String::from("amazing_key")),
+        )]
+        .iter()
+        .cloned()
+        .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ContainsKeyValue(
+                        "my_entity".to_string(),
+                        "name".to_string(),
+                        Types::String("julia".to_string())
+                    ),
+                    Clause::ContainsKeyValue(
+                        "my_entity".to_string(),
+                        "id".to_string(),
+                        Types::Integer(349875325)
+                    ),
+                ],
+                hm
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod diff_intersect {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn intersect_key() {
+        let f_uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").ok();
+        let s_uuid = Uuid::from_str("49dab8cf-2df2-474d-6fd1-c596c0bb8a00").ok();
+        let query = "INTERSECT KEY SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 | SelEct * FROM my_entity ID 49dab8cf-2df2-474d-6fd1-c596c0bb8a00";
+        let wql = Wql::from_str(query);
+        assert_eq!(
+            wql.unwrap(),
+            Wql::RelationQuery(
+                vec![
+                    Wql::Select(
+                        "my_entity".to_string(),
+                        ToSelect::All,
+                        f_uuid,
+                        HashMap::new()
+                    ),
+                    Wql::Select(
+                        "my_entity".to_string(),
+                        ToSelect::All,
+                        s_uuid,
+                        HashMap::new()
+                    ),
+                ],
+                Relation::Intersect,
+                RelationType::Key
+            )
+        );
+    }
+
+    #[test]
+    fn diff_key_value() {
+        let f_uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").ok();
+        let s_uuid = Uuid::from_str("49dab8cf-2df2-474d-6fd1-c596c0bb8a00").ok();
+        let query = "DIFFERENCE KEY-VALUE SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 | SelEct * FROM my_entity ID 49dab8cf-2df2-474d-6fd1-c596c0bb8a00 WHEN AT 2020-01-01T00:00:00Z";
+        let wql = Wql::from_str(query);
+        assert_eq!(
+            wql.unwrap(),
+            Wql::RelationQuery(
+                vec![
+                    Wql::Select(
+                        "my_entity".to_string(),
+                        ToSelect::All,
+                        f_uuid,
+                        HashMap::new()
+                    ),
+                    Wql::SelectWhen(
+                        "my_entity".to_string(),
+                        ToSelect::All,
+                        s_uuid,
+                        "2020-01-01T00:00:00Z".to_string()
+                    ),
+                ],
+                Relation::Difference,
+                RelationType::KeyValue
+            )
+        );
+    }
+
+    #[test]
+    fn union_key() {
+        let f_uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").ok();
+        let s_uuid = Uuid::from_str("49dab8cf-2df2-474d-6fd1-c596c0bb8a00").ok();
+        let query = "UNION KEY SelEct * FROM my_entity ID 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 | SelEct * FROM my_entity ID 49dab8cf-2df2-474d-6fd1-c596c0bb8a00 WHEN AT 2020-01-01T00:00:00Z";
+        let wql = Wql::from_str(query);
+        assert_eq!(
+            wql.unwrap(),
+            Wql::RelationQuery(
+                vec![
+                    Wql::Select(
+                        "my_entity".to_string(),
+                        ToSelect::All,
+                        f_uuid,
+                        HashMap::new()
+                    ),
+                    Wql::SelectWhen(
+                        "my_entity".to_string(),
+                        ToSelect::All,
+                        s_uuid,
+                        "2020-01-01T00:00:00Z".to_string()
+                    ),
+                ],
+                Relation::Union,
+                RelationType::Key
+            )
+        );
+    }
+}
+
+#[cfg(test)]
+mod types_ordering {
+    use super::*;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn map(entries: Vec<(&str, Types)>) -> Types {
+        Types::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<HashMap<String, Types>>(),
+        )
+    }
+
+    fn hash(t: &Types) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        t.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_maps() {
+        let a = map(vec![("a", Types::Integer(1)), ("b", Types::Float(2.5))]);
+        let b = map(vec![("b", Types::Float(2.5)), ("a", Types::Integer(1))]);
+
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Equal));
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn maps_by_first_differing_entry() {
+        let a = map(vec![("a", Types::Integer(1)), ("b", Types::Integer(9))]);
+        let b = map(vec![("a", Types::Integer(2)), ("b", Types::Integer(0))]);
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Less));
+
+        let c = map(vec![("a", Types::Integer(1)), ("c", Types::Integer(0))]);
+        assert_eq!(c.partial_cmp(&a), Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn maps_by_length() {
+        let a = map(vec![("a", Types::Integer(1))]);
+        let b = map(vec![("a", Types::Integer(1)), ("b", Types::Integer(0))]);
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Less));
+        assert_eq!(b.partial_cmp(&a), Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn nested_maps() {
+        let a = map(vec![(
+            "a",
+            map(vec![("x", Types::String("a".to_string()))]),
+        )]);
+        let b = map(vec![(
+            "a",
+            map(vec![("x", Types::String("b".to_string()))]),
+        )]);
+        assert_eq!(a.partial_cmp(&b), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn floats_hash_equal_iff_compare_equal() {
+        let floats = vec![
+            Types::Float(1.5),
+            Types::Float(1.5),
+            Types::Float(0.0),
+            Types::Float(-0.0),
+            Types::Float(f64::NAN),
+            Types::Float(f64::NAN),
+            Types::Float(0.1 + 0.2),
+            Types::Float(0.3),
+            Types::Point { x: 1.0, y: 0.0 },
+            Types::Point { x: 1.0, y: -0.0 },
+        ];
+
+        for a in &floats {
+            for b in &floats {
+                let cmp_equal = a.partial_cmp(b) == Some(Ordering::Equal);
+                assert_eq!(cmp_equal, hash(a) == hash(b), "{:?} and {:?}", a, b);
+                assert_eq!(cmp_equal, a == b, "{:?} and {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn precise_numeric_order() {
+        let precise = |p: &str| Types::Precise(p.to_string());
+
+        assert_eq!(precise("10").partial_cmp(&precise("9")), Some(Ordering::Greater));
+        assert_eq!(precise("-10").partial_cmp(&precise("-9")), Some(Ordering::Less));
+        assert_eq!(precise("1.50").partial_cmp(&precise("1.5")), Some(Ordering::Equal));
+        assert_eq!(precise("1.50"), precise("1.5"));
+        assert_eq!(hash(&precise("1.50")), hash(&precise("1.5")));
+    }
+
+    #[test]
+    fn precise_add_and_sub() {
+        let precise = |p: &str| Types::Precise(p.to_string());
+
+        assert_eq!(
+            precise("0.1").precise_add(&precise("0.2")),
+            Some(precise("0.3"))
+        );
+        assert_eq!(
+            precise("10").precise_sub(&Types::Integer(11)),
+            Some(precise("-1"))
+        );
+        assert_eq!(Types::Integer(1).precise_add(&Types::Integer(1)), None);
+        assert_eq!(precise("1").precise_add(&Types::Float(1.0)), None);
+    }
+
+    #[test]
+    fn ip_addr_order() {
+        let ip = |ip: &str| Types::IpAddr(ip.parse().unwrap());
+
+        assert_eq!(
+            ip("10.0.0.2").partial_cmp(&ip("9.255.255.255")),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(ip("::1").partial_cmp(&ip("::2")), Some(Ordering::Less));
+        assert_eq!(ip("10.0.0.1").partial_cmp(&ip("::ffff:10.0.0.1")), None);
+        assert_ne!(ip("10.0.0.1"), ip("::ffff:10.0.0.1"));
+        assert_eq!(hash(&ip("10.0.0.1")), hash(&ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn ratio_order() {
+        let ratio = |num, den| Types::Ratio { num, den };
+
+        assert_eq!(Types::ratio(2, 6), Ok(ratio(1, 3)));
+        assert_eq!(Types::ratio(0, -5), Ok(ratio(0, 1)));
+        assert!(Types::ratio(i64::MIN, -1).is_err());
+        assert_eq!(ratio(1, 3).partial_cmp(&ratio(1, 2)), Some(Ordering::Less));
+        assert_eq!(
+            ratio(i64::MAX, 2).partial_cmp(&ratio(i64::MAX - 1, 2)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(ratio(1, 3).partial_cmp(&Types::Float(0.3)), None);
+        assert_eq!(ratio(2, 6), ratio(1, 3));
+        assert_eq!(hash(&ratio(2, 6)), hash(&ratio(1, 3)));
+        assert_eq!(ratio(1, 3).to_hash(Some(4)).map(|h| h.is_hash()), Ok(true));
+    }
+
+    #[test]
+    fn color_order() {
+        let color = |c: &str| Types::color(c).unwrap();
+
+        assert_eq!(color("#FF8800"), color("#ff8800"));
+        assert_eq!(hash(&color("#FF8800")), hash(&color("#ff8800")));
+        assert_ne!(hash(&color("#ff8800")), hash(&color("#0088ff")));
+        assert_eq!(color("#ff8800").packed_color(), Some(0xff8800));
+        assert_eq!(
+            color("#00ffff").partial_cmp(&color("#010000")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            color("#ff8800").partial_cmp(&Types::Integer(0xff8800)),
+            None
+        );
+        assert_eq!(
+            color("#ff8800").to_hash(Some(4)).map(|h| h.is_hash()),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn email_order() {
+        let email = |e: &str| Types::email(e).unwrap();
+
+        assert_eq!(email("Ana@Example.com"), email("ana@example.com"));
+        assert_eq!(
+            hash(&email("ANA@EXAMPLE.COM")),
+            hash(&email("ana@example.com"))
+        );
+        assert_eq!(
+            email("ana@example.com").partial_cmp(&email("bia@example.com")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            email("ana@example.com").partial_cmp(&Types::String("ana@example.com".to_string())),
+            None
+        );
+        assert_eq!(
+            email("ana@mail.Example.com").email_domain(),
+            Some("mail.example.com")
+        );
+        assert_eq!(
+            Types::String("ana@example.com".to_string()).email_domain(),
+            None
+        );
+    }
+
+    #[test]
+    fn phone_order() {
+        let phone = |p: &str| Types::phone(p).unwrap();
+
+        assert_eq!(phone("+1 (555) 123-4567"), phone("001 555 123 4567"));
+        assert_eq!(
+            hash(&phone("+1 (555) 123-4567")),
+            hash(&phone("+15551234567"))
+        );
+        assert_eq!(
+            phone("+15551234567").partial_cmp(&phone("+442079460958")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            phone("+15551234567").partial_cmp(&Types::String("+15551234567".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn percentage_order() {
+        assert_eq!(
+            Types::Percentage(80.5).partial_cmp(&Types::Percentage(80.0)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Types::Percentage(80.0).partial_cmp(&Types::Float(80.0)),
+            None
+        );
+        assert_eq!(Types::Percentage(50.0), Types::Percentage(50.0));
+        assert_eq!(
+            hash(&Types::Percentage(50.0)),
+            hash(&Types::Percentage(50.0))
+        );
+        assert_eq!(
+            Types::Percentage(12.5)
+                .to_hash(Some(4))
+                .map(|h| h.is_hash()),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn floats_total_order() {
+        assert_eq!(
+            Types::Float(1.0).partial_cmp(&Types::Float(1.0)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            Types::Float(-0.0).partial_cmp(&Types::Float(0.0)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            Types::Float(f64::NAN).partial_cmp(&Types::Float(f64::INFINITY)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Types::Integer(2).partial_cmp(&Types::Float(1.5)),
+            Some(Ordering::Greater)
+        );
+    }
+}
+
+#[cfg(test)]
+mod types_from_str_as {
+    use super::*;
+
+    #[test]
+    fn numbers_follow_requested_type() {
+        assert_eq!(Types::from_str_as("10", "Integer"), Ok(Types::Integer(10)));
+        assert_eq!(Types::from_str_as("10", "Float"), Ok(Types::Float(10.0)));
+        assert_eq!(
+            Types::from_str_as(" 10.50 ", "Precise"),
+            Ok(Types::Precise("10.50".to_string()))
+        );
+        assert_eq!(
+            Types::from_str_as("10", "String"),
+            Ok(Types::String("10".to_string()))
+        );
+        assert_eq!(
+            Types::from_str_as("10.5", "Integer"),
+            Err(String::from("`10.5` could not be parsed as Integer"))
+        );
+    }
+
+    #[test]
+    fn other_types() {
+        assert_eq!(Types::from_str_as("a", "Char"), Ok(Types::Char('a')));
+        assert_eq!(
+            Types::from_str_as("true", "Boolean"),
+            Ok(Types::Boolean(true))
+        );
+        assert_eq!(Types::from_str_as("nil", "Nil"), Ok(Types::Nil));
+        assert_eq!(
+            Types::from_str_as("1.5, -2", "Point"),
+            Ok(Types::Point { x: 1.5, y: -2.0 })
+        );
+        assert_eq!(
+            Types::from_str_as("[1, \"a\",]", "Vector"),
+            Ok(Types::Vector(vec![
+                Types::Integer(1),
+                Types::String("a".to_string())
+            ]))
+        );
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), Types::Integer(1));
+        assert_eq!(Types::from_str_as("{a: 1,}", "Map"), Ok(Types::Map(map)));
+        assert_eq!(
+            Types::from_str_as("10.0.0.1", "IpAddr"),
+            Ok(Types::IpAddr("10.0.0.1".parse().unwrap()))
+        );
+        assert_eq!(
+            Types::from_str_as("80%", "Percentage"),
+            Ok(Types::Percentage(80.0))
+        );
+        assert_eq!(
+            Types::from_str_as("80", "Percentage"),
+            Ok(Types::Percentage(80.0))
+        );
+        assert!(Types::from_str_as("101", "Percentage").is_err());
+        assert_eq!(
+            Types::from_str_as("3 / 9", "Ratio"),
+            Ok(Types::Ratio { num: 1, den: 3 })
+        );
+        assert!(Types::from_str_as("1/0", "Ratio").is_err());
+        assert_eq!(
+            Types::from_str_as(" Ana@Example.com ", "Email"),
+            Ok(Types::Email("ana@example.com".to_string()))
+        );
+        assert!(Types::from_str_as("ana", "Email").is_err());
+        assert_eq!(
+            Types::from_str_as(" #FF8800 ", "Color"),
+            Ok(Types::Color(255, 136, 0))
+        );
+        assert!(Types::from_str_as("ff8800", "Color").is_err());
+        assert_eq!(
+            Types::from_str_as(" +44 (20) 7946 0958 ", "Phone"),
+            Ok(Types::Phone("+442079460958".to_string()))
+        );
+        assert!(Types::from_str_as("20 7946 0958", "Phone").is_err());
+    }
+
+    #[test]
+    fn invalid_values_and_types() {
+        assert_eq!(
+            Types::from_str_as("ab", "Char"),
+            Err(String::from("`ab` could not be parsed as Char"))
+        );
+        assert_eq!(
+            Types::from_str_as("[1, 2] 3", "Vector"),
+            Err(String::from("`[1, 2] 3` could not be parsed as Vector"))
+        );
+        assert_eq!(
+            Types::from_str_as("abc", "Hash"),
+            Err(String::from(
+                "Hash values are created by ENCRYPTS and cannot be parsed"
+            ))
+        );
+        assert_eq!(
+            Types::from_str_as("10", "Text"),
+            Err(String::from("Type `Text` is not valid. Valid types are [\"Char\", \"Integer\", \"String\", \"Uuid\", \"Float\", \"Boolean\", \"Vector\", \"Map\", \"Hash\", \"Precise\", \"DateTime\", \"Nil\", \"Point\", \"IpAddr\", \"Percentage\", \"Ratio\", \"Email\", \"Color\", \"Phone\"]"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod approx_size {
+    use super::*;
+    use std::mem::size_of;
+
+    #[test]
+    fn counts_heap_bytes() {
+        let base = size_of::<Types>();
+        assert_eq!(Types::Integer(1).approx_size(), base);
+        assert_eq!(Types::String("hello".to_string()).approx_size(), base + 5);
+        assert_eq!(
+            Types::Vector(vec![Types::Nil, Types::String("ab".to_string())]).approx_size(),
+            3 * base + 2
+        );
+
+        let mut map = HashMap::new();
+        map.insert("key".to_string(), Types::Boolean(true));
+        assert_eq!(
+            Types::Map(map).approx_size(),
+            2 * base + size_of::<String>() + 3
+        );
+    }
+}
+
+#[cfg(test)]
+mod dry_run {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn dry_run_insert() {
+        let wql = Wql::from_str("DRY RUN INSERT {a: 123,} INTO my_entity");
+        let hm: HashMap<String, Types> = vec![("a".to_string(), Types::Integer(123))]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::DryRun(Box::new(Wql::Insert("my_entity".to_string(), hm, None)))
+        );
+    }
+
+    #[test]
+    fn dry_run_match_update() {
+        let wql = Wql::from_str(
+            "dry run MATCH ALL(a > 100,) UPDATE my_entity SET {a: 1,} INTO 2df2b8cf-49da-474d-8a00-c596c0bb6fd1",
+        );
+
+        assert!(
+            matches!(wql.unwrap(), Wql::DryRun(tx) if matches!(*tx, Wql::MatchUpdate(_, _, _, _, _)))
+        );
+    }
+
+    #[test]
+    fn dry_run_requires_run() {
+        let wql = Wql::from_str("DRY INSERT {a: 123,} INTO my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Keyword RUN is required for DRY RUN"))
+        );
+    }
+
+    #[test]
+    fn dry_run_select_err() {
+        let wql = Wql::from_str("DRY RUN SELECT * FROM my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("DRY RUN is only available for tx statements"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod version {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn version_and_ping() {
+        assert_eq!(Wql::from_str("VERSION").unwrap(), Wql::Version);
+        assert_eq!(Wql::from_str("  ping  ").unwrap(), Wql::Version);
+    }
+
+    #[test]
+    fn version_with_args_err() {
+        let wql = Wql::from_str("VERSION my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("VERSION does not take arguments"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod describe {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn describe_entity() {
+        assert_eq!(
+            Wql::from_str("DESCRIBE ENTITY my_entity").unwrap(),
+            Wql::Describe(String::from("my_entity"))
+        );
+        assert_eq!(
+            Wql::from_str("describe entity my_entity  ").unwrap(),
+            Wql::Describe(String::from("my_entity"))
+        );
+    }
+
+    #[test]
+    fn describe_without_entity_keyword_err() {
+        let wql = Wql::from_str("DESCRIBE my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Keyword ENTITY is required for DESCRIBE"))
+        );
+    }
+
+    #[test]
+    fn describe_without_name_err() {
+        let wql = Wql::from_str("DESCRIBE ENTITY");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Entity name is required for DESCRIBE"))
+        );
+    }
+
+    #[test]
+    fn describe_with_extra_args_err() {
+        let wql = Wql::from_str("DESCRIBE ENTITY my_entity WHEN AT 2014-11-28T21:00:09+09:00");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("DESCRIBE ENTITY only takes an entity name"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod exists {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn exists_id_in_entity() {
+        let uuid = Uuid::from_str("2df2b8cf-49da-474d-8a00-c596c0bb6fd1").unwrap();
+        assert_eq!(
+            Wql::from_str("EXISTS 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 IN my_entity").unwrap(),
+            Wql::Exists(String::from("my_entity"), uuid)
+        );
+        assert_eq!(
+            Wql::from_str("exists  2df2b8cf-49da-474d-8a00-c596c0bb6fd1 in my_entity ").unwrap(),
+            Wql::Exists(String::from("my_entity"), uuid)
+        );
+    }
+
+    #[test]
+    fn exists_errors() {
+        assert_eq!(
+            Wql::from_str("EXISTS my_entity").err(),
+            Some(String::from("EXISTS requires a Uuid, found `my_entity`"))
+        );
+        assert_eq!(
+            Wql::from_str("EXISTS 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 FROM my_entity").err(),
+            Some(String::from("Keyword IN is required for EXISTS"))
+        );
+        assert_eq!(
+            Wql::from_str("EXISTS 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 IN").err(),
+            Some(String::from("Entity name is required after IN"))
+        );
+        assert_eq!(
+            Wql::from_str("EXISTS 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 IN my_entity ID 1").err(),
+            Some(String::from("EXISTS only takes an Uuid and an entity name"))
+        );
+    }
+}
+
+#[cfg(test)]
+mod transaction {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn begin_commit() {
+        let wql = Wql::from_str(
+            "BEGIN
+                INSERT {a: 123, b: \"x;y\",} INTO my_entity;
+                MATCH ALL(a > 100,) UPDATE my_entity SET {a: 1,} INTO 2df2b8cf-49da-474d-8a00-c596c0bb6fd1;
+            COMMIT",
+        )
+        .unwrap();
+
+        match wql {
+            Wql::Transaction(statements) => {
+                assert_eq!(statements.len(), 2);
+                assert!(
+                    matches!(&statements[0], Wql::Insert(_, content, None) if content["b"] == Types::String("x;y".to_string()))
+                );
+                assert!(matches!(statements[1], Wql::MatchUpdate(_, _, _, _, _)));
+            }
+            _ => panic!("expected a transaction"),
+        }
+    }
+
+    #[test]
+    fn begin_commit_with_escaped_quotes() {
+        let wql = Wql::from_str(
+            "BEGIN INSERT {a: \"a\\\";b\",} INTO my_entity; INSERT {a: \"\\\\\",} INTO my_entity; COMMIT",
+        )
+        .unwrap();
+
+        match wql {
+            Wql::Transaction(statements) => {
+                assert_eq!(statements.len(), 2);
+                assert!(
+                    matches!(&statements[0], Wql::Insert(_, content, None) if content["a"] == Types::String("a\";b".to_string()))
+                );
+                assert!(
+                    matches!(&statements[1], Wql::Insert(_, content, None) if content["a"] == Types::String("\\".to_string()))
+                );
+            }
+            _ => panic!("expected a transaction"),
+        }
+    }
+
+    #[test]
+    fn begin_without_commit_err() {
+        let wql = Wql::from_str("BEGIN INSERT {a: 123,} INTO my_entity;");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("Keyword COMMIT is required to end a BEGIN block"))
+        );
+    }
+
+    #[test]
+    fn begin_empty_err() {
+        let wql = Wql::from_str("begin commit");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from("BEGIN block requires at least one statement"))
+        );
+    }
+
+    #[test]
+    fn begin_with_select_err() {
+        let wql = Wql::from_str("BEGIN SELECT * FROM my_entity; COMMIT");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "BEGIN blocks only accept INSERT, UPDATE, REPLACE and MATCH UPDATE statements"
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_begin_commit() {
+        let wql = Wql::from_str(
+            "VALIDATE BEGIN INSERT {a: 123,} INTO my_entity; INSERT {a: 1,} INTO my_entity; COMMIT",
+        )
+        .unwrap();
+
+        assert!(
+            matches!(wql, Wql::Validate(tx) if matches!(&*tx, Wql::Transaction(statements) if statements.len() == 2))
+        );
+        assert!(matches!(
+            Wql::from_str("validate INSERT {a: 123,} INTO my_entity"),
+            Ok(Wql::Validate(_))
+        ));
+    }
+
+    #[test]
+    fn validate_non_write_err() {
+        let wql =
+            Wql::from_str("VALIDATE DELETE 2df2b8cf-49da-474d-8a00-c596c0bb6fd1 FROM my_entity");
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "VALIDATE is only available for INSERT, UPDATE, REPLACE, MATCH UPDATE and BEGIN blocks"
+            ))
         );
     }
 }
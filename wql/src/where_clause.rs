@@ -1,6 +1,12 @@
-use std::str::FromStr;
+use std::{net::IpAddr, str::FromStr};
 
-use crate::{logic::parse_value, select::algebra_functions, ToSelect, Types, Wql};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+use crate::{
+    logic::{parse_point, parse_subnet, parse_value},
+    select::algebra_functions,
+    Expression, Operator, ScalarArg, ToSelect, Types, Wql, TYPE_NAMES,
+};
 use serde::{Deserialize, Serialize};
 
 pub fn where_selector(
@@ -8,6 +14,28 @@ pub fn where_selector(
     arg: ToSelect,
     chars: &mut std::str::Chars,
 ) -> Result<Wql, String> {
+    let clauses = where_clauses(&entity_name, chars)?;
+
+    let next_symbol = chars
+        .skip_while(|c| c.is_whitespace())
+        .take_while(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+
+    Ok(Wql::SelectWhere(
+        entity_name,
+        arg,
+        clauses,
+        algebra_functions(next_symbol, chars)?,
+    ))
+}
+
+/// Reads the ` {...}` block of clauses after `WHERE`, commas and braces inside strings
+/// belong to their clause.
+pub(crate) fn where_clauses(
+    entity_name: &str,
+    chars: &mut std::str::Chars,
+) -> Result<Vec<Clause>, String> {
     let mut open = chars.skip_while(|c| c.is_whitespace()).take(1);
 
     if open.next() != Some('{') {
@@ -18,12 +46,42 @@ pub fn where_selector(
 
     let mut clauses = Vec::new();
     let mut clause = String::new();
+    let mut in_point = false;
+    let mut in_string = false;
+    let mut depth = 0_usize;
     loop {
         match chars.next() {
+            Some('"') => {
+                in_string = !in_string;
+                clause.push('"');
+            }
+            Some('\\') if in_string => {
+                clause.push('\\');
+                clause.extend(chars.next());
+            }
+            Some(c) if in_string => clause.push(c),
+            Some('(') if ends_with_point(&clause) => {
+                in_point = true;
+                clause.push('(');
+            }
+            Some(')') if in_point => {
+                in_point = false;
+                clause.push(')');
+            }
+            Some(',') if in_point => clause.push(' '),
+            Some(',') if depth > 0 => clause.push(','),
             Some(',') => {
                 clauses.push(clause);
                 clause = String::new();
             }
+            Some('{') => {
+                depth += 1;
+                clause.push('{');
+            }
+            Some('}') if depth > 0 => {
+                depth -= 1;
+                clause.push('}');
+            }
             Some('}') | None => break,
             Some(c) => clause.push(c),
         }
@@ -34,32 +92,19 @@ pub fn where_selector(
         .filter(|c| !c.is_empty())
         .map(|c| {
             let mut chs = c.trim().chars();
-            set_clause(&entity_name, &mut chs)
+            set_clause(entity_name, &mut chs)
         })
         .collect::<Vec<Clause>>();
     if clauses.is_empty() {
         return Err(String::from("WHERE clause cannot be empty"));
     }
-
-    let next_symbol = chars
-        .skip_while(|c| c.is_whitespace())
-        .take_while(|c| !c.is_whitespace())
-        .collect::<String>()
-        .to_uppercase();
-
-    Ok(Wql::SelectWhere(
-        entity_name,
-        arg,
-        clauses,
-        algebra_functions(next_symbol, chars)?,
-    ))
+    validate_type_names(&clauses)?;
+    validate_subqueries(&clauses)?;
+    Ok(clauses)
 }
 
 fn set_clause(entity_name: &str, chs: &mut std::str::Chars) -> Clause {
-    let c_str: String = chs
-        .skip_while(|c| c.is_whitespace())
-        .take_while(|c| c != &',')
-        .collect();
+    let c_str: String = chs.skip_while(|c| c.is_whitespace()).collect();
 
     if c_str.starts_with("?*") {
         clause_entity_definition(entity_name, &c_str)
@@ -70,16 +115,197 @@ fn set_clause(entity_name: &str, chs: &mut std::str::Chars) -> Clause {
     }
 }
 fn clause_function(entity_name: &str, clause: &str) -> Clause {
+    if clause.trim_start().starts_with('"') {
+        return verify_all_clause(clause);
+    }
     let args: Vec<&str> = clause
         .split(' ')
         .filter(|c| !c.is_empty())
         .map(str::trim)
         .collect();
+    if args.len() == 1 && is_boolean_arg(args[0]) {
+        return Clause::Predicate(Function::IsBool(args[0].to_string(), true));
+    }
+    if args.len() == 2 && args[0].eq_ignore_ascii_case("not") {
+        return not_clause(entity_name, clause);
+    }
     if args.len() < 3 {
         return Clause::Error;
     }
+    if args.len() == 4
+        && args[1].eq_ignore_ascii_case("WITHIN")
+        && args[2].eq_ignore_ascii_case("LAST")
+    {
+        return match parse_duration(args[3]) {
+            Some(seconds) => Clause::Predicate(Function::WithinLast(args[0].to_string(), seconds)),
+            None => Clause::Error,
+        };
+    }
+    if args.len() >= 5
+        && args[1].eq_ignore_ascii_case("WITHIN")
+        && args[3].eq_ignore_ascii_case("OF")
+    {
+        return match (args[2].parse::<f64>(), parse_point(&args[4..].join(" "))) {
+            (Ok(distance), Ok(point)) if distance >= 0_f64 => {
+                Clause::Predicate(Function::Within(args[0].to_string(), distance, point))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args.len() == 3 && args[1].eq_ignore_ascii_case("IN_SUBNET") {
+        return match parse_subnet(args[2].trim_matches('"')) {
+            Some((network, prefix)) => {
+                Clause::Predicate(Function::InSubnet(args[0].to_string(), network, prefix))
+            }
+            None => Clause::Error,
+        };
+    }
+    if args[1].eq_ignore_ascii_case("HAS_KEY") {
+        let map_key = clause.trim()[args[0].len()..].trim_start()[args[1].len()..].trim();
+        let mut chs = map_key.chars();
+        return match chs.next().map(|c| parse_value(c, &mut chs)) {
+            Some(Ok(Types::String(map_key))) => {
+                Clause::Predicate(Function::HasKey(args[0].to_string(), map_key))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args[1].eq_ignore_ascii_case("DOMAIN") {
+        let domain = clause.trim()[args[0].len()..].trim_start()[args[1].len()..].trim();
+        let mut chs = domain.chars();
+        return match chs.next().map(|c| parse_value(c, &mut chs)) {
+            Some(Ok(Types::String(domain))) if !domain.is_empty() => {
+                Clause::Predicate(Function::Domain(args[0].to_string(), domain.to_lowercase()))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args.len() == 3 && args[1].eq_ignore_ascii_case("COUNTRY") {
+        return match country_code(args[2].trim_matches('"')) {
+            Some(code) => Clause::Predicate(Function::Country(args[0].to_string(), code)),
+            None => Clause::Error,
+        };
+    }
+    let is_map_size = args[1].eq_ignore_ascii_case("MAP_SIZE");
+    if args.len() == 4 && (is_map_size || args[1].eq_ignore_ascii_case("STR_LEN")) {
+        let function = Function::from_str(args[2]).unwrap();
+        let is_comparison = function.is_comparison();
+        return match args[3].parse::<usize>() {
+            Ok(size) if is_comparison && is_map_size => Clause::Predicate(Function::MapSize(
+                args[0].to_string(),
+                Box::new(function),
+                size,
+            )),
+            Ok(len) if is_comparison => Clause::Predicate(Function::StrLen(
+                args[0].to_string(),
+                Box::new(function),
+                len,
+            )),
+            _ => Clause::Error,
+        };
+    }
+    if args[1].eq_ignore_ascii_case("VERIFY") {
+        let candidate = clause.trim()[args[0].len()..].trim_start()[args[1].len()..].trim();
+        let mut chs = candidate.chars();
+        return match chs.next().map(|c| parse_value(c, &mut chs)) {
+            Some(Ok(Types::String(candidate))) => {
+                Clause::Predicate(Function::Verify(args[0].to_string(), candidate))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args[1].eq_ignore_ascii_case("HASH_EQ") {
+        let hash = clause.trim()[args[0].len()..].trim_start()[args[1].len()..].trim();
+        let mut chs = hash.chars();
+        return match chs.next().map(|c| parse_value(c, &mut chs)) {
+            Some(Ok(Types::String(hash))) if !hash.is_empty() => {
+                Clause::Predicate(Function::HashEq(args[0].to_string(), hash))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args.len() == 4
+        && args[1].eq_ignore_ascii_case("CHANGED")
+        && args[2].eq_ignore_ascii_case("SINCE")
+    {
+        return match args[3].trim_matches('"').parse::<DateTime<Utc>>() {
+            Ok(date) if args[0].starts_with('?') => {
+                Clause::Predicate(Function::ChangedSince(args[0].to_string(), date))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args.len() == 4 && args[1].eq_ignore_ascii_case("UUID_TIME") {
+        let function = match &args[2].to_uppercase()[..] {
+            "BEFORE" => Function::L,
+            "AFTER" => Function::G,
+            _ => Function::Error,
+        };
+        return match parse_date(args[3].trim_matches('"')) {
+            Some(date) if function != Function::Error && args[0].starts_with('?') => {
+                Clause::Predicate(Function::UuidTime(
+                    args[0].to_string(),
+                    Box::new(function),
+                    date,
+                ))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args.len() == 3
+        && args[1].eq_ignore_ascii_case("IS")
+        && args[2].eq_ignore_ascii_case("NIL_UUID")
+        && args[0].starts_with('?')
+    {
+        return Clause::Predicate(Function::NilUuid(args[0].to_string()));
+    }
+    if args.len() == 3 && args[1].eq_ignore_ascii_case("UUID_VERSION") {
+        return match args[2].parse::<usize>() {
+            Ok(version) if version < 16 && args[0].starts_with('?') => {
+                Clause::Predicate(Function::UuidVersion(args[0].to_string(), version))
+            }
+            _ => Clause::Error,
+        };
+    }
+    if args.len() == 5
+        && args[1].eq_ignore_ascii_case("IS")
+        && args[2].eq_ignore_ascii_case("OF")
+        && args[3].eq_ignore_ascii_case("TYPE")
+    {
+        return Clause::Predicate(Function::IsType(args[0].to_string(), args[4].to_string()));
+    }
+    if let (5, Some(operator)) = (args.len(), Operator::parse(args[1])) {
+        let function = Function::from_str(args[3]).unwrap();
+        return match (where_arg(args[0]), where_arg(args[2]), where_arg(args[4])) {
+            (Some(left), Some(right), Some(other)) if function.is_comparison() => {
+                Clause::Predicate(Function::Arithmetic(
+                    Expression {
+                        left,
+                        operator,
+                        right,
+                    },
+                    Box::new(function),
+                    other,
+                ))
+            }
+            _ => Clause::Error,
+        };
+    }
 
     match &args[0].to_lowercase()[..] {
+        ">=" | ">" | "==" | "<=" | "<" | "!="
+            if args.len() == 3 && args[2].eq_ignore_ascii_case("now()") =>
+        {
+            let function = Function::from_str(args[0]).unwrap();
+            Clause::Predicate(Function::ComparedToNow(
+                args[1].to_string(),
+                Box::new(function),
+            ))
+        }
+        ">=" | ">" | "==" | "<=" | "<" | "!=" if args.len() == 3 && args[2].starts_with('?') => {
+            let function = Function::from_str(args[0]).unwrap();
+            Clause::KeyComparisonFunction(function, args[1].to_string(), args[2].to_string())
+        }
         ">=" | ">" | "==" | "<=" | "<" | "like" => {
             let mut chs = args[2].chars();
             let function = Function::from_str(args[0]).unwrap();
@@ -91,6 +317,16 @@ fn clause_function(entity_name: &str, clause: &str) -> Clause {
                 Clause::Error
             }
         }
+        "in" if args[2].starts_with('(') => {
+            let subquery = clause.trim()[2..].trim_start()[args[1].len()..].trim();
+            if !subquery.ends_with(')') {
+                return Clause::Error;
+            }
+            match Wql::from_str(&subquery[1..subquery.len() - 1]) {
+                Ok(wql) => Clause::SubqueryIn(args[1].to_string(), Box::new(wql)),
+                Err(_) => Clause::Error,
+            }
+        }
         "in" | "between" => {
             let function = Function::from_str(args[0]).unwrap();
             let key = args[1].to_string();
@@ -111,29 +347,131 @@ fn clause_function(entity_name: &str, clause: &str) -> Clause {
             }
         }
         "or" => {
-            let clauses = or_clauses(entity_name, clause);
+            let clauses = or_clauses(entity_name, &clause.trim_start()[2..]);
             Clause::Or(Function::Or, clauses)
         }
+        "xor" => {
+            let clauses = or_clauses(entity_name, &clause.trim_start()[3..]);
+            if clauses.len() == 2 {
+                Clause::Or(Function::Xor, clauses)
+            } else {
+                Clause::Error
+            }
+        }
+        "not" => not_clause(entity_name, clause),
         _ => Clause::Error,
     }
 }
 
-fn or_clauses(entity_name: &str, clause: &str) -> Vec<Clause> {
-    let mut chars = clause[2..].chars();
+/// Parses `"candidate" VERIFY_ALL {?a, ?b}`, the candidate must match the hashes of all keys.
+fn verify_all_clause(clause: &str) -> Clause {
+    let mut chs = clause.trim_start().chars();
+    let candidate = match chs.next().map(|c| parse_value(c, &mut chs)) {
+        Some(Ok(Types::String(candidate))) => candidate,
+        _ => return Clause::Error,
+    };
+    let rest = chs.as_str().trim_start();
+    if !rest.to_uppercase().starts_with("VERIFY_ALL") {
+        return Clause::Error;
+    }
+    let keys = rest["VERIFY_ALL".len()..].trim();
+    if !keys.starts_with('{') || !keys.ends_with('}') {
+        return Clause::Error;
+    }
+    let keys = keys[1..keys.len() - 1]
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+        .collect::<Vec<String>>();
+    if keys.is_empty() || keys.iter().any(|k| !k.starts_with('?')) {
+        Clause::Error
+    } else {
+        Clause::Predicate(Function::VerifyAll(keys, candidate))
+    }
+}
+
+/// Parses a duration of days, hours or minutes, like `7d`, `12h` or `30m`, into seconds.
+fn parse_duration(duration: &str) -> Option<i64> {
+    let unit = duration.chars().last()?;
+    let unit_seconds = match unit.to_ascii_lowercase() {
+        'd' => 86_400,
+        'h' => 3_600,
+        'm' => 60,
+        _ => return None,
+    };
+    let amount = duration[..duration.len() - unit.len_utf8()]
+        .parse::<i64>()
+        .ok()
+        .filter(|amount| *amount >= 0)?;
+    amount.checked_mul(unit_seconds)
+}
+
+/// Parses an RFC 3339 date time or a date, like `2023-01-01`, at midnight UTC.
+pub(crate) fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    date.parse::<DateTime<Utc>>().ok().or_else(|| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| Utc.from_utc_datetime(&date))
+    })
+}
+
+/// Negates the only clause inside `not`, `(not (not ...))` cancels out. Value
+/// attributions and `IN` subqueries can't be negated. `(not ?active)` is the
+/// `Types::Boolean` shorthand for `false`.
+fn not_clause(entity_name: &str, clause: &str) -> Clause {
+    let negated = clause.trim()[3..].trim();
+    if is_boolean_arg(negated) {
+        return Clause::Predicate(Function::IsBool(negated.to_string(), false));
+    }
+    let mut chs = negated.chars();
+    match set_clause(entity_name, &mut chs) {
+        Clause::Not(inner) => *inner,
+        Clause::ValueAttribution(_, _, _)
+        | Clause::ValueAttributionAsOf(_, _, _, _)
+        | Clause::SubqueryIn(_, _)
+        | Clause::Error => Clause::Error,
+        inner => Clause::Not(Box::new(inner)),
+    }
+}
+
+/// Parses the clauses inside `or` and `xor`, `operands` is what follows the keyword.
+fn or_clauses(entity_name: &str, operands: &str) -> Vec<Clause> {
+    let mut chars = operands.chars();
     let mut clauses = Vec::new();
     let mut clause = String::new();
+    let mut in_point = false;
+    let mut depth = 0_usize;
     loop {
         match chars.next() {
-            Some(',') => {
+            Some('(') if ends_with_point(&clause) => {
+                in_point = true;
+                clause.push('(');
+            }
+            Some(')') if in_point => {
+                in_point = false;
+                clause.push(')');
+            }
+            Some(',') if depth == 0 => {
                 clauses.push(clause);
                 clause = String::new();
             }
             Some(')') => {
                 clause.push(')');
-                clauses.push(clause);
-                clause = String::new();
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    clauses.push(clause);
+                    clause = String::new();
+                }
+            }
+            Some('(') if depth == 0 => {
+                depth += 1;
+                clause = String::from('(');
+            }
+            Some('(') => {
+                depth += 1;
+                clause.push('(');
             }
-            Some('(') => clause = String::from('('),
             Some(c) => clause.push(c),
             None => break,
         }
@@ -148,17 +486,41 @@ fn or_clauses(entity_name: &str, clause: &str) -> Vec<Clause> {
         .collect::<Vec<Clause>>()
 }
 
+/// `?active` of the boolean shorthands `(?active)` and `(not ?active)`.
+fn is_boolean_arg(arg: &str) -> bool {
+    arg.len() > 1
+        && arg.starts_with('?')
+        && arg[1..].chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Calling code of `COUNTRY` with its `+`, 1 to 3 digits without a leading zero.
+fn country_code(code: &str) -> Option<String> {
+    let digits = code.strip_prefix('+').unwrap_or(code);
+    if (1..=3).contains(&digits.len())
+        && !digits.starts_with('0')
+        && digits.chars().all(|c| c.is_ascii_digit())
+    {
+        Some(format!("+{}", digits))
+    } else {
+        None
+    }
+}
+
+fn ends_with_point(clause: &str) -> bool {
+    clause.to_lowercase().ends_with("point")
+}
+
 fn clause_entity_definition(entity_name: &str, clause: &str) -> Clause {
     let elements = clause
         .split(' ')
         .filter(|c| !c.is_empty())
         .map(str::trim)
         .collect::<Vec<&str>>();
-    if elements.len() != 3 {
+    if elements.len() != 3 && elements.len() != 6 {
         return Clause::Error;
     }
 
-    let last_element = elements.last().unwrap();
+    let last_element = &elements[2];
     let entity_key = elements[1].split(':').collect::<Vec<&str>>();
     if entity_key.len() != 2 {
         return Clause::Error;
@@ -169,6 +531,10 @@ fn clause_entity_definition(entity_name: &str, clause: &str) -> Clause {
         return Clause::Error;
     }
 
+    if elements.len() == 6 {
+        return clause_as_of(entity, key, last_element, &elements[3..]);
+    }
+
     let mut last = last_element.chars();
     if last_element.starts_with('?') {
         Clause::ValueAttribution(
@@ -183,13 +549,80 @@ fn clause_entity_definition(entity_name: &str, clause: &str) -> Clause {
     }
 }
 
+fn clause_as_of(entity: &str, key: &str, arg: &str, as_of: &[&str]) -> Clause {
+    if !arg.starts_with('?')
+        || !as_of[0].eq_ignore_ascii_case("AS")
+        || !as_of[1].eq_ignore_ascii_case("OF")
+    {
+        return Clause::Error;
+    }
+
+    if let Some(date) = parse_date(as_of[2].trim_matches('"')) {
+        Clause::ValueAttributionAsOf(
+            entity.to_owned(),
+            key.to_owned(),
+            Value(arg.to_string()),
+            date,
+        )
+    } else {
+        Clause::Error
+    }
+}
+
+fn validate_type_names(clauses: &[Clause]) -> Result<(), String> {
+    for clause in clauses {
+        match clause {
+            Clause::Predicate(Function::IsType(_, name)) if !TYPE_NAMES.contains(&&name[..]) => {
+                return Err(format!(
+                    "Type `{}` is not valid for IS OF TYPE. Valid types are {:?}",
+                    name, TYPE_NAMES
+                ))
+            }
+            Clause::Or(_, inner_clauses) => validate_type_names(inner_clauses)?,
+            Clause::Not(inner) => validate_type_names(std::slice::from_ref(&**inner))?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// `IN` subqueries must select exactly one key and cannot contain other subqueries.
+fn validate_subqueries(clauses: &[Clause]) -> Result<(), String> {
+    for clause in clauses {
+        if let Clause::SubqueryIn(_, subquery) = clause {
+            match &**subquery {
+                Wql::Select(_, ToSelect::Keys(keys), None, _) if keys.len() == 1 => (),
+                Wql::SelectWhere(_, ToSelect::Keys(keys), inner_clauses, _) if keys.len() == 1 => {
+                    if inner_clauses
+                        .iter()
+                        .any(|c| matches!(c, Clause::SubqueryIn(_, _)))
+                    {
+                        return Err(String::from("IN subqueries cannot be nested"));
+                    }
+                }
+                _ => {
+                    return Err(String::from(
+                        "IN subquery must select a single key, like `(in ?id (SELECT #{id,} FROM users))`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Clause {
     ContainsKeyValue(String, String, Types),
     ValueAttribution(String, String, Value),
+    ValueAttributionAsOf(String, String, Value, DateTime<Utc>),
     SimpleComparisonFunction(Function, String, Types),
+    KeyComparisonFunction(Function, String, String),
     ComplexComparisonFunctions(Function, String, Vec<Types>),
+    SubqueryIn(String, Box<Wql>),
     Or(Function, Vec<Clause>),
+    Not(Box<Clause>),
+    Predicate(Function),
     Error,
 }
 
@@ -204,7 +637,50 @@ pub enum Function {
     Like,
     Between,
     Or,
+    /// `Clause::Or` of two clauses that is `true` when exactly one of them is `true`.
+    Xor,
     In,
+    IsType(String, String),
+    Within(String, f64, Types),
+    /// Checks a candidate against the bcrypt or argon2 `Types::Hash` stored at the key.
+    Verify(String, String),
+    /// Checks a candidate against the `Types::Hash`es stored at all the keys.
+    VerifyAll(Vec<String>, String),
+    /// Compares the `Types::Hash` stored at the key with a hash string, nothing is verified.
+    HashEq(String, String),
+    /// Matches entities with a registry newer than the date.
+    ChangedSince(String, DateTime<Utc>),
+    /// Checks if the `Types::IpAddr` at the key is inside the network with the prefix length.
+    InSubnet(String, IpAddr, u8),
+    /// Checks if the `Types::Map` at the key contains the map key.
+    HasKey(String, String),
+    /// Checks if the `Types::Email` at the key has the lowercased domain.
+    Domain(String, String),
+    /// Checks if the `Types::Boolean` at the key is the bool, `(?active)` is `true` and
+    /// `(not ?active)` is `false`.
+    IsBool(String, bool),
+    /// Checks if the `Types::Phone` at the key starts with the country calling code, like `+1`.
+    Country(String, String),
+    /// Compares the number of entries of the `Types::Map` at the key with the size.
+    MapSize(String, Box<Function>, usize),
+    /// Compares the number of chars of the `Types::String` or `Types::Precise` at the key
+    /// with the length.
+    StrLen(String, Box<Function>, usize),
+    /// Compares the time embedded in the v1 `Types::Uuid` at the key with the date, `L` is
+    /// `BEFORE` and `G` is `AFTER`.
+    UuidTime(String, Box<Function>, DateTime<Utc>),
+    /// Checks if the `Types::Uuid` at the key is the nil Uuid, all bits zero.
+    NilUuid(String),
+    /// Checks if the `Types::Uuid` at the key has the version, the 4 bits of its version field.
+    UuidVersion(String, usize),
+    /// Compares the value of the `Expression` over `?` args and numbers with an arg or a
+    /// value, `(?price * 1.2 < ?budget)`.
+    Arithmetic(Expression, Box<Function>, ScalarArg),
+    /// Checks if the `Types::DateTime` at the key is at most the seconds before now.
+    WithinLast(String, i64),
+    /// Compares the `Types::DateTime` at the key with the time the query is evaluated,
+    /// `(< ?expires_at now())`.
+    ComparedToNow(String, Box<Function>),
     Error,
 }
 
@@ -227,6 +703,32 @@ impl FromStr for Function {
     }
 }
 
+impl Function {
+    /// `==`, `!=`, `>=`, `>`, `<=` and `<`.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Function::Eq
+                | Function::NotEq
+                | Function::GEq
+                | Function::G
+                | Function::LEq
+                | Function::L
+        )
+    }
+}
+
+/// `?` args are `ScalarArg::Key`s, other tokens are parsed as values.
+fn where_arg(token: &str) -> Option<ScalarArg> {
+    if token.starts_with('?') {
+        return Some(ScalarArg::Key(token.to_string()));
+    }
+    let mut chs = token.chars();
+    parse_value(chs.next()?, &mut chs)
+        .ok()
+        .map(ScalarArg::Value)
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Value(pub String);
 
@@ -340,6 +842,37 @@ mod test {
         )
     }
 
+    #[test]
+    fn percentage_comparison() {
+        let mut chars = " {
+            ?* my_entity:completion ?completion,
+            (> ?completion 80%),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "completion".to_string(),
+                        Value("?completion".to_string())
+                    ),
+                    Clause::SimpleComparisonFunction(
+                        Function::G,
+                        "?completion".to_string(),
+                        Types::Percentage(80.0)
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
     #[test]
     fn complex_comp_func() {
         let mut chars = " {
@@ -444,4 +977,1148 @@ mod test {
             )
         )
     }
+
+    #[test]
+    fn value_attribution_as_of() {
+        let mut chars = " {
+            ?* my_entity:price ?price AS OF \"2023-01-01T00:00:00Z\",
+            (> ?price 100),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttributionAsOf(
+                        "my_entity".to_string(),
+                        "price".to_string(),
+                        Value("?price".to_string()),
+                        "2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                    ),
+                    Clause::SimpleComparisonFunction(
+                        Function::G,
+                        "?price".to_string(),
+                        Types::Integer(100)
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn value_attribution_as_of_day() {
+        let mut chars = " {
+            ?* my_entity:price ?price AS OF \"2023-01-01\",
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![Clause::ValueAttributionAsOf(
+                    "my_entity".to_string(),
+                    "price".to_string(),
+                    Value("?price".to_string()),
+                    "2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                )],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn value_attribution_as_of_err() {
+        let mut chars = " {
+            ?* my_entity:price 100 AS OF 2023-01-01T00:00:00Z,
+            ?* my_entity:price ?price AS OF yesterday,
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![Clause::Error, Clause::Error],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn is_of_type() {
+        let mut chars = " {
+            ?* my_entity:value ?value,
+            (?value IS OF TYPE Integer),
+            (or
+                (?value is of type Float)
+                (?value is of type Nil)
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "value".to_string(),
+                        Value("?value".to_string())
+                    ),
+                    Clause::Predicate(Function::IsType(
+                        "?value".to_string(),
+                        "Integer".to_string()
+                    )),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Predicate(Function::IsType(
+                                "?value".to_string(),
+                                "Float".to_string()
+                            )),
+                            Clause::Predicate(Function::IsType(
+                                "?value".to_string(),
+                                "Nil".to_string()
+                            )),
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn uuid_time() {
+        let mut chars = " {
+            ?* my_entity:id ?id,
+            (?id UUID_TIME BEFORE \"2023-01-01\"),
+            (?id uuid_time after 2022-06-01T12:00:00Z),
+            (?id UUID_TIME SINCE \"2023-01-01\"),
+            (?id UUID_TIME BEFORE yesterday),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "id".to_string(),
+                        Value("?id".to_string())
+                    ),
+                    Clause::Predicate(Function::UuidTime(
+                        "?id".to_string(),
+                        Box::new(Function::L),
+                        "2023-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                    )),
+                    Clause::Predicate(Function::UuidTime(
+                        "?id".to_string(),
+                        Box::new(Function::G),
+                        "2022-06-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                    )),
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn uuid_nil_and_version() {
+        let mut chars = " {
+            ?* my_entity:id ?id,
+            (?id IS NIL_UUID),
+            (?id uuid_version 4),
+            (?id UUID_VERSION 16),
+            (?id UUID_VERSION v4),
+            (?id IS NIL),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "id".to_string(),
+                        Value("?id".to_string())
+                    ),
+                    Clause::Predicate(Function::NilUuid("?id".to_string())),
+                    Clause::Predicate(Function::UuidVersion("?id".to_string(), 4)),
+                    Clause::Error,
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn boolean_shorthand() {
+        let mut chars = " {
+            ?* my_entity:active ?active,
+            (?active),
+            (not ?active),
+            (NOT ?active),
+            (not (?active)),
+            (or (?active) (== ?active Nil)),
+            (?*),
+            (not ?),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "active".to_string(),
+                        Value("?active".to_string())
+                    ),
+                    Clause::Predicate(Function::IsBool("?active".to_string(), true)),
+                    Clause::Predicate(Function::IsBool("?active".to_string(), false)),
+                    Clause::Predicate(Function::IsBool("?active".to_string(), false)),
+                    Clause::Not(Box::new(Clause::Predicate(Function::IsBool(
+                        "?active".to_string(),
+                        true
+                    )))),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Predicate(Function::IsBool("?active".to_string(), true)),
+                            Clause::SimpleComparisonFunction(
+                                Function::Eq,
+                                "?active".to_string(),
+                                Types::Nil
+                            ),
+                        ]
+                    ),
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn within_last() {
+        let mut chars = " {
+            ?* my_entity:created_at ?created,
+            (?created WITHIN LAST 7d),
+            (?created within last 12H),
+            (?created WITHIN LAST 30m),
+            (?created WITHIN LAST 7w),
+            (?created WITHIN LAST -1d),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "created_at".to_string(),
+                        Value("?created".to_string())
+                    ),
+                    Clause::Predicate(Function::WithinLast("?created".to_string(), 604_800)),
+                    Clause::Predicate(Function::WithinLast("?created".to_string(), 43_200)),
+                    Clause::Predicate(Function::WithinLast("?created".to_string(), 1_800)),
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn compared_to_now() {
+        let mut chars = " {
+            ?* my_entity:expires_at ?expires,
+            (< ?expires now()),
+            (>= ?expires NOW()),
+            (< ?expires now),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "expires_at".to_string(),
+                        Value("?expires".to_string())
+                    ),
+                    Clause::Predicate(Function::ComparedToNow(
+                        "?expires".to_string(),
+                        Box::new(Function::L)
+                    )),
+                    Clause::Predicate(Function::ComparedToNow(
+                        "?expires".to_string(),
+                        Box::new(Function::GEq)
+                    )),
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn is_of_type_unknown_type_err() {
+        let mut chars = " {
+            ?* my_entity:value ?value,
+            (?value IS OF TYPE Number),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert!(wql
+            .err()
+            .unwrap()
+            .starts_with("Type `Number` is not valid for IS OF TYPE"));
+    }
+
+    #[test]
+    fn key_comparison() {
+        let mut chars = " {
+            ?* my_entity:start ?start,
+            ?* my_entity:end ?end,
+            (< ?start ?end),
+            (or
+                (== ?start ?end)
+                (>= ?start 3)
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "start".to_string(),
+                        Value("?start".to_string())
+                    ),
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "end".to_string(),
+                        Value("?end".to_string())
+                    ),
+                    Clause::KeyComparisonFunction(
+                        Function::L,
+                        "?start".to_string(),
+                        "?end".to_string()
+                    ),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::KeyComparisonFunction(
+                                Function::Eq,
+                                "?start".to_string(),
+                                "?end".to_string()
+                            ),
+                            Clause::SimpleComparisonFunction(
+                                Function::GEq,
+                                "?start".to_string(),
+                                Types::Integer(3)
+                            ),
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn verify_hash() {
+        let mut chars = " {
+            ?* my_entity:pswd ?pswd,
+            (?pswd VERIFY \"my password\"),
+            (or
+                (?pswd verify \"other\")
+                (== ?pswd 3)
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "pswd".to_string(),
+                        Value("?pswd".to_string())
+                    ),
+                    Clause::Predicate(Function::Verify(
+                        "?pswd".to_string(),
+                        "my password".to_string()
+                    )),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Predicate(Function::Verify(
+                                "?pswd".to_string(),
+                                "other".to_string()
+                            )),
+                            Clause::SimpleComparisonFunction(
+                                Function::Eq,
+                                "?pswd".to_string(),
+                                Types::Integer(3)
+                            ),
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn hash_eq() {
+        let mut chars = " {
+            ?* my_entity:content_hash ?hash,
+            (?hash HASH_EQ \"$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA\"),
+            (?hash hash_eq \"\"),
+            (?hash HASH_EQ 3),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "content_hash".to_string(),
+                        Value("?hash".to_string())
+                    ),
+                    Clause::Predicate(Function::HashEq(
+                        "?hash".to_string(),
+                        "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA".to_string()
+                    )),
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn verify_all_hashes() {
+        let mut chars = " {
+            ?* my_entity:pswd ?pswd,
+            ?* my_entity:pin ?pin,
+            (\"my secret\" VERIFY_ALL {?pswd, ?pin}),
+            (\"other\" verify_all {?pswd}),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "pswd".to_string(),
+                        Value("?pswd".to_string())
+                    ),
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "pin".to_string(),
+                        Value("?pin".to_string())
+                    ),
+                    Clause::Predicate(Function::VerifyAll(
+                        vec!["?pswd".to_string(), "?pin".to_string()],
+                        "my secret".to_string()
+                    )),
+                    Clause::Predicate(Function::VerifyAll(
+                        vec!["?pswd".to_string()],
+                        "other".to_string()
+                    )),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn verify_all_invalid_keys() {
+        for clause in &[
+            "(\"secret\" VERIFY_ALL {})",
+            "(\"secret\" VERIFY_ALL {pswd})",
+            "(\"secret\" VERIFY_ALL ?pswd)",
+            "(\"secret\" VERIFY ?pswd)",
+            "(123 VERIFY_ALL {?pswd})",
+        ] {
+            let query = format!("{{ ?* my_entity:pswd ?pswd, {}, }}", clause);
+            let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut query.chars());
+            match wql {
+                Ok(Wql::SelectWhere(_, _, clauses, _)) => assert_eq!(clauses[1], Clause::Error),
+                other => panic!("expected SELECT WHERE, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn verify_non_string_candidate() {
+        let mut chars = " {
+            ?* my_entity:pswd ?pswd,
+            (?pswd VERIFY 123),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "pswd".to_string(),
+                        Value("?pswd".to_string())
+                    ),
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn changed_since() {
+        let mut chars = " {
+            ?* my_entity:name ?name,
+            (?name CHANGED SINCE \"2021-02-09T16:50:00Z\"),
+            (or
+                (?name changed since \"2021-02-09T16:50:00Z\")
+                (== ?name \"ana\")
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+        let date = "2021-02-09T16:50:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "name".to_string(),
+                        Value("?name".to_string())
+                    ),
+                    Clause::Predicate(Function::ChangedSince("?name".to_string(), date)),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Predicate(Function::ChangedSince("?name".to_string(), date)),
+                            Clause::SimpleComparisonFunction(
+                                Function::Eq,
+                                "?name".to_string(),
+                                Types::String("ana".to_string())
+                            ),
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn changed_since_err() {
+        let mut chars = " {
+            ?* my_entity:name ?name,
+            (?name CHANGED SINCE yesterday),
+            (name CHANGED SINCE \"2021-02-09T16:50:00Z\"),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "name".to_string(),
+                        Value("?name".to_string())
+                    ),
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn within_point() {
+        let mut chars = " {
+            ?* my_entity:loc ?loc,
+            (?loc WITHIN 5.0 OF point(12.0, 45.0)),
+            (or
+                (?loc within 1 of point(0 0))
+                (== ?loc 3)
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "loc".to_string(),
+                        Value("?loc".to_string())
+                    ),
+                    Clause::Predicate(Function::Within(
+                        "?loc".to_string(),
+                        5.0,
+                        Types::Point { x: 12.0, y: 45.0 }
+                    )),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Predicate(Function::Within(
+                                "?loc".to_string(),
+                                1.0,
+                                Types::Point { x: 0.0, y: 0.0 }
+                            )),
+                            Clause::SimpleComparisonFunction(
+                                Function::Eq,
+                                "?loc".to_string(),
+                                Types::Integer(3)
+                            ),
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn subquery_in() {
+        let mut chars = " {
+            ?* orders:user_id ?user_id,
+            (in ?user_id (SELECT #{id,} FROM users WHERE {
+                ?* users:active true,
+            })),
+        }"
+        .chars();
+        let wql = where_selector("orders".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "orders".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "orders".to_string(),
+                        "user_id".to_string(),
+                        Value("?user_id".to_string())
+                    ),
+                    Clause::SubqueryIn(
+                        "?user_id".to_string(),
+                        Box::new(Wql::SelectWhere(
+                            "users".to_string(),
+                            ToSelect::Keys(vec!["id".to_string()]),
+                            vec![Clause::ContainsKeyValue(
+                                "users".to_string(),
+                                "active".to_string(),
+                                Types::Boolean(true)
+                            )],
+                            HashMap::new()
+                        ))
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn subquery_in_err() {
+        let mut chars = " {
+            ?* orders:user_id ?user_id,
+            (in ?user_id (SELECT #{id, name,} FROM users)),
+        }"
+        .chars();
+        let wql = where_selector("orders".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.err(),
+            Some(String::from(
+                "IN subquery must select a single key, like `(in ?id (SELECT #{id,} FROM users))`"
+            ))
+        );
+    }
+
+    #[test]
+    fn not() {
+        let mut chars = " {
+            ?* my_entity:status ?status,
+            ?* my_entity:age ?age,
+            (not (== ?status \"open\")),
+            (not (not (>= ?age 30))),
+            (or
+                (not (between ?age 10 20))
+                (like ?status \"%closed%\")
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "status".to_string(),
+                        Value("?status".to_string())
+                    ),
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "age".to_string(),
+                        Value("?age".to_string())
+                    ),
+                    Clause::Not(Box::new(Clause::SimpleComparisonFunction(
+                        Function::Eq,
+                        "?status".to_string(),
+                        Types::String("open".to_string())
+                    ))),
+                    Clause::SimpleComparisonFunction(
+                        Function::GEq,
+                        "?age".to_string(),
+                        Types::Integer(30)
+                    ),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Not(Box::new(Clause::ComplexComparisonFunctions(
+                                Function::Between,
+                                "?age".to_string(),
+                                vec![Types::Integer(10), Types::Integer(20)]
+                            ))),
+                            Clause::SimpleComparisonFunction(
+                                Function::Like,
+                                "?status".to_string(),
+                                Types::String("%closed%".to_string())
+                            ),
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn not_value_attribution_is_error() {
+        let mut chars = " {
+            (not ?* my_entity:age ?age),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![Clause::Error],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn xor() {
+        let mut chars = " {
+            ?* my_entity:status ?status,
+            ?* my_entity:age ?age,
+            (xor
+                (== ?status \"open\")
+                (or (>= ?age 30) (not (xor (< ?age 10) (> ?age 20))))
+            ),
+            (xor (== ?status \"open\") (< ?age 10) (> ?age 20)),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "status".to_string(),
+                        Value("?status".to_string())
+                    ),
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "age".to_string(),
+                        Value("?age".to_string())
+                    ),
+                    Clause::Or(
+                        Function::Xor,
+                        vec![
+                            Clause::SimpleComparisonFunction(
+                                Function::Eq,
+                                "?status".to_string(),
+                                Types::String("open".to_string())
+                            ),
+                            Clause::Or(
+                                Function::Or,
+                                vec![
+                                    Clause::SimpleComparisonFunction(
+                                        Function::GEq,
+                                        "?age".to_string(),
+                                        Types::Integer(30)
+                                    ),
+                                    Clause::Not(Box::new(Clause::Or(
+                                        Function::Xor,
+                                        vec![
+                                            Clause::SimpleComparisonFunction(
+                                                Function::L,
+                                                "?age".to_string(),
+                                                Types::Integer(10)
+                                            ),
+                                            Clause::SimpleComparisonFunction(
+                                                Function::G,
+                                                "?age".to_string(),
+                                                Types::Integer(20)
+                                            ),
+                                        ]
+                                    ))),
+                                ]
+                            ),
+                        ]
+                    ),
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn email_domain() {
+        let mut chars = " {
+            ?* my_entity:email ?email,
+            (?email DOMAIN \"Example.com\"),
+            (?email DOMAIN example.com),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "email".to_string(),
+                        Value("?email".to_string())
+                    ),
+                    Clause::Predicate(Function::Domain(
+                        "?email".to_string(),
+                        "example.com".to_string()
+                    )),
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn phone_country() {
+        let mut chars = " {
+            ?* my_entity:phone ?phone,
+            (?phone COUNTRY \"+1\"),
+            (?phone country 44),
+            (?phone COUNTRY +0),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "phone".to_string(),
+                        Value("?phone".to_string())
+                    ),
+                    Clause::Predicate(Function::Country("?phone".to_string(), "+1".to_string())),
+                    Clause::Predicate(Function::Country("?phone".to_string(), "+44".to_string())),
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn in_subnet() {
+        let mut chars = " {
+            ?* my_entity:ip ?ip,
+            (?ip IN_SUBNET \"10.0.0.0/8\"),
+            (or
+                (?ip in_subnet \"2001:db8::/32\")
+                (?ip IN_SUBNET \"10.0.0.0/33\")
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "ip".to_string(),
+                        Value("?ip".to_string())
+                    ),
+                    Clause::Predicate(Function::InSubnet(
+                        "?ip".to_string(),
+                        "10.0.0.0".parse().unwrap(),
+                        8
+                    )),
+                    Clause::Or(
+                        Function::Or,
+                        vec![
+                            Clause::Predicate(Function::InSubnet(
+                                "?ip".to_string(),
+                                "2001:db8::".parse().unwrap(),
+                                32
+                            )),
+                            Clause::Error,
+                        ]
+                    ),
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn str_len() {
+        let mut chars = " {
+            ?* my_entity:name ?name,
+            (?name STR_LEN > 255),
+            (not (?name str_len <= 3)),
+            (?name STR_LEN like 3),
+            (?name STR_LEN > -1),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "name".to_string(),
+                        Value("?name".to_string())
+                    ),
+                    Clause::Predicate(Function::StrLen(
+                        "?name".to_string(),
+                        Box::new(Function::G),
+                        255
+                    )),
+                    Clause::Not(Box::new(Clause::Predicate(Function::StrLen(
+                        "?name".to_string(),
+                        Box::new(Function::LEq),
+                        3
+                    )))),
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn arithmetic_comparison() {
+        let mut chars = " {
+            ?* my_entity:price ?price,
+            ?* my_entity:budget ?budget,
+            (?price * 1.2 < ?budget),
+            (not (?price - ?budget >= 10)),
+            (?price % 1.2 < ?budget),
+            (?price * 1.2 like ?budget),
+            (?price * ?budget),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+        let key = |k: &str| ScalarArg::Key(k.to_string());
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "price".to_string(),
+                        Value("?price".to_string())
+                    ),
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "budget".to_string(),
+                        Value("?budget".to_string())
+                    ),
+                    Clause::Predicate(Function::Arithmetic(
+                        Expression {
+                            left: key("?price"),
+                            operator: Operator::Mul,
+                            right: ScalarArg::Value(Types::Float(1.2)),
+                        },
+                        Box::new(Function::L),
+                        key("?budget")
+                    )),
+                    Clause::Not(Box::new(Clause::Predicate(Function::Arithmetic(
+                        Expression {
+                            left: key("?price"),
+                            operator: Operator::Sub,
+                            right: key("?budget"),
+                        },
+                        Box::new(Function::GEq),
+                        ScalarArg::Value(Types::Integer(10))
+                    )))),
+                    Clause::Error,
+                    Clause::Error,
+                    Clause::Error,
+                ],
+                HashMap::new()
+            )
+        )
+    }
+
+    #[test]
+    fn map_structure() {
+        let mut chars = " {
+            ?* my_entity:metadata ?metadata,
+            (?metadata HAS_KEY \"owner name\"),
+            (?metadata map_size > 0),
+            (or
+                (?metadata MAP_SIZE like 2)
+                (?metadata HAS_KEY owner)
+            ),
+        }"
+        .chars();
+        let wql = where_selector("my_entity".to_string(), ToSelect::All, &mut chars);
+
+        assert_eq!(
+            wql.unwrap(),
+            Wql::SelectWhere(
+                "my_entity".to_string(),
+                ToSelect::All,
+                vec![
+                    Clause::ValueAttribution(
+                        "my_entity".to_string(),
+                        "metadata".to_string(),
+                        Value("?metadata".to_string())
+                    ),
+                    Clause::Predicate(Function::HasKey(
+                        "?metadata".to_string(),
+                        "owner name".to_string()
+                    )),
+                    Clause::Predicate(Function::MapSize(
+                        "?metadata".to_string(),
+                        Box::new(Function::G),
+                        0
+                    )),
+                    Clause::Or(Function::Or, vec![Clause::Error, Clause::Error]),
+                ],
+                HashMap::new()
+            )
+        )
+    }
 }